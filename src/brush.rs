@@ -0,0 +1,332 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContext, Inspectable};
+use bevy_mod_picking::PickingCamera;
+
+use crate::{
+    camera::FlyCam,
+    capabilities::GpuCapabilities,
+    chunk::{Chunk, ChunkMap},
+    density_texture::{DensityTexture, TextureRevision},
+    field::DensityField,
+    generation::WorldSettings,
+    gpu_brush::GpuBrushEdit,
+    terrain_root::MainTerrain,
+    Data, StartMarching,
+};
+
+/// How the brush finds the point of the terrain under the cursor
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrushTargeting {
+    /// Intersection with the chunk meshes registered by the picking plugin
+    Picking,
+    /// Ray from the camera through the cursor marched in the density field,
+    /// works without the picking plugin and before the meshes are registered
+    FieldRaycast,
+}
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrushMode {
+    Add,
+    Remove,
+}
+
+/// Press B to toggle the brush, then hold the left mouse button to sculpt
+/// the terrain under the cursor
+#[derive(Inspectable)]
+pub struct Brush {
+    pub enabled: bool,
+    pub targeting: BrushTargeting,
+    pub mode: BrushMode,
+    /// Radius of the sphere of edited points in world units
+    #[inspectable(min = 0.1, max = 32.0)]
+    pub radius: f32,
+    /// Density added or removed per second at the center of the brush
+    #[inspectable(min = 0.0, max = 10.0)]
+    pub strength: f32,
+    /// Longest ray marched in the field
+    #[inspectable(min = 1.0, speed = 1.0)]
+    pub max_distance: f32,
+    /// Also applies the strokes to the density textures with a compute
+    /// shader, instead of uploading the edited chunks again. The points of
+    /// the chunks are still edited for the meshing, which runs on the CPU.
+    /// Ignored when the GPU has no compute shaders.
+    pub gpu_edits: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            targeting: BrushTargeting::FieldRaycast,
+            mode: BrushMode::Add,
+            radius: 2.0,
+            strength: 2.0,
+            max_distance: 500.0,
+            gpu_edits: false,
+        }
+    }
+}
+
+/// Point of the terrain under the cursor, `None` when the brush is disabled
+/// or the cursor isn't over the terrain
+#[derive(Default)]
+pub struct BrushTarget(pub Option<Vec3>);
+
+/// Ray from the camera through a cursor position in pixels, with the origin
+/// at the bottom left of the window like the bevy cursor positions
+pub fn cursor_ray(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    window_size: Vec2,
+    cursor: Vec2,
+) -> Option<(Vec3, Vec3)> {
+    let ndc = cursor / window_size * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+    // the depth is reversed, 1 is the near plane and 0 is infinitely far
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(0.1));
+    let direction = (far - near).normalize_or_zero();
+    (direction != Vec3::ZERO).then(|| (near, direction))
+}
+
+/// Point of the terrain under the cursor, found by marching the cursor ray
+/// of the camera in the density field
+pub fn cursor_hit(
+    windows: &Windows,
+    cameras: &Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    field: &DensityField,
+    isolevel: f32,
+    max_distance: f32,
+) -> Option<Vec3> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let (camera, transform) = cameras.get_single().ok()?;
+    let size = Vec2::new(window.width(), window.height());
+    let (origin, direction) = cursor_ray(camera, transform, size, cursor)?;
+    field.raycast(origin, direction, max_distance, isolevel)
+}
+
+pub fn toggle_brush(keyboard_input: Res<Input<KeyCode>>, mut brush: ResMut<Brush>) {
+    if keyboard_input.just_pressed(KeyCode::B) {
+        brush.enabled = !brush.enabled;
+    }
+}
+
+pub fn update_brush_target(
+    brush: Res<Brush>,
+    data: Res<Data>,
+    windows: Res<Windows>,
+    field: DensityField,
+    cameras: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    picking_cameras: Query<&PickingCamera>,
+    mut target: ResMut<BrushTarget>,
+) {
+    let hit = if !brush.enabled {
+        None
+    } else {
+        match brush.targeting {
+            BrushTargeting::Picking => picking_cameras
+                .iter()
+                .find_map(|camera| camera.intersect_top())
+                .map(|(_, intersection)| intersection.position()),
+            BrushTargeting::FieldRaycast => cursor_hit(
+                &windows,
+                &cameras,
+                &field,
+                data.isolevel,
+                brush.max_distance,
+            ),
+        }
+    };
+    if target.0 != hit {
+        target.0 = hit;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_brush(
+    time: Res<Time>,
+    brush: Res<Brush>,
+    target: Res<BrushTarget>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut egui_context: ResMut<EguiContext>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut chunks: Query<&mut Chunk>,
+    mut textures: Query<(&DensityTexture, &mut TextureRevision)>,
+    mut gpu_edits: EventWriter<GpuBrushEdit>,
+    capabilities: Res<GpuCapabilities>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let center = match target.0 {
+        Some(center) if brush.enabled && mouse_input.pressed(MouseButton::Left) => center,
+        _ => return,
+    };
+    if egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+
+    let sign = match brush.mode {
+        BrushMode::Add => 1.0,
+        BrushMode::Remove => -1.0,
+    };
+    let amount = sign * brush.strength * time.delta_seconds();
+    let chunk_extent = world_settings.chunk_extent();
+    // only the textures holding the current points can be edited in place,
+    // the others are uploaded again with the stroke anyway
+    let up_to_date: Vec<(IVec3, Entity, u64)> = if brush.gpu_edits && capabilities.compute {
+        let reach = Vec3::splat(brush.radius);
+        chunk_map
+            .in_world_box(center - reach, center + reach, chunk_extent)
+            .filter_map(|(coord, entity)| {
+                let revision = chunks.get(entity).ok()?.revision();
+                let (_, texture_revision) = textures.get(entity).ok()?;
+                (texture_revision.0 == revision).then(|| (coord, entity, revision))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let edited = edit_sphere(
+        chunk_map,
+        &mut chunks,
+        &world_settings,
+        center,
+        brush.radius,
+        |value, falloff| (value + amount * falloff).clamp(0.0, 1.0),
+    );
+    for (coord, entity, revision) in up_to_date {
+        let chunk = match chunks.get(entity) {
+            Ok(chunk) if chunk.revision() != revision => chunk,
+            _ => continue,
+        };
+        let local_center = center - coord.as_vec3() * chunk_extent;
+        let cell_size = world_settings.cell_size;
+        let (min, max) =
+            match GpuBrushEdit::region(local_center, brush.radius, cell_size, chunk.size) {
+                Some(region) => region,
+                None => continue,
+            };
+        if let Ok((texture, mut texture_revision)) = textures.get_mut(entity) {
+            texture_revision.0 = chunk.revision();
+            gpu_edits.send(GpuBrushEdit {
+                texture: texture.0.clone(),
+                center: local_center,
+                radius: brush.radius,
+                amount,
+                cell_size,
+                min,
+                max,
+            });
+        }
+    }
+    if edited {
+        start_marching_events.send_default();
+    }
+}
+
+/// Replaces the points within `radius` of `center` by the value returned by
+/// `edit` for their current value and their falloff, which goes from 1 at
+/// the center to 0 at the radius. Returns true if a point changed.
+pub fn edit_sphere(
+    chunk_map: &ChunkMap,
+    chunks: &mut Query<&mut Chunk>,
+    world_settings: &WorldSettings,
+    center: Vec3,
+    radius: f32,
+    mut edit: impl FnMut(f32, f32) -> f32,
+) -> bool {
+    let half_size = Vec3::splat(radius);
+    edit_box(
+        chunk_map,
+        chunks,
+        world_settings,
+        center - half_size,
+        center + half_size,
+        |pos, value| {
+            let distance = pos.distance(center);
+            if distance > radius {
+                value
+            } else {
+                edit(value, 1.0 - distance / radius)
+            }
+        },
+    )
+}
+
+/// Replaces the points from `min` to `max` in world space by the value
+/// returned by `edit` for their world position and their current value,
+/// across every chunk in the box. Returns true if a point changed.
+///
+/// Only the changed points are written so a saturated edit doesn't flag the
+/// chunks for meshing every frame.
+pub fn edit_box(
+    chunk_map: &ChunkMap,
+    chunks: &mut Query<&mut Chunk>,
+    world_settings: &WorldSettings,
+    min: Vec3,
+    max: Vec3,
+    mut edit: impl FnMut(Vec3, f32) -> f32,
+) -> bool {
+    let chunk_extent = world_settings.chunk_extent();
+    let cell_size = world_settings.cell_size;
+
+    let mut edited = false;
+    for (coord, entity) in chunk_map.in_world_box(min, max, chunk_extent) {
+        let mut chunk = match chunks.get_mut(entity) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+        let origin = coord.as_vec3() * chunk_extent;
+        let first = ((min - origin) / cell_size).ceil().max(Vec3::ZERO);
+        let last = ((max - origin) / cell_size)
+            .floor()
+            .min(chunk.size.as_vec3());
+        if first.cmpgt(last).any() {
+            continue;
+        }
+        for point in Chunk::new_iter_3d((last - first).as_uvec3()) {
+            let pos = first + point.as_vec3();
+            let previous = chunk.get(pos);
+            let value = edit(origin + pos * cell_size, previous);
+            if value != previous {
+                chunk.set(pos, value);
+                edited = true;
+            }
+        }
+    }
+    edited
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::camera::{CameraProjection, PerspectiveProjection};
+
+    use super::*;
+
+    #[test]
+    fn cursor_ray_goes_through_the_cursor() {
+        let projection = PerspectiveProjection {
+            aspect_ratio: 2.0,
+            ..default()
+        };
+        let camera = Camera {
+            projection_matrix: projection.get_projection_matrix(),
+            ..default()
+        };
+        let transform = GlobalTransform::from_xyz(1.0, 2.0, 3.0);
+        let size = Vec2::new(200.0, 100.0);
+
+        let (origin, direction) = cursor_ray(&camera, &transform, size, size / 2.0).unwrap();
+        assert!(direction.abs_diff_eq(-Vec3::Z, 1e-4));
+        assert!(origin.abs_diff_eq(Vec3::new(1.0, 2.0, 3.0 - projection.near), 1e-3));
+
+        // the top right corner of the window is up and to the right of the view
+        let (_, corner) = cursor_ray(&camera, &transform, size, size).unwrap();
+        assert!(corner.x > 0.0 && corner.y > 0.0 && corner.z < 0.0);
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::{prelude::*, utils::Instant};
+
+use crate::{
+    chunk::{Chunk, ChunkMesh},
+    generation::{fill_points, NoiseSettings, WorldSettings},
+    march_cube, Data, GridCell,
+};
+
+/// Benchmarks the generation and the meshing of a chunk at several sizes on
+/// the current machine to pick the chunk size of a streaming world.
+///
+/// Small chunks remesh quickly after an edit but need more draw calls to
+/// cover the same view distance. The recommended size is the largest one
+/// remeshed within the latency budget.
+#[derive(Clone, Debug)]
+pub struct CalibrationSettings {
+    /// Number of cells of the chunks on each axis
+    pub sizes: Vec<u32>,
+    /// Chunks remeshed at each size, the median time is kept
+    pub samples: usize,
+    /// Longest acceptable remesh of a chunk in milliseconds
+    pub latency_budget: f32,
+    /// Cells streamed around the camera on each axis, used to count the
+    /// chunks drawn at each size
+    pub view_distance: u32,
+}
+
+impl Default for CalibrationSettings {
+    fn default() -> Self {
+        Self {
+            sizes: vec![8, 16, 24, 32, 48, 64],
+            samples: 5,
+            // a quarter of a frame at 60 fps
+            latency_budget: 4.0,
+            view_distance: 256,
+        }
+    }
+}
+
+/// Measures of one chunk size
+#[derive(Clone, Debug)]
+pub struct SizeBenchmark {
+    pub size: u32,
+    /// Median time to generate, march and weld a chunk in milliseconds
+    pub remesh_ms: f32,
+    /// Chunks needed to cover the view distance
+    pub draw_calls: u32,
+}
+
+/// Times every size of the settings with the noise and the meshing options
+/// of the world
+pub fn benchmark(
+    settings: &CalibrationSettings,
+    noise_settings: &NoiseSettings,
+    world_settings: &WorldSettings,
+    data: &Data,
+) -> Vec<SizeBenchmark> {
+    let noise = noise_settings.stack();
+    let mut buffer = Vec::new();
+    settings
+        .sizes
+        .iter()
+        .map(|&size| {
+            let size = size.max(1);
+            let size_3d = UVec3::splat(size);
+            let mut times: Vec<f32> = (0..settings.samples.max(1))
+                .map(|sample| {
+                    // a different part of the terrain for each sample
+                    let origin = IVec3::new(sample as i32 * size as i32, 0, 0);
+                    let start = Instant::now();
+                    fill_points(
+                        &mut buffer,
+                        &noise,
+                        origin,
+                        size_3d,
+                        noise_settings,
+                        world_settings,
+                        None,
+                    );
+                    let chunk = Chunk::new(buffer.clone(), size_3d);
+                    let mut chunk_mesh = ChunkMesh::default();
+                    for cell in Chunk::new_iter_3d(size_3d - UVec3::ONE) {
+                        let grid_cell =
+                            GridCell::sample(cell.as_vec3(), world_settings.cell_size, &chunk);
+                        if let Some(triangles) =
+                            march_cube(&grid_cell, data.isolevel, data.interpolation)
+                        {
+                            chunk_mesh.triangles.extend(triangles);
+                        }
+                    }
+                    chunk_mesh.indexed(data.normals, |_| None);
+                    start.elapsed().as_secs_f32() * 1000.0
+                })
+                .collect();
+            times.sort_by(|a, b| a.total_cmp(b));
+            let chunks_per_axis = (settings.view_distance + size - 1) / size;
+            SizeBenchmark {
+                size,
+                remesh_ms: times[times.len() / 2],
+                draw_calls: chunks_per_axis.pow(3),
+            }
+        })
+        .collect()
+}
+
+/// Largest size remeshed within `latency_budget` milliseconds, or the
+/// fastest size when none is
+pub fn recommend(benchmarks: &[SizeBenchmark], latency_budget: f32) -> Option<&SizeBenchmark> {
+    benchmarks
+        .iter()
+        .filter(|benchmark| benchmark.remesh_ms <= latency_budget)
+        .min_by_key(|benchmark| benchmark.draw_calls)
+        .or_else(|| {
+            benchmarks
+                .iter()
+                .min_by(|a, b| a.remesh_ms.total_cmp(&b.remesh_ms))
+        })
+}
+
+/// Runs the calibration with the default world and logs a report, returns
+/// the recommended chunk size
+pub fn run_calibration(settings: &CalibrationSettings) -> Option<u32> {
+    let benchmarks = benchmark(
+        settings,
+        &NoiseSettings::default(),
+        &WorldSettings::default(),
+        &Data::default(),
+    );
+    println!("Chunk size calibration");
+    for benchmark in &benchmarks {
+        println!(
+            "  {0}x{0}x{0} cells: remesh {1:.2} ms, {2} chunks in view",
+            benchmark.size, benchmark.remesh_ms, benchmark.draw_calls
+        );
+    }
+    let recommended = recommend(&benchmarks, settings.latency_budget)?;
+    println!(
+        "  recommended chunk size: {} cells, remeshed in {:.2} ms for a budget of {:.2} ms",
+        recommended.size, recommended.remesh_ms, settings.latency_budget
+    );
+    Some(recommended.size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recommends_the_largest_size_within_budget() {
+        let measure = |size, remesh_ms, draw_calls| SizeBenchmark {
+            size,
+            remesh_ms,
+            draw_calls,
+        };
+        let benchmarks = [
+            measure(8, 0.5, 1000),
+            measure(16, 2.0, 125),
+            measure(32, 9.0, 27),
+        ];
+        assert_eq!(recommend(&benchmarks, 4.0).map(|b| b.size), Some(16));
+        assert_eq!(recommend(&benchmarks, 0.1).map(|b| b.size), Some(8));
+        assert!(recommend(&[], 4.0).is_none());
+
+        let settings = CalibrationSettings {
+            sizes: vec![4, 8],
+            samples: 1,
+            view_distance: 16,
+            ..default()
+        };
+        let measured = benchmark(
+            &settings,
+            &NoiseSettings::default(),
+            &WorldSettings::default(),
+            &Data::default(),
+        );
+        assert_eq!(measured.len(), 2);
+        assert_eq!(measured[0].draw_calls, 64);
+        assert_eq!(measured[1].draw_calls, 8);
+    }
+}
@@ -3,6 +3,26 @@ use bevy::{input::mouse::MouseMotion, prelude::*};
 #[derive(Component, Default)]
 pub struct FlyCam;
 
+/// Keeps the cameras with a `T` at the position of the [`FlyCam`], for the
+/// cameras rendering the same view to an image
+pub fn follow_fly_cam<T: Component>(
+    fly_cam: Query<(&Transform, &PerspectiveProjection), With<FlyCam>>,
+    mut cameras: Query<(&mut Transform, &mut PerspectiveProjection), (With<T>, Without<FlyCam>)>,
+) {
+    let (fly_transform, fly_projection) = match fly_cam.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        if *transform != *fly_transform {
+            *transform = *fly_transform;
+        }
+        if projection.fov != fly_projection.fov {
+            projection.fov = fly_projection.fov;
+        }
+    }
+}
+
 pub fn fly_camera(
     time: Res<Time>,
     mut camera_transform: Query<&mut Transform, With<FlyCam>>,
@@ -0,0 +1,101 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{WgpuFeatures, WgpuLimits},
+        renderer::RenderDevice,
+    },
+};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+/// Optional GPU features used by the plugin, detected on the render device
+/// when the plugin is built. The tools needing a missing feature are
+/// disabled and listed in a notice instead of failing to start.
+///
+/// The renderer enables every feature of the adapter by default, don't
+/// require them in the `WgpuSettings`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GpuCapabilities {
+    /// `POLYGON_MODE_LINE`, used by the wireframes
+    pub wireframe: bool,
+    /// Compute shaders writing storage textures, used by the GPU brush edits
+    /// and the GPU meshing
+    pub compute: bool,
+}
+
+impl GpuCapabilities {
+    pub fn detect(features: WgpuFeatures, limits: &WgpuLimits) -> Self {
+        Self {
+            wireframe: features.contains(WgpuFeatures::POLYGON_MODE_LINE),
+            // WebGL has neither
+            compute: limits.max_compute_invocations_per_workgroup > 0
+                && limits.max_storage_textures_per_shader_stage > 0,
+        }
+    }
+
+    /// Tools disabled for lack of a feature
+    pub fn disabled_tools(&self) -> Vec<&'static str> {
+        let mut disabled = Vec::new();
+        if !self.wireframe {
+            disabled.push("Wireframes: the GPU doesn't support POLYGON_MODE_LINE");
+        }
+        if !self.compute {
+            disabled.push(
+                "GPU brush edits and GPU meshing: the GPU doesn't support compute shaders, strokes are uploaded and chunks are marched on the CPU",
+            );
+        }
+        disabled
+    }
+}
+
+impl FromWorld for GpuCapabilities {
+    fn from_world(world: &mut World) -> Self {
+        match world.get_resource::<RenderDevice>() {
+            Some(render_device) => Self::detect(render_device.features(), &render_device.limits()),
+            None => Self::detect(
+                WgpuFeatures::empty(),
+                &WgpuLimits::downlevel_webgl2_defaults(),
+            ),
+        }
+    }
+}
+
+/// Lists the tools disabled by the [`GpuCapabilities`] until closed
+pub fn capabilities_notice_ui(
+    mut egui_context: ResMut<EguiContext>,
+    capabilities: Res<GpuCapabilities>,
+    mut closed: Local<bool>,
+) {
+    let disabled = capabilities.disabled_tools();
+    if disabled.is_empty() || *closed {
+        return;
+    }
+    let mut open = true;
+    egui::Window::new("Unsupported GPU features")
+        .open(&mut open)
+        .show(egui_context.ctx_mut(), |ui| {
+            for tool in disabled {
+                ui.label(tool);
+            }
+        });
+    *closed = !open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webgl_disables_the_optional_tools() {
+        let webgl = GpuCapabilities::detect(
+            WgpuFeatures::empty(),
+            &WgpuLimits::downlevel_webgl2_defaults(),
+        );
+        assert!(!webgl.wireframe && !webgl.compute);
+        assert_eq!(webgl.disabled_tools().len(), 2);
+
+        let native =
+            GpuCapabilities::detect(WgpuFeatures::POLYGON_MODE_LINE, &WgpuLimits::default());
+        assert!(native.wireframe && native.compute);
+        assert!(native.disabled_tools().is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+use std::{
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, RenderTarget},
+        render_asset::RenderAssets,
+        render_resource::{
+            BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+            ImageCopyTexture, ImageDataLayout, MapMode, Origin3d, TextureAspect, TextureFormat,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::GpuImage,
+        RenderApp, RenderStage,
+    },
+    tasks::IoTaskPool,
+};
+use bevy_inspector_egui::Inspectable;
+use image::RgbaImage;
+
+use crate::{
+    camera::{follow_fly_cam, FlyCam},
+    field_sync::padded_row_len,
+    generation::WorldSettings,
+    inspection_view::{add_image_camera, fit_render_target, render_target_image},
+    terrain_root::MainTerrain,
+    CHUNK_SIZE,
+};
+
+const CAPTURE_CAMERA: &str = "capture_camera";
+const CAPTURE_PASS_DRIVER: &str = "capture_pass_driver";
+
+/// Orbits the camera around the selected chunk, and writes every frame of the
+/// orbit to `directory` while `record` is enabled.
+///
+/// Press T to toggle it and F12 to take a screenshot.
+#[derive(Inspectable)]
+pub struct TurntableSettings {
+    pub enabled: bool,
+    /// Degrees per second
+    #[inspectable(min = -180.0, max = 180.0, speed = 1.0)]
+    pub speed: f32,
+    #[inspectable(min = 1.0, max = 200.0, speed = 0.5)]
+    pub radius: f32,
+    /// Height of the camera above the center of the chunk
+    #[inspectable(min = -100.0, max = 100.0, speed = 0.5)]
+    pub height: f32,
+    /// Write each frame of the orbit as `turntable_00000.png`,
+    /// `turntable_00001.png`...
+    pub record: bool,
+    /// Frames per second of the recording, the orbit advances by the same
+    /// angle every frame while recording whatever the real frame time is
+    #[inspectable(min = 1.0, max = 120.0)]
+    pub frame_rate: f32,
+    /// Where the screenshots and the recorded frames are written
+    #[inspectable(ignore)]
+    pub directory: PathBuf,
+}
+
+impl Default for TurntableSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            speed: 20.0,
+            radius: CHUNK_SIZE as f32 * 1.5,
+            height: CHUNK_SIZE as f32,
+            record: false,
+            frame_rate: 30.0,
+            directory: PathBuf::from("captures"),
+        }
+    }
+}
+
+/// Renders the view of the [`FlyCam`] to an image and writes it to the
+/// requested files, the egui windows aren't part of it.
+///
+/// The camera rendering the image is spawned when a capture is requested and
+/// the image is read back once it rendered a frame, so the files are written
+/// a few frames after the request.
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        let readbacks = CaptureReadbacks::default();
+        app.init_resource::<Captures>()
+            .init_resource::<CaptureTarget>()
+            .insert_resource(readbacks.clone())
+            .add_system(toggle_turntable)
+            .add_system(turntable_camera.after(toggle_turntable))
+            .add_system(screenshot)
+            .add_system(
+                update_capture_camera
+                    .after(turntable_camera)
+                    .after(screenshot),
+            )
+            .add_system(follow_fly_cam::<CaptureCamera>.after(update_capture_camera))
+            .add_system(save_captures);
+
+        add_image_camera(app, CAPTURE_CAMERA, CAPTURE_PASS_DRIVER);
+        app.sub_app_mut(RenderApp)
+            .insert_resource(readbacks)
+            .init_resource::<ExtractedCaptures>()
+            .add_system_to_stage(RenderStage::Extract, extract_captures)
+            // after the render graph so the frame is read
+            .add_system_to_stage(RenderStage::Cleanup, read_captures);
+    }
+}
+
+/// Files waiting for the next rendered frame
+#[derive(Default)]
+pub struct Captures {
+    /// Requested this frame or waiting for the capture camera
+    pending: Vec<PathBuf>,
+    /// Written with the image rendered this frame
+    ready: Vec<PathBuf>,
+}
+
+impl Captures {
+    /// Writes the next frame rendered by the [`FlyCam`] to `path`
+    pub fn request(&mut self, path: PathBuf) {
+        self.pending.push(path);
+    }
+}
+
+/// Camera following the [`FlyCam`] to render the captured frames
+#[derive(Component)]
+struct CaptureCamera;
+
+struct CaptureImage {
+    handle: Handle<Image>,
+    camera: Entity,
+}
+
+/// Spawned while captures are requested
+#[derive(Default)]
+struct CaptureTarget(Option<CaptureImage>);
+
+/// Pixels read back from the GPU and the files to write them to
+struct CapturedFrame {
+    paths: Vec<PathBuf>,
+    image: RgbaImage,
+}
+
+/// Frames read back from the GPU, shared between the main and the render world
+#[derive(Default, Clone)]
+struct CaptureReadbacks(Arc<Mutex<Vec<CapturedFrame>>>);
+
+/// Image of the capture camera and the files its frame is written to
+#[derive(Default)]
+struct ExtractedCaptures(Option<(Handle<Image>, Vec<PathBuf>)>);
+
+pub fn toggle_turntable(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<TurntableSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn turntable_camera(
+    time: Res<Time>,
+    settings: Res<TurntableSettings>,
+    world_settings: Res<WorldSettings>,
+    terrain: MainTerrain,
+    chunks: Query<&GlobalTransform, Without<FlyCam>>,
+    mut camera: Query<&mut Transform, With<FlyCam>>,
+    mut captures: ResMut<Captures>,
+    mut angle: Local<f32>,
+    mut frame: Local<u32>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    // orbit the center of the selected chunk, or the world origin if nothing is selected
+    let half_chunk = world_settings.chunk_extent() / 2.0;
+    let center = terrain
+        .selected_chunk()
+        .and_then(|entity| chunks.get(entity).ok())
+        .map(|transform| transform.translation + half_chunk)
+        .unwrap_or(half_chunk);
+
+    let delta = if settings.record {
+        let path = settings
+            .directory
+            .join(format!("turntable_{:05}.png", *frame));
+        captures.request(path);
+        *frame += 1;
+        settings.frame_rate.max(1.0).recip()
+    } else {
+        *frame = 0;
+        time.delta_seconds()
+    };
+    *angle += settings.speed.to_radians() * delta;
+    let offset =
+        Vec3::new(angle.cos(), 0.0, angle.sin()) * settings.radius + Vec3::Y * settings.height;
+
+    let mut transform = camera.single_mut();
+    *transform = Transform::from_translation(center + offset).looking_at(center, Vec3::Y);
+}
+
+/// Press F12 to write the next frame to `screenshot_<milliseconds since the
+/// epoch>.png`
+pub fn screenshot(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<TurntableSettings>,
+    mut captures: ResMut<Captures>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F12) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        captures.request(settings.directory.join(format!("screenshot_{time}.png")));
+    }
+}
+
+fn update_capture_camera(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    fly_cam: Query<&Transform, With<FlyCam>>,
+    mut images: ResMut<Assets<Image>>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut target: ResMut<CaptureTarget>,
+    mut captures: ResMut<Captures>,
+) {
+    // the frame of the last ready captures is extracted at the end of this
+    // frame, the camera is kept until then
+    if captures.pending.is_empty() && captures.ready.is_empty() {
+        if let Some(image) = target.0.take() {
+            commands.entity(image.camera).despawn();
+            images.remove(&image.handle);
+            active_cameras.remove(CAPTURE_CAMERA);
+        }
+        return;
+    }
+
+    // the pixels of the image are the pixels of the window
+    let size = match windows.get_primary() {
+        Some(window) => {
+            UVec2::new(window.physical_width(), window.physical_height()).max(UVec2::ONE)
+        }
+        None => return,
+    };
+    match &target.0 {
+        Some(image) => {
+            fit_render_target(&mut images, &image.handle, size);
+            // the camera rendered the previous frame, it renders this one too
+            captures.ready = std::mem::take(&mut captures.pending);
+        }
+        None => {
+            let handle = images.add(render_target_image(size.x, size.y));
+            let mut bundle = PerspectiveCameraBundle::new_3d();
+            bundle.camera.name = Some(CAPTURE_CAMERA.to_string());
+            bundle.camera.target = RenderTarget::Image(handle.clone());
+            if let Ok(transform) = fly_cam.get_single() {
+                bundle.transform = *transform;
+            }
+            let camera = commands.spawn_bundle(bundle).insert(CaptureCamera).id();
+            active_cameras.add(CAPTURE_CAMERA);
+            target.0 = Some(CaptureImage { handle, camera });
+            captures.ready.clear();
+        }
+    }
+}
+
+fn extract_captures(mut commands: Commands, captures: Res<Captures>, target: Res<CaptureTarget>) {
+    let request = target
+        .0
+        .as_ref()
+        .filter(|_| !captures.ready.is_empty())
+        .map(|image| (image.handle.clone_weak(), captures.ready.clone()));
+    commands.insert_resource(ExtractedCaptures(request));
+}
+
+fn read_captures(
+    request: Res<ExtractedCaptures>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    readbacks: Res<CaptureReadbacks>,
+) {
+    let (handle, paths) = match &request.0 {
+        Some(request) => request,
+        None => return,
+    };
+    let image = match images.get(handle) {
+        Some(image) => image,
+        None => return,
+    };
+    if let Some(image) = read_image(image, &render_device, &render_queue) {
+        readbacks.0.lock().unwrap().push(CapturedFrame {
+            paths: paths.clone(),
+            image,
+        });
+    }
+}
+
+/// Copies an image rendered this frame back to the CPU, call it after the
+/// render graph ran
+pub(crate) fn read_image(
+    image: &GpuImage,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) -> Option<RgbaImage> {
+    let (width, height) = (image.size.width as u32, image.size.height as u32);
+    let row_len = width * 4;
+    let padded_row_len = padded_row_len(row_len);
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("capture_readback"),
+        size: (padded_row_len * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("capture_readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture: &image.texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row_len),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    render_device.map_buffer(&slice, MapMode::Read);
+    let pixels = rgba_pixels(
+        &slice.get_mapped_range(),
+        row_len,
+        padded_row_len,
+        image.texture_format,
+    );
+    buffer.unmap();
+    RgbaImage::from_raw(width, height, pixels)
+}
+
+/// Pixels of rows of `row_len` bytes stored every `padded_row_len` bytes in
+/// a texture of `format`, in RGBA order
+fn rgba_pixels(bytes: &[u8], row_len: u32, padded_row_len: u32, format: TextureFormat) -> Vec<u8> {
+    let bgra = matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    );
+    bytes
+        .chunks(padded_row_len as usize)
+        .flat_map(|row| row[..row_len as usize].chunks_exact(4))
+        .flat_map(|pixel| {
+            if bgra {
+                [pixel[2], pixel[1], pixel[0], pixel[3]]
+            } else {
+                [pixel[0], pixel[1], pixel[2], pixel[3]]
+            }
+        })
+        .collect()
+}
+
+/// Writes the frames read back on the IO pool
+fn save_captures(readbacks: Res<CaptureReadbacks>, pool: Res<IoTaskPool>) {
+    let frames = std::mem::take(&mut *readbacks.0.lock().unwrap());
+    for frame in frames {
+        pool.spawn(async move {
+            for path in &frame.paths {
+                if let Some(directory) = path.parent() {
+                    if let Err(err) = std::fs::create_dir_all(directory) {
+                        error!("Failed to create capture directory: {err}");
+                        continue;
+                    }
+                }
+                match frame.image.save(path) {
+                    Ok(()) => info!("Captured {path:?}"),
+                    Err(err) => error!("Failed to write {path:?}: {err}"),
+                }
+            }
+        })
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_are_unpadded_and_reordered() {
+        let mut bytes = vec![0; 512];
+        bytes[..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        bytes[256..260].copy_from_slice(&[9, 10, 11, 12]);
+        assert_eq!(
+            rgba_pixels(&bytes, 4, 256, TextureFormat::Rgba8UnormSrgb),
+            vec![1, 2, 3, 4, 9, 10, 11, 12]
+        );
+        assert_eq!(
+            rgba_pixels(&bytes, 8, 256, TextureFormat::Bgra8UnormSrgb)[..8],
+            [3, 2, 1, 4, 7, 6, 5, 8]
+        );
+    }
+}
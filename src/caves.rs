@@ -0,0 +1,337 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::generation::EMPTY;
+
+/// Branches spawned by other branches stop after this many levels
+const MAX_BRANCH_DEPTH: u32 = 3;
+/// Steepest tunnel, in radians from the horizontal plane
+const MAX_PITCH: f32 = 0.6;
+
+/// Carves winding tunnels in the generated density with random walks.
+///
+/// Each worm starts at a random point of the world and walks `length` steps,
+/// turning a little at each step. Worms branch into smaller worms, and
+/// `connections` extra tunnels link random points of the network together,
+/// so the caves form a connected network instead of the isolated pockets
+/// given by a noise threshold.
+///
+/// The tunnels aren't covered by the `deterministic` mode of the
+/// [`WorldSettings`](crate::generation::WorldSettings), the walks use
+/// trigonometric functions that can round differently across platforms.
+#[derive(Inspectable)]
+pub struct CaveSettings {
+    pub enabled: bool,
+    pub seed: u32,
+    /// Number of worms started in the world
+    #[inspectable(min = 0, max = 64)]
+    pub worms: usize,
+    /// Number of steps of each worm, branches are half as long as their parent
+    #[inspectable(min = 1, max = 256)]
+    pub length: usize,
+    /// Distance walked at each step in world units
+    #[inspectable(min = 0.25, max = 8.0, speed = 0.05)]
+    pub step: f32,
+    /// Largest change of direction per step in degrees
+    #[inspectable(min = 0.0, max = 90.0)]
+    pub turn: f32,
+    /// Average radius of the tunnels in world units
+    #[inspectable(min = 0.5, max = 16.0, speed = 0.05)]
+    pub radius: f32,
+    /// How much the radius varies along a tunnel, 0 keeps it constant and 1
+    /// lets it go from 0 to twice the radius
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub radius_variation: f32,
+    /// Chance of a step to start a branch
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.005)]
+    pub branch_chance: f32,
+    /// Number of tunnels linking two random points of the network
+    #[inspectable(min = 0, max = 64)]
+    pub connections: usize,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: 0,
+            worms: 4,
+            length: 48,
+            step: 1.0,
+            turn: 25.0,
+            radius: 2.0,
+            radius_variation: 0.4,
+            branch_chance: 0.03,
+            connections: 2,
+        }
+    }
+}
+
+/// Straight piece of a tunnel
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CaveSegment {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+}
+
+impl CaveSegment {
+    pub fn distance(&self, pos: Vec3) -> f32 {
+        let axis = self.end - self.start;
+        let t = if axis.length_squared() > 0.0 {
+            ((pos - self.start).dot(axis) / axis.length_squared()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        pos.distance(self.start + axis * t)
+    }
+
+    fn overlaps(&self, min: Vec3, max: Vec3) -> bool {
+        let reach = Vec3::splat(self.radius);
+        let segment_min = self.start.min(self.end) - reach;
+        let segment_max = self.start.max(self.end) + reach;
+        segment_min.cmple(max).all() && segment_max.cmpge(min).all()
+    }
+}
+
+/// Tunnels generated from the [`CaveSettings`]
+#[derive(Default, Clone, Debug)]
+pub struct CaveNetwork {
+    pub segments: Vec<CaveSegment>,
+}
+
+/// A worm waiting to be walked
+struct PendingWorm {
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    length: usize,
+    depth: u32,
+}
+
+impl CaveNetwork {
+    /// Walks the worms of `settings` starting in the world box from `min` to
+    /// `max`, identical settings give identical networks
+    pub fn generate(settings: &CaveSettings, min: Vec3, max: Vec3) -> Self {
+        let mut rng = SplitMix64::new(settings.seed);
+        let mut network = Self::default();
+        let base_radius = settings.radius;
+        let variation = settings.radius_variation.clamp(0.0, 1.0);
+        let turn = settings.turn.to_radians();
+
+        let mut pending: Vec<PendingWorm> = (0..settings.worms)
+            .map(|_| PendingWorm {
+                position: min + (max - min) * rng.next_vec3(),
+                yaw: rng.range(-PI, PI),
+                pitch: rng.range(-MAX_PITCH, MAX_PITCH) / 2.0,
+                length: settings.length,
+                depth: 0,
+            })
+            .collect();
+        while let Some(mut worm) = pending.pop() {
+            let mut radius = base_radius;
+            for _ in 0..worm.length {
+                worm.yaw += rng.range(-turn, turn);
+                worm.pitch =
+                    (worm.pitch + rng.range(-turn, turn) / 2.0).clamp(-MAX_PITCH, MAX_PITCH);
+                // slow random walk of the radius so the tunnels swell and narrow
+                radius = (radius + rng.range(-0.25, 0.25) * variation * base_radius).clamp(
+                    base_radius * (1.0 - variation),
+                    base_radius * (1.0 + variation),
+                );
+                let end = worm.position + direction(worm.yaw, worm.pitch) * settings.step;
+                network.segments.push(CaveSegment {
+                    start: worm.position,
+                    end,
+                    radius,
+                });
+                worm.position = end;
+
+                if worm.depth < MAX_BRANCH_DEPTH
+                    && worm.length > 1
+                    && rng.next_f32() < settings.branch_chance
+                {
+                    let side = if rng.next_f32() < 0.5 { -1.0 } else { 1.0 };
+                    pending.push(PendingWorm {
+                        position: end,
+                        yaw: worm.yaw + side * FRAC_PI_2 * rng.range(0.5, 1.0),
+                        pitch: worm.pitch,
+                        length: worm.length / 2,
+                        depth: worm.depth + 1,
+                    });
+                }
+            }
+        }
+
+        for _ in 0..settings.connections {
+            if network.segments.len() < 2 {
+                break;
+            }
+            let pick = |rng: &mut SplitMix64| {
+                let index = (rng.next_f32() * network.segments.len() as f32) as usize;
+                network.segments[index.min(network.segments.len() - 1)].end
+            };
+            let (from, to) = (pick(&mut rng), pick(&mut rng));
+            let tunnel = connect(&mut rng, from, to, settings.step, turn, base_radius);
+            network.segments.extend(tunnel);
+        }
+        network
+    }
+
+    /// Density at `pos` once the tunnels are subtracted from `value`.
+    ///
+    /// The full density is removed within half the radius of a tunnel, then
+    /// less and less up to the radius, so the walls are smooth.
+    pub fn carve(&self, pos: Vec3, value: f32) -> f32 {
+        self.carve_with(self.segments.iter(), pos, value)
+    }
+
+    fn carve_with<'a>(
+        &self,
+        segments: impl Iterator<Item = &'a CaveSegment>,
+        pos: Vec3,
+        value: f32,
+    ) -> f32 {
+        let strength = segments.fold(0.0f32, |strength, segment| {
+            let t = (2.0 * (1.0 - segment.distance(pos) / segment.radius)).clamp(0.0, 1.0);
+            strength.max(t * t * (3.0 - 2.0 * t))
+        });
+        (value - strength).max(EMPTY)
+    }
+
+    /// Carves the points of a chunk of `size` cells starting at the grid
+    /// point `origin`, stored in the order of [`Chunk::points`](crate::chunk::Chunk)
+    pub fn carve_points(&self, points: &mut [f32], origin: IVec3, size: UVec3, cell_size: f32) {
+        let min = origin.as_vec3() * cell_size;
+        let max = (origin + size.as_ivec3()).as_vec3() * cell_size;
+        let segments: Vec<&CaveSegment> = self
+            .segments
+            .iter()
+            .filter(|segment| segment.overlaps(min, max))
+            .collect();
+        if segments.is_empty() {
+            return;
+        }
+        for (value, point) in points
+            .iter_mut()
+            .zip(crate::chunk::Chunk::new_iter_3d(size))
+        {
+            let pos = min + point.as_vec3() * cell_size;
+            *value = self.carve_with(segments.iter().copied(), pos, *value);
+        }
+    }
+}
+
+/// Tunnel wandering from `from` to `to`
+fn connect(
+    rng: &mut SplitMix64,
+    from: Vec3,
+    to: Vec3,
+    step: f32,
+    turn: f32,
+    radius: f32,
+) -> Vec<CaveSegment> {
+    let mut segments = Vec::new();
+    let mut position = from;
+    let max_steps = (2.0 * from.distance(to) / step).ceil() as usize;
+    for _ in 0..max_steps {
+        if position.distance(to) <= step {
+            break;
+        }
+        let toward = (to - position).normalize();
+        let yaw = toward.z.atan2(toward.x) + rng.range(-turn, turn);
+        let pitch = toward.y.asin() + rng.range(-turn, turn) / 2.0;
+        let end = position + direction(yaw, pitch) * step;
+        segments.push(CaveSegment {
+            start: position,
+            end,
+            radius,
+        });
+        position = end;
+    }
+    segments.push(CaveSegment {
+        start: position,
+        end: to,
+        radius,
+    });
+    segments
+}
+
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+        yaw.sin() * pitch.cos(),
+    )
+}
+
+/// Small seedable generator, the walks only need to be reproducible
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u32) -> Self {
+        Self(seed as u64)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    fn next_vec3(&mut self) -> Vec3 {
+        Vec3::new(self.next_f32(), self.next_f32(), self.next_f32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(seed: u32) -> CaveNetwork {
+        let settings = CaveSettings {
+            enabled: true,
+            seed,
+            ..default()
+        };
+        CaveNetwork::generate(&settings, Vec3::ZERO, Vec3::splat(32.0))
+    }
+
+    #[test]
+    fn same_seed_same_network() {
+        assert_eq!(network(3).segments, network(3).segments);
+        assert_ne!(network(3).segments, network(4).segments);
+    }
+
+    #[test]
+    fn tunnels_are_carved() {
+        let network = network(1);
+        let segment = network.segments[0];
+        assert_eq!(network.carve(segment.start, 1.0), EMPTY);
+        let far = Vec3::splat(-1000.0);
+        assert_eq!(network.carve(far, 0.7), 0.7);
+
+        // carving a chunk matches carving each point
+        let size = UVec3::splat(4);
+        let origin = segment.start.floor().as_ivec3() - IVec3::splat(2);
+        let mut points = vec![1.0; crate::chunk::Chunk::points_len(size)];
+        network.carve_points(&mut points, origin, size, 1.0);
+        for (value, point) in points.iter().zip(crate::chunk::Chunk::new_iter_3d(size)) {
+            let pos = (origin + point.as_ivec3()).as_vec3();
+            assert_eq!(*value, network.carve(pos, 1.0));
+        }
+    }
+}
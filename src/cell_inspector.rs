@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContext},
+    Inspectable,
+};
+
+use crate::{
+    brush::cursor_hit,
+    camera::FlyCam,
+    chunk::{is_inside, Chunk, ChunkIsolevel},
+    compaction::{cube_index, triangle_count},
+    field::DensityField,
+    gpu_picking::{GpuPick, GpuPicking},
+    lines::line_mesh,
+    marching_cube_tables::{EDGE_CONNECTION, TRIANGLE_TABLE},
+    presets::{classify, CELL_PRESETS},
+    unlit_material, Data, CELL_CORNERS,
+};
+
+const MAX_DISTANCE: f32 = 500.0;
+
+/// Shows the corners and the table case of the cell under the cursor in a
+/// tooltip and outlines the cell, to debug bad triangles
+#[derive(Inspectable, Default)]
+pub struct CellInspector {
+    pub enabled: bool,
+}
+
+/// Cell of the terrain under the cursor, `None` when the [`CellInspector`] is
+/// disabled or the cursor isn't over the terrain
+#[derive(Default)]
+pub struct HoveredCell(pub Option<CellInfo>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellInfo {
+    pub chunk: Entity,
+    /// Coordinates of the cell in the chunk
+    pub cell: UVec3,
+    /// Values of the corners, in the order of the corners of [`march_cube`](crate::march_cube)
+    pub corners: [f32; 8],
+    pub isolevel: f32,
+    /// Position of the first corner in world space
+    pub origin: Vec3,
+}
+
+impl CellInfo {
+    /// Index of the cell in the marching cube tables
+    pub fn cube_index(&self) -> usize {
+        cube_index(&self.corners, self.isolevel)
+    }
+
+    /// Index in [`CELL_PRESETS`] of the configuration of the cell
+    pub fn case(&self) -> usize {
+        classify(!(self.cube_index() as u8))
+    }
+}
+
+/// Outline of the [`HoveredCell`]
+#[derive(Component)]
+pub struct CellHighlight;
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_hovered_cell(
+    mut commands: Commands,
+    inspector: Res<CellInspector>,
+    data: Res<Data>,
+    windows: Res<Windows>,
+    field: DensityField,
+    cameras: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    chunks: Query<(&Chunk, &GlobalTransform, Option<&ChunkIsolevel>)>,
+    gpu_picking: Res<GpuPicking>,
+    gpu_pick: Res<GpuPick>,
+    mut hovered: ResMut<HoveredCell>,
+    mut highlights: Query<(&mut Transform, &mut Visibility), With<CellHighlight>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut highlight: Local<Option<Entity>>,
+) {
+    let picked = if !inspector.enabled {
+        None
+    } else if gpu_picking.enabled {
+        gpu_pick.0
+    } else {
+        cursor_hit(&windows, &cameras, &field, data.isolevel, MAX_DISTANCE)
+            .and_then(|hit| field.cell(hit))
+            .map(|(chunk_entity, _, cell)| (chunk_entity, cell))
+    };
+    let cell_size = field.cell_size();
+    let info = picked.and_then(|(chunk_entity, cell)| {
+        let (chunk, transform, isolevel) = chunks.get(chunk_entity).ok()?;
+        let cell_pos = cell.as_vec3();
+        Some(CellInfo {
+            chunk: chunk_entity,
+            cell,
+            corners: CELL_CORNERS.map(|corner| chunk.get(cell_pos + corner)),
+            isolevel: isolevel.map_or(data.isolevel, |isolevel| isolevel.0),
+            origin: transform.translation + cell_pos * cell_size,
+        })
+    });
+    if hovered.0 != info {
+        hovered.0 = info;
+    }
+
+    match highlight.and_then(|entity| highlights.get_mut(entity).ok()) {
+        Some((mut transform, mut visibility)) => {
+            if visibility.is_visible != hovered.0.is_some() {
+                visibility.is_visible = hovered.0.is_some();
+            }
+            if let Some(info) = &hovered.0 {
+                *transform =
+                    Transform::from_translation(info.origin).with_scale(Vec3::splat(cell_size));
+            }
+        }
+        None => {
+            let edges: Vec<_> = EDGE_CONNECTION
+                .iter()
+                .map(|[a, b]| (CELL_CORNERS[*a], CELL_CORNERS[*b]))
+                .collect();
+            *highlight = Some(
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: meshes.add(line_mesh(&edges)),
+                        material: materials.add(unlit_material(Color::YELLOW)),
+                        visibility: Visibility { is_visible: false },
+                        ..default()
+                    })
+                    .insert(CellHighlight)
+                    .id(),
+            );
+        }
+    }
+}
+
+pub fn cell_inspector_ui(mut egui_context: ResMut<EguiContext>, hovered: Res<HoveredCell>) {
+    let info = match &hovered.0 {
+        Some(info) => info,
+        None => return,
+    };
+    let ctx = egui_context.ctx_mut();
+    if ctx.wants_pointer_input() {
+        return;
+    }
+
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("cell_inspector"), |ui| {
+        let cell = info.cell;
+        ui.label(format!(
+            "Cell {} {} {} of {:?}",
+            cell.x, cell.y, cell.z, info.chunk
+        ));
+        egui::Grid::new("cell_corners").show(ui, |ui| {
+            for (i, value) in info.corners.iter().enumerate() {
+                let inside = if is_inside(*value, info.isolevel) {
+                    "inside"
+                } else {
+                    "outside"
+                };
+                ui.label(format!("corner {i}"));
+                ui.label(format!("{value:.4}"));
+                ui.label(inside);
+                ui.end_row();
+            }
+        });
+        let cube_index = info.cube_index();
+        ui.label(format!("Cube index {cube_index} ({cube_index:08b})"));
+        ui.label(format!("Case {}", CELL_PRESETS[info.case()].name));
+        let edges: Vec<String> = TRIANGLE_TABLE[cube_index]
+            .chunks_exact(3)
+            .take_while(|triangle| triangle[0] >= 0)
+            .map(|triangle| format!("{} {} {}", triangle[0], triangle[1], triangle[2]))
+            .collect();
+        ui.label(format!(
+            "{} triangles on the edges: {}",
+            triangle_count(cube_index),
+            edges.join(", ")
+        ));
+    });
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use noise::{NoiseFn, RangeFunction, Seedable, Worley};
+
+/// Distance used to find the closest feature point of the cellular noise
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CellularDistance {
+    /// Round bumps
+    Euclidean,
+    /// Diamond shaped facets
+    Manhattan,
+    /// Square blocks and terraces
+    Chebyshev,
+}
+
+impl From<CellularDistance> for RangeFunction {
+    fn from(distance: CellularDistance) -> Self {
+        match distance {
+            CellularDistance::Euclidean => RangeFunction::Euclidean,
+            CellularDistance::Manhattan => RangeFunction::Manhattan,
+            CellularDistance::Chebyshev => RangeFunction::Chebyshev,
+        }
+    }
+}
+
+/// Adds angular rock formations to the terrain with cellular (Worley) noise.
+///
+/// The distance to the closest feature point is added on top of the noise
+/// layers of the [`NoiseSettings`](crate::generation::NoiseSettings), so each cell
+/// becomes a rock with a peak at its feature point and sharp edges where the
+/// cells meet. Stretching the noise vertically turns the rocks into columns.
+#[derive(Inspectable)]
+pub struct CellularSettings {
+    pub enabled: bool,
+    pub seed: u32,
+    /// Number of cells per world unit
+    #[inspectable(min = 0.001, max = 1.0, speed = 0.005)]
+    pub frequency: f64,
+    pub distance: CellularDistance,
+    /// Vertical stretch of the cells, values above 1 give columns
+    #[inspectable(min = 0.1, max = 16.0, speed = 0.05)]
+    pub column_stretch: f64,
+    /// Weight of the cellular noise added to the noise layers, negative values carve
+    /// the cells into the terrain instead
+    #[inspectable(min = -2.0, max = 2.0, speed = 0.01)]
+    pub strength: f64,
+}
+
+impl Default for CellularSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: 0,
+            frequency: 0.08,
+            distance: CellularDistance::Chebyshev,
+            column_stretch: 1.0,
+            strength: 0.5,
+        }
+    }
+}
+
+impl CellularSettings {
+    pub fn worley(&self) -> Worley {
+        Worley::new()
+            .set_seed(self.seed)
+            .set_frequency(self.frequency)
+            .set_range_function(self.distance.into())
+            .enable_range(true)
+    }
+
+    /// Composes `base` with the cellular noise, or returns it unchanged when
+    /// the cellular noise is disabled
+    pub fn compose<N>(&self, base: N) -> CellularRocks<N> {
+        CellularRocks {
+            base,
+            worley: self.enabled.then(|| self.worley()),
+            column_stretch: self.column_stretch.max(0.001),
+            strength: self.strength,
+        }
+    }
+}
+
+/// Sum of a base noise and of the cellular noise of the [`CellularSettings`]
+pub struct CellularRocks<N> {
+    base: N,
+    worley: Option<Worley>,
+    column_stretch: f64,
+    strength: f64,
+}
+
+impl<N: NoiseFn<[f64; 3]>> NoiseFn<[f64; 3]> for CellularRocks<N> {
+    fn get(&self, [x, y, z]: [f64; 3]) -> f64 {
+        let base = self.base.get([x, y, z]);
+        match &self.worley {
+            // the range grows away from the feature points, flip it so the
+            // feature points are the peaks of the rocks
+            Some(worley) => {
+                let range = worley.get([x, y / self.column_stretch, z]).clamp(-1.0, 1.0);
+                base - self.strength * range
+            }
+            None => base,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::Constant;
+
+    use super::*;
+
+    #[test]
+    fn disabled_cells_keep_the_base() {
+        let settings = CellularSettings::default();
+        let noise = settings.compose(Constant::new(0.25));
+        assert_eq!(noise.get([1.0, 2.0, 3.0]), 0.25);
+    }
+
+    #[test]
+    fn columns_are_stretched() {
+        let settings = CellularSettings {
+            enabled: true,
+            column_stretch: 1000.0,
+            ..default()
+        };
+        let noise = settings.compose(Constant::new(0.0));
+        let bottom = noise.get([3.0, 0.0, 5.0]);
+        assert!(bottom.abs() <= settings.strength);
+        // barely changes along a column
+        assert!((noise.get([3.0, 10.0, 5.0]) - bottom).abs() < 0.05);
+    }
+}
@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+
+use crate::{
+    camera::{self, FlyCam},
+    chunk::is_inside,
+    collider::{self, ChunkCollider, ColliderQueue, ColliderSettings},
+    field::DensityField,
+    generation::WorldSettings,
+    terrain_root::MainTerrain,
+    Data,
+};
+
+/// Passes of the collision resolution each frame
+const COLLISION_ITERATIONS: usize = 4;
+/// Steps of half a cell the character is lifted by at most when buried
+const MAX_LIFT_STEPS: usize = 256;
+
+/// Kinematic character walking on the [`ChunkCollider`]s, run the demo with
+/// `--character`.
+///
+/// WASD walks relative to the camera and Space jumps. The slopes steeper than
+/// `max_slope` can't be climbed, the character slides down from them. It holds
+/// still while the collider of a chunk around it is missing or being
+/// rebuilt, so digging the ground out from under it makes it fall once the
+/// new collider is ready instead of falling through the old one, and it is
+/// lifted out of the terrain when an edit buries it.
+#[derive(Inspectable)]
+pub struct CharacterSettings {
+    #[inspectable(min = 0.0, max = 50.0)]
+    pub walk_speed: f32,
+    #[inspectable(min = 0.0, max = 50.0)]
+    pub jump_speed: f32,
+    #[inspectable(min = 0.0, max = 100.0)]
+    pub gravity: f32,
+    /// Steepest slope in degrees the character can stand on
+    #[inspectable(min = 0.0, max = 90.0)]
+    pub max_slope: f32,
+    /// Radius of the capsule, the mesh keeps the size it was spawned with
+    #[inspectable(min = 0.1, max = 4.0)]
+    pub radius: f32,
+    /// Height of the capsule, at least twice the radius
+    #[inspectable(min = 0.2, max = 8.0)]
+    pub height: f32,
+    /// Distance from the camera to the character
+    #[inspectable(min = 1.0, max = 50.0)]
+    pub camera_distance: f32,
+}
+
+impl Default for CharacterSettings {
+    fn default() -> Self {
+        Self {
+            walk_speed: 6.0,
+            jump_speed: 7.0,
+            gravity: 20.0,
+            max_slope: 45.0,
+            radius: 0.4,
+            height: 1.8,
+            camera_distance: 8.0,
+        }
+    }
+}
+
+/// Capsule controlled by the player, its translation is the center of the
+/// capsule
+#[derive(Component, Default)]
+pub struct Character {
+    pub velocity: Vec3,
+    pub grounded: bool,
+    /// Placed above the world
+    placed: bool,
+}
+
+pub struct CharacterDemoPlugin;
+
+impl Plugin for CharacterDemoPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ColliderSettings {
+            enabled: true,
+            ..default()
+        })
+        .add_plugin(InspectorPlugin::<CharacterSettings>::new())
+        .add_startup_system(spawn_character)
+        .add_system(move_character.after(collider::rebuild_colliders))
+        .add_system(
+            follow_character
+                .after(move_character)
+                .after(camera::fly_camera),
+        );
+    }
+}
+
+/// Point of the triangle closest to `point`
+pub fn closest_point_on_triangle(point: Vec3, [a, b, c]: [Vec3; 3]) -> Vec3 {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = point - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = point - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    // inside the face
+    let denom = 1.0 / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+/// Direction and distance to push a sphere out of a triangle it overlaps
+pub fn sphere_contact(center: Vec3, radius: f32, triangle: [Vec3; 3]) -> Option<(Vec3, f32)> {
+    let offset = center - closest_point_on_triangle(center, triangle);
+    let distance = offset.length();
+    if distance >= radius {
+        return None;
+    }
+    let normal = if distance > 1e-6 {
+        offset / distance
+    } else {
+        // the center is on the triangle
+        let [a, b, c] = triangle;
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        if normal == Vec3::ZERO {
+            Vec3::Y
+        } else {
+            normal
+        }
+    };
+    Some((normal, radius - distance))
+}
+
+fn spawn_character(
+    mut commands: Commands,
+    settings: Res<CharacterSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Capsule {
+                radius: settings.radius,
+                depth: (settings.height - 2.0 * settings.radius).max(0.0),
+                ..default()
+            })),
+            material: materials.add(Color::rgb(0.9, 0.45, 0.15).into()),
+            ..default()
+        })
+        .insert(Character::default());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_character(
+    time: Res<Time>,
+    settings: Res<CharacterSettings>,
+    key_input: Res<Input<KeyCode>>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    data: Res<Data>,
+    field: DensityField,
+    collider_queue: Res<ColliderQueue>,
+    colliders: Query<(&ChunkCollider, &GlobalTransform)>,
+    camera: Query<&Transform, (With<FlyCam>, Without<Character>)>,
+    mut characters: Query<(&mut Character, &mut Transform)>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let (mut character, mut transform) = match characters.get_single_mut() {
+        Ok(character) => character,
+        Err(_) => return,
+    };
+    let extent = world_settings.chunk_extent();
+    let world_min = chunk_map.min().as_vec3() * extent;
+    let world_max = world_min + chunk_map.dimensions().as_vec3() * extent;
+    if chunk_map.iter().next().is_none() {
+        return;
+    }
+    if !character.placed || transform.translation.y < world_min.y - extent.y {
+        let center = (world_min + world_max) / 2.0;
+        transform.translation = Vec3::new(center.x, world_max.y + settings.height, center.z);
+        *character = Character {
+            placed: true,
+            ..default()
+        };
+    }
+
+    let radius = settings.radius;
+    let half_segment = Vec3::Y * (settings.height / 2.0 - radius).max(0.0);
+    let reach = Vec3::new(radius, settings.height / 2.0, radius);
+    let bounds_min = transform.translation - reach;
+    let bounds_max = transform.translation + reach;
+
+    // the old collider may still hold up a ground that was dug out
+    let waiting = chunk_map
+        .in_world_box(bounds_min - reach, bounds_max + reach, extent)
+        .any(|(_, entity)| colliders.get(entity).is_err() || collider_queue.is_pending(entity));
+    if waiting {
+        character.velocity.y = 0.0;
+        return;
+    }
+
+    // buried by an edit
+    let cell_size = world_settings.cell_size;
+    let mut lifted = false;
+    for _ in 0..MAX_LIFT_STEPS {
+        let inside = field
+            .density(transform.translation - half_segment)
+            .map_or(false, |value| is_inside(value, data.isolevel));
+        if !inside {
+            break;
+        }
+        transform.translation.y += cell_size / 2.0;
+        lifted = true;
+    }
+    if lifted {
+        character.velocity.y = 0.0;
+    }
+
+    let dt = time.delta_seconds().min(0.05);
+    let mut input = Vec3::ZERO;
+    for (key, direction) in [
+        (KeyCode::W, Vec3::Z),
+        (KeyCode::S, -Vec3::Z),
+        (KeyCode::D, Vec3::X),
+        (KeyCode::A, -Vec3::X),
+    ] {
+        if key_input.pressed(key) {
+            input += direction;
+        }
+    }
+    let (forward, right) = match camera.get_single() {
+        Ok(camera) => (camera.forward(), camera.right()),
+        Err(_) => (-Vec3::Z, Vec3::X),
+    };
+    let forward = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+    let right = Vec3::new(right.x, 0.0, right.z).normalize_or_zero();
+    let walk = (forward * input.z + right * input.x).normalize_or_zero() * settings.walk_speed;
+    character.velocity.x = walk.x;
+    character.velocity.z = walk.z;
+    if character.grounded && key_input.just_pressed(KeyCode::Space) {
+        character.velocity.y = settings.jump_speed;
+    }
+    character.velocity.y -= settings.gravity * dt;
+
+    // the triangles around the whole move
+    let motion = character.velocity * dt;
+    let query_min = bounds_min.min(bounds_min + motion) - Vec3::splat(radius);
+    let query_max = bounds_max.max(bounds_max + motion) + Vec3::splat(radius);
+    let mut triangles = Vec::new();
+    for (_, entity) in chunk_map.in_world_box(query_min, query_max, extent) {
+        let (collider, global_transform) = match colliders.get(entity) {
+            Ok(collider) => collider,
+            Err(_) => continue,
+        };
+        let offset = global_transform.translation;
+        if (collider.min + offset).cmpgt(query_max).any()
+            || (collider.max + offset).cmplt(query_min).any()
+        {
+            continue;
+        }
+        for indices in &collider.indices {
+            let triangle = indices.map(|index| collider.vertices[index as usize] + offset);
+            let triangle_min = triangle[0].min(triangle[1]).min(triangle[2]);
+            let triangle_max = triangle[0].max(triangle[1]).max(triangle[2]);
+            if triangle_min.cmple(query_max).all() && triangle_max.cmpge(query_min).all() {
+                triangles.push(triangle);
+            }
+        }
+    }
+
+    // small steps so a fall doesn't go through the ground
+    let steps = (motion.length() / (radius / 2.0)).ceil().max(1.0) as usize;
+    let min_ground_normal = settings.max_slope.to_radians().cos();
+    let mut grounded = false;
+    for _ in 0..steps {
+        transform.translation += character.velocity * dt / steps as f32;
+        for _ in 0..COLLISION_ITERATIONS {
+            let mut pushed = false;
+            for triangle in &triangles {
+                for sphere in [-half_segment, half_segment] {
+                    let center = transform.translation + sphere;
+                    let (normal, depth) = match sphere_contact(center, radius, *triangle) {
+                        Some(contact) => contact,
+                        None => continue,
+                    };
+                    let push = if normal.y >= min_ground_normal {
+                        grounded = true;
+                        normal * depth
+                    } else {
+                        // too steep, pushed away horizontally so it can't
+                        // be climbed
+                        let wall = Vec3::new(normal.x, 0.0, normal.z).normalize_or_zero();
+                        if normal.y > 0.0 && wall != Vec3::ZERO {
+                            wall * depth / wall.dot(normal).max(0.1)
+                        } else {
+                            normal * depth
+                        }
+                    };
+                    transform.translation += push;
+                    let direction = push.normalize_or_zero();
+                    let into = character.velocity.dot(direction);
+                    if into < 0.0 {
+                        character.velocity -= direction * into;
+                    }
+                    pushed = true;
+                }
+            }
+            if !pushed {
+                break;
+            }
+        }
+    }
+    character.grounded = grounded;
+}
+
+/// Keeps the camera behind the character, it can still be turned with the
+/// right mouse button
+fn follow_character(
+    settings: Res<CharacterSettings>,
+    characters: Query<&Transform, (With<Character>, Without<FlyCam>)>,
+    mut camera: Query<&mut Transform, With<FlyCam>>,
+) {
+    let (character, mut camera) = match (characters.get_single(), camera.get_single_mut()) {
+        (Ok(character), Ok(camera)) => (character, camera),
+        _ => return,
+    };
+    let target = character.translation + Vec3::Y * settings.height / 2.0;
+    camera.translation = target + camera.back() * settings.camera_distance;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_point_is_on_the_face_or_the_edges() {
+        let triangle = [Vec3::ZERO, Vec3::X * 2.0, Vec3::Z * 2.0];
+        assert_eq!(
+            closest_point_on_triangle(Vec3::new(0.5, 1.0, 0.5), triangle),
+            Vec3::new(0.5, 0.0, 0.5)
+        );
+        assert_eq!(
+            closest_point_on_triangle(Vec3::new(-1.0, 0.0, -1.0), triangle),
+            Vec3::ZERO
+        );
+        assert!(
+            closest_point_on_triangle(Vec3::new(2.0, 0.0, 2.0), triangle)
+                .abs_diff_eq(Vec3::new(1.0, 0.0, 1.0), 1e-6)
+        );
+    }
+
+    #[test]
+    fn spheres_are_pushed_out_of_the_triangles() {
+        let floor = [
+            Vec3::new(-5.0, 0.0, -5.0),
+            Vec3::new(-5.0, 0.0, 5.0),
+            Vec3::new(5.0, 0.0, 0.0),
+        ];
+        let (normal, depth) = sphere_contact(Vec3::new(0.0, 0.3, 0.0), 0.5, floor).unwrap();
+        assert!(normal.abs_diff_eq(Vec3::Y, 1e-6));
+        assert!((depth - 0.2).abs() < 1e-6);
+        assert!(sphere_contact(Vec3::new(0.0, 0.6, 0.0), 0.5, floor).is_none());
+    }
+}
@@ -1,120 +1,1079 @@
-use bevy::{
-    prelude::*,
-    render::mesh::{Indices, PrimitiveTopology},
-};
-
-use crate::iters::Iter3d;
-
-#[derive(Component, Clone)]
-pub struct Chunk {
-    pub points: Vec<f32>,
-    pub size: usize,
-}
-
-impl Chunk {
-    pub fn new(points: Vec<f32>, size: usize) -> Self {
-        Self { points, size }
-    }
-
-    pub fn get(&self, pos: Vec3) -> f32 {
-        self.points[self.index(pos)]
-    }
-
-    pub fn set(&mut self, pos: Vec3, value: f32) {
-        let index = self.index(pos);
-        self.points[index] = value;
-    }
-
-    pub fn new_iter_3d(size: u32) -> Iter3d {
-        Iter3d::new(UVec3::ZERO, UVec3::new(size, size, size))
-    }
-
-    fn index(&self, pos: Vec3) -> usize {
-        (pos.z as usize * self.size * self.size) + (pos.y as usize * self.size) + pos.x as usize
-    }
-}
-
-#[derive(Component, Default, Clone)]
-pub struct ChunkMesh {
-    pub triangles: Vec<[Vec3; 3]>,
-}
-
-impl From<ChunkMesh> for Mesh {
-    fn from(chunk: ChunkMesh) -> Self {
-        // This tries to re-use vertices when they share a normal
-        // if they have a different a normal it uses a different index.
-        // This makes it possible to use face normals instead of vertex normals
-        // while still using the smallest amount of vertices possible.
-
-        fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
-            (b - a).cross(c - a).normalize()
-        }
-
-        let mut indices = Vec::new();
-        let mut vertices_normals = Vec::new();
-        for [a, b, c] in chunk.triangles {
-            let normal = face_normal(a, b, c);
-            for vertex in [a, b, c] {
-                // find a matching vertex/normal pair
-                match vertices_normals
-                    .iter()
-                    .position(|&(v, n)| v == vertex && n == normal)
-                {
-                    Some(index) => indices.push(index as u32),
-                    None => {
-                        vertices_normals.push((vertex, normal));
-                        indices.push(vertices_normals.len() as u32 - 1);
-                    }
-                }
-            }
-        }
-
-        let mut positions = Vec::new();
-        let mut uvs = Vec::new();
-        let mut normals = Vec::new();
-
-        for (vertex, normal) in &vertices_normals {
-            positions.push([vertex.x, vertex.y, vertex.z]);
-            uvs.push([0.0, 0.0]);
-            normals.push([normal.x, normal.y, normal.z]);
-        }
-
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh
-    }
-}
-
-/// Computes vertex normals which makes it possible to share the same vertex for multiple face
-fn _compute_vertex_normals(vertices: &Vec<Vec3>, indices: &Vec<u32>) -> Vec<[f32; 3]> {
-    let mut normals = vec![Vec3::ZERO; vertices.len()];
-
-    // For each face, compute the face normal, and accumulate it into each vertex.
-    for indices in indices.chunks(3) {
-        if let [a, b, c] = indices {
-            let edge_ab = vertices[*b as usize] - vertices[*a as usize];
-            let edge_ac = vertices[*c as usize] - vertices[*a as usize];
-
-            // The cross product is perpendicular to both input vectors (normal to the plane).
-            // Flip the argument order if you need the opposite winding.
-            let normal = edge_ab.cross(edge_ac);
-
-            // Don't normalize this vector just yet. Its magnitude is proportional to the
-            // area of the triangle (times 2), so this helps ensure tiny/skinny triangles
-            // don't have an outsized impact on the final normal per vertex.
-            normals[*a as usize] += normal;
-            normals[*b as usize] += normal;
-            normals[*c as usize] += normal;
-        }
-    }
-
-    // Finally, normalize all the sums to get a unit-length, area-weighted average.
-    for normal in normals.iter_mut() {
-        *normal = normal.normalize();
-    }
-
-    normals.iter().map(|n| [n.x, n.y, n.z]).collect()
-}
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+    tasks::TaskPool,
+    utils::HashMap,
+};
+
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    drivable::DrivableSmoothing, field::DensitySource, generation::EMPTY,
+    interpolation::Interpolation, iters::Iter3d,
+};
+
+/// Whether a point with `value` is inside the surface.
+///
+/// Values exactly equal to the isolevel are inside. Quantized or hand edited
+/// points often land on the isolevel, every classification uses this rule so
+/// the cells, the chunks and the queries agree on the side of these points,
+/// and the interpolation places the vertex exactly on them.
+pub fn is_inside(value: f32, isolevel: f32) -> bool {
+    value >= isolevel
+}
+
+#[derive(Component, Clone)]
+pub struct Chunk {
+    /// Writing the points directly isn't tracked, call [`Chunk::mark_dirty`] after
+    pub points: Vec<f32>,
+    /// Number of cells on each axis
+    pub size: UVec3,
+    dirty: Option<DirtyRegion>,
+    revision: u64,
+    /// Region of the last modifications with the revision they produced
+    history: Vec<(u64, DirtyRegion)>,
+}
+
+/// Modifications remembered by [`Chunk::dirty_since`]
+const HISTORY_LEN: usize = 16;
+
+/// Inclusive bounds of the points modified in a chunk
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DirtyRegion {
+    pub min: UVec3,
+    pub max: UVec3,
+}
+
+impl DirtyRegion {
+    pub fn union(self, other: DirtyRegion) -> DirtyRegion {
+        DirtyRegion {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn contains(&self, point: UVec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+}
+
+impl Chunk {
+    /// New chunks are entirely dirty
+    pub fn new(points: Vec<f32>, size: UVec3) -> Self {
+        Self {
+            points,
+            size,
+            dirty: Some(DirtyRegion {
+                min: UVec3::ZERO,
+                max: size,
+            }),
+            revision: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// Chunk of `size` cells with the value returned for each point
+    pub fn from_fn(size: UVec3, value: impl FnMut(UVec3) -> f32) -> Self {
+        Self::new(Self::new_iter_3d(size).map(value).collect(), size)
+    }
+
+    /// Sets every point to the value returned for its position
+    pub fn fill_with(&mut self, value: impl FnMut(UVec3) -> f32) {
+        let values = Self::new_iter_3d(self.size).map(value);
+        for (point, value) in self.points.iter_mut().zip(values) {
+            *point = value;
+        }
+        self.mark_dirty(UVec3::ZERO, self.size);
+    }
+
+    /// Like [`Chunk::fill_with`], with the Z slices of points filled in parallel
+    pub fn par_fill_with(&mut self, pool: &TaskPool, value: impl Fn(UVec3) -> f32 + Sync) {
+        let size = self.size;
+        let slice_len = (size.x as usize + 1) * (size.y as usize + 1);
+        let value = &value;
+        pool.scope(|scope| {
+            for (z, slice) in self.points.chunks_mut(slice_len).enumerate() {
+                scope.spawn(async move {
+                    let min = UVec3::new(0, 0, z as u32);
+                    let max = UVec3::new(size.x, size.y, z as u32);
+                    for (point, pos) in slice.iter_mut().zip(Iter3d::new(min, max)) {
+                        *point = value(pos);
+                    }
+                });
+            }
+        });
+        self.mark_dirty(UVec3::ZERO, size);
+    }
+
+    /// Samples `source` at every point, `transform` maps the grid coordinates
+    /// of the points to world positions, so its scale is the cell size.
+    /// Points where the source has no value are empty.
+    pub fn fill_from(&mut self, source: &impl DensitySource, transform: &Transform) {
+        self.fill_with(|point| {
+            let pos = transform.mul_vec3(point.as_vec3());
+            source.density_at(pos).unwrap_or(EMPTY)
+        });
+    }
+
+    /// Number of points stored by a chunk of `size` cells
+    pub fn points_len(size: UVec3) -> usize {
+        let points = size + UVec3::ONE;
+        points.x as usize * points.y as usize * points.z as usize
+    }
+
+    pub fn get(&self, pos: Vec3) -> f32 {
+        self.points[self.index(pos)]
+    }
+
+    pub fn set(&mut self, pos: Vec3, value: f32) {
+        let index = self.index(pos);
+        self.points[index] = value;
+        let point = pos.as_uvec3();
+        self.mark_dirty(point, point);
+    }
+
+    /// Sets every point from `min` to `max` inclusive to the value returned
+    /// for its position, the region is clamped to the chunk.
+    pub fn set_region(&mut self, min: UVec3, max: UVec3, mut value: impl FnMut(UVec3) -> f32) {
+        let max = max.min(self.size);
+        if min.cmpgt(max).any() {
+            return;
+        }
+        for point in Iter3d::new(min, max) {
+            let index = self.index(point.as_vec3());
+            self.points[index] = value(point);
+        }
+        self.mark_dirty(min, max);
+    }
+
+    /// Records that the points from `min` to `max` inclusive were modified
+    pub fn mark_dirty(&mut self, min: UVec3, max: UVec3) {
+        let region = DirtyRegion { min, max };
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+        self.revision += 1;
+        if self.history.len() == HISTORY_LEN {
+            self.history.remove(0);
+        }
+        self.history.push((self.revision, region));
+    }
+
+    /// Replaces every point, the chunk can change size
+    pub fn replace_points(&mut self, points: Vec<f32>, size: UVec3) {
+        self.points = points;
+        self.size = size;
+        self.mark_dirty(UVec3::ZERO, size);
+    }
+
+    /// Bounds of the points modified since the last [`Chunk::clear_dirty`]
+    pub fn dirty_region(&self) -> Option<DirtyRegion> {
+        self.dirty
+    }
+
+    /// Forgets the modified region, call it once every consumer handled it.
+    /// Consumers that don't own the chunk can compare the [`Chunk::revision`]
+    /// instead.
+    pub fn clear_dirty(&mut self) -> Option<DirtyRegion> {
+        self.dirty.take()
+    }
+
+    /// Bounds of the points modified after `revision`, or `None` when the
+    /// chunk is still at that revision.
+    ///
+    /// Only the last modifications are remembered, the whole chunk is
+    /// returned for older revisions.
+    pub fn dirty_since(&self, revision: u64) -> Option<DirtyRegion> {
+        if revision >= self.revision {
+            return None;
+        }
+        if self.revision - revision > self.history.len() as u64 {
+            return Some(DirtyRegion {
+                min: UVec3::ZERO,
+                max: self.size,
+            });
+        }
+        self.history
+            .iter()
+            .filter(|(edit, _)| *edit > revision)
+            .map(|(_, region)| *region)
+            .reduce(DirtyRegion::union)
+    }
+
+    /// Incremented by every modification of the points
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether some points are solid and others empty, a chunk entirely above
+    /// or below the isolevel has no surface
+    pub fn crosses(&self, isolevel: f32) -> bool {
+        let mut points = self.points.iter();
+        match points.next() {
+            Some(first) => {
+                let solid = is_inside(*first, isolevel);
+                points.any(|value| is_inside(*value, isolevel) != solid)
+            }
+            None => false,
+        }
+    }
+
+    /// Bytes allocated for the points
+    pub fn memory_bytes(&self) -> usize {
+        self.points.capacity() * std::mem::size_of::<f32>()
+    }
+
+    /// Stable hash of the chunk size and point values
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        // the cubic chunks hash their size once, like before the sizes per axis
+        if self.size == UVec3::splat(self.size.x) {
+            hasher.write(&(self.size.x as u64).to_le_bytes());
+        } else {
+            for size in self.size.to_array() {
+                hasher.write(&(size as u64).to_le_bytes());
+            }
+        }
+        for point in &self.points {
+            hasher.write(&point.to_le_bytes());
+        }
+        hasher.finish()
+    }
+
+    /// Volume of the solid part of the chunk enclosed by its marched `mesh`.
+    ///
+    /// The isosurface is open where it leaves the chunk, it's closed by the
+    /// solid part of the faces of the chunk. Relative to the origin of the chunk
+    /// only the faces on the far side of each axis contribute to the volume.
+    pub fn enclosed_volume(
+        &self,
+        mesh: &ChunkMesh,
+        isolevel: f32,
+        interpolation: Interpolation,
+        cell_size: f32,
+    ) -> f32 {
+        let caps: f32 = (0..3)
+            .map(|axis| {
+                let distance = self.size[axis] as f32 * cell_size;
+                distance * self.cap_area(axis, isolevel, interpolation, cell_size)
+            })
+            .sum();
+        mesh.signed_volume() + caps / 3.0
+    }
+
+    /// Area of the solid part of the face of the chunk on the far side of `axis`.
+    ///
+    /// Each square of the face is marched like the faces of the cubes so the
+    /// outline matches the mesh. Squares with 2 opposite solid corners are
+    /// split in 2 corners, which may not match the triangulation of the cube.
+    pub fn cap_area(
+        &self,
+        axis: usize,
+        isolevel: f32,
+        interpolation: Interpolation,
+        cell_size: f32,
+    ) -> f32 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut face = Vec3::ZERO;
+        face[axis] = self.size[axis] as f32;
+        let (mut du, mut dv) = (Vec3::ZERO, Vec3::ZERO);
+        du[u] = 1.0;
+        dv[v] = 1.0;
+
+        let mut area = 0.0;
+        for j in 0..self.size[v] {
+            for i in 0..self.size[u] {
+                let origin = face + du * i as f32 + dv * j as f32;
+                let corners = [origin, origin + du, origin + du + dv, origin + dv];
+                let values = corners.map(|corner| self.get(corner));
+                let solid = values.map(|value| is_inside(value, isolevel));
+
+                let crossing = |a: usize, b: usize| {
+                    // same direction as the edges of the cubes to get the same vertex
+                    let (a, b) = if corners[a].cmpgt(corners[b]).any() {
+                        (b, a)
+                    } else {
+                        (a, b)
+                    };
+                    interpolation
+                        .interpolate(isolevel, corners[a], corners[b], values[a], values[b])
+                };
+
+                let saddle = solid[0] == solid[2] && solid[1] == solid[3] && solid[0] != solid[1];
+                let polygons: Vec<Vec<Vec3>> = if saddle {
+                    (0..4)
+                        .filter(|&i| solid[i])
+                        .map(|i| {
+                            let (prev, next) = ((i + 3) % 4, (i + 1) % 4);
+                            vec![crossing(prev, i), corners[i], crossing(i, next)]
+                        })
+                        .collect()
+                } else {
+                    let mut polygon = Vec::new();
+                    for (i, corner) in corners.iter().enumerate() {
+                        let next = (i + 1) % 4;
+                        if solid[i] {
+                            polygon.push(*corner);
+                        }
+                        if solid[i] != solid[next] {
+                            polygon.push(crossing(i, next));
+                        }
+                    }
+                    vec![polygon]
+                };
+
+                for polygon in polygons {
+                    let mut vector_area = Vec3::ZERO;
+                    for (i, a) in polygon.iter().enumerate() {
+                        vector_area += a.cross(polygon[(i + 1) % polygon.len()]);
+                    }
+                    area += vector_area[axis].abs() / 2.0;
+                }
+            }
+        }
+        area * cell_size * cell_size
+    }
+
+    /// Iterates from zero to `max` included
+    pub fn new_iter_3d(max: UVec3) -> Iter3d {
+        Iter3d::new(UVec3::ZERO, max)
+    }
+
+    /// Points are stored on a grid of `size + 1` points per axis so the last
+    /// cell of the chunk has all its corners
+    fn index(&self, pos: Vec3) -> usize {
+        let stride_y = self.size.x as usize + 1;
+        let stride_z = stride_y * (self.size.y as usize + 1);
+        (pos.z as usize * stride_z) + (pos.y as usize * stride_y) + pos.x as usize
+    }
+}
+
+/// FNV-1a hasher.
+///
+/// Unlike `std::hash`, the result doesn't depend on the Rust version or the
+/// platform so it can be stored on disk and compared across runs.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl StableHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Inputs of the last march of a chunk, used to skip marching it again when
+/// nothing changed
+#[derive(Component, Default, Clone, Copy, PartialEq)]
+pub struct MeshedFrom {
+    pub points_hash: u64,
+    pub isolevel: f32,
+    pub interpolation: Interpolation,
+    pub cell_size: f32,
+    /// Jitter of the grid points in world units, 0 without jitter
+    pub jitter: f32,
+    pub sharp_features: bool,
+    pub drivable: DrivableSmoothing,
+    /// Revision of the [`HermiteData`](crate::hermite::HermiteData) used for
+    /// the crossings and the normals, if any
+    pub hermite_revision: Option<u64>,
+}
+
+/// Cells crossed by the surface in the last march of a chunk, in the order
+/// they were marched.
+///
+/// When only the isolevel changes, the next march floods from these cells
+/// instead of going through every cell of the chunk.
+#[derive(Component, Default, Clone)]
+pub struct ActiveCells(pub Vec<UVec3>);
+
+/// Overrides the global isolevel for a single chunk.
+///
+/// The surface won't line up with the neighboring chunks if they use a
+/// different isolevel.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct ChunkIsolevel(pub f32);
+
+/// Builds the mesh of the chunk without welding its vertices, see
+/// [`ChunkMesh::to_non_indexed_mesh`].
+///
+/// Chunks with fewer triangles than `Data::non_indexed_max_triangles` use the
+/// non indexed path without this marker. It's read when the chunk is remeshed.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct NonIndexed;
+
+/// Stage of the generation pipeline a chunk is in
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChunkStatus {
+    /// The points changed since the chunk was last marched
+    Dirty,
+    /// The triangles were generated but not uploaded to the mesh yet
+    Meshing,
+    /// The mesh is up to date
+    Loaded,
+    /// The mesh is up to date and has no triangles
+    Empty,
+}
+
+impl Default for ChunkStatus {
+    fn default() -> Self {
+        ChunkStatus::Dirty
+    }
+}
+
+/// Position of a chunk in the chunk grid
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ChunkCoord(pub IVec3);
+
+/// Generates the points of a chunk again from the world generator at its
+/// coordinate, removed once they are
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct RegenerateChunk;
+
+/// Lookup of chunk entities by their coordinate in the chunk grid of a
+/// terrain, on its [`TerrainRoot`](crate::terrain_root::TerrainRoot)
+#[derive(Component, Default)]
+pub struct ChunkMap {
+    chunks: HashMap<IVec3, Entity>,
+    min: IVec3,
+    max: IVec3,
+}
+
+impl ChunkMap {
+    pub fn insert(&mut self, coord: IVec3, entity: Entity) {
+        if self.chunks.is_empty() {
+            self.min = coord;
+            self.max = coord;
+        } else {
+            self.min = self.min.min(coord);
+            self.max = self.max.max(coord);
+        }
+        self.chunks.insert(coord, entity);
+    }
+
+    /// Removes the chunk at `coord`, the bounds shrink to the chunks left
+    pub fn remove(&mut self, coord: IVec3) -> Option<Entity> {
+        let entity = self.chunks.remove(&coord)?;
+        let mut coords = self.chunks.keys().copied();
+        if let Some(first) = coords.next() {
+            let (min, max) = coords.fold((first, first), |(min, max), coord| {
+                (min.min(coord), max.max(coord))
+            });
+            self.min = min;
+            self.max = max;
+        }
+        Some(entity)
+    }
+
+    pub fn get(&self, coord: IVec3) -> Option<Entity> {
+        self.chunks.get(&coord).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, Entity)> + '_ {
+        self.chunks.iter().map(|(coord, entity)| (*coord, *entity))
+    }
+
+    /// Smallest chunk coordinate in the map
+    pub fn min(&self) -> IVec3 {
+        self.min
+    }
+
+    /// Number of chunks along each axis
+    pub fn dimensions(&self) -> IVec3 {
+        self.max - self.min + IVec3::ONE
+    }
+
+    /// Chunks overlapping the box from `min` to `max` in world space, including
+    /// the chunks that only share a face with it since their border points
+    /// are shared
+    pub fn in_world_box(
+        &self,
+        min: Vec3,
+        max: Vec3,
+        chunk_extent: Vec3,
+    ) -> impl Iterator<Item = (IVec3, Entity)> + '_ {
+        let min_coord = ((min / chunk_extent).ceil().as_ivec3() - IVec3::ONE).max(self.min);
+        let max_coord = (max / chunk_extent).floor().as_ivec3().min(self.max);
+        let empty = self.chunks.is_empty() || min_coord.cmpgt(max_coord).any();
+        let count = if empty {
+            UVec3::ZERO
+        } else {
+            (max_coord - min_coord + IVec3::ONE).as_uvec3()
+        };
+        Iter3d::new(UVec3::ZERO, count.max(UVec3::ONE) - UVec3::ONE)
+            .filter(move |_| !empty)
+            .map(move |offset| min_coord + offset.as_ivec3())
+            .filter_map(|coord| Some((coord, self.get(coord)?)))
+    }
+
+    /// Finds the chunk at `offset` from `coord`.
+    ///
+    /// When `wrap` is set, lookups that fall off the X or Z edge of the map
+    /// come back in from the opposite edge.
+    pub fn neighbor(&self, coord: IVec3, offset: IVec3, wrap: bool) -> Option<Entity> {
+        let mut target = coord + offset;
+        if wrap {
+            let dimensions = self.dimensions();
+            target.x = self.min.x + (target.x - self.min.x).rem_euclid(dimensions.x);
+            target.z = self.min.z + (target.z - self.min.z).rem_euclid(dimensions.z);
+        }
+        self.get(target)
+    }
+}
+
+#[derive(Component, Default, Clone)]
+pub struct ChunkMesh {
+    pub triangles: Vec<[Vec3; 3]>,
+}
+
+impl ChunkMesh {
+    /// Bytes allocated for the triangles
+    pub fn memory_bytes(&self) -> usize {
+        self.triangles.capacity() * std::mem::size_of::<[Vec3; 3]>()
+    }
+
+    /// Stable hash of the triangle count and vertex positions
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        hasher.write(&(self.triangles.len() as u64).to_le_bytes());
+        for vertex in self.triangles.iter().flatten() {
+            for coord in vertex.to_array() {
+                hasher.write(&coord.to_le_bytes());
+            }
+        }
+        hasher.finish()
+    }
+}
+
+impl From<ChunkMesh> for Mesh {
+    fn from(chunk: ChunkMesh) -> Self {
+        chunk.to_mesh(NormalMode::Flat, |_| None)
+    }
+}
+
+/// How the normals of the chunk meshes are computed
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NormalMode {
+    /// One normal per triangle, vertices are only shared by coplanar triangles
+    Flat,
+    /// Average of the normals of the triangles sharing a vertex
+    Smooth,
+    /// Gradient of the density field at each vertex
+    Gradient,
+}
+
+impl Default for NormalMode {
+    fn default() -> Self {
+        NormalMode::Flat
+    }
+}
+
+/// Triangles sharing their vertices
+#[derive(Default, Clone, Debug)]
+pub struct IndexedMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    /// 3 indices per triangle
+    pub indices: Vec<u32>,
+}
+
+impl IndexedMesh {
+    /// Reorders the triangles for the vertex cache of the GPU, see
+    /// [`optimize_vertex_cache`](crate::vertex_cache::optimize_vertex_cache)
+    pub fn optimize_vertex_cache(&mut self) {
+        let _span = info_span!("vertex_cache", triangles = self.indices.len() / 3).entered();
+        crate::vertex_cache::optimize_vertex_cache(&mut self.indices, self.positions.len());
+    }
+}
+
+impl From<IndexedMesh> for Mesh {
+    fn from(indexed: IndexedMesh) -> Self {
+        let uvs = vec![[0.0, 0.0]; indexed.positions.len()];
+        let positions: Vec<[f32; 3]> = indexed.positions.iter().map(|p| p.to_array()).collect();
+        let normals: Vec<[f32; 3]> = indexed.normals.iter().map(|n| n.to_array()).collect();
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_indices(Some(Indices::U32(indexed.indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh
+    }
+}
+
+impl ChunkMesh {
+    /// Triangles with a non zero area
+    pub fn iter_triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.triangles
+            .iter()
+            .copied()
+            .filter(|[a, b, c]| (*b - *a).cross(*c - *a) != Vec3::ZERO)
+    }
+
+    /// Area of the surface in the chunk
+    pub fn surface_area(&self) -> f32 {
+        self.triangles
+            .iter()
+            .map(|[a, b, c]| (*b - *a).cross(*c - *a).length() / 2.0)
+            .sum()
+    }
+
+    /// Sum of the signed volumes of the tetrahedrons formed by each triangle
+    /// and the origin of the chunk.
+    ///
+    /// It's the enclosed volume when the surface is closed. Triangles face the
+    /// empty side of the surface, so the volume of the solid is positive.
+    pub fn signed_volume(&self) -> f32 {
+        self.triangles
+            .iter()
+            .map(|[a, b, c]| a.dot(b.cross(*c)) / 6.0)
+            .sum()
+    }
+
+    /// Builds the render mesh of the chunk, see [`ChunkMesh::indexed`]
+    pub fn to_mesh(
+        &self,
+        mode: NormalMode,
+        gradient_normal: impl Fn(Vec3) -> Option<Vec3>,
+    ) -> Mesh {
+        self.indexed(mode, gradient_normal).into()
+    }
+
+    /// Builds the render mesh without sharing vertices between triangles.
+    ///
+    /// Faster to build than [`ChunkMesh::to_mesh`] since nothing is welded,
+    /// which suits small meshes rebuilt often, but every triangle uploads its
+    /// own 3 vertices. The normals are the face normals, or the gradient where
+    /// `gradient_normal` returns one. Smooth normals need the welded vertices.
+    pub fn to_non_indexed_mesh(&self, gradient_normal: impl Fn(Vec3) -> Option<Vec3>) -> Mesh {
+        let _span = info_span!("non_indexed", triangles = self.triangles.len()).entered();
+        let mut positions = Vec::with_capacity(self.triangles.len() * 3);
+        let mut normals = Vec::with_capacity(self.triangles.len() * 3);
+        for &[a, b, c] in &self.triangles {
+            let face_normal = (b - a).cross(c - a).normalize_or_zero();
+            for vertex in [a, b, c] {
+                let normal = match gradient_normal(vertex) {
+                    // keep the side of the triangle like the indexed path
+                    Some(gradient) if gradient != Vec3::ZERO => {
+                        if gradient.dot(face_normal) < 0.0 {
+                            -gradient
+                        } else {
+                            gradient
+                        }
+                    }
+                    _ => face_normal,
+                };
+                positions.push(vertex.to_array());
+                normals.push(normal.to_array());
+            }
+        }
+        let uvs = vec![[0.0, 0.0]; positions.len()];
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh
+    }
+
+    /// Shares the vertices between triangles and computes their normals.
+    ///
+    /// `gradient_normal` returns the normal of the density field at a position
+    /// in the chunk, the smooth normal is used where it returns `None`. It's
+    /// ignored by [`NormalMode::Flat`]. With [`NormalMode::Smooth`], return it
+    /// only for the vertices on the faces of the chunk, where the triangles of
+    /// the neighboring chunks are missing from the average.
+    pub fn indexed(
+        &self,
+        mode: NormalMode,
+        gradient_normal: impl Fn(Vec3) -> Option<Vec3>,
+    ) -> IndexedMesh {
+        let _span = info_span!("welding", ?mode).entered();
+        let (positions, normals, indices) = match mode {
+            NormalMode::Flat => self.flat_vertices(),
+            NormalMode::Smooth | NormalMode::Gradient => {
+                let (positions, indices) = self.welded();
+                let mut normals = compute_vertex_normals(&positions, &indices);
+                for (normal, position) in normals.iter_mut().zip(&positions) {
+                    if let Some(gradient) = gradient_normal(*position) {
+                        if gradient == Vec3::ZERO {
+                            continue;
+                        }
+                        // keep the side of the triangles so every mode lights the same side
+                        *normal = if gradient.dot(*normal) < 0.0 {
+                            -gradient
+                        } else {
+                            gradient
+                        };
+                    }
+                }
+                (positions, normals, indices)
+            }
+        };
+        IndexedMesh {
+            positions,
+            normals,
+            indices,
+        }
+    }
+
+    /// Vertices with face normals
+    fn flat_vertices(&self) -> (Vec<Vec3>, Vec<Vec3>, Vec<u32>) {
+        // This tries to re-use vertices when they share a normal
+        // if they have a different a normal it uses a different index.
+        // This makes it possible to use face normals instead of vertex normals
+        // while still using the smallest amount of vertices possible.
+
+        fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+            (b - a).cross(c - a).normalize()
+        }
+
+        let mut indices = Vec::new();
+        let mut vertices_normals = Vec::new();
+        for &[a, b, c] in &self.triangles {
+            let normal = face_normal(a, b, c);
+            for vertex in [a, b, c] {
+                // find a matching vertex/normal pair
+                match vertices_normals
+                    .iter()
+                    .position(|&(v, n)| v == vertex && n == normal)
+                {
+                    Some(index) => indices.push(index as u32),
+                    None => {
+                        vertices_normals.push((vertex, normal));
+                        indices.push(vertices_normals.len() as u32 - 1);
+                    }
+                }
+            }
+        }
+
+        let (positions, normals) = vertices_normals.into_iter().unzip();
+        (positions, normals, indices)
+    }
+
+    /// Positions shared by the triangles, and 3 indices per triangle
+    pub fn welded(&self) -> (Vec<Vec3>, Vec<u32>) {
+        let mut positions = Vec::new();
+        let mut indices = Vec::with_capacity(self.triangles.len() * 3);
+        let mut position_indices = HashMap::default();
+        for vertex in self.triangles.iter().flatten() {
+            // neighboring cells compute bit identical vertices for the edges they share
+            let key = vertex.to_array().map(f32::to_bits);
+            let index = *position_indices.entry(key).or_insert_with(|| {
+                positions.push(*vertex);
+                positions.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+        (positions, indices)
+    }
+}
+
+/// Computes vertex normals which makes it possible to share the same vertex for multiple face
+pub(crate) fn compute_vertex_normals(vertices: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+
+    // For each face, compute the face normal, and accumulate it into each vertex.
+    for indices in indices.chunks(3) {
+        if let [a, b, c] = indices {
+            let edge_ab = vertices[*b as usize] - vertices[*a as usize];
+            let edge_ac = vertices[*c as usize] - vertices[*a as usize];
+
+            // The cross product is perpendicular to both input vectors (normal to the plane).
+            // Flip the argument order if you need the opposite winding.
+            let normal = edge_ab.cross(edge_ac);
+
+            // Don't normalize this vector just yet. Its magnitude is proportional to the
+            // area of the triangle (times 2), so this helps ensure tiny/skinny triangles
+            // don't have an outsized impact on the final normal per vertex.
+            normals[*a as usize] += normal;
+            normals[*b as usize] += normal;
+            normals[*c as usize] += normal;
+        }
+    }
+
+    // Finally, normalize all the sums to get a unit-length, area-weighted average.
+    // Vertices only used by degenerate triangles don't have a normal.
+    for normal in normals.iter_mut() {
+        *normal = normal.normalize_or_zero();
+    }
+
+    normals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, ChunkMap, ChunkMesh, NormalMode, HISTORY_LEN};
+    use crate::{flood_active_cells, interpolation::Interpolation, march_cube, GridCell};
+    use bevy::{
+        math::{IVec3, UVec3, Vec3},
+        prelude::{Entity, Transform},
+        render::mesh::{Mesh, VertexAttributeValues},
+        tasks::TaskPool,
+    };
+
+    /// Tetrahedron with its 3 right angles at the origin, wound like the marched triangles
+    fn tetrahedron() -> ChunkMesh {
+        let (o, x, y, z) = (Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z);
+        ChunkMesh {
+            triangles: vec![[o, y, x], [o, x, z], [o, z, y], [x, y, z]],
+        }
+    }
+
+    #[test]
+    fn tetrahedron_area_and_volume() {
+        let mesh = tetrahedron();
+        let area = 1.5 + 3f32.sqrt() / 2.0;
+        assert!((mesh.surface_area() - area).abs() < 1e-6);
+        assert!((mesh.signed_volume() - 1.0 / 6.0).abs() < 1e-6);
+    }
+
+    /// Marches a chunk where the points are solid below `height`
+    fn ground(size: UVec3, height: f32) -> (Chunk, ChunkMesh) {
+        let chunk = Chunk::from_fn(size, |pos| (height - pos.y as f32 + 0.5).clamp(0.0, 1.0));
+        let mut mesh = ChunkMesh::default();
+        for pos in Chunk::new_iter_3d(size - UVec3::ONE) {
+            let grid_cell = GridCell::sample(pos.as_vec3(), 1.0, &chunk);
+            if let Some(triangles) = march_cube(&grid_cell, 0.5, Interpolation::Linear) {
+                mesh.triangles.extend(triangles);
+            }
+        }
+        (chunk, mesh)
+    }
+
+    #[test]
+    fn ground_volume() {
+        let (chunk, mesh) = ground(UVec3::splat(4), 1.5);
+        assert!((mesh.surface_area() - 16.0).abs() < 1e-4);
+        let volume = chunk.enclosed_volume(&mesh, 0.5, Interpolation::Linear, 1.0);
+        assert!((volume - 24.0).abs() < 1e-4, "{volume}");
+
+        let (chunk, mesh) = ground(UVec3::new(2, 6, 3), 1.5);
+        assert!((mesh.surface_area() - 6.0).abs() < 1e-4);
+        let volume = chunk.enclosed_volume(&mesh, 0.5, Interpolation::Linear, 1.0);
+        assert!((volume - 9.0).abs() < 1e-4, "{volume}");
+    }
+
+    /// Cells of a full march of `chunk`
+    fn active_cells(chunk: &Chunk, isolevel: f32) -> Vec<UVec3> {
+        Chunk::new_iter_3d(chunk.size - UVec3::ONE)
+            .filter(|pos| {
+                let grid_cell = GridCell::sample(pos.as_vec3(), 1.0, chunk);
+                march_cube(&grid_cell, isolevel, Interpolation::Linear).is_some()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flooded_cells_match_full_march() {
+        let size = UVec3::new(8, 6, 7);
+        let center = Vec3::new(3.6, 2.8, 3.1);
+        let mut chunk = Chunk::new(vec![0.0; Chunk::points_len(size)], size);
+        for pos in Chunk::new_iter_3d(size) {
+            let pos = pos.as_vec3();
+            chunk.set(pos, 1.0 - pos.distance(center) / 5.0);
+        }
+        let previous = active_cells(&chunk, 0.5);
+        for isolevel in [0.45, 0.5, 0.58] {
+            let flooded = flood_active_cells(&chunk, &previous, isolevel);
+            assert_eq!(flooded, active_cells(&chunk, isolevel), "{isolevel}");
+        }
+    }
+
+    #[test]
+    fn non_cubic_points_are_distinct() {
+        let size = UVec3::new(2, 4, 3);
+        let mut chunk = Chunk::new(vec![0.0; Chunk::points_len(size)], size);
+        for (i, pos) in Chunk::new_iter_3d(size).enumerate() {
+            chunk.set(pos.as_vec3(), i as f32);
+        }
+        for (i, pos) in Chunk::new_iter_3d(size).enumerate() {
+            assert_eq!(chunk.get(pos.as_vec3()), i as f32);
+        }
+    }
+
+    #[test]
+    fn solid_chunk_volume() {
+        let size = UVec3::splat(4);
+        let chunk = Chunk::new(vec![1.0; Chunk::points_len(size)], size);
+        let volume = chunk.enclosed_volume(&ChunkMesh::default(), 0.5, Interpolation::Linear, 1.0);
+        assert!((volume - 64.0).abs() < 1e-4);
+        let volume = chunk.enclosed_volume(&ChunkMesh::default(), 0.5, Interpolation::Linear, 0.5);
+        assert!((volume - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn indexed_shares_vertices() {
+        let mesh = tetrahedron();
+        let smooth = mesh.indexed(NormalMode::Smooth, |_| None);
+        assert_eq!(smooth.positions.len(), 4);
+        assert_eq!(smooth.indices.len(), 12);
+        // each face has its own normal
+        let flat = mesh.indexed(NormalMode::Flat, |_| None);
+        assert_eq!(flat.positions.len(), 12);
+    }
+
+    #[test]
+    fn smooth_normals_use_the_gradient_where_given() {
+        let mesh = tetrahedron();
+        let smooth = mesh.indexed(NormalMode::Smooth, |_| None);
+        // only the vertex at the origin is on a seam
+        let seams = mesh.indexed(NormalMode::Smooth, |pos| {
+            (pos == Vec3::ZERO).then(|| Vec3::Y)
+        });
+        for ((position, normal), smooth_normal) in seams
+            .positions
+            .iter()
+            .zip(&seams.normals)
+            .zip(&smooth.normals)
+        {
+            if *position == Vec3::ZERO {
+                // on the side of the averaged normal
+                assert_eq!(*normal, -Vec3::Y);
+            } else {
+                assert_eq!(normal, smooth_normal);
+            }
+        }
+    }
+
+    #[test]
+    fn fill_from_samples_world_positions() {
+        let size = UVec3::new(3, 2, 4);
+        let transform = Transform::from_xyz(8.0, 0.0, -8.0).with_scale(Vec3::splat(0.5));
+        let mut chunk = Chunk::new(vec![0.0; Chunk::points_len(size)], size);
+        chunk.fill_from(&|pos: Vec3| pos.x + pos.z, &transform);
+        let expected = Chunk::from_fn(size, |p| 8.0 + p.x as f32 * 0.5 - 8.0 + p.z as f32 * 0.5);
+        assert_eq!(chunk.points, expected.points);
+
+        let pool = TaskPool::new();
+        chunk.par_fill_with(&pool, |p| p.x as f32 * 0.5 + p.z as f32 * 0.5);
+        assert_eq!(chunk.points, expected.points);
+    }
+
+    #[test]
+    fn world_box_includes_touching_chunks() {
+        let mut map = ChunkMap::default();
+        for x in -1..=1 {
+            map.insert(IVec3::new(x, 0, 0), Entity::from_raw((x + 1) as u32));
+        }
+        let extent = Vec3::splat(16.0);
+        let coords = |min: Vec3, max: Vec3| {
+            map.in_world_box(min, max, extent)
+                .map(|(coord, _)| coord.x)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            coords(Vec3::new(2.0, 2.0, 2.0), Vec3::new(4.0, 4.0, 4.0)),
+            [0]
+        );
+        // the face at x = 16 is shared by chunks 0 and 1
+        assert_eq!(
+            coords(Vec3::new(16.0, 2.0, 2.0), Vec3::new(20.0, 4.0, 4.0)),
+            [0, 1]
+        );
+        assert_eq!(coords(Vec3::splat(-100.0), Vec3::splat(100.0)), [-1, 0, 1]);
+        assert!(coords(Vec3::new(2.0, 40.0, 2.0), Vec3::new(4.0, 50.0, 4.0)).is_empty());
+    }
+
+    #[test]
+    fn dirty_region_grows_with_edits() {
+        let size = UVec3::splat(4);
+        let mut chunk = Chunk::new(vec![0.0; Chunk::points_len(size)], size);
+        assert_eq!(chunk.clear_dirty().map(|dirty| dirty.max), Some(size));
+        assert_eq!(chunk.dirty_region(), None);
+
+        chunk.set(Vec3::new(1.0, 2.0, 3.0), 1.0);
+        chunk.set_region(UVec3::new(2, 0, 2), UVec3::splat(8), |_| 0.5);
+        let dirty = chunk.dirty_region().unwrap();
+        assert_eq!(dirty.min, UVec3::new(1, 0, 2));
+        assert_eq!(dirty.max, size);
+        assert_eq!(chunk.revision(), 2);
+        assert_eq!(chunk.get(Vec3::splat(4.0)), 0.5);
+        assert_eq!(chunk.get(Vec3::new(1.0, 2.0, 3.0)), 1.0);
+        assert_eq!(chunk.get(Vec3::ZERO), 0.0);
+    }
+
+    #[test]
+    fn dirty_since_forgets_old_edits() {
+        let size = UVec3::splat(4);
+        let mut chunk = Chunk::new(vec![0.0; Chunk::points_len(size)], size);
+        chunk.set(Vec3::new(1.0, 2.0, 3.0), 1.0);
+        chunk.set_region(UVec3::new(2, 0, 2), UVec3::splat(8), |_| 0.5);
+
+        assert_eq!(chunk.dirty_since(2), None);
+        let since = chunk.dirty_since(1).unwrap();
+        assert_eq!((since.min, since.max), (UVec3::new(2, 0, 2), size));
+        for _ in 0..HISTORY_LEN {
+            chunk.set(Vec3::ONE, 0.0);
+        }
+        assert_eq!(
+            chunk.dirty_since(1).map(|dirty| dirty.min),
+            Some(UVec3::ZERO)
+        );
+        assert_eq!(
+            chunk.dirty_since(3).map(|dirty| dirty.max),
+            Some(UVec3::ONE)
+        );
+    }
+
+    #[test]
+    fn buried_chunk_doesnt_cross() {
+        let size = UVec3::splat(3);
+        let mut chunk = Chunk::from_fn(size, |_| 1.0);
+        assert!(!chunk.crosses(0.5));
+        chunk.set(Vec3::new(1.0, 2.0, 1.0), 0.2);
+        assert!(chunk.crosses(0.5));
+        assert!(!chunk.crosses(0.1));
+    }
+
+    #[test]
+    fn non_indexed_has_face_normals() {
+        let mesh = tetrahedron().to_non_indexed_mesh(|_| None);
+        assert!(mesh.indices().is_none());
+        assert_eq!(mesh.count_vertices(), 12);
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => normals,
+            _ => panic!("missing normals"),
+        };
+        // the first face lies on the XY plane and faces away from the solid
+        assert!(normals[..3].iter().all(|&n| n == [0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn degenerate_triangles_are_skipped() {
+        let mut mesh = tetrahedron();
+        mesh.triangles.push([Vec3::ZERO, Vec3::X, Vec3::X]);
+        assert_eq!(mesh.iter_triangles().count(), 4);
+    }
+
+    // These hashes are shown in the stats window to compare chunks between
+    // runs and builds, they must never change
+    #[test]
+    fn chunk_hash_is_stable() {
+        let chunk = Chunk::new((0..8).map(|i| i as f32 * 0.125).collect(), UVec3::ONE);
+        assert_eq!(chunk.content_hash(), 0x5e41b4bf345071f6);
+        let size = UVec3::new(1, 2, 1);
+        let chunk = Chunk::new((0..12).map(|i| i as f32 * 0.125).collect(), size);
+        assert_eq!(chunk.content_hash(), 0xcbadad7563322799);
+    }
+
+    #[test]
+    fn mesh_hash_is_stable() {
+        let mesh = ChunkMesh {
+            triangles: vec![[Vec3::ZERO, Vec3::X, Vec3::Y]],
+        };
+        assert_eq!(mesh.content_hash(), 0xda7b798c4a5c7b24);
+    }
+}
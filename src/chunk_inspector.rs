@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{
+    bevy_egui::egui, Context, Inspectable, RegisterInspectable, WorldInspectorPlugin,
+};
+
+use crate::chunk::Chunk;
+
+/// Shows the entities in the egui world inspector, with a viewer for the
+/// points of the chunks
+pub struct ChunkInspectorPlugin;
+
+impl Plugin for ChunkInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(WorldInspectorPlugin::new())
+            .register_inspectable::<Chunk>();
+    }
+}
+
+impl Inspectable for Chunk {
+    type Attributes = ();
+
+    fn ui(&mut self, ui: &mut egui::Ui, _: Self::Attributes, _: &mut Context) -> bool {
+        let (min, max) = self
+            .points
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+
+        egui::Grid::new("chunk_inspector").show(ui, |ui| {
+            ui.label("Size");
+            ui.label(format!(
+                "{} x {} x {}",
+                self.size.x, self.size.y, self.size.z
+            ));
+            ui.end_row();
+
+            ui.label("Density");
+            ui.label(format!("{min:.3} to {max:.3}"));
+            ui.end_row();
+
+            ui.label("Dirty");
+            match self.dirty_region() {
+                Some(dirty) => ui.label(format!("{} to {}", dirty.min, dirty.max)),
+                None => ui.label("no"),
+            };
+            ui.end_row();
+
+            ui.label("Revision");
+            ui.label(self.revision().to_string());
+            ui.end_row();
+        });
+
+        // the slice is UI state, keep it in the egui memory of this widget
+        let slice_id = ui.id().with("chunk_slice");
+        let mut slice = *ui.data().get_temp_mut_or_default::<u32>(slice_id);
+        ui.add(egui::Slider::new(&mut slice, 0..=self.size.z).text("Z slice"));
+        ui.data().insert_temp(slice_id, slice);
+
+        egui::ScrollArea::both().max_height(240.0).show(ui, |ui| {
+            egui::Grid::new("chunk_slice").striped(true).show(ui, |ui| {
+                // highest row first so the slice reads like a side view
+                for y in (0..=self.size.y).rev() {
+                    for x in 0..=self.size.x {
+                        let pos = UVec3::new(x, y, slice).as_vec3();
+                        ui.monospace(format!("{:.2}", self.get(pos)));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        false
+    }
+}
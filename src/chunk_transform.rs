@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    chunk::{Chunk, ChunkCoord, ChunkMap, RegenerateChunk},
+    generation::WorldSettings,
+    hermite::HermiteData,
+    lines::line_mesh,
+    terrain_root::{MainTerrain, TerrainRoot},
+    unlit_material, StartMarching,
+};
+
+/// Where a moved chunk gets its points from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveMode {
+    /// Generated again from the world generator at the new location, the
+    /// edits are lost
+    Regenerate,
+    /// Keeps the points, with the edits, and turns them with the chunk
+    Carry,
+}
+
+impl Default for MoveMode {
+    fn default() -> Self {
+        MoveMode::Regenerate
+    }
+}
+
+/// Moves a chunk by `offset` chunks and turns it by `quarter_turns` around
+/// the Y axis, counterclockwise seen from above.
+///
+/// A chunk already at the destination takes the place of the moved one. The
+/// turns need the same number of cells on X and Z and only apply when the
+/// points are carried.
+pub struct MoveChunk {
+    pub chunk: Entity,
+    pub offset: IVec3,
+    pub quarter_turns: u32,
+    pub mode: MoveMode,
+}
+
+/// Moves the selected chunk on the chunk grid from the `Chunk transform`
+/// window, with a gizmo showing its axes
+#[derive(Default)]
+pub struct ChunkTransformTool {
+    pub mode: MoveMode,
+    pub show_gizmo: bool,
+}
+
+/// Lines of the axes of the selected chunk
+#[derive(Component)]
+pub struct TransformGizmo;
+
+/// Points of a chunk of `size` cells turned by `quarter_turns` around the Y
+/// axis, `None` when X and Z don't have the same number of cells
+pub fn rotate_points_y(points: &[f32], size: UVec3, quarter_turns: u32) -> Option<Vec<f32>> {
+    if size.x != size.z {
+        return None;
+    }
+    let n = size.x;
+    let index =
+        |point: UVec3| ((point.z * (size.y + 1) + point.y) * (size.x + 1) + point.x) as usize;
+    let mut rotated = vec![0.0; points.len()];
+    for point in Chunk::new_iter_3d(size) {
+        // where the point lands, (x, z) turns to (z, n - x)
+        let mut target = point;
+        for _ in 0..quarter_turns % 4 {
+            target = UVec3::new(target.z, target.y, n - target.x);
+        }
+        rotated[index(target)] = points[index(point)];
+    }
+    Some(rotated)
+}
+
+pub fn chunk_transform_ui(
+    mut egui_context: ResMut<EguiContext>,
+    terrain: MainTerrain,
+    mut tool: ResMut<ChunkTransformTool>,
+    coords: Query<&ChunkCoord>,
+    mut move_events: EventWriter<MoveChunk>,
+) {
+    let (entity, coord) = match terrain
+        .selected_chunk()
+        .and_then(|entity| Some((entity, coords.get(entity).ok()?)))
+    {
+        Some(selected) => selected,
+        None => return,
+    };
+
+    let mut offset = IVec3::ZERO;
+    let mut quarter_turns = 0;
+    egui::Window::new("Chunk transform")
+        .default_open(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!("Chunk {}", coord.0));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut tool.mode, MoveMode::Regenerate, "Regenerate");
+                ui.radio_value(&mut tool.mode, MoveMode::Carry, "Carry the points");
+            });
+            ui.checkbox(&mut tool.show_gizmo, "Show gizmo");
+            let axes = [
+                ("X", IVec3::X, egui::Color32::RED),
+                ("Y", IVec3::Y, egui::Color32::GREEN),
+                ("Z", IVec3::Z, egui::Color32::LIGHT_BLUE),
+            ];
+            for (name, axis, color) in axes {
+                ui.horizontal(|ui| {
+                    ui.colored_label(color, name);
+                    if ui.button("-").clicked() {
+                        offset -= axis;
+                    }
+                    if ui.button("+").clicked() {
+                        offset += axis;
+                    }
+                });
+            }
+            ui.add_enabled_ui(tool.mode == MoveMode::Carry, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Turn left").clicked() {
+                        quarter_turns = 1;
+                    }
+                    if ui.button("Turn right").clicked() {
+                        quarter_turns = 3;
+                    }
+                });
+            });
+        });
+
+    if offset != IVec3::ZERO || quarter_turns != 0 {
+        move_events.send(MoveChunk {
+            chunk: entity,
+            offset,
+            quarter_turns,
+            mode: tool.mode,
+        });
+    }
+}
+
+pub fn move_chunks(
+    mut commands: Commands,
+    mut events: EventReader<MoveChunk>,
+    mut terrains: Query<(&mut ChunkMap, &WorldSettings), With<TerrainRoot>>,
+    mut chunks: Query<(
+        &mut Chunk,
+        &mut ChunkCoord,
+        &mut Transform,
+        Option<&mut HermiteData>,
+        &Parent,
+    )>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let mut moved = false;
+    for event in events.iter() {
+        // moved on the grid of its terrain
+        let (from, root) = match chunks.get(event.chunk) {
+            Ok((_, coord, .., parent)) => (coord.0, parent.0),
+            Err(_) => continue,
+        };
+        let (mut chunk_map, world_settings) = match terrains.get_mut(root) {
+            Ok(terrain) => terrain,
+            Err(_) => continue,
+        };
+        let to = from + event.offset;
+        let mut moves = vec![(event.chunk, to, event.quarter_turns)];
+        if to != from {
+            match chunk_map.get(to) {
+                // swapped, the other chunk isn't turned
+                Some(other) => {
+                    chunk_map.insert(from, other);
+                    moves.push((other, from, 0));
+                }
+                None => {
+                    chunk_map.remove(from);
+                }
+            }
+            chunk_map.insert(to, event.chunk);
+        }
+
+        for (entity, coord, quarter_turns) in moves {
+            let (mut chunk, mut chunk_coord, mut transform, hermite, _) =
+                match chunks.get_mut(entity) {
+                    Ok(chunk) => chunk,
+                    Err(_) => continue,
+                };
+            chunk_coord.0 = coord;
+            transform.translation = coord.as_vec3() * world_settings.chunk_extent();
+            match event.mode {
+                MoveMode::Regenerate => {
+                    commands.entity(entity).insert(RegenerateChunk);
+                }
+                MoveMode::Carry => {
+                    if quarter_turns % 4 != 0 {
+                        match rotate_points_y(&chunk.points, chunk.size, quarter_turns) {
+                            Some(points) => chunk.points = points,
+                            None => warn!("can't turn a chunk with a different size on X and Z"),
+                        }
+                    }
+                    // the crossings were found at the old location
+                    if let Some(mut hermite) = hermite {
+                        *hermite = HermiteData::default();
+                    }
+                    let size = chunk.size;
+                    chunk.mark_dirty(UVec3::ZERO, size);
+                }
+            }
+            moved = true;
+        }
+    }
+    if moved {
+        start_marching_events.send_default();
+    }
+}
+
+/// Draws the axes of the selected chunk from its center while the gizmo is
+/// shown
+#[allow(clippy::too_many_arguments)]
+pub fn update_transform_gizmo(
+    mut commands: Commands,
+    tool: Res<ChunkTransformTool>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    chunks: Query<&Transform, With<Chunk>>,
+    gizmos: Query<Entity, With<TransformGizmo>>,
+    mut drawn: Local<Option<Vec3>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let extent = world_settings.chunk_extent();
+    let center = terrain
+        .selected_chunk()
+        .filter(|_| tool.show_gizmo)
+        .and_then(|entity| chunks.get(entity).ok())
+        .map(|transform| transform.translation + extent / 2.0);
+    if *drawn == center {
+        return;
+    }
+    *drawn = center;
+    for entity in gizmos.iter() {
+        commands.entity(entity).despawn();
+    }
+    let center = match center {
+        Some(center) => center,
+        None => return,
+    };
+
+    let axes = [
+        (Vec3::X, Vec3::Y, Color::RED),
+        (Vec3::Y, Vec3::X, Color::GREEN),
+        (Vec3::Z, Vec3::Y, Color::BLUE),
+    ];
+    for (axis, side, color) in axes {
+        let end = center + axis * extent;
+        // arrow head pointing along the axis
+        let side = side * extent.min_element() * 0.1;
+        let back = end - axis * extent.min_element() * 0.15;
+        let segments = [(center, end), (end, back + side), (end, back - side)];
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(line_mesh(&segments)),
+                material: materials.add(unlit_material(color)),
+                ..default()
+            })
+            .insert(TransformGizmo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_go_around_the_y_axis() {
+        let size = UVec3::new(4, 2, 4);
+        let chunk = Chunk::from_fn(size, |point| {
+            (point.x * 100 + point.y * 10 + point.z) as f32
+        });
+        let points = rotate_points_y(&chunk.points, size, 1).unwrap();
+        let turned = Chunk::new(points, size);
+        // the +X side turns to -Z
+        assert_eq!(
+            turned.get(Vec3::new(0.0, 1.0, 0.0)),
+            chunk.get(Vec3::new(4.0, 1.0, 0.0))
+        );
+        assert_eq!(
+            turned.get(Vec3::new(1.0, 0.0, 4.0)),
+            chunk.get(Vec3::new(0.0, 0.0, 1.0))
+        );
+
+        let back = rotate_points_y(&turned.points, size, 3).unwrap();
+        assert_eq!(back, chunk.points);
+        assert_eq!(
+            rotate_points_y(&chunk.points, size, 4).unwrap(),
+            chunk.points
+        );
+        assert!(rotate_points_y(&chunk.points, UVec3::new(4, 2, 3), 1).is_none());
+    }
+}
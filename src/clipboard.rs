@@ -0,0 +1,339 @@
+use std::ops::DerefMut;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    chunk::{Chunk, ChunkCoord},
+    generation::EMPTY,
+    iters::Iter3d,
+    terrain_root::MainTerrain,
+    StartMarching,
+};
+
+/// Box of density values copied from the field
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldRegion {
+    /// Number of points on each axis
+    pub size: UVec3,
+    /// Ordered like the points of a chunk, X first then Y then Z
+    pub points: Vec<f32>,
+}
+
+impl FieldRegion {
+    /// Copies the points from `min` to `max` inclusive, in world grid
+    /// coordinates. Points outside of the given chunks are empty.
+    pub fn copy<'a>(
+        min: IVec3,
+        max: IVec3,
+        chunks: impl IntoIterator<Item = (IVec3, &'a Chunk)>,
+    ) -> Self {
+        let size = (max - min + IVec3::ONE).max(IVec3::ZERO).as_uvec3();
+        let len = size.x as usize * size.y as usize * size.z as usize;
+        let mut region = Self {
+            size,
+            points: vec![EMPTY; len],
+        };
+        for (coord, chunk) in chunks {
+            let chunk_origin = coord * chunk.size.as_ivec3();
+            if let Some((local_min, local_max)) = overlap(chunk_origin, chunk.size, min, size) {
+                for local in Iter3d::new(local_min, local_max) {
+                    let point = (chunk_origin + local.as_ivec3() - min).as_uvec3();
+                    let index = region.index(point);
+                    region.points[index] = chunk.get(local.as_vec3());
+                }
+            }
+        }
+        region
+    }
+
+    /// Writes the region with its first point at `origin` in world grid
+    /// coordinates, into every given chunk it overlaps.
+    ///
+    /// The points shared by neighboring chunks are written in each of them so
+    /// the seams stay closed.
+    pub fn paste<C: DerefMut<Target = Chunk>>(
+        &self,
+        origin: IVec3,
+        chunks: impl IntoIterator<Item = (IVec3, C)>,
+    ) {
+        for (coord, mut chunk) in chunks {
+            let chunk_origin = coord * chunk.size.as_ivec3();
+            if let Some((local_min, local_max)) =
+                overlap(chunk_origin, chunk.size, origin, self.size)
+            {
+                chunk.set_region(local_min, local_max, |local| {
+                    self.get((chunk_origin + local.as_ivec3() - origin).as_uvec3())
+                });
+            }
+        }
+    }
+
+    pub fn get(&self, point: UVec3) -> f32 {
+        self.points[self.index(point)]
+    }
+
+    /// Flips the region along an axis, 0 is X, 1 is Y and 2 is Z
+    pub fn mirrored(&self, axis: usize) -> Self {
+        let points = self
+            .iter_points()
+            .map(|mut point| {
+                point[axis] = self.size[axis] - 1 - point[axis];
+                self.get(point)
+            })
+            .collect();
+        Self {
+            size: self.size,
+            points,
+        }
+    }
+
+    /// Rotates the region by 90° counterclockwise around the Y axis, seen from above
+    pub fn rotated_y(&self) -> Self {
+        let size = UVec3::new(self.size.z, self.size.y, self.size.x);
+        let rotated = Self {
+            size,
+            points: Vec::new(),
+        };
+        let points = rotated
+            .iter_points()
+            .map(|point| self.get(UVec3::new(self.size.x - 1 - point.z, point.y, point.x)))
+            .collect();
+        Self { size, points }
+    }
+
+    fn iter_points(&self) -> impl Iterator<Item = UVec3> {
+        let empty = self.size.cmpeq(UVec3::ZERO).any();
+        Iter3d::new(UVec3::ZERO, self.size.max(UVec3::ONE) - UVec3::ONE).filter(move |_| !empty)
+    }
+
+    fn index(&self, point: UVec3) -> usize {
+        let size = self.size;
+        (point.z as usize * size.y as usize + point.y as usize) * size.x as usize + point.x as usize
+    }
+}
+
+/// Points of the chunk at `chunk_origin` inside the box of `size` points
+/// starting at `min`, in chunk coordinates
+fn overlap(
+    chunk_origin: IVec3,
+    chunk_size: UVec3,
+    min: IVec3,
+    size: UVec3,
+) -> Option<(UVec3, UVec3)> {
+    if size.cmpeq(UVec3::ZERO).any() {
+        return None;
+    }
+    let max = min + size.as_ivec3() - IVec3::ONE;
+    let local_min = (min - chunk_origin).max(IVec3::ZERO);
+    let local_max = (max - chunk_origin).min(chunk_size.as_ivec3());
+    if local_min.cmpgt(local_max).any() {
+        return None;
+    }
+    Some((local_min.as_uvec3(), local_max.as_uvec3()))
+}
+
+/// Copied region of the field and the settings of the clipboard window
+#[derive(Default)]
+pub struct Clipboard {
+    pub region: Option<FieldRegion>,
+    /// First corner of the copied box, in world grid coordinates
+    pub copy_min: IVec3,
+    /// Last corner of the copied box, inclusive
+    pub copy_max: IVec3,
+    /// Where the first point of the region is pasted
+    pub paste_origin: IVec3,
+    /// Mirror the pasted region along X, Y and Z
+    pub mirror: [bool; 3],
+    /// Quarter turns around the Y axis applied after mirroring
+    pub rotations: u32,
+}
+
+impl Clipboard {
+    /// Region with the mirroring and rotations applied
+    pub fn transformed_region(&self) -> Option<FieldRegion> {
+        let mut region = self.region.clone()?;
+        for axis in 0..3 {
+            if self.mirror[axis] {
+                region = region.mirrored(axis);
+            }
+        }
+        for _ in 0..self.rotations % 4 {
+            region = region.rotated_y();
+        }
+        Some(region)
+    }
+}
+
+fn ivec3_ui(ui: &mut egui::Ui, value: &mut IVec3) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut value.x).prefix("x "));
+        ui.add(egui::DragValue::new(&mut value.y).prefix("y "));
+        ui.add(egui::DragValue::new(&mut value.z).prefix("z "));
+    });
+}
+
+/// Window to copy a box of the density field and paste it elsewhere
+pub fn clipboard_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut clipboard: ResMut<Clipboard>,
+    terrain: MainTerrain,
+    mut chunks: Query<(&ChunkCoord, &mut Chunk, &Parent)>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let clipboard = &mut *clipboard;
+    let (mut copy, mut paste) = (false, false);
+    egui::Window::new("Clipboard")
+        .default_open(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Grid::new("clipboard").show(ui, |ui| {
+                ui.label("Copy from");
+                ivec3_ui(ui, &mut clipboard.copy_min);
+                ui.end_row();
+
+                ui.label("Copy to");
+                ivec3_ui(ui, &mut clipboard.copy_max);
+                ui.end_row();
+
+                ui.label("");
+                ui.horizontal(|ui| {
+                    let selected = terrain
+                        .selected_chunk()
+                        .and_then(|entity| chunks.get(entity).ok());
+                    if let Some((coord, chunk, _)) = selected {
+                        if ui.button("Selected chunk").clicked() {
+                            clipboard.copy_min = coord.0 * chunk.size.as_ivec3();
+                            clipboard.copy_max = clipboard.copy_min + chunk.size.as_ivec3();
+                        }
+                    }
+                    copy = ui.button("Copy").clicked();
+                });
+                ui.end_row();
+
+                ui.label("Paste at");
+                ivec3_ui(ui, &mut clipboard.paste_origin);
+                ui.end_row();
+
+                ui.label("Mirror");
+                ui.horizontal(|ui| {
+                    for (axis, name) in ["X", "Y", "Z"].into_iter().enumerate() {
+                        ui.checkbox(&mut clipboard.mirror[axis], name);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Rotation");
+                ui.horizontal(|ui| {
+                    for (rotations, name) in ["0°", "90°", "180°", "270°"].into_iter().enumerate()
+                    {
+                        ui.selectable_value(&mut clipboard.rotations, rotations as u32, name);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("");
+                ui.horizontal(|ui| {
+                    paste = ui
+                        .add_enabled(clipboard.region.is_some(), egui::Button::new("Paste"))
+                        .clicked();
+                    if let Some(region) = &clipboard.region {
+                        ui.label(format!(
+                            "{}x{}x{} points",
+                            region.size.x, region.size.y, region.size.z
+                        ));
+                    }
+                });
+                ui.end_row();
+            });
+        });
+
+    // the coordinates are on the grid of the main terrain
+    let root = terrain.root();
+    if copy {
+        let min = clipboard.copy_min.min(clipboard.copy_max);
+        let max = clipboard.copy_min.max(clipboard.copy_max);
+        clipboard.region = Some(FieldRegion::copy(
+            min,
+            max,
+            chunks
+                .iter()
+                .filter(|(.., parent)| Some(parent.0) == root)
+                .map(|(coord, chunk, _)| (coord.0, chunk)),
+        ));
+        info!("Copied the field from {min} to {max}");
+    }
+    if paste {
+        if let Some(region) = clipboard.transformed_region() {
+            region.paste(
+                clipboard.paste_origin,
+                chunks
+                    .iter_mut()
+                    .filter(|(.., parent)| Some(parent.0) == root)
+                    .map(|(coord, chunk, _)| (coord.0, chunk)),
+            );
+            info!("Pasted the field at {}", clipboard.paste_origin);
+            start_marching_events.send_default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(size: UVec3) -> FieldRegion {
+        let len = (size.x * size.y * size.z) as usize;
+        FieldRegion {
+            size,
+            points: (0..len).map(|i| i as f32).collect(),
+        }
+    }
+
+    #[test]
+    fn transforms_round_trip() {
+        let region = numbered(UVec3::new(2, 3, 4));
+        let rotated = region.rotated_y();
+        assert_eq!(rotated.size, UVec3::new(4, 3, 2));
+        assert_ne!(rotated, region);
+        let full_turn = rotated.rotated_y().rotated_y().rotated_y();
+        assert_eq!(full_turn, region);
+        for axis in 0..3 {
+            assert_eq!(region.mirrored(axis).mirrored(axis), region);
+        }
+        assert_eq!(region.mirrored(0).get(UVec3::ZERO), region.get(UVec3::X));
+    }
+
+    #[test]
+    fn rotation_turns_counterclockwise() {
+        let region = numbered(UVec3::new(2, 1, 1));
+        // +X becomes -Z
+        let rotated = region.rotated_y();
+        assert_eq!(rotated.get(UVec3::new(0, 0, 0)), region.get(UVec3::X));
+        assert_eq!(rotated.get(UVec3::new(0, 0, 1)), region.get(UVec3::ZERO));
+    }
+
+    #[test]
+    fn paste_writes_shared_points() {
+        let size = UVec3::splat(2);
+        let mut chunks = [
+            (IVec3::ZERO, Chunk::from_fn(size, |_| 0.0)),
+            (IVec3::X, Chunk::from_fn(size, |_| 0.0)),
+        ];
+        let region = numbered(UVec3::new(3, 1, 1));
+        region.paste(
+            IVec3::new(1, 1, 1),
+            chunks.iter_mut().map(|(coord, chunk)| (*coord, chunk)),
+        );
+        // x = 2 is the last point of the first chunk and the first of the second
+        assert_eq!(chunks[0].1.get(Vec3::new(2.0, 1.0, 1.0)), 1.0);
+        assert_eq!(chunks[1].1.get(Vec3::new(0.0, 1.0, 1.0)), 1.0);
+        assert_eq!(chunks[1].1.get(Vec3::new(1.0, 1.0, 1.0)), 2.0);
+
+        let copied = FieldRegion::copy(
+            IVec3::new(1, 1, 1),
+            IVec3::new(3, 1, 1),
+            chunks.iter().map(|(coord, chunk)| (*coord, chunk)),
+        );
+        assert_eq!(copied, region);
+    }
+}
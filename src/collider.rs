@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
+};
+use bevy_inspector_egui::Inspectable;
+use futures_lite::future;
+
+use crate::chunk::ChunkMesh;
+
+/// Rebuilds the [`ChunkCollider`]s of the remeshed chunks on the async
+/// compute pool.
+///
+/// The rebuilds are queued and only a few are started each frame, so a large
+/// edit spreads its colliders over the next frames instead of stalling the
+/// rendering. The colliders lag behind the meshes until the queue is empty.
+#[derive(Inspectable)]
+pub struct ColliderSettings {
+    pub enabled: bool,
+    /// Rebuilds started each frame
+    #[inspectable(min = 1, max = 64)]
+    pub rebuilds_per_frame: usize,
+    /// Rebuilds running at the same time
+    #[inspectable(min = 1, max = 64)]
+    pub max_in_flight: usize,
+}
+
+impl Default for ColliderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rebuilds_per_frame: 2,
+            max_in_flight: 4,
+        }
+    }
+}
+
+/// Triangle mesh of a chunk for the physics, in the local space of the chunk.
+///
+/// The vertices shared by several triangles are welded.
+#[derive(Component, Default, Clone, Debug)]
+pub struct ChunkCollider {
+    pub vertices: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ChunkCollider {
+    pub fn from_triangles(triangles: impl IntoIterator<Item = [Vec3; 3]>) -> Self {
+        let mut ids: HashMap<[u32; 3], u32> = HashMap::default();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for triangle in triangles {
+            let triangle = triangle.map(|vertex| {
+                *ids.entry(vertex.to_array().map(f32::to_bits))
+                    .or_insert_with(|| {
+                        min = min.min(vertex);
+                        max = max.max(vertex);
+                        vertices.push(vertex);
+                        vertices.len() as u32 - 1
+                    })
+            });
+            indices.push(triangle);
+        }
+        if vertices.is_empty() {
+            min = Vec3::ZERO;
+            max = Vec3::ZERO;
+        }
+        Self {
+            vertices,
+            indices,
+            min,
+            max,
+        }
+    }
+}
+
+/// Chunks waiting for their collider and the rebuilds in flight
+#[derive(Default)]
+pub struct ColliderQueue {
+    queue: VecDeque<Entity>,
+    queued: HashSet<Entity>,
+    tasks: HashMap<Entity, Task<ChunkCollider>>,
+}
+
+impl ColliderQueue {
+    /// Colliders waiting to be rebuilt or being rebuilt
+    pub fn pending(&self) -> usize {
+        self.queue.len() + self.tasks.len()
+    }
+
+    /// Whether the collider of the chunk is waiting to be rebuilt or being
+    /// rebuilt
+    pub fn is_pending(&self, entity: Entity) -> bool {
+        self.queued.contains(&entity) || self.tasks.contains_key(&entity)
+    }
+}
+
+pub fn rebuild_colliders(
+    mut commands: Commands,
+    settings: Res<ColliderSettings>,
+    mut queue: ResMut<ColliderQueue>,
+    pool: Res<AsyncComputeTaskPool>,
+    changed_chunks: Query<Entity, Changed<ChunkMesh>>,
+    chunks: Query<&ChunkMesh>,
+) {
+    if !settings.enabled {
+        // dropping the tasks cancels them
+        *queue = ColliderQueue::default();
+        return;
+    }
+    let queue = &mut *queue;
+    for entity in changed_chunks.iter() {
+        if queue.queued.insert(entity) {
+            queue.queue.push_back(entity);
+        }
+    }
+
+    let mut finished = Vec::new();
+    for (entity, task) in queue.tasks.iter_mut() {
+        if let Some(collider) = future::block_on(future::poll_once(task)) {
+            finished.push((*entity, collider));
+        }
+    }
+    for (entity, collider) in finished {
+        queue.tasks.remove(&entity);
+        // despawned while it was rebuilt
+        if chunks.get(entity).is_ok() {
+            commands.entity(entity).insert(collider);
+        }
+    }
+
+    let mut started = 0;
+    // the chunks still being rebuilt wait for their rebuild to finish
+    let mut waiting = Vec::new();
+    while started < settings.rebuilds_per_frame && queue.tasks.len() < settings.max_in_flight {
+        let entity = match queue.queue.pop_front() {
+            Some(entity) => entity,
+            None => break,
+        };
+        if queue.tasks.contains_key(&entity) {
+            waiting.push(entity);
+            continue;
+        }
+        queue.queued.remove(&entity);
+        let chunk_mesh = match chunks.get(entity) {
+            Ok(chunk_mesh) => chunk_mesh,
+            Err(_) => continue,
+        };
+        // the triangles without an area would break the physics
+        let triangles: Vec<[Vec3; 3]> = chunk_mesh.iter_triangles().collect();
+        let task = pool.spawn(async move {
+            let _span = info_span!("chunk_collider", triangles = triangles.len()).entered();
+            ChunkCollider::from_triangles(triangles)
+        });
+        queue.tasks.insert(entity, task);
+        started += 1;
+    }
+    for entity in waiting.into_iter().rev() {
+        queue.queue.push_front(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_vertices_are_welded() {
+        let quad = [
+            [Vec3::ZERO, Vec3::Z, Vec3::X],
+            [Vec3::X, Vec3::Z, Vec3::new(1.0, 0.0, 1.0)],
+        ];
+        let collider = ChunkCollider::from_triangles(quad);
+        assert_eq!(collider.vertices.len(), 4);
+        assert_eq!(collider.indices, vec![[0, 1, 2], [2, 1, 3]]);
+        assert_eq!(collider.min, Vec3::ZERO);
+        assert_eq!(collider.max, Vec3::new(1.0, 0.0, 1.0));
+
+        let empty = ChunkCollider::from_triangles(Vec::new());
+        assert!(empty.indices.is_empty());
+        assert_eq!(empty.min, Vec3::ZERO);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{chunk::is_inside, marching_cube_tables::TRIANGLE_TABLE};
+
+/// Index in the marching cubes tables of a cell with the corner `values`,
+/// each corner outside of the surface sets its bit
+pub fn cube_index(values: &[f32; 8], isolevel: f32) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| !is_inside(**value, isolevel))
+        .fold(0, |index, (i, _)| index | 1 << i)
+}
+
+/// Number of triangles generated for the cells of `cube_index`
+pub fn triangle_count(cube_index: usize) -> u32 {
+    TRIANGLE_TABLE[cube_index]
+        .iter()
+        .take_while(|edge| **edge >= 0)
+        .count() as u32
+        / 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_match_the_table() {
+        assert_eq!(triangle_count(0), 0);
+        assert_eq!(triangle_count(255), 0);
+        // a single corner below the isolevel
+        assert_eq!(
+            triangle_count(cube_index(&[0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0], 0.5)),
+            1
+        );
+        assert_eq!(cube_index(&[0.0; 8], 0.5), 255);
+    }
+
+    #[test]
+    fn isolevel_ties_are_inside() {
+        assert!(is_inside(0.5, 0.5));
+        assert_eq!(cube_index(&[0.5; 8], 0.5), 0);
+        // the corners on the isolevel don't flip the classification
+        let values = [0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5];
+        assert_eq!(cube_index(&values, 0.5), 0b10);
+        assert_eq!(triangle_count(cube_index(&values, 0.5)), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, RenderTarget},
+        view::RenderLayers,
+    },
+};
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContext},
+    Inspectable,
+};
+
+use crate::{
+    camera::follow_fly_cam,
+    chunk::Chunk,
+    inspection_view::{add_image_camera, fit_render_target, render_target_image},
+    mesh_parts::ChunkMeshPart,
+};
+
+const COMPARE_CAMERA: &str = "compare_camera";
+const COMPARE_PASS_DRIVER: &str = "compare_pass_driver";
+/// Render layer of the frozen meshes, only seen by the compare camera
+const FROZEN_LAYER: u8 = 1;
+
+/// Split-screen comparison of the terrain before and after tweaking the
+/// settings.
+///
+/// Press C to freeze the chunk meshes, the left part of the screen keeps
+/// showing them from the camera while the right part shows the live terrain
+/// updated with the new settings. Press C again to leave, freezing again
+/// takes a new reference.
+#[derive(Inspectable)]
+pub struct CompareMode {
+    pub enabled: bool,
+    /// Fraction of the screen width showing the frozen meshes
+    #[inspectable(min = 0.1, max = 0.9, speed = 0.01)]
+    pub split: f32,
+}
+
+impl Default for CompareMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            split: 0.5,
+        }
+    }
+}
+
+/// Copy of a chunk mesh taken when the [`CompareMode`] was enabled
+#[derive(Component)]
+pub struct FrozenMesh;
+
+/// Camera following the [`FlyCam`](crate::camera::FlyCam) to render the [`FrozenMesh`]es
+#[derive(Component)]
+struct CompareCamera;
+
+struct CompareImage {
+    handle: Handle<Image>,
+    texture_id: egui::TextureId,
+    camera: Entity,
+}
+
+#[derive(Default)]
+struct CompareTarget(Option<CompareImage>);
+
+pub struct CompareModePlugin;
+
+impl Plugin for CompareModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CompareTarget>()
+            .add_system(toggle_compare_mode)
+            .add_system(update_compare_mode.after(toggle_compare_mode))
+            .add_system(follow_fly_cam::<CompareCamera>.after(update_compare_mode))
+            .add_system(compare_mode_ui.after(update_compare_mode));
+
+        add_image_camera(app, COMPARE_CAMERA, COMPARE_PASS_DRIVER);
+    }
+}
+
+fn toggle_compare_mode(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<CompareMode>) {
+    if keyboard_input.just_pressed(KeyCode::C) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_compare_mode(
+    mut commands: Commands,
+    settings: Res<CompareMode>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut target: ResMut<CompareTarget>,
+    chunks: Query<
+        (&Handle<Mesh>, &Handle<StandardMaterial>, &GlobalTransform),
+        Or<(With<Chunk>, With<ChunkMeshPart>)>,
+    >,
+    frozen: Query<(Entity, &Handle<Mesh>), With<FrozenMesh>>,
+) {
+    let size = match windows.get_primary() {
+        Some(window) => {
+            UVec2::new(window.physical_width(), window.physical_height()).max(UVec2::ONE)
+        }
+        None => return,
+    };
+
+    // the split is changed without freezing again
+    if settings.enabled != target.0.is_some() {
+        for (entity, mesh) in frozen.iter() {
+            meshes.remove(mesh);
+            commands.entity(entity).despawn();
+        }
+        if let Some(image) = target.0.take() {
+            commands.entity(image.camera).despawn();
+            egui_context.remove_image(&image.handle);
+            images.remove(&image.handle);
+            active_cameras.remove(COMPARE_CAMERA);
+        }
+        if !settings.enabled {
+            return;
+        }
+
+        let mut frozen_count = 0;
+        for (mesh, material, transform) in chunks.iter() {
+            let mesh = match meshes.get(mesh) {
+                Some(mesh) => mesh.clone(),
+                None => continue,
+            };
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: material.clone(),
+                    transform: (*transform).into(),
+                    ..default()
+                })
+                .insert(RenderLayers::layer(FROZEN_LAYER))
+                .insert(FrozenMesh);
+            frozen_count += 1;
+        }
+        info!("Froze {frozen_count} chunk meshes for the comparison");
+
+        let handle = images.add(render_target_image(size.x, size.y));
+        let mut bundle = PerspectiveCameraBundle::new_3d();
+        bundle.camera.name = Some(COMPARE_CAMERA.to_string());
+        bundle.camera.target = RenderTarget::Image(handle.clone());
+        let camera = commands
+            .spawn_bundle(bundle)
+            .insert(RenderLayers::layer(FROZEN_LAYER))
+            .insert(CompareCamera)
+            .id();
+        active_cameras.add(COMPARE_CAMERA);
+        target.0 = Some(CompareImage {
+            texture_id: egui_context.add_image(handle.clone()),
+            handle,
+            camera,
+        });
+    } else if let Some(compare_image) = &target.0 {
+        // follow the window size so both halves line up
+        fit_render_target(&mut images, &compare_image.handle, size);
+    }
+}
+
+fn compare_mode_ui(
+    mut egui_context: ResMut<EguiContext>,
+    settings: Res<CompareMode>,
+    target: Res<CompareTarget>,
+) {
+    let texture_id = match &target.0 {
+        Some(image) if settings.enabled => image.texture_id,
+        _ => return,
+    };
+    let ctx = egui_context.ctx_mut();
+    let screen = ctx.input().screen_rect();
+    let split = settings.split.clamp(0.0, 1.0);
+    let divider = screen.left() + screen.width() * split;
+    egui::Area::new("compare_mode")
+        .fixed_pos(screen.left_top())
+        .order(egui::Order::Background)
+        .interactable(false)
+        .show(ctx, |ui| {
+            // the image covers the screen, only its left part is shown
+            ui.add(
+                egui::Image::new(texture_id, [screen.width() * split, screen.height()]).uv(
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(split, 1.0)),
+                ),
+            );
+            let painter = ui.painter();
+            painter.vline(
+                divider,
+                screen.top()..=screen.bottom(),
+                egui::Stroke::new(2.0, egui::Color32::WHITE),
+            );
+            let font = egui::FontId::proportional(16.0);
+            painter.text(
+                egui::pos2(divider - 8.0, screen.bottom() - 8.0),
+                egui::Align2::RIGHT_BOTTOM,
+                "Frozen",
+                font.clone(),
+                egui::Color32::WHITE,
+            );
+            painter.text(
+                egui::pos2(divider + 8.0, screen.bottom() - 8.0),
+                egui::Align2::LEFT_BOTTOM,
+                "Live",
+                font,
+                egui::Color32::WHITE,
+            );
+        });
+}
@@ -0,0 +1,200 @@
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::{Aabb, Frustum},
+        view::VisibilitySystems,
+    },
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    camera::FlyCam, chunk::Chunk, lines::line_mesh, lod::LodImpostor, mesh_parts::ChunkMeshPart,
+    unlit_material,
+};
+
+/// Detaches the camera from the culling to check it from outside.
+///
+/// Press F11 to lock the view used by the culling and the LOD where the
+/// camera is, then fly around it: the chunks and impostors outside of the
+/// locked frustum are hidden and the LOD distances are measured from the
+/// locked position. The locked frustum is drawn in orange, press F11 again
+/// to attach the camera back.
+#[derive(Inspectable)]
+pub struct DebugCamera {
+    pub detached: bool,
+    /// Length of the drawn frustum, the culling uses the whole frustum
+    #[inspectable(min = 1.0, max = 2000.0)]
+    pub frustum_length: f32,
+}
+
+impl Default for DebugCamera {
+    fn default() -> Self {
+        Self {
+            detached: false,
+            frustum_length: 100.0,
+        }
+    }
+}
+
+/// View of the camera when the [`DebugCamera`] was detached
+#[derive(Clone, Debug)]
+pub struct LockedView {
+    pub transform: GlobalTransform,
+    pub frustum: Frustum,
+}
+
+/// View used by the culling and the LOD instead of the camera, `None` while
+/// the [`DebugCamera`] is attached
+#[derive(Default)]
+pub struct CullingView(pub Option<LockedView>);
+
+impl CullingView {
+    /// Position the distances to the camera are measured from
+    pub fn position(&self, camera: Vec3) -> Vec3 {
+        self.0
+            .as_ref()
+            .map_or(camera, |view| view.transform.translation)
+    }
+}
+
+/// Lines of the locked frustum
+#[derive(Component)]
+pub struct FrustumGizmo;
+
+pub struct DebugCameraPlugin;
+
+impl Plugin for DebugCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CullingView>()
+            .add_system(toggle_debug_camera)
+            .add_system(update_culling_view.after(toggle_debug_camera))
+            // the views hide what's outside of their own frustum first
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                cull_to_locked_view.after(VisibilitySystems::CheckVisibility),
+            );
+    }
+}
+
+/// Edges of a perspective frustum from `near` to `far`, in the space of its camera
+fn frustum_segments(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Vec<(Vec3, Vec3)> {
+    let corners = |distance: f32| {
+        let half_height = distance * (fov / 2.0).tan();
+        let half_width = half_height * aspect_ratio;
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(x, y)| Vec3::new(x * half_width, y * half_height, -distance))
+    };
+    let near_corners = corners(near);
+    let far_corners = corners(far);
+    let mut segments = Vec::with_capacity(12);
+    for i in 0..4 {
+        let next = (i + 1) % 4;
+        segments.push((near_corners[i], near_corners[next]));
+        segments.push((far_corners[i], far_corners[next]));
+        segments.push((near_corners[i], far_corners[i]));
+    }
+    segments
+}
+
+fn toggle_debug_camera(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<DebugCamera>) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        settings.detached = !settings.detached;
+    }
+}
+
+fn update_culling_view(
+    mut commands: Commands,
+    settings: Res<DebugCamera>,
+    mut view: ResMut<CullingView>,
+    camera: Query<(&GlobalTransform, &Frustum, &PerspectiveProjection), With<FlyCam>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut gizmo: Local<Option<Entity>>,
+) {
+    if !settings.detached {
+        if view.0.take().is_some() {
+            info!("Debug camera attached");
+        }
+        if let Some(entity) = gizmo.take() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    if view.0.is_some() && !settings.is_changed() {
+        return;
+    }
+
+    let (transform, frustum, projection) = match camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let locked = view.0.get_or_insert_with(|| {
+        info!(
+            "Debug camera detached, culling from {}",
+            transform.translation
+        );
+        LockedView {
+            transform: *transform,
+            frustum: frustum.clone(),
+        }
+    });
+    if let Some(entity) = gizmo.take() {
+        commands.entity(entity).despawn();
+    }
+    let far = settings.frustum_length.max(projection.near * 2.0);
+    let segments = frustum_segments(
+        projection.fov,
+        projection.aspect_ratio,
+        projection.near,
+        far,
+    );
+    *gizmo = Some(
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(line_mesh(&segments)),
+                material: materials.add(unlit_material(Color::ORANGE)),
+                transform: locked.transform.into(),
+                ..default()
+            })
+            .insert(FrustumGizmo)
+            .id(),
+    );
+}
+
+/// Hides the meshes of the terrain outside of the [`CullingView`]
+fn cull_to_locked_view(
+    view: Res<CullingView>,
+    mut meshes: Query<
+        (&Aabb, &GlobalTransform, &mut ComputedVisibility),
+        Or<(With<Chunk>, With<ChunkMeshPart>, With<LodImpostor>)>,
+    >,
+) {
+    let locked = match &view.0 {
+        Some(locked) => locked,
+        None => return,
+    };
+    for (aabb, transform, mut visibility) in meshes.iter_mut() {
+        if visibility.is_visible
+            && !locked
+                .frustum
+                .intersects_obb(aabb, &transform.compute_matrix())
+        {
+            visibility.is_visible = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frustum_widens_with_the_distance() {
+        let segments = frustum_segments(std::f32::consts::FRAC_PI_2, 2.0, 1.0, 10.0);
+        assert_eq!(segments.len(), 12);
+        // the third edge goes from the bottom left near corner to the far one
+        let (near, far) = segments[2];
+        assert!((near - Vec3::new(-2.0, -1.0, -1.0)).length() < 1e-5);
+        assert!((far - Vec3::new(-20.0, -10.0, -10.0)).length() < 1e-5);
+    }
+}
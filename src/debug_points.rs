@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContext},
+    Inspectable,
+};
+
+use crate::{chunk::is_inside, terrain_root::MainTerrain, Data};
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub enum ColorMapMode {
+    /// The value is used as the brightness of the point
+    Grayscale,
+    /// Points fade from `at_isolevel` to `below` or `above` the further they are from the isolevel
+    Diverging,
+}
+
+/// Colors of the debug points of the selected chunk
+#[derive(Inspectable)]
+pub struct PointColors {
+    pub mode: ColorMapMode,
+    pub below: Color,
+    pub at_isolevel: Color,
+    pub above: Color,
+    /// Scale the points by their distance to the isolevel so the points close
+    /// to the surface are the smallest
+    pub scale_radius: bool,
+    pub show_legend: bool,
+}
+
+impl Default for PointColors {
+    fn default() -> Self {
+        Self {
+            mode: ColorMapMode::Diverging,
+            below: Color::BLUE,
+            at_isolevel: Color::WHITE,
+            above: Color::RED,
+            scale_radius: false,
+            show_legend: true,
+        }
+    }
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let [ar, ag, ab, aa] = a.as_rgba_f32();
+    let [br, bg, bb, ba] = b.as_rgba_f32();
+    Color::rgba(
+        ar + (br - ar) * t,
+        ag + (bg - ag) * t,
+        ab + (bb - ab) * t,
+        aa + (ba - aa) * t,
+    )
+}
+
+impl PointColors {
+    pub fn color(&self, val: f32, isolevel: f32) -> Color {
+        match self.mode {
+            ColorMapMode::Grayscale => Color::rgb(val, val, val),
+            ColorMapMode::Diverging => {
+                if !is_inside(val, isolevel) {
+                    let t = (isolevel - val) / isolevel.max(f32::EPSILON);
+                    lerp_color(self.at_isolevel, self.below, t.clamp(0.0, 1.0))
+                } else {
+                    let t = (val - isolevel) / (1.0 - isolevel).max(f32::EPSILON);
+                    lerp_color(self.at_isolevel, self.above, t.clamp(0.0, 1.0))
+                }
+            }
+        }
+    }
+
+    pub fn scale(&self, val: f32, isolevel: f32) -> Vec3 {
+        if self.scale_radius {
+            Vec3::splat(0.5 + (val - isolevel).abs() * 3.0)
+        } else {
+            Vec3::ONE
+        }
+    }
+}
+
+/// Gradient of the point colors from 0 to 1 with the isolevel marked
+pub fn point_colors_legend(
+    mut egui_context: ResMut<EguiContext>,
+    colors: Res<PointColors>,
+    data: Res<Data>,
+    terrain: MainTerrain,
+) {
+    if !colors.show_legend || terrain.selected_chunk().is_none() {
+        return;
+    }
+
+    egui::Window::new("Point colors")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            let steps = 64;
+            let (response, painter) =
+                ui.allocate_painter(egui::Vec2::new(256.0, 16.0), egui::Sense::hover());
+            let rect = response.rect;
+            let step_width = rect.width() / steps as f32;
+            for i in 0..steps {
+                let val = (i as f32 + 0.5) / steps as f32;
+                let [r, g, b, _] = colors.color(val, data.isolevel).as_rgba_f32();
+                let color = egui::Rgba::from_rgb(r, g, b);
+                let min = rect.min + egui::Vec2::new(i as f32 * step_width, 0.0);
+                painter.rect_filled(
+                    egui::Rect::from_min_size(min, egui::Vec2::new(step_width, rect.height())),
+                    0.0,
+                    color,
+                );
+            }
+            let iso_x = rect.min.x + data.isolevel * rect.width();
+            painter.line_segment(
+                [egui::pos2(iso_x, rect.min.y), egui::pos2(iso_x, rect.max.y)],
+                (2.0, egui::Color32::BLACK),
+            );
+
+            ui.horizontal(|ui| {
+                ui.label("0.0");
+                ui.add_space(80.0);
+                ui.label(format!("isolevel {:.2}", data.isolevel));
+                ui.add_space(80.0);
+                ui.label("1.0");
+            });
+        });
+}
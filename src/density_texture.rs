@@ -0,0 +1,108 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+};
+
+use crate::{
+    chunk::Chunk,
+    field_sync::{DensityUpload, DensityUploads},
+};
+
+/// GPU copy of the points of a chunk as a single channel `R32Float` 3d texture
+/// with one texel per point.
+///
+/// It's kept in sync with the [`Chunk`] every time it changes, so shaders
+/// (the volume preview, a GPU mesher or custom user shaders) can read the
+/// density field without going through the CPU data. The texture can't be
+/// filtered, use `textureLoad` and interpolate manually.
+///
+/// Only the points changed since the [`TextureRevision`] are written to the
+/// GPU, the data of the `Image` asset holds the points from when the texture
+/// was created. Read the [`Chunk`] for the current points.
+#[derive(Component, Clone)]
+pub struct DensityTexture(pub Handle<Image>);
+
+/// [`Chunk::revision`] of the points in the [`DensityTexture`].
+///
+/// Edits applied to both the chunk and the texture, like the
+/// [`gpu_brush`](crate::gpu_brush) edits, move it to the revision of the
+/// chunk so the points aren't uploaded again.
+#[derive(Component, Clone, Copy, Default)]
+pub struct TextureRevision(pub u64);
+
+pub fn density_image(chunk: &Chunk) -> Image {
+    let points = chunk.size + UVec3::ONE;
+    let mut image = Image::new(
+        Extent3d {
+            width: points.x,
+            height: points.y,
+            depth_or_array_layers: points.z,
+        },
+        TextureDimension::D3,
+        density_bytes(chunk),
+        TextureFormat::R32Float,
+    );
+    // edited in place by the compute shaders and read back to the chunks
+    image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC;
+    image
+}
+
+fn density_bytes(chunk: &Chunk) -> Vec<u8> {
+    chunk
+        .points
+        .iter()
+        .flat_map(|point| point.to_ne_bytes())
+        .collect()
+}
+
+/// Creates the texture of new chunks and uploads the points modified in
+/// changed chunks
+pub fn sync_density_textures(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut uploads: ResMut<DensityUploads>,
+    mut chunks: Query<
+        (
+            Entity,
+            &Chunk,
+            Option<&DensityTexture>,
+            Option<&mut TextureRevision>,
+        ),
+        Changed<Chunk>,
+    >,
+) {
+    for (entity, chunk, texture, revision) in chunks.iter_mut() {
+        let uploaded = match revision {
+            Some(revision) if texture.is_some() && revision.0 == chunk.revision() => continue,
+            Some(mut revision) => std::mem::replace(&mut revision.0, chunk.revision()),
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(TextureRevision(chunk.revision()));
+                0
+            }
+        };
+        let same_size = texture
+            .and_then(|texture| images.get(&texture.0))
+            .map(|image| image.data.len() == chunk.points.len() * 4);
+        match (texture, same_size) {
+            (Some(texture), Some(true)) => {
+                if let Some(region) = chunk.dirty_since(uploaded) {
+                    uploads
+                        .0
+                        .push(DensityUpload::new(texture.0.clone_weak(), chunk, region));
+                }
+            }
+            // the chunk was resized, the texture has to be recreated
+            (Some(texture), Some(false)) => {
+                if let Some(image) = images.get_mut(&texture.0) {
+                    *image = density_image(chunk);
+                }
+            }
+            _ => {
+                let handle = images.add(density_image(chunk));
+                commands.entity(entity).insert(DensityTexture(handle));
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{chunk::compute_vertex_normals, on_chunk_face};
+
+/// Smooths the heights of the near horizontal surfaces of the meshes so
+/// vehicles can drive on them, the steeper surfaces like the sides of the
+/// mountains are left as they are.
+///
+/// The vertices only move vertically, by at most `max_offset`, so the mesh
+/// stays close to the density field. The vertices on the faces of the chunks
+/// don't move to keep the seams closed.
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub struct DrivableSmoothing {
+    pub enabled: bool,
+    /// Slope in degrees under which the surface is smoothed, the smoothing
+    /// fades out towards it
+    #[inspectable(min = 0.0, max = 60.0)]
+    pub max_slope: f32,
+    #[inspectable(min = 1, max = 16)]
+    pub iterations: u32,
+    /// How far a vertex moves towards the mean height of its neighbors in
+    /// each iteration
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub strength: f32,
+    /// Largest vertical offset of a vertex, in cells
+    #[inspectable(min = 0.0, max = 2.0, speed = 0.01)]
+    pub max_offset: f32,
+}
+
+impl Default for DrivableSmoothing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_slope: 20.0,
+            iterations: 4,
+            strength: 0.5,
+            max_offset: 0.5,
+        }
+    }
+}
+
+impl DrivableSmoothing {
+    /// Smooths the `triangles` of a chunk of `extent` world units
+    pub fn smooth(&self, triangles: &mut [[Vec3; 3]], extent: Vec3, cell_size: f32) {
+        let mut ids: HashMap<[u32; 3], u32> = HashMap::default();
+        let mut positions = Vec::new();
+        let indices: Vec<u32> = triangles
+            .iter()
+            .flatten()
+            .map(|vertex| {
+                *ids.entry(vertex.to_array().map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(*vertex);
+                        positions.len() as u32 - 1
+                    })
+            })
+            .collect();
+
+        let mut neighbors: Vec<Vec<u32>> = vec![Vec::new(); positions.len()];
+        for triangle in indices.chunks_exact(3) {
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (triangle[a], triangle[b]);
+                if !neighbors[a as usize].contains(&b) {
+                    neighbors[a as usize].push(b);
+                    neighbors[b as usize].push(a);
+                }
+            }
+        }
+        let pinned: Vec<bool> = positions
+            .iter()
+            .map(|position| on_chunk_face(*position, extent))
+            .collect();
+        let original: Vec<f32> = positions.iter().map(|position| position.y).collect();
+        let min_up = self.max_slope.to_radians().cos();
+        let max_offset = self.max_offset * cell_size;
+
+        for _ in 0..self.iterations {
+            let normals = compute_vertex_normals(&positions, &indices);
+            let heights: Vec<f32> = positions.iter().map(|position| position.y).collect();
+            for (i, position) in positions.iter_mut().enumerate() {
+                let up = normals[i].y;
+                if pinned[i] || neighbors[i].is_empty() || up <= min_up {
+                    continue;
+                }
+                // no step where the smoothed area ends
+                let weight = ((up - min_up) / (1.0 - min_up).max(1e-6)).clamp(0.0, 1.0);
+                let mean = neighbors[i]
+                    .iter()
+                    .map(|neighbor| heights[*neighbor as usize])
+                    .sum::<f32>()
+                    / neighbors[i].len() as f32;
+                let height = position.y + (mean - position.y) * self.strength * weight;
+                position.y = height.clamp(original[i] - max_offset, original[i] + max_offset);
+            }
+        }
+
+        for (triangle, ids) in triangles.iter_mut().zip(indices.chunks_exact(3)) {
+            *triangle = [0, 1, 2].map(|i| positions[ids[i] as usize]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 8 x 8 quads facing up with the heights of `height`
+    fn grid(height: impl Fn(f32, f32) -> f32) -> Vec<[Vec3; 3]> {
+        let point = |x: u32, z: u32| Vec3::new(x as f32, height(x as f32, z as f32), z as f32);
+        let mut triangles = Vec::new();
+        for z in 0..8 {
+            for x in 0..8 {
+                triangles.push([point(x, z), point(x, z + 1), point(x + 1, z + 1)]);
+                triangles.push([point(x, z), point(x + 1, z + 1), point(x + 1, z)]);
+            }
+        }
+        triangles
+    }
+
+    fn height_at(triangles: &[[Vec3; 3]], x: f32, z: f32) -> f32 {
+        triangles
+            .iter()
+            .flatten()
+            .find(|vertex| vertex.x == x && vertex.z == z)
+            .unwrap()
+            .y
+    }
+
+    #[test]
+    fn bumps_on_flat_ground_are_smoothed() {
+        let smoothing = DrivableSmoothing {
+            enabled: true,
+            ..default()
+        };
+        let extent = Vec3::splat(8.0);
+        let bump = |x: f32, z: f32| if x == 4.0 && z == 4.0 { 2.3 } else { 2.0 };
+        let mut triangles = grid(bump);
+        smoothing.smooth(&mut triangles, extent, 1.0);
+        let height = height_at(&triangles, 4.0, 4.0);
+        assert!(height < 2.2 && height >= 1.5);
+        // the seams don't move
+        assert_eq!(height_at(&triangles, 0.0, 4.0), 2.0);
+
+        // too steep
+        let slope = |x: f32, z: f32| x + bump(x, z) - 2.0;
+        let mut triangles = grid(slope);
+        smoothing.smooth(&mut triangles, Vec3::splat(64.0), 1.0);
+        assert_eq!(triangles, grid(slope));
+    }
+}
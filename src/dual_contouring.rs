@@ -0,0 +1,278 @@
+use bevy::prelude::*;
+
+use crate::{
+    chunk::{is_inside, Chunk},
+    hermite::CurrentCrossings,
+};
+
+/// Pull of the vertices towards the mean of the crossings of their cell, it
+/// keeps the vertices of flat and degenerate cells in place
+const QEF_REGULARIZATION: f32 = 0.02;
+
+/// Where the surface crosses an edge of the grid, with its normal there
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeCrossing {
+    /// Position in the local space of the chunk, in grid points
+    pub position: Vec3,
+    /// Pointing out of the surface
+    pub normal: Vec3,
+}
+
+fn axis_vec(axis: usize) -> IVec3 {
+    [IVec3::X, IVec3::Y, IVec3::Z][axis]
+}
+
+fn value(chunk: &Chunk, point: IVec3) -> Option<f32> {
+    let in_chunk = point.cmpge(IVec3::ZERO).all() && point.cmple(chunk.size.as_ivec3()).all();
+    in_chunk.then(|| chunk.get(point.as_vec3()))
+}
+
+/// Position where the edge from `point` along `axis` crosses the isolevel
+fn crossing_position(chunk: &Chunk, point: IVec3, axis: usize, isolevel: f32) -> Option<Vec3> {
+    let a = value(chunk, point)?;
+    let b = value(chunk, point + axis_vec(axis))?;
+    if is_inside(a, isolevel) == is_inside(b, isolevel) {
+        return None;
+    }
+    let t = (isolevel - a) / (b - a);
+    Some(point.as_vec3() + axis_vec(axis).as_vec3() * t)
+}
+
+/// Gradient of the density at a point from its neighbors
+fn point_gradient(chunk: &Chunk, point: IVec3) -> Vec3 {
+    let mut gradient = Vec3::ZERO;
+    for axis in 0..3 {
+        let step = axis_vec(axis);
+        let (high, low) = (point + step, point - step);
+        gradient[axis] = match (value(chunk, high), value(chunk, low)) {
+            (Some(h), Some(l)) => (h - l) / 2.0,
+            (Some(h), None) => h - chunk.get(point.as_vec3()),
+            (None, Some(l)) => chunk.get(point.as_vec3()) - l,
+            (None, None) => 0.0,
+        };
+    }
+    gradient
+}
+
+/// Crossing of the edge from `point` along `axis`.
+///
+/// The normal is taken from the plane through the crossings of the parallel
+/// edges around it. Near an edge of a stamped shape the gradient of the
+/// points mixes the normals of both faces, while the neighboring crossings
+/// on the same face stay on its plane. Isolated crossings fall back to the
+/// gradient.
+pub fn edge_crossing(
+    chunk: &Chunk,
+    point: UVec3,
+    axis: usize,
+    isolevel: f32,
+) -> Option<EdgeCrossing> {
+    let point = point.as_ivec3();
+    let position = crossing_position(chunk, point, axis, isolevel)?;
+    let start_inside = is_inside(chunk.get(point.as_vec3()), isolevel);
+    // the density grows towards the inside
+    let outward = axis_vec(axis).as_vec3() * if start_inside { 1.0 } else { -1.0 };
+
+    let tangent = |side: usize| {
+        let step = axis_vec(side);
+        let forward = crossing_position(chunk, point + step, axis, isolevel);
+        let backward = crossing_position(chunk, point - step, axis, isolevel);
+        match (forward, backward) {
+            (Some(f), Some(b)) => Some(f - b),
+            (Some(f), None) => Some(f - position),
+            (None, Some(b)) => Some(position - b),
+            (None, None) => None,
+        }
+    };
+    let normal = match (tangent((axis + 1) % 3), tangent((axis + 2) % 3)) {
+        (Some(u), Some(v)) => u.cross(v).normalize_or_zero(),
+        _ => Vec3::ZERO,
+    };
+    let normal = if normal == Vec3::ZERO {
+        let end = point + axis_vec(axis);
+        let t = position[axis] - point[axis] as f32;
+        let gradient = point_gradient(chunk, point).lerp(point_gradient(chunk, end), t);
+        -gradient.normalize_or_zero()
+    } else {
+        normal
+    };
+    let normal = if normal.dot(outward) < 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    Some(EdgeCrossing { position, normal })
+}
+
+/// Position minimizing the distances to the planes of the crossings, kept
+/// in the box from `min` to `max`
+pub fn solve_qef(crossings: &[EdgeCrossing], min: Vec3, max: Vec3) -> Vec3 {
+    let mean = crossings
+        .iter()
+        .fold(Vec3::ZERO, |sum, crossing| sum + crossing.position)
+        / crossings.len().max(1) as f32;
+    let mut ata = Mat3::from_diagonal(Vec3::splat(QEF_REGULARIZATION));
+    let mut atb = Vec3::ZERO;
+    for crossing in crossings {
+        let n = crossing.normal;
+        ata += Mat3::from_cols(n * n.x, n * n.y, n * n.z);
+        atb += n * n.dot(crossing.position - mean);
+    }
+    // solved around the mean so the regularization pulls towards it
+    let offset = if ata.determinant().abs() > f32::EPSILON {
+        ata.inverse() * atb
+    } else {
+        Vec3::ZERO
+    };
+    (mean + offset).clamp(min, max)
+}
+
+/// Corners of the 12 edges of a cell, as the first point and the axis of the edge
+const CELL_EDGES: [(UVec3, usize); 12] = [
+    (UVec3::new(0, 0, 0), 0),
+    (UVec3::new(0, 1, 0), 0),
+    (UVec3::new(0, 0, 1), 0),
+    (UVec3::new(0, 1, 1), 0),
+    (UVec3::new(0, 0, 0), 1),
+    (UVec3::new(1, 0, 0), 1),
+    (UVec3::new(0, 0, 1), 1),
+    (UVec3::new(1, 0, 1), 1),
+    (UVec3::new(0, 0, 0), 2),
+    (UVec3::new(1, 0, 0), 2),
+    (UVec3::new(0, 1, 0), 2),
+    (UVec3::new(1, 1, 0), 2),
+];
+
+/// Triangles of the surface of `chunk` with a vertex per cell crossing the
+/// isolevel, placed on the edges and corners of the surface instead of on the
+/// edges of the grid like with marching cubes.
+///
+/// The positions are in the local space of the chunk. A face only joins the
+/// cells of the chunk, the seams with the neighboring chunks are left open.
+/// The crossings of `hermite` are used where it has them, the others are
+/// derived from the points.
+pub fn dual_contour(
+    chunk: &Chunk,
+    isolevel: f32,
+    cell_size: f32,
+    hermite: Option<CurrentCrossings>,
+) -> Vec<[Vec3; 3]> {
+    let size = chunk.size;
+    let cell_index = |cell: UVec3| {
+        (cell.z as usize * size.y as usize + cell.y as usize) * size.x as usize + cell.x as usize
+    };
+    let mut vertices = vec![None; size.x as usize * size.y as usize * size.z as usize];
+    let mut crossings = Vec::with_capacity(12);
+    for cell in Chunk::new_iter_3d(size - UVec3::ONE) {
+        crossings.clear();
+        crossings.extend(CELL_EDGES.iter().filter_map(|(corner, axis)| {
+            let point = cell + *corner;
+            hermite
+                .and_then(|hermite| hermite.get(point, *axis))
+                .or_else(|| edge_crossing(chunk, point, *axis, isolevel))
+        }));
+        if !crossings.is_empty() {
+            let min = cell.as_vec3();
+            vertices[cell_index(cell)] = Some(solve_qef(&crossings, min, min + Vec3::ONE));
+        }
+    }
+
+    let mut triangles = Vec::new();
+    for point in Chunk::new_iter_3d(size) {
+        for axis in 0..3 {
+            let point = point.as_ivec3();
+            let start = match value(chunk, point) {
+                Some(start) => start,
+                None => continue,
+            };
+            let end = match value(chunk, point + axis_vec(axis)) {
+                Some(end) => end,
+                None => continue,
+            };
+            if is_inside(start, isolevel) == is_inside(end, isolevel) {
+                continue;
+            }
+            // the 4 cells around the edge, counterclockwise seen from the end
+            let (u, v) = (axis_vec((axis + 1) % 3), axis_vec((axis + 2) % 3));
+            let quad = [IVec3::ZERO, u, u + v, v].map(|offset| {
+                let cell = point + offset - u - v;
+                let in_chunk = cell.cmpge(IVec3::ZERO).all() && cell.cmplt(size.as_ivec3()).all();
+                if in_chunk {
+                    vertices[cell_index(cell.as_uvec3())]
+                } else {
+                    None
+                }
+            });
+            let mut quad = match quad {
+                [Some(a), Some(b), Some(c), Some(d)] => [a, b, c, d].map(|v| v * cell_size),
+                _ => continue,
+            };
+            // the front faces look out of the surface
+            if !is_inside(start, isolevel) {
+                quad.reverse();
+            }
+            triangles.push([quad[0], quad[1], quad[2]]);
+            triangles.push([quad[0], quad[2], quad[3]]);
+        }
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatten::surface_density;
+
+    /// Chunk of 8 cells containing a box stamped like the tools do
+    fn box_chunk(min: Vec3, max: Vec3) -> Chunk {
+        Chunk::from_fn(UVec3::splat(8), |point| {
+            let p = point.as_vec3();
+            let q = (p - (min + max) / 2.0).abs() - (max - min) / 2.0;
+            let distance = q.max(Vec3::ZERO).length() + q.max_element().min(0.0);
+            surface_density(distance, 0.5, 1.0)
+        })
+    }
+
+    #[test]
+    fn box_keeps_its_corners() {
+        let (min, max) = (Vec3::splat(2.3), Vec3::splat(5.6));
+        let triangles = dual_contour(&box_chunk(min, max), 0.5, 1.0, None);
+        assert!(!triangles.is_empty());
+        let closest = |corner: Vec3| {
+            triangles
+                .iter()
+                .flatten()
+                .map(|v| v.distance(corner))
+                .fold(f32::MAX, f32::min)
+        };
+        // marching cubes cuts the corners about 0.7 cells deep
+        assert!(closest(max) < 0.1, "{}", closest(max));
+        assert!(closest(min) < 0.1, "{}", closest(min));
+        assert!(closest(Vec3::new(2.3, 5.6, 2.3)) < 0.15);
+
+        let center = (min + max) / 2.0;
+        for [a, b, c] in &triangles {
+            let normal = (*b - *a).cross(*c - *a);
+            assert!(normal.dot((*a + *b + *c) / 3.0 - center) > 0.0);
+        }
+    }
+
+    #[test]
+    fn flat_crossings_keep_their_plane() {
+        let chunk = box_chunk(Vec3::splat(2.3), Vec3::splat(5.6));
+        // next to the edge of the box at y = z = 5.6
+        let crossing = edge_crossing(&chunk, UVec3::new(4, 5, 5), 1, 0.5).unwrap();
+        assert!((crossing.position.y - 5.6).abs() < 1e-4);
+        assert!(
+            crossing.normal.abs_diff_eq(Vec3::Y, 1e-4),
+            "{}",
+            crossing.normal
+        );
+        let crossing = edge_crossing(&chunk, UVec3::new(4, 4, 5), 2, 0.5).unwrap();
+        assert!(
+            crossing.normal.abs_diff_eq(Vec3::Z, 1e-4),
+            "{}",
+            crossing.normal
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin, NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::Indices,
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, ShaderStages,
+        },
+        renderer::RenderDevice,
+    },
+};
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+
+use crate::{camera::FlyCam, CHUNK_SIZE};
+
+pub const SKY_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x7d12_e4a9_30c6_5b8f);
+
+/// Radius of the sky sphere, it must stay within the far plane of the camera
+const SKY_RADIUS: f32 = 500.0;
+
+/// Directional light lighting the terrain.
+///
+/// Bevy doesn't have cascaded shadow maps, a single orthographic shadow map
+/// of `shadow_size` units around the world covers the whole terrain.
+#[derive(Inspectable)]
+pub struct SunSettings {
+    /// Angle around the Y axis in degrees
+    #[inspectable(min = 0.0, max = 360.0)]
+    pub azimuth: f32,
+    /// Angle above the horizon in degrees
+    #[inspectable(min = 1.0, max = 89.0)]
+    pub elevation: f32,
+    pub color: Color,
+    /// Illuminance in lux
+    #[inspectable(min = 0.0, max = 100_000.0, speed = 100.0)]
+    pub illuminance: f32,
+    pub shadows_enabled: bool,
+    /// Half size of the area covered by the shadow map, smaller values give sharper shadows
+    #[inspectable(min = 1.0, max = 256.0)]
+    pub shadow_size: f32,
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.001)]
+    pub shadow_depth_bias: f32,
+    #[inspectable(min = 0.0, max = 4.0, speed = 0.01)]
+    pub shadow_normal_bias: f32,
+}
+
+impl Default for SunSettings {
+    fn default() -> Self {
+        Self {
+            azimuth: 30.0,
+            elevation: 45.0,
+            color: Color::rgb(1.0, 0.96, 0.88),
+            illuminance: 20_000.0,
+            shadows_enabled: true,
+            shadow_size: 48.0,
+            shadow_depth_bias: DirectionalLight::DEFAULT_SHADOW_DEPTH_BIAS,
+            shadow_normal_bias: DirectionalLight::DEFAULT_SHADOW_NORMAL_BIAS,
+        }
+    }
+}
+
+impl SunSettings {
+    /// Direction pointing towards the sun
+    pub fn direction(&self) -> Vec3 {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.sin(),
+            elevation.cos() * azimuth.sin(),
+        )
+    }
+}
+
+/// Gradient drawn behind the terrain
+#[derive(Inspectable)]
+pub struct SkySettings {
+    pub enabled: bool,
+    pub zenith: Color,
+    pub horizon: Color,
+    pub ground: Color,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            zenith: Color::rgb(0.25, 0.45, 0.8),
+            horizon: Color::rgb(0.75, 0.85, 0.95),
+            ground: Color::rgb(0.35, 0.33, 0.3),
+        }
+    }
+}
+
+/// Light and colors around the terrain.
+///
+/// The fog is drawn by the materials with their own shader, like the slope
+/// coloring, bevy's standard material doesn't have any.
+#[derive(Inspectable)]
+pub struct RenderSettings {
+    /// Light reaching every surface, keeps the caves and the sides turned
+    /// away from the sun visible
+    pub ambient_color: Color,
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub ambient_brightness: f32,
+    /// Drawn behind the terrain when the sky is disabled
+    pub clear_color: Color,
+    pub fog_enabled: bool,
+    pub fog_color: Color,
+    /// Distance to the camera where the fog starts
+    #[inspectable(min = 0.0, speed = 1.0)]
+    pub fog_start: f32,
+    /// Distance to the camera where the fog hides everything
+    #[inspectable(min = 0.0, speed = 1.0)]
+    pub fog_end: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            ambient_color: Color::WHITE,
+            ambient_brightness: 0.15,
+            clear_color: Color::rgb(0.75, 0.85, 0.95),
+            fog_enabled: false,
+            fog_color: Color::rgb(0.75, 0.85, 0.95),
+            fog_start: 64.0,
+            fog_end: 256.0,
+        }
+    }
+}
+
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            SKY_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/sky.wgsl")),
+        );
+
+        app.add_plugin(MaterialPlugin::<SkyMaterial>::default())
+            .add_plugin(InspectorPlugin::<SunSettings>::new())
+            .add_plugin(InspectorPlugin::<SkySettings>::new())
+            .add_plugin(InspectorPlugin::<RenderSettings>::new())
+            .add_startup_system(setup_environment)
+            .add_system(update_render_settings)
+            .add_system(update_sun)
+            .add_system(update_sky)
+            .add_system(follow_camera);
+    }
+}
+
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Component)]
+pub struct Sky;
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "3e8c5a71-d24f-4b09-86e3-1f9a7c2d5b40"]
+pub struct SkyMaterial {
+    pub zenith: Color,
+    pub horizon: Color,
+    pub ground: Color,
+}
+
+#[derive(Clone)]
+pub struct GpuSkyMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for SkyMaterial {
+    type ExtractedAsset = SkyMaterial;
+    type PreparedAsset = GpuSkyMaterial;
+    type Param = (SRes<RenderDevice>, SRes<MaterialPipeline<Self>>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        // matches the SkyMaterial struct of the shader, the colors are blended in linear space
+        let contents: Vec<u8> = [material.zenith, material.horizon, material.ground]
+            .iter()
+            .flat_map(|color| color.as_linear_rgba_f32())
+            .flat_map(|v| v.to_ne_bytes())
+            .collect();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("sky_material_uniform"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("sky_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(GpuSkyMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for SkyMaterial {
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(SKY_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("sky_material_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(12 * 4),
+                },
+                count: None,
+            }],
+        })
+    }
+}
+
+/// Sphere seen from the inside, its triangles are flipped so they aren't culled
+fn sky_mesh() -> Mesh {
+    let mut mesh = Mesh::from(shape::Icosphere {
+        radius: SKY_RADIUS,
+        subdivisions: 4,
+    });
+    if let Some(Indices::U32(indices)) = mesh.indices_mut() {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+    mesh
+}
+
+fn setup_environment(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+    sky: Res<SkySettings>,
+) {
+    commands
+        .spawn_bundle(DirectionalLightBundle::default())
+        .insert(Sun);
+
+    commands
+        .spawn_bundle(MaterialMeshBundle {
+            mesh: meshes.add(sky_mesh()),
+            material: materials.add(SkyMaterial {
+                zenith: sky.zenith,
+                horizon: sky.horizon,
+                ground: sky.ground,
+            }),
+            ..default()
+        })
+        .insert(Sky)
+        .insert(NotShadowCaster)
+        .insert(NotShadowReceiver);
+}
+
+fn update_render_settings(
+    settings: Res<RenderSettings>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    ambient_light.color = settings.ambient_color;
+    ambient_light.brightness = settings.ambient_brightness;
+    clear_color.0 = settings.clear_color;
+}
+
+fn update_sun(
+    settings: Res<SunSettings>,
+    mut suns: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    // keep the shadow map centered on the world
+    let center = Vec3::splat(CHUNK_SIZE as f32 / 2.0);
+    for (mut light, mut transform) in suns.iter_mut() {
+        light.color = settings.color;
+        light.illuminance = settings.illuminance;
+        light.shadows_enabled = settings.shadows_enabled;
+        light.shadow_depth_bias = settings.shadow_depth_bias;
+        light.shadow_normal_bias = settings.shadow_normal_bias;
+        let size = settings.shadow_size;
+        light.shadow_projection = OrthographicProjection {
+            left: -size,
+            right: size,
+            bottom: -size,
+            top: size,
+            near: -size,
+            far: size,
+            ..default()
+        };
+        *transform =
+            Transform::from_translation(center).looking_at(center - settings.direction(), Vec3::Y);
+    }
+}
+
+fn update_sky(
+    settings: Res<SkySettings>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+    mut skies: Query<(&Handle<SkyMaterial>, &mut Visibility), With<Sky>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (handle, mut visibility) in skies.iter_mut() {
+        visibility.is_visible = settings.enabled;
+        if let Some(material) = materials.get_mut(handle) {
+            material.zenith = settings.zenith;
+            material.horizon = settings.horizon;
+            material.ground = settings.ground;
+        }
+    }
+}
+
+fn follow_camera(
+    cameras: Query<&GlobalTransform, With<FlyCam>>,
+    mut skies: Query<&mut Transform, With<Sky>>,
+) {
+    let camera = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    for mut transform in skies.iter_mut() {
+        transform.translation = camera.translation;
+    }
+}
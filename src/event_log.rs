@@ -0,0 +1,184 @@
+use std::{collections::VecDeque, fmt, time::Duration};
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+/// Kinds of [`Activity`], each can be hidden in the event log
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ActivityCategory {
+    Generation,
+    Meshing,
+    Save,
+    Tasks,
+}
+
+impl ActivityCategory {
+    pub const ALL: [ActivityCategory; 4] = [
+        ActivityCategory::Generation,
+        ActivityCategory::Meshing,
+        ActivityCategory::Save,
+        ActivityCategory::Tasks,
+    ];
+}
+
+/// Something the generation, the meshing or the saves did, sent as an event
+/// and shown in the `Event log` window instead of being logged every frame
+#[derive(Clone, Debug, PartialEq)]
+pub enum Activity {
+    ChunksGenerated { chunks: usize, duration: Duration },
+    ChunksMarched { skipped: usize, duration: Duration },
+    ChunkRemeshed { chunk: Entity, triangles: usize },
+    WorldSaved { written: usize, unchanged: usize },
+    ChunksLoaded { chunks: usize },
+    Autosaved { slot: usize, chunks: usize },
+    TaskCancelled { task: &'static str },
+}
+
+impl Activity {
+    pub fn category(&self) -> ActivityCategory {
+        match self {
+            Activity::ChunksGenerated { .. } => ActivityCategory::Generation,
+            Activity::ChunksMarched { .. } | Activity::ChunkRemeshed { .. } => {
+                ActivityCategory::Meshing
+            }
+            Activity::WorldSaved { .. }
+            | Activity::ChunksLoaded { .. }
+            | Activity::Autosaved { .. } => ActivityCategory::Save,
+            Activity::TaskCancelled { .. } => ActivityCategory::Tasks,
+        }
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Activity::ChunksGenerated { chunks, duration } => {
+                write!(f, "Generated {chunks} chunks in {duration:?}")
+            }
+            Activity::ChunksMarched { skipped, duration } => write!(
+                f,
+                "Marched the chunks in {duration:?}, {skipped} solid or empty chunks skipped"
+            ),
+            Activity::ChunkRemeshed { chunk, triangles } => {
+                write!(f, "Remeshed chunk {chunk:?}, {triangles} triangles")
+            }
+            Activity::WorldSaved { written, unchanged } => {
+                write!(
+                    f,
+                    "Saved world: {written} chunks written, {unchanged} unchanged"
+                )
+            }
+            Activity::ChunksLoaded { chunks } => write!(f, "Loaded {chunks} chunks"),
+            Activity::Autosaved { slot, chunks } => {
+                write!(f, "Autosaved {chunks} chunks to slot {slot}")
+            }
+            Activity::TaskCancelled { task } => write!(f, "Cancelled the {task} task"),
+        }
+    }
+}
+
+/// Last [`Activity`] events with the second they were received at
+pub struct EventLog {
+    /// Oldest entries are dropped past this many
+    pub capacity: usize,
+    pub hidden: HashSet<ActivityCategory>,
+    entries: VecDeque<(f64, Activity)>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self {
+            capacity: 500,
+            hidden: HashSet::default(),
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl EventLog {
+    pub fn push(&mut self, time: f64, activity: Activity) {
+        self.entries.push_back((time, activity));
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Entries of the categories that aren't hidden, newest first
+    pub fn visible(&self) -> impl Iterator<Item = &(f64, Activity)> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|(_, activity)| !self.hidden.contains(&activity.category()))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+pub fn record_activity(
+    time: Res<Time>,
+    mut events: EventReader<Activity>,
+    mut log: ResMut<EventLog>,
+) {
+    for activity in events.iter() {
+        log.push(time.seconds_since_startup(), activity.clone());
+    }
+}
+
+pub fn event_log_ui(mut egui_context: ResMut<EguiContext>, mut log: ResMut<EventLog>) {
+    egui::Window::new("Event log")
+        .default_open(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for category in ActivityCategory::ALL {
+                    let mut shown = !log.hidden.contains(&category);
+                    if ui.checkbox(&mut shown, format!("{category:?}")).changed() {
+                        if shown {
+                            log.hidden.remove(&category);
+                        } else {
+                            log.hidden.insert(category);
+                        }
+                    }
+                }
+                if ui.button("Clear").clicked() {
+                    log.clear();
+                }
+            });
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for (time, activity) in log.visible() {
+                        ui.label(format!("{time:>8.2}s  {activity}"));
+                    }
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_entries_of_the_shown_categories() {
+        let mut log = EventLog {
+            capacity: 2,
+            ..default()
+        };
+        log.push(0.0, Activity::ChunksLoaded { chunks: 1 });
+        log.push(1.0, Activity::TaskCancelled { task: "autosave" });
+        log.push(
+            2.0,
+            Activity::ChunksMarched {
+                skipped: 0,
+                duration: Duration::ZERO,
+            },
+        );
+        let times: Vec<f64> = log.visible().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![2.0, 1.0]);
+
+        log.hidden.insert(ActivityCategory::Meshing);
+        let times: Vec<f64> = log.visible().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![1.0]);
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    chunk::{is_inside, Chunk, ChunkMap},
+    generation::WorldSettings,
+    terrain_root::{MainTerrain, TerrainRoot},
+};
+
+/// Step used when marching a ray through the field, in cells
+const RAY_STEP: f32 = 0.25;
+
+/// Density sampled at world positions, used to fill chunks with
+/// [`Chunk::fill_from`]. Closures returning a density implement it.
+pub trait DensitySource {
+    /// Density at a world position, `None` where the source has no value
+    fn density_at(&self, pos: Vec3) -> Option<f32>;
+}
+
+impl<F: Fn(Vec3) -> f32> DensitySource for F {
+    fn density_at(&self, pos: Vec3) -> Option<f32> {
+        Some(self(pos))
+    }
+}
+
+impl<'w, 's> DensitySource for DensityField<'w, 's> {
+    fn density_at(&self, pos: Vec3) -> Option<f32> {
+        self.density(pos)
+    }
+}
+
+/// Read access to the density field of every chunk of the main terrain in
+/// world space, [`DensityField::terrain`] reads the other terrains in the
+/// space of their root
+#[derive(SystemParam)]
+pub struct DensityField<'w, 's> {
+    main: MainTerrain<'w, 's>,
+    terrains: Query<'w, 's, (&'static ChunkMap, &'static WorldSettings), With<TerrainRoot>>,
+    world_settings: Res<'w, WorldSettings>,
+    chunks: Query<'w, 's, &'static Chunk>,
+}
+
+impl<'w, 's> DensityField<'w, 's> {
+    /// Field of the terrain of `root`
+    pub fn terrain(&self, root: Entity) -> Option<TerrainField<'_, 'w, 's>> {
+        let (chunk_map, world_settings) = self.terrains.get(root).ok()?;
+        Some(TerrainField {
+            chunk_map,
+            world_settings,
+            chunks: &self.chunks,
+        })
+    }
+
+    /// Field of the main terrain, `None` once despawned
+    fn main(&self) -> Option<TerrainField<'_, 'w, 's>> {
+        Some(TerrainField {
+            chunk_map: self.main.chunk_map()?,
+            world_settings: &self.world_settings,
+            chunks: &self.chunks,
+        })
+    }
+
+    /// See [`TerrainField::density`]
+    pub fn density(&self, pos: Vec3) -> Option<f32> {
+        self.main()?.density(pos)
+    }
+
+    /// See [`TerrainField::cell`]
+    pub fn cell(&self, pos: Vec3) -> Option<(Entity, &Chunk, UVec3)> {
+        self.main()?.cell(pos)
+    }
+
+    /// See [`TerrainField::is_solid`]
+    pub fn is_solid(&self, pos: Vec3, isolevel: f32) -> bool {
+        self.main()
+            .map_or(false, |field| field.is_solid(pos, isolevel))
+    }
+
+    /// See [`TerrainField::raycast`]
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        isolevel: f32,
+    ) -> Option<Vec3> {
+        self.main()?
+            .raycast(origin, direction, max_distance, isolevel)
+    }
+
+    /// See [`TerrainField::normal`]
+    pub fn normal(&self, pos: Vec3) -> Option<Vec3> {
+        self.main()?.normal(pos)
+    }
+
+    pub fn chunk_map(&self) -> Option<&ChunkMap> {
+        self.main.chunk_map()
+    }
+
+    /// Distance between two points of the grid of the main terrain in world units
+    pub fn cell_size(&self) -> f32 {
+        self.world_settings.cell_size
+    }
+
+    /// Size of a chunk of the main terrain in world units
+    pub fn chunk_extent(&self) -> Vec3 {
+        self.world_settings.chunk_extent()
+    }
+}
+
+/// Density field of the chunks of a single terrain, in the space of its root
+pub struct TerrainField<'a, 'w, 's> {
+    chunk_map: &'a ChunkMap,
+    world_settings: &'a WorldSettings,
+    chunks: &'a Query<'w, 's, &'static Chunk>,
+}
+
+impl<'a, 'w, 's> TerrainField<'a, 'w, 's> {
+    /// Trilinear interpolation of the density at a world position,
+    /// `None` outside of the loaded chunks
+    pub fn density(&self, pos: Vec3) -> Option<f32> {
+        let cell_size = self.cell_size();
+        let chunk_size = self.world_settings.chunk_extent();
+        let coord = (pos / chunk_size).floor().as_ivec3();
+        let chunk = self
+            .chunk_map
+            .get(coord)
+            .and_then(|entity| self.chunks.get(entity).ok())?;
+
+        // in grid points
+        let local = (pos - coord.as_vec3() * chunk_size) / cell_size;
+        // the last point of the chunk is shared with the next chunk, stay in the last cell
+        let max_cell = (chunk.size - UVec3::ONE).as_vec3();
+        let cell = local.floor().min(max_cell);
+        let t = local - cell;
+
+        let corner = |offset: Vec3| chunk.get(cell + offset);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let x00 = lerp(corner(Vec3::ZERO), corner(Vec3::X), t.x);
+        let x10 = lerp(corner(Vec3::Y), corner(Vec3::new(1.0, 1.0, 0.0)), t.x);
+        let x01 = lerp(corner(Vec3::Z), corner(Vec3::new(1.0, 0.0, 1.0)), t.x);
+        let x11 = lerp(corner(Vec3::new(0.0, 1.0, 1.0)), corner(Vec3::ONE), t.x);
+        let y0 = lerp(x00, x10, t.y);
+        let y1 = lerp(x01, x11, t.y);
+        Some(lerp(y0, y1, t.z))
+    }
+
+    /// Chunk under a world position and the cell of that chunk holding it
+    pub fn cell(&self, pos: Vec3) -> Option<(Entity, &'a Chunk, UVec3)> {
+        let chunk_size = self.world_settings.chunk_extent();
+        let coord = (pos / chunk_size).floor().as_ivec3();
+        let entity = self.chunk_map.get(coord)?;
+        let chunk = self.chunks.get(entity).ok()?;
+        let local = (pos - coord.as_vec3() * chunk_size) / self.cell_size();
+        let cell = local
+            .floor()
+            .max(Vec3::ZERO)
+            .as_uvec3()
+            .min(chunk.size - UVec3::ONE);
+        Some((entity, chunk, cell))
+    }
+
+    /// Returns true if the density at `pos` is inside the surface
+    pub fn is_solid(&self, pos: Vec3, isolevel: f32) -> bool {
+        self.density(pos).map_or(false, |d| is_inside(d, isolevel))
+    }
+
+    /// Marches a ray through the field and returns the first point where it
+    /// enters the surface.
+    ///
+    /// The crossing is refined by linear interpolation between the two
+    /// samples surrounding it, like the vertices of the marched mesh.
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        isolevel: f32,
+    ) -> Option<Vec3> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return None;
+        }
+
+        let mut previous: Option<(f32, f32)> = None;
+        let mut distance = 0.0;
+        while distance <= max_distance {
+            let pos = origin + direction * distance;
+            match self.density(pos) {
+                Some(value) => {
+                    if is_inside(value, isolevel) {
+                        let hit_distance = match previous {
+                            Some((prev_distance, prev_value)) => {
+                                let t = (isolevel - prev_value) / (value - prev_value);
+                                prev_distance + (distance - prev_distance) * t
+                            }
+                            None => distance,
+                        };
+                        return Some(origin + direction * hit_distance);
+                    }
+                    previous = Some((distance, value));
+                }
+                None => previous = None,
+            }
+            distance += RAY_STEP * self.cell_size();
+        }
+        None
+    }
+
+    /// Gradient of the field pointing away from the surface
+    pub fn normal(&self, pos: Vec3) -> Option<Vec3> {
+        let h = 0.5 * self.cell_size();
+        let dx = self.density(pos + Vec3::X * h)? - self.density(pos - Vec3::X * h)?;
+        let dy = self.density(pos + Vec3::Y * h)? - self.density(pos - Vec3::Y * h)?;
+        let dz = self.density(pos + Vec3::Z * h)? - self.density(pos - Vec3::Z * h)?;
+        // density grows towards the inside of the surface
+        Some(-Vec3::new(dx, dy, dz).normalize_or_zero())
+    }
+
+    pub fn chunk_map(&self) -> &'a ChunkMap {
+        self.chunk_map
+    }
+
+    /// Distance between two points of the grid in world units
+    pub fn cell_size(&self) -> f32 {
+        self.world_settings.cell_size
+    }
+
+    /// Size of a chunk in world units
+    pub fn chunk_extent(&self) -> Vec3 {
+        self.world_settings.chunk_extent()
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{
+            BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+            ImageCopyTexture, ImageDataLayout, MapMode, Origin3d, TextureAspect,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp, RenderStage,
+    },
+};
+
+use crate::{
+    chunk::{Chunk, DirtyRegion},
+    density_texture::{DensityTexture, TextureRevision},
+    MarchingCubesSystem,
+};
+
+/// Rows of a texture copied to a buffer start at a multiple of this many bytes
+const ROW_ALIGNMENT: u32 = 256;
+
+/// Points of a [`DirtyRegion`] of a chunk written to its [`DensityTexture`]
+/// instead of uploading the whole chunk again
+#[derive(Clone, Debug)]
+pub struct DensityUpload {
+    pub texture: Handle<Image>,
+    /// First point of the region
+    pub origin: UVec3,
+    /// Number of points of the region on each axis
+    pub extent: UVec3,
+    /// Points of the region, x first then y then z like the texture
+    pub bytes: Vec<u8>,
+}
+
+impl DensityUpload {
+    pub fn new(texture: Handle<Image>, chunk: &Chunk, region: DirtyRegion) -> Self {
+        let max = region.max.min(chunk.size);
+        let origin = region.min.min(max);
+        let extent = max - origin + UVec3::ONE;
+        let points = chunk.size + UVec3::ONE;
+        let mut bytes =
+            Vec::with_capacity(extent.x as usize * extent.y as usize * extent.z as usize * 4);
+        for z in origin.z..=max.z {
+            for y in origin.y..=max.y {
+                let row = (z * points.y + y) * points.x;
+                let start = (row + origin.x) as usize;
+                let end = (row + max.x) as usize;
+                for point in &chunk.points[start..=end] {
+                    bytes.extend(point.to_ne_bytes());
+                }
+            }
+        }
+        Self {
+            texture,
+            origin,
+            extent,
+            bytes,
+        }
+    }
+}
+
+/// Uploads waiting for the next extraction, filled by
+/// [`sync_density_textures`](crate::density_texture::sync_density_textures)
+#[derive(Default)]
+pub struct DensityUploads(pub Vec<DensityUpload>);
+
+/// Copies the [`DensityTexture`] of a chunk back to the CPU and writes it
+/// into the [`Chunk`], for the edits made on the GPU only.
+///
+/// The points are read after the frame is rendered and applied during the
+/// next frame, a [`DensityTextureRead`] is sent once they are.
+pub struct ReadDensityTexture(pub Entity);
+
+/// Sent when the points of a [`ReadDensityTexture`] were written into the
+/// chunk, queries, saves and colliders see the GPU edits from then on
+pub struct DensityTextureRead(pub Entity);
+
+/// Points read back from the GPU, shared between the main and the render world
+#[derive(Default, Clone)]
+pub struct DensityReadbacks(Arc<Mutex<Vec<(Entity, Vec<f32>)>>>);
+
+/// Keeps the [`Chunk`]s and their [`DensityTexture`]s coherent.
+///
+/// Modified chunks only upload the points changed since the revision of
+/// their texture, and the textures edited by compute shaders can be read
+/// back into the chunks with [`ReadDensityTexture`].
+pub struct FieldSyncPlugin;
+
+impl Plugin for FieldSyncPlugin {
+    fn build(&self, app: &mut App) {
+        let readbacks = DensityReadbacks::default();
+        app.init_resource::<DensityUploads>()
+            .insert_resource(readbacks.clone())
+            .add_event::<ReadDensityTexture>()
+            .add_event::<DensityTextureRead>()
+            .add_system(apply_density_readbacks.before(MarchingCubesSystem::Meshing));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .insert_resource(readbacks)
+            .init_resource::<ExtractedDensityUploads>()
+            .init_resource::<ExtractedDensityReadbacks>()
+            .add_system_to_stage(RenderStage::Extract, extract_density_sync)
+            .add_system_to_stage(RenderStage::Queue, write_density_uploads)
+            // after the render graph so the compute shader edits are read
+            .add_system_to_stage(RenderStage::Cleanup, read_density_textures);
+    }
+}
+
+#[derive(Default)]
+struct ExtractedDensityUploads(Vec<DensityUpload>);
+
+/// Texture and number of points of each chunk to read back
+#[derive(Default)]
+struct ExtractedDensityReadbacks(Vec<(Entity, Handle<Image>, UVec3)>);
+
+fn extract_density_sync(
+    mut commands: Commands,
+    mut uploads: ResMut<DensityUploads>,
+    mut requests: EventReader<ReadDensityTexture>,
+    chunks: Query<(&Chunk, &DensityTexture)>,
+) {
+    commands.insert_resource(ExtractedDensityUploads(std::mem::take(&mut uploads.0)));
+    let readbacks = requests
+        .iter()
+        .filter_map(|ReadDensityTexture(entity)| {
+            let (chunk, texture) = chunks.get(*entity).ok()?;
+            Some((*entity, texture.0.clone_weak(), chunk.size + UVec3::ONE))
+        })
+        .collect();
+    commands.insert_resource(ExtractedDensityReadbacks(readbacks));
+}
+
+fn write_density_uploads(
+    uploads: Res<ExtractedDensityUploads>,
+    images: Res<RenderAssets<Image>>,
+    render_queue: Res<RenderQueue>,
+) {
+    for upload in &uploads.0 {
+        let texture = match images.get(&upload.texture) {
+            Some(texture) => texture,
+            None => continue,
+        };
+        render_queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: upload.origin.x,
+                    y: upload.origin.y,
+                    z: upload.origin.z,
+                },
+                aspect: TextureAspect::All,
+            },
+            &upload.bytes,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(upload.extent.x * 4),
+                rows_per_image: NonZeroU32::new(upload.extent.y),
+            },
+            extent(upload.extent),
+        );
+    }
+}
+
+fn read_density_textures(
+    requests: Res<ExtractedDensityReadbacks>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    readbacks: Res<DensityReadbacks>,
+) {
+    for (entity, handle, points) in &requests.0 {
+        let texture = match images.get(handle) {
+            Some(texture) => texture,
+            None => continue,
+        };
+        let row_len = points.x * 4;
+        let padded_row_len = padded_row_len(row_len);
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("density_readback"),
+            size: (padded_row_len * points.y * points.z) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("density_readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_len),
+                    rows_per_image: NonZeroU32::new(points.y),
+                },
+            },
+            extent(*points),
+        );
+        render_queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        render_device.map_buffer(&slice, MapMode::Read);
+        let values = unpad_rows(&slice.get_mapped_range(), row_len, padded_row_len);
+        buffer.unmap();
+        readbacks.0.lock().unwrap().push((*entity, values));
+    }
+}
+
+fn extent(size: UVec3) -> Extent3d {
+    Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: size.z,
+    }
+}
+
+/// Bytes between the starts of the rows of `row_len` bytes of a texture
+/// copied to a buffer, shared by every readback of a texture
+pub(crate) fn padded_row_len(row_len: u32) -> u32 {
+    (row_len + ROW_ALIGNMENT - 1) / ROW_ALIGNMENT * ROW_ALIGNMENT
+}
+
+/// Points of rows of `row_len` bytes stored every `padded_row_len` bytes
+fn unpad_rows(bytes: &[u8], row_len: u32, padded_row_len: u32) -> Vec<f32> {
+    bytes
+        .chunks(padded_row_len as usize)
+        .flat_map(|row| row[..row_len as usize].chunks_exact(4))
+        .map(|point| f32::from_ne_bytes([point[0], point[1], point[2], point[3]]))
+        .collect()
+}
+
+fn apply_density_readbacks(
+    readbacks: Res<DensityReadbacks>,
+    mut chunks: Query<(&mut Chunk, &mut TextureRevision)>,
+    mut events: EventWriter<DensityTextureRead>,
+) {
+    let results = std::mem::take(&mut *readbacks.0.lock().unwrap());
+    for (entity, values) in results {
+        let (mut chunk, mut revision) = match chunks.get_mut(entity) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+        // the chunk was resized while the texture was read
+        if values.len() != chunk.points.len() {
+            continue;
+        }
+        if values != chunk.points {
+            let size = chunk.size;
+            chunk.points = values;
+            chunk.mark_dirty(UVec3::ZERO, size);
+            // the texture already holds these points
+            revision.0 = chunk.revision();
+        }
+        events.send(DensityTextureRead(entity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uploads_only_the_region() {
+        let size = UVec3::new(3, 2, 2);
+        let chunk = Chunk::from_fn(size, |point| {
+            (point.x + 10 * point.y + 100 * point.z) as f32
+        });
+        let region = DirtyRegion {
+            min: UVec3::new(1, 1, 0),
+            max: UVec3::new(2, 5, 1),
+        };
+        let upload = DensityUpload::new(Handle::default(), &chunk, region);
+        assert_eq!(upload.origin, UVec3::new(1, 1, 0));
+        assert_eq!(upload.extent, UVec3::new(2, 2, 2));
+        let values = unpad_rows(&upload.bytes, 8, 8);
+        assert_eq!(
+            values,
+            vec![11.0, 12.0, 21.0, 22.0, 111.0, 112.0, 121.0, 122.0]
+        );
+    }
+
+    #[test]
+    fn readback_rows_are_unpadded() {
+        assert_eq!(padded_row_len(4), 256);
+        assert_eq!(padded_row_len(256), 256);
+        let mut bytes = vec![0; 512];
+        bytes[..4].copy_from_slice(&1.0f32.to_ne_bytes());
+        bytes[256..260].copy_from_slice(&2.0f32.to_ne_bytes());
+        assert_eq!(unpad_rows(&bytes, 4, 256), vec![1.0, 2.0]);
+    }
+}
@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContext, Inspectable};
+
+use crate::{
+    brush::{cursor_hit, edit_box},
+    camera::FlyCam,
+    chunk::Chunk,
+    field::DensityField,
+    generation::WorldSettings,
+    terrain_root::MainTerrain,
+    Data, StartMarching,
+};
+
+/// Longest ray marched in the field to find the clicked point
+const MAX_DISTANCE: f32 = 500.0;
+
+/// Press H to toggle the flatten tool, then click the terrain to level a
+/// horizontal pad at the height of the clicked point.
+///
+/// The terrain above the pad is cleared and the terrain below it is filled,
+/// the pad blends back into the terrain around it.
+#[derive(Inspectable)]
+pub struct FlattenTool {
+    pub enabled: bool,
+    /// Radius of the flat part of the pad
+    #[inspectable(min = 0.5, max = 64.0)]
+    pub radius: f32,
+    /// Width of the ring around the pad blending it into the terrain
+    #[inspectable(min = 0.0, max = 64.0)]
+    pub blend: f32,
+    /// Height cleared above the pad
+    #[inspectable(min = 0.0, max = 64.0)]
+    pub clearance: f32,
+    /// Depth filled below the pad
+    #[inspectable(min = 0.0, max = 64.0)]
+    pub depth: f32,
+}
+
+impl Default for FlattenTool {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 4.0,
+            blend: 3.0,
+            clearance: 8.0,
+            depth: 4.0,
+        }
+    }
+}
+
+impl FlattenTool {
+    /// Density of the point at `pos` once flattened around a pad at `center`.
+    ///
+    /// Inside the pad the density follows a plane at the height of `center`,
+    /// solid below it. In the blend ring it's interpolated back to `value`
+    /// with a smoothstep.
+    pub fn pad_density(
+        &self,
+        center: Vec3,
+        pos: Vec3,
+        value: f32,
+        isolevel: f32,
+        cell_size: f32,
+    ) -> f32 {
+        let height = pos.y - center.y;
+        if height > self.clearance || height < -self.depth {
+            return value;
+        }
+        let distance = Vec2::new(pos.x - center.x, pos.z - center.z).length();
+        let weight = edge_weight(distance, self.radius, self.blend);
+        value + (surface_density(height, isolevel, cell_size) - value) * weight
+    }
+}
+
+/// Density of a point `height` above a flat surface, it crosses the
+/// isolevel on the surface and is saturated two cells away
+pub(crate) fn surface_density(height: f32, isolevel: f32, cell_size: f32) -> f32 {
+    (isolevel - height / (2.0 * cell_size)).clamp(0.0, 1.0)
+}
+
+/// 1 up to `radius`, then a smoothstep down to 0 at `radius + blend`
+pub(crate) fn edge_weight(distance: f32, radius: f32, blend: f32) -> f32 {
+    let t = if blend > 0.0 {
+        ((distance - radius) / blend).clamp(0.0, 1.0)
+    } else if distance <= radius {
+        0.0
+    } else {
+        1.0
+    };
+    1.0 - t * t * (3.0 - 2.0 * t)
+}
+
+/// Levels a pad centered on `center` with the [`FlattenTool`] settings
+pub struct FlattenPad {
+    pub center: Vec3,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pick_flatten_pad(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut tool: ResMut<FlattenTool>,
+    data: Res<Data>,
+    windows: Res<Windows>,
+    mut egui_context: ResMut<EguiContext>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    field: DensityField,
+    mut pads: EventWriter<FlattenPad>,
+) {
+    if keyboard_input.just_pressed(KeyCode::H) {
+        tool.enabled = !tool.enabled;
+    }
+    if !tool.enabled
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || egui_context.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+    if let Some(center) = cursor_hit(&windows, &cameras, &field, data.isolevel, MAX_DISTANCE) {
+        pads.send(FlattenPad { center });
+    }
+}
+
+pub fn flatten_pads(
+    tool: Res<FlattenTool>,
+    data: Res<Data>,
+    mut pads: EventReader<FlattenPad>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut chunks: Query<&mut Chunk>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let mut edited = false;
+    for FlattenPad { center } in pads.iter() {
+        let reach = tool.radius + tool.blend;
+        let min = *center - Vec3::new(reach, tool.depth, reach);
+        let max = *center + Vec3::new(reach, tool.clearance, reach);
+        edited |= edit_box(
+            chunk_map,
+            &mut chunks,
+            &world_settings,
+            min,
+            max,
+            |pos, value| {
+                tool.pad_density(*center, pos, value, data.isolevel, world_settings.cell_size)
+            },
+        );
+    }
+    if edited {
+        start_marching_events.send_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pad_is_flat_and_blends() {
+        let tool = FlattenTool::default();
+        let center = Vec3::new(10.0, 5.0, 10.0);
+        let at = |x: f32, y: f32, value: f32| {
+            tool.pad_density(center, Vec3::new(x, y, 10.0), value, 0.5, 1.0)
+        };
+        // a hill is cut and a hole is filled inside the pad
+        assert!(at(12.0, 6.0, 1.0) < 0.5);
+        assert!(at(12.0, 4.0, 0.0) > 0.5);
+        assert_eq!(at(12.0, 5.0, 0.0), 0.5);
+        // halfway through the blend ring
+        let blended = at(10.0 + tool.radius + tool.blend / 2.0, 6.0, 1.0);
+        assert!(blended > 0.0 && blended < 1.0);
+        // outside the pad and its ring, or above the clearance
+        assert_eq!(at(10.0 + tool.radius + tool.blend + 0.1, 6.0, 0.8), 0.8);
+        assert_eq!(at(12.0, 5.0 + tool.clearance + 1.0, 0.8), 0.8);
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{volume::VolumePreview, Data};
+
+/// Degrades the debug visuals while the frames are slow so the editor stays
+/// usable after a setting was pushed too far.
+///
+/// Past `degrade_above` the debug points are hidden, the wireframes are
+/// disabled and the volume preview takes fewer steps. They are restored once
+/// the frame time falls below `restore_below`.
+#[derive(Inspectable)]
+pub struct FrameTimeGuard {
+    pub enabled: bool,
+    /// Smoothed frame time in milliseconds above which the visuals are degraded
+    #[inspectable(min = 1.0, max = 200.0)]
+    pub degrade_above: f32,
+    /// Smoothed frame time in milliseconds below which they are restored
+    #[inspectable(min = 1.0, max = 200.0)]
+    pub restore_below: f32,
+    /// Seconds the frame time must stay past a threshold before switching
+    #[inspectable(min = 0.0, max = 10.0)]
+    pub delay: f32,
+    /// The volume preview steps are divided by this while degraded
+    #[inspectable(min = 1, max = 16)]
+    pub preview_step_divisor: u32,
+    /// Whether the visuals are currently degraded
+    #[inspectable(ignore)]
+    pub degraded: bool,
+}
+
+impl Default for FrameTimeGuard {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            // 20 fps
+            degrade_above: 50.0,
+            restore_below: 25.0,
+            delay: 1.0,
+            preview_step_divisor: 4,
+            degraded: false,
+        }
+    }
+}
+
+/// Smoothed frame time and the time spent past a threshold
+#[derive(Default)]
+pub struct FrameTimeMonitor {
+    average_ms: Option<f32>,
+    past_threshold: f32,
+}
+
+impl FrameTimeMonitor {
+    /// Seconds over which the frame times are averaged
+    const SMOOTHING: f32 = 0.5;
+
+    /// Adds a frame of `delta` seconds, returns whether the visuals must now
+    /// be degraded when that changes
+    pub fn update(&mut self, delta: f32, guard: &FrameTimeGuard) -> Option<bool> {
+        let frame_ms = delta * 1000.0;
+        let average = match self.average_ms {
+            Some(average) => {
+                let t = 1.0 - (-delta / Self::SMOOTHING).exp();
+                average + (frame_ms - average) * t
+            }
+            None => frame_ms,
+        };
+        self.average_ms = Some(average);
+
+        let past_threshold = if guard.degraded {
+            average < guard.restore_below
+        } else {
+            average > guard.degrade_above
+        };
+        if !past_threshold {
+            self.past_threshold = 0.0;
+            return None;
+        }
+        self.past_threshold += delta;
+        if self.past_threshold < guard.delay {
+            return None;
+        }
+        self.past_threshold = 0.0;
+        Some(!guard.degraded)
+    }
+}
+
+/// Settings changed by the guard, restored with the visuals
+#[derive(Default)]
+pub struct DegradedSettings {
+    show_wireframe: bool,
+    preview_steps: u32,
+}
+
+pub fn guard_frame_time(
+    time: Res<Time>,
+    mut guard: ResMut<FrameTimeGuard>,
+    mut data: ResMut<Data>,
+    mut volume_preview: ResMut<VolumePreview>,
+    mut monitor: Local<FrameTimeMonitor>,
+    mut saved: Local<DegradedSettings>,
+) {
+    if !guard.enabled {
+        if guard.degraded {
+            restore(&mut guard, &mut data, &mut volume_preview, &saved);
+        }
+        return;
+    }
+
+    match monitor.update(time.delta_seconds(), &guard) {
+        Some(true) => {
+            *saved = DegradedSettings {
+                show_wireframe: data.show_wireframe,
+                preview_steps: volume_preview.steps,
+            };
+            if data.show_wireframe {
+                data.show_wireframe = false;
+            }
+            let steps = saved.preview_steps;
+            volume_preview.steps = (steps / guard.preview_step_divisor.max(1)).max(steps.min(8));
+            guard.degraded = true;
+            warn!("Frames are slow, the debug visuals are degraded");
+        }
+        Some(false) => {
+            restore(&mut guard, &mut data, &mut volume_preview, &saved);
+            info!("Frames recovered, the debug visuals are restored");
+        }
+        None => {}
+    }
+}
+
+fn restore(
+    guard: &mut FrameTimeGuard,
+    data: &mut Data,
+    volume_preview: &mut VolumePreview,
+    saved: &DegradedSettings,
+) {
+    if saved.show_wireframe {
+        data.show_wireframe = true;
+    }
+    volume_preview.steps = saved.preview_steps;
+    guard.degraded = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_after_the_delay_with_hysteresis() {
+        let mut guard = FrameTimeGuard::default();
+        let mut monitor = FrameTimeMonitor::default();
+        // fast frames
+        for _ in 0..100 {
+            assert_eq!(monitor.update(0.016, &guard), None);
+        }
+        // a single spike is ignored
+        assert_eq!(monitor.update(0.2, &guard), None);
+        // slow frames degrade once the delay passed
+        let switched = (0..20).find_map(|_| monitor.update(0.1, &guard));
+        assert_eq!(switched, Some(true));
+        guard.degraded = true;
+        // between the thresholds nothing changes
+        for _ in 0..200 {
+            assert_eq!(monitor.update(0.035, &guard), None);
+        }
+        let switched = (0..200).find_map(|_| monitor.update(0.016, &guard));
+        assert_eq!(switched, Some(false));
+    }
+}
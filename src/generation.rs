@@ -0,0 +1,901 @@
+use bevy::{
+    math::{DVec2, DVec3},
+    prelude::*,
+};
+use bevy_inspector_egui::Inspectable;
+use noise::{Billow, Fbm, MultiFractal, NoiseFn, RidgedMulti, Seedable};
+
+/// Density value used for points that are forced to be solid
+pub const SOLID: f32 = 1.0;
+/// Density value used for points that are forced to be empty
+pub const EMPTY: f32 = 0.0;
+
+/// Kind of noise of a [`NoiseLayer`]
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseKind {
+    /// Fractal brownian motion, rolling hills
+    Fbm,
+    /// Absolute value of the octaves, puffy rounded shapes
+    Billow,
+    /// Inverted absolute value of the octaves, sharp ridges
+    RidgedMulti,
+}
+
+/// How a [`NoiseLayer`] is combined with the layers under it
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    Add,
+    Multiply,
+    Min,
+    Max,
+    /// Replaces the layers under it, useful with a mask or an opacity below 1
+    Lerp,
+}
+
+/// Limits where a [`NoiseLayer`] is applied, the layer is blended by the
+/// value of the mask from 0 to 1.
+///
+/// The ranges fade the layer in from their first bound to their second
+/// bound, swapping the bounds fades it out instead. Positions are in world
+/// units, without the noise offset.
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub enum LayerMask {
+    None,
+    /// Low frequency noise remapped to 0..1, `coverage` is the part of the
+    /// world where the layer is applied
+    Noise {
+        #[inspectable(min = 0.001, max = 1.0, speed = 0.005)]
+        frequency: f64,
+        #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+        coverage: f64,
+    },
+    /// Fades the layer in from the `from` to the `to` height, e.g. caves
+    /// only below the sea level with `from` above `to`
+    Height {
+        #[inspectable(speed = 0.1)]
+        from: f64,
+        #[inspectable(speed = 0.1)]
+        to: f64,
+    },
+    /// Fades the layer in from the `from` to the `to` steepness of the layers
+    /// under it, the length of their gradient. Ridges are steep and plateaus
+    /// are flat, so a `from` above `to` confines the layer to the plateaus.
+    Slope {
+        #[inspectable(min = 0.0, speed = 0.01)]
+        from: f64,
+        #[inspectable(min = 0.0, speed = 0.01)]
+        to: f64,
+    },
+    /// Fades the layer in from the `from` to the `to` horizontal distance to
+    /// the world origin, e.g. mountains only far from the spawn
+    Radial {
+        #[inspectable(min = 0.0, speed = 0.5)]
+        from: f64,
+        #[inspectable(min = 0.0, speed = 0.5)]
+        to: f64,
+    },
+}
+
+/// One layer of the [`NoiseSettings`] stack
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub struct NoiseLayer {
+    pub enabled: bool,
+    pub kind: NoiseKind,
+    /// Added to the seed of the [`NoiseSettings`] so identical layers don't
+    /// produce identical noise
+    pub seed_offset: u32,
+
+    /// Total number of frequency octaves to generate the noise with.
+    ///
+    /// The number of octaves control the _amount of detail_ in the noise
+    /// function. Adding more octaves increases the detail, with the drawback
+    /// of increasing the calculation time.
+    #[inspectable(min = 1, max = 32)]
+    pub octaves: usize,
+
+    /// The number of cycles per unit length that the noise function outputs.
+    #[inspectable(min = 0.0, max = 5.0, speed = 0.1)]
+    pub frequency: f64,
+
+    /// A multiplier that determines how quickly the frequency increases for
+    /// each successive octave in the noise function.
+    ///
+    /// The frequency of each successive octave is equal to the product of the
+    /// previous octave's frequency and the lacunarity value.
+    ///
+    /// A lacunarity of 2.0 results in the frequency doubling every octave. For
+    /// almost all cases, 2.0 is a good value to use.
+    #[inspectable(min = 0.0, max = 5.0, speed = 0.1)]
+    pub lacunarity: f64,
+
+    /// A multiplier that determines how quickly the amplitudes diminish for
+    /// each successive octave in the noise function.
+    ///
+    /// The amplitude of each successive octave is equal to the product of the
+    /// previous octave's amplitude and the persistence value. Increasing the
+    /// persistence produces "rougher" noise.
+    #[inspectable(min = 0.05, max = 2.0, speed = 0.05)]
+    pub persistence: f64,
+
+    /// Multiplier of the noise before it's blended
+    #[inspectable(min = -4.0, max = 4.0, speed = 0.01)]
+    pub amplitude: f64,
+    pub blend: BlendMode,
+    /// Weight of the layer, 0 leaves the layers under it unchanged
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub opacity: f64,
+    pub mask: LayerMask,
+}
+
+impl Default for NoiseLayer {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            kind: NoiseKind::Fbm,
+            seed_offset: 0,
+            octaves: Fbm::DEFAULT_OCTAVE_COUNT,
+            frequency: Fbm::DEFAULT_FREQUENCY,
+            lacunarity: 0.2,
+            persistence: Fbm::DEFAULT_PERSISTENCE,
+            amplitude: 1.0,
+            blend: BlendMode::Add,
+            opacity: 1.0,
+            mask: LayerMask::None,
+        }
+    }
+}
+
+impl NoiseLayer {
+    pub fn with_kind(mut self, kind: NoiseKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_seed_offset(mut self, seed_offset: u32) -> Self {
+        self.seed_offset = seed_offset;
+        self
+    }
+
+    pub fn with_octaves(mut self, octaves: usize) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn with_frequency(mut self, frequency: f64) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn with_lacunarity(mut self, lacunarity: f64) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    pub fn with_persistence(mut self, persistence: f64) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f64) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f64) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: LayerMask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    fn build(&self, seed: u32) -> Option<BuiltLayer> {
+        if !self.enabled {
+            return None;
+        }
+        let seed = seed.wrapping_add(self.seed_offset);
+        let source = match self.kind {
+            NoiseKind::Fbm => LayerSource::Fbm(multi_fractal(Fbm::new(), seed, self)),
+            NoiseKind::Billow => LayerSource::Billow(multi_fractal(Billow::new(), seed, self)),
+            NoiseKind::RidgedMulti => {
+                LayerSource::RidgedMulti(multi_fractal(RidgedMulti::new(), seed, self))
+            }
+        };
+        let mask = match self.mask {
+            LayerMask::None => BuiltMask::None,
+            LayerMask::Noise {
+                frequency,
+                coverage,
+            } => BuiltMask::Noise {
+                // a different seed so the mask doesn't follow the layer
+                noise: Fbm::new()
+                    .set_seed(seed.wrapping_add(1))
+                    .set_octaves(2)
+                    .set_frequency(frequency),
+                coverage,
+            },
+            LayerMask::Height { from, to } => BuiltMask::Height { from, to },
+            LayerMask::Slope { from, to } => BuiltMask::Slope { from, to },
+            LayerMask::Radial { from, to } => BuiltMask::Radial { from, to },
+        };
+        Some(BuiltLayer {
+            source,
+            amplitude: self.amplitude,
+            blend: self.blend,
+            opacity: self.opacity,
+            mask,
+        })
+    }
+}
+
+fn multi_fractal<T: MultiFractal + Seedable>(noise: T, seed: u32, layer: &NoiseLayer) -> T {
+    noise
+        .set_seed(seed)
+        .set_octaves(layer.octaves)
+        .set_persistence(layer.persistence)
+        .set_lacunarity(layer.lacunarity)
+        .set_frequency(layer.frequency)
+}
+
+/// Noise the points are sampled from, also a component of each
+/// [`TerrainRoot`](crate::terrain_root::TerrainRoot)
+#[derive(Inspectable, Component, Clone)]
+#[non_exhaustive]
+pub struct NoiseSettings {
+    /// Seed used by every noise function, identical seeds generate identical worlds
+    pub seed: u32,
+
+    /// Layers blended from the first to the last, starting from 0
+    pub layers: Vec<NoiseLayer>,
+
+    #[inspectable(speed = 0.05)]
+    pub offset: Vec3,
+
+    #[inspectable(min = 0.1, max = 1.5, speed = 0.01)]
+    pub scale: f32,
+}
+
+impl Default for NoiseSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            layers: vec![NoiseLayer::default()],
+            offset: Vec3::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl NoiseSettings {
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Replaces the whole stack
+    pub fn with_layers(mut self, layers: Vec<NoiseLayer>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Adds a layer on top of the stack
+    pub fn with_layer(mut self, layer: NoiseLayer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Builds the noise functions of the enabled layers
+    pub fn stack(&self) -> NoiseStack {
+        NoiseStack {
+            layers: self
+                .layers
+                .iter()
+                .filter_map(|layer| layer.build(self.seed))
+                .collect(),
+            offset: self.offset.as_dvec3(),
+        }
+    }
+}
+
+enum LayerSource {
+    Fbm(Fbm),
+    Billow(Billow),
+    RidgedMulti(RidgedMulti),
+}
+
+enum BuiltMask {
+    None,
+    Noise { noise: Fbm, coverage: f64 },
+    Height { from: f64, to: f64 },
+    Slope { from: f64, to: f64 },
+    Radial { from: f64, to: f64 },
+}
+
+struct BuiltLayer {
+    source: LayerSource,
+    amplitude: f64,
+    blend: BlendMode,
+    opacity: f64,
+    mask: BuiltMask,
+}
+
+/// Noise functions of the [`NoiseSettings`] layers, sampled by the generation
+pub struct NoiseStack {
+    layers: Vec<BuiltLayer>,
+    /// Offset of the noise, removed from the sample positions by the masks
+    offset: DVec3,
+}
+
+/// Distance between the samples of the slope masks in noise space
+const SLOPE_STEP: f64 = 0.05;
+
+impl NoiseStack {
+    /// Value of the first `count` layers at `point`
+    fn value_of(&self, count: usize, point: [f64; 3]) -> f64 {
+        self.layers[..count]
+            .iter()
+            .enumerate()
+            .fold(0.0, |value, (index, layer)| {
+                let weight = layer.opacity * self.mask(index, &layer.mask, point);
+                if weight <= 0.0 {
+                    return value;
+                }
+                let noise = layer.amplitude
+                    * match &layer.source {
+                        LayerSource::Fbm(noise) => noise.get(point),
+                        LayerSource::Billow(noise) => noise.get(point),
+                        LayerSource::RidgedMulti(noise) => noise.get(point),
+                    };
+                let blended = match layer.blend {
+                    BlendMode::Add => value + noise,
+                    BlendMode::Multiply => value * noise,
+                    BlendMode::Min => value.min(noise),
+                    BlendMode::Max => value.max(noise),
+                    BlendMode::Lerp => noise,
+                };
+                value + (blended - value) * weight
+            })
+    }
+
+    /// Value of the mask of the layer at `index`
+    fn mask(&self, index: usize, mask: &BuiltMask, point: [f64; 3]) -> f64 {
+        let world = DVec3::from(point) - self.offset;
+        match *mask {
+            BuiltMask::None => 1.0,
+            BuiltMask::Noise {
+                ref noise,
+                coverage,
+            } => {
+                // the mask noise is mostly within -0.5..0.5
+                let mask = noise.get(point) + 0.5;
+                smoothstep_f64(1.0 - coverage - 0.1, 1.0 - coverage + 0.1, mask)
+            }
+            BuiltMask::Height { from, to } => fade(from, to, world.y),
+            BuiltMask::Slope { from, to } => {
+                let p = DVec3::from(point);
+                let value = self.value_of(index, point);
+                let gradient = DVec3::new(
+                    self.value_of(index, (p + DVec3::X * SLOPE_STEP).to_array()) - value,
+                    self.value_of(index, (p + DVec3::Y * SLOPE_STEP).to_array()) - value,
+                    self.value_of(index, (p + DVec3::Z * SLOPE_STEP).to_array()) - value,
+                ) / SLOPE_STEP;
+                fade(from, to, gradient.length())
+            }
+            BuiltMask::Radial { from, to } => fade(from, to, world.x.hypot(world.z)),
+        }
+    }
+}
+
+impl NoiseFn<[f64; 3]> for NoiseStack {
+    fn get(&self, point: [f64; 3]) -> f64 {
+        self.value_of(self.layers.len(), point)
+    }
+}
+
+/// Smoothstep from 0 at `from` to 1 at `to`, decreasing when `to` is below
+/// `from` and a step when they are equal
+fn fade(from: f64, to: f64, x: f64) -> f64 {
+    if from == to {
+        if x >= from {
+            1.0
+        } else {
+            0.0
+        }
+    } else {
+        smoothstep_f64(from, to, x)
+    }
+}
+
+/// Settings that shape the world volume independently of the noise function,
+/// also a component of each [`TerrainRoot`](crate::terrain_root::TerrainRoot)
+#[derive(Inspectable, Component, Clone)]
+#[non_exhaustive]
+pub struct WorldSettings {
+    /// Distance between two points of the grid in world units, the noise and
+    /// the bounds are sampled in world units so smaller cells add details
+    #[inspectable(min = 0.1, max = 4.0, speed = 0.05)]
+    pub cell_size: f32,
+    /// Number of cells of the chunks on each axis, only read when the chunks are spawned
+    #[inspectable(ignore)]
+    pub chunk_size: UVec3,
+    /// Number of chunks on each axis, centered on the origin on X and Z and
+    /// stacked up from zero on Y. Only read when the chunks are spawned.
+    #[inspectable(ignore)]
+    pub chunk_count: UVec3,
+    pub bounds: WorldBounds,
+    /// Wraps noise sampling and chunk neighbors around the X/Z edges of the
+    /// world so opposite edges connect and the terrain can be tiled
+    pub wrap: bool,
+    /// Guarantees identical density values for identical seeds on every platform.
+    ///
+    /// Sample positions are always derived from integer grid coordinates in
+    /// f64, and the noise functions only use IEEE 754 basic operations, which
+    /// are reproducible. This mode additionally does every post-processing
+    /// step in f64 in a fixed order and quantizes the result to 16.16 fixed
+    /// point, so the stored f32 values don't depend on how the compiler or
+    /// the platform rounds intermediate results.
+    pub deterministic: bool,
+    /// Keeps where the surface crosses the edges of the generated chunks,
+    /// found on the noise itself, with its normals. Dual contouring and the
+    /// normals of the meshes use them until the edges are edited.
+    pub hermite_data: bool,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.0,
+            chunk_size: UVec3::splat(crate::CHUNK_SIZE as u32),
+            chunk_count: UVec3::new(3, 1, 3),
+            bounds: WorldBounds::default(),
+            wrap: false,
+            deterministic: false,
+            hermite_data: false,
+        }
+    }
+}
+
+impl WorldSettings {
+    pub fn with_cell_size(mut self, cell_size: f32) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: UVec3) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_chunk_count(mut self, chunk_count: UVec3) -> Self {
+        self.chunk_count = chunk_count;
+        self
+    }
+
+    /// Size of a chunk in world units
+    pub fn chunk_extent(&self) -> Vec3 {
+        self.chunk_size.as_vec3() * self.cell_size
+    }
+
+    pub fn with_bounds(mut self, bounds: WorldBounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+}
+
+/// Forces the density to solid below `floor_y` and to empty above `ceiling_y`.
+///
+/// The transition is blended over `blend` world units so the clamped regions
+/// don't produce a hard step in the generated surface.
+#[derive(Inspectable, Clone, Copy)]
+#[non_exhaustive]
+pub struct WorldBounds {
+    pub floor_enabled: bool,
+    #[inspectable(speed = 0.1)]
+    pub floor_y: f32,
+    pub ceiling_enabled: bool,
+    #[inspectable(speed = 0.1)]
+    pub ceiling_y: f32,
+    #[inspectable(min = 0.0, max = 8.0, speed = 0.05)]
+    pub blend: f32,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            floor_enabled: false,
+            floor_y: 1.0,
+            ceiling_enabled: false,
+            ceiling_y: 14.0,
+            blend: 2.0,
+        }
+    }
+}
+
+impl WorldBounds {
+    /// Enables the floor at height `y`
+    pub fn with_floor(mut self, y: f32) -> Self {
+        self.floor_enabled = true;
+        self.floor_y = y;
+        self
+    }
+
+    /// Enables the ceiling at height `y`
+    pub fn with_ceiling(mut self, y: f32) -> Self {
+        self.ceiling_enabled = true;
+        self.ceiling_y = y;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: f32) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Applies the floor and ceiling to a density value sampled at world height `y`
+    pub fn apply(&self, y: f32, value: f32) -> f32 {
+        // avoid a zero width transition, smoothstep would divide by zero
+        let blend = self.blend.max(0.001);
+        let mut value = value;
+        if self.floor_enabled {
+            let t = smoothstep(self.floor_y + blend, self.floor_y, y);
+            value = lerp(value, SOLID, t);
+        }
+        if self.ceiling_enabled {
+            let t = smoothstep(self.ceiling_y - blend, self.ceiling_y, y);
+            value = lerp(value, EMPTY, t);
+        }
+        value
+    }
+
+    /// Same as [`WorldBounds::apply`] but every step is done in f64, used by
+    /// the deterministic mode
+    pub fn apply_f64(&self, y: f64, value: f64) -> f64 {
+        let blend = (self.blend as f64).max(0.001);
+        let (floor_y, ceiling_y) = (self.floor_y as f64, self.ceiling_y as f64);
+        let mut value = value;
+        if self.floor_enabled {
+            let t = smoothstep_f64(floor_y + blend, floor_y, y);
+            value += (SOLID as f64 - value) * t;
+        }
+        if self.ceiling_enabled {
+            let t = smoothstep_f64(ceiling_y - blend, ceiling_y, y);
+            value += (EMPTY as f64 - value) * t;
+        }
+        value
+    }
+}
+
+/// Region of the X/Z plane that repeats when the world wraps
+#[derive(Clone, Copy)]
+pub struct WrapPeriod {
+    pub origin: IVec2,
+    pub size: IVec2,
+}
+
+/// Bounds the number of chunks generated at the same time.
+///
+/// Each worker keeps its scratch buffer between generations, so filling many
+/// chunks at once allocates at most `max_in_flight` buffers instead of one
+/// per chunk.
+#[derive(Inspectable)]
+pub struct GenerationWorkers {
+    #[inspectable(min = 1, max = 32)]
+    pub max_in_flight: usize,
+}
+
+impl Default for GenerationWorkers {
+    fn default() -> Self {
+        Self { max_in_flight: 4 }
+    }
+}
+
+/// Fills `buffer` with the densities of the points of a chunk of `size` cells
+/// starting at `origin`, in the same order as [`Chunk::points`](crate::chunk::Chunk).
+///
+/// The buffer is cleared first and keeps its allocation.
+pub fn fill_points(
+    buffer: &mut Vec<f32>,
+    noise: &impl NoiseFn<[f64; 3]>,
+    origin: IVec3,
+    size: UVec3,
+    noise_settings: &NoiseSettings,
+    world_settings: &WorldSettings,
+    wrap: Option<WrapPeriod>,
+) {
+    buffer.clear();
+    buffer.extend(crate::chunk::Chunk::new_iter_3d(size).map(|point| {
+        let pos = origin + point.as_ivec3();
+        sample_density(noise, pos, noise_settings, world_settings, wrap)
+    }));
+}
+
+/// Computes the density of the grid point at `pos` in world grid coordinates
+pub fn sample_density(
+    noise: &impl NoiseFn<[f64; 3]>,
+    pos: IVec3,
+    noise_settings: &NoiseSettings,
+    world_settings: &WorldSettings,
+    wrap: Option<WrapPeriod>,
+) -> f32 {
+    sample_density_at(noise, pos.as_dvec3(), noise_settings, world_settings, wrap)
+}
+
+/// Computes the density at `pos` in world grid coordinates, it can be
+/// between the grid points
+pub fn sample_density_at(
+    noise: &impl NoiseFn<[f64; 3]>,
+    pos: DVec3,
+    noise_settings: &NoiseSettings,
+    world_settings: &WorldSettings,
+    wrap: Option<WrapPeriod>,
+) -> f32 {
+    let offset = noise_settings.offset.as_dvec3();
+    let cell_size = world_settings.cell_size as f64;
+    let val = match wrap {
+        Some(period) if world_settings.wrap => {
+            sample_tileable(noise, pos, cell_size, offset, period)
+        }
+        _ => {
+            let p = pos * cell_size + offset;
+            noise.get([p.x, p.y, p.z])
+        }
+    };
+    let val = (val + 1.0) / 2.0;
+
+    if world_settings.deterministic {
+        let val = val * noise_settings.scale as f64;
+        let val = world_settings.bounds.apply_f64(pos.y * cell_size, val);
+        quantize(val)
+    } else {
+        let val = val as f32 * noise_settings.scale;
+        let y = pos.y as f32 * world_settings.cell_size;
+        world_settings.bounds.apply(y, val)
+    }
+}
+
+/// Rounds `value` to 16.16 fixed point
+pub fn quantize(value: f64) -> f32 {
+    ((value * 65536.0).round() / 65536.0) as f32
+}
+
+/// Samples `noise` at `pos` so the output repeats every `period` units along X and Z.
+///
+/// The position is first wrapped into the period, then the noise is blended
+/// with copies of itself shifted by one period so both edges of the period
+/// sample the same values. The wrapped position is scaled by `cell_size` to
+/// world units, then `offset` is added so it scrolls the noise without
+/// breaking the tiling.
+pub fn sample_tileable(
+    noise: &impl NoiseFn<[f64; 3]>,
+    pos: DVec3,
+    cell_size: f64,
+    offset: DVec3,
+    period: WrapPeriod,
+) -> f64 {
+    let origin = period.origin.as_dvec2();
+    let u = (pos.x - origin.x).rem_euclid(period.size.x as f64);
+    let v = (pos.z - origin.y).rem_euclid(period.size.y as f64);
+    let p = DVec3::new(origin.x + u, pos.y, origin.y + v) * cell_size + offset;
+    let size = period.size.as_dvec2() * cell_size;
+
+    let a = noise.get([p.x, p.y, p.z]);
+    let b = noise.get([p.x - size.x, p.y, p.z]);
+    let c = noise.get([p.x, p.y, p.z - size.y]);
+    let d = noise.get([p.x - size.x, p.y, p.z - size.y]);
+
+    let t = DVec2::new(u, v) / period.size.as_dvec2();
+    let ab = a + (b - a) * t.x;
+    let cd = c + (d - c) * t.x;
+    ab + (cd - ab) * t.y
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn smoothstep_f64(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(seed: u32) -> (NoiseSettings, WorldSettings) {
+        let noise_settings = NoiseSettings { seed, ..default() };
+        let world_settings = WorldSettings {
+            deterministic: true,
+            ..default()
+        };
+        (noise_settings, world_settings)
+    }
+
+    fn sample_block(seed: u32, origin: IVec3) -> Vec<u32> {
+        let (noise_settings, world_settings) = settings(seed);
+        let noise = noise_settings.stack();
+        crate::chunk::Chunk::new_iter_3d(UVec3::splat(4))
+            .map(|p| {
+                let pos = origin + p.as_ivec3();
+                sample_density(&noise, pos, &noise_settings, &world_settings, None).to_bits()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_values() {
+        let origin = IVec3::new(-16, 0, 32);
+        assert_eq!(sample_block(42, origin), sample_block(42, origin));
+    }
+
+    /// Catches drift across runs, platforms and versions of the noise crate
+    #[test]
+    fn seed_values_are_pinned() {
+        let (noise_settings, world_settings) = settings(42);
+        let noise = noise_settings.stack();
+        let golden = [
+            (IVec3::new(-16, 0, 32), 0x3ef83a00),
+            (IVec3::new(-15, 1, 33), 0x3efcf000),
+            (IVec3::new(-13, 3, 35), 0x3ef1ba00),
+            (IVec3::new(-12, 4, 36), 0x3ef31e00),
+        ];
+        for (pos, bits) in golden {
+            let val = sample_density(&noise, pos, &noise_settings, &world_settings, None);
+            assert_eq!(val.to_bits(), bits, "density at {pos}");
+        }
+    }
+
+    #[test]
+    fn different_seed_different_values() {
+        let origin = IVec3::new(-16, 0, 32);
+        assert_ne!(sample_block(1, origin), sample_block(2, origin));
+    }
+
+    #[test]
+    fn values_are_quantized() {
+        for bits in sample_block(7, IVec3::ZERO) {
+            let value = f32::from_bits(bits) as f64 * 65536.0;
+            assert_eq!(value, value.round());
+        }
+    }
+
+    #[test]
+    fn filled_points_match_samples() {
+        let (noise_settings, world_settings) = settings(5);
+        let noise = noise_settings.stack();
+        let origin = IVec3::new(8, 0, -8);
+        let size = UVec3::new(2, 5, 3);
+        let mut chunk =
+            crate::chunk::Chunk::new(vec![0.0; crate::chunk::Chunk::points_len(size)], size);
+        for point in crate::chunk::Chunk::new_iter_3d(size) {
+            let pos = origin + point.as_ivec3();
+            let val = sample_density(&noise, pos, &noise_settings, &world_settings, None);
+            chunk.set(point.as_vec3(), val);
+        }
+
+        let mut buffer = Vec::with_capacity(1024);
+        let capacity = buffer.capacity();
+        fill_points(
+            &mut buffer,
+            &noise,
+            origin,
+            size,
+            &noise_settings,
+            &world_settings,
+            None,
+        );
+        assert_eq!(buffer, chunk.points);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn layers_blend_in_order() {
+        let point = [1.5, 2.5, -3.5];
+        let sample = |settings: NoiseSettings| settings.stack().get(point);
+        let base = sample(NoiseSettings::default());
+        let doubled = sample(NoiseSettings::default().with_layer(NoiseLayer::default()));
+        assert!((doubled - 2.0 * base).abs() < 1e-9);
+        let hidden = NoiseLayer::default().with_opacity(0.0);
+        assert_eq!(sample(NoiseSettings::default().with_layer(hidden)), base);
+        let replaced = NoiseLayer::default()
+            .with_amplitude(0.5)
+            .with_blend(BlendMode::Lerp);
+        let half = sample(NoiseSettings::default().with_layer(replaced));
+        assert!((half - 0.5 * base).abs() < 1e-9);
+        let above = NoiseLayer::default().with_mask(LayerMask::Height {
+            from: 10.0,
+            to: 12.0,
+        });
+        assert_eq!(sample(NoiseSettings::default().with_layer(above)), base);
+        assert_eq!(
+            sample(NoiseSettings::default().with_layers(Vec::new())),
+            0.0
+        );
+    }
+
+    #[test]
+    fn masks_confine_layers() {
+        let masked = |mask: LayerMask| {
+            NoiseSettings::default()
+                .with_layers(vec![NoiseLayer::default().with_mask(mask)])
+                .stack()
+        };
+        let unmasked = NoiseSettings::default().stack();
+        let (near, far) = ([1.5, 2.5, 1.5], [150.5, 2.5, 1.5]);
+
+        let radial = masked(LayerMask::Radial {
+            from: 50.0,
+            to: 100.0,
+        });
+        assert_eq!(radial.get(near), 0.0);
+        assert_eq!(radial.get(far), unmasked.get(far));
+        // swapped bounds keep the layer near the origin
+        let central = masked(LayerMask::Radial {
+            from: 100.0,
+            to: 50.0,
+        });
+        assert_eq!(central.get(near), unmasked.get(near));
+        assert_eq!(central.get(far), 0.0);
+
+        // the first layer has nothing under it, so it's flat everywhere
+        let ridges = masked(LayerMask::Slope { from: 0.1, to: 0.2 });
+        assert_eq!(ridges.get(near), 0.0);
+        let ridges_on_top = NoiseSettings::default()
+            .with_layer(NoiseLayer::default().with_mask(LayerMask::Slope { from: 0.0, to: 0.0 }))
+            .stack();
+        assert!((ridges_on_top.get(near) - 2.0 * unmasked.get(near)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wrapped_edges_match() {
+        let (noise_settings, mut world_settings) = settings(3);
+        world_settings.wrap = true;
+        let noise = noise_settings.stack();
+        let period = WrapPeriod {
+            origin: IVec2::new(-16, -16),
+            size: IVec2::new(48, 48),
+        };
+        for y in 0..4 {
+            for z in -16..32 {
+                let a = IVec3::new(-16, y, z);
+                let b = IVec3::new(32, y, z);
+                assert_eq!(
+                    sample_density(&noise, a, &noise_settings, &world_settings, Some(period)),
+                    sample_density(&noise, b, &noise_settings, &world_settings, Some(period)),
+                );
+            }
+        }
+    }
+}
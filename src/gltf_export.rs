@@ -0,0 +1,310 @@
+use std::io::{self, Write};
+
+use bevy::prelude::*;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+/// Material of a [`GltfScene`], with the factors of the metallic roughness model
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMaterial {
+    pub name: String,
+    /// Linear RGBA
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub double_sided: bool,
+    pub alpha_mode: AlphaMode,
+}
+
+/// Triangles of a [`GltfNode`] in the space of the node
+#[derive(Clone, Debug, Default)]
+pub struct GltfPrimitive {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    /// Linear RGBA multiplied by the base color of the material
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub indices: Vec<u32>,
+    /// Index in [`GltfScene::materials`]
+    pub material: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GltfNode {
+    pub name: String,
+    pub translation: Vec3,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// Nodes written under a single root node by [`write_glb`]
+#[derive(Clone, Debug, Default)]
+pub struct GltfScene {
+    pub name: String,
+    pub materials: Vec<GltfMaterial>,
+    pub nodes: Vec<GltfNode>,
+}
+
+/// Binary buffer of a glTF file with its views and accessors
+#[derive(Default)]
+struct GltfBuffer {
+    bytes: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl GltfBuffer {
+    /// Appends `values` in a new view and returns the index of its accessor
+    fn push_vec3(&mut self, values: &[Vec3], with_bounds: bool) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_array())
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        // the positions must have bounds
+        let bounds = if with_bounds {
+            let min = values.iter().fold(Vec3::splat(f32::MAX), |a, b| a.min(*b));
+            let max = values.iter().fold(Vec3::splat(f32::MIN), |a, b| a.max(*b));
+            format!(
+                r#","min":[{},{},{}],"max":[{},{},{}]"#,
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )
+        } else {
+            String::new()
+        };
+        self.push(&bytes, ARRAY_BUFFER, FLOAT, values.len(), "VEC3", &bounds)
+    }
+
+    fn push_colors(&mut self, colors: &[[f32; 4]]) -> usize {
+        let bytes: Vec<u8> = colors
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        self.push(&bytes, ARRAY_BUFFER, FLOAT, colors.len(), "VEC4", "")
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        self.push(
+            &bytes,
+            ELEMENT_ARRAY_BUFFER,
+            UNSIGNED_INT,
+            indices.len(),
+            "SCALAR",
+            "",
+        )
+    }
+
+    fn push(
+        &mut self,
+        bytes: &[u8],
+        target: u32,
+        component_type: u32,
+        count: usize,
+        kind: &str,
+        bounds: &str,
+    ) -> usize {
+        // every component is 4 bytes so the views stay aligned
+        let offset = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        self.views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{},"target":{target}}}"#,
+            bytes.len()
+        ));
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{component_type},"count":{count},"type":"{kind}"{bounds}}}"#,
+            self.views.len() - 1
+        ));
+        self.accessors.len() - 1
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_array(items: &[String]) -> String {
+    format!("[{}]", items.join(","))
+}
+
+fn material_json(material: &GltfMaterial) -> String {
+    let [r, g, b, a] = material.base_color;
+    let alpha = match material.alpha_mode {
+        AlphaMode::Opaque => r#""alphaMode":"OPAQUE""#.to_string(),
+        AlphaMode::Mask(cutoff) => format!(r#""alphaMode":"MASK","alphaCutoff":{cutoff}"#),
+        AlphaMode::Blend => r#""alphaMode":"BLEND""#.to_string(),
+    };
+    format!(
+        r#"{{"name":{},"pbrMetallicRoughness":{{"baseColorFactor":[{r},{g},{b},{a}],"metallicFactor":{},"roughnessFactor":{}}},"doubleSided":{},{alpha}}}"#,
+        json_string(&material.name),
+        material.metallic,
+        material.roughness,
+        material.double_sided,
+    )
+}
+
+/// Writes `scene` as a binary glTF file, Y up with counter clockwise front
+/// faces as required by glTF
+pub fn write_glb(scene: &GltfScene, mut writer: impl Write) -> io::Result<()> {
+    let mut buffer = GltfBuffer::default();
+    let mut meshes = Vec::new();
+    let mut nodes = vec![String::new()];
+    for node in &scene.nodes {
+        let mut primitives = Vec::new();
+        for primitive in &node.primitives {
+            if primitive.indices.is_empty() {
+                continue;
+            }
+            let mut attributes = vec![
+                format!(
+                    r#""POSITION":{}"#,
+                    buffer.push_vec3(&primitive.positions, true)
+                ),
+                format!(
+                    r#""NORMAL":{}"#,
+                    buffer.push_vec3(&primitive.normals, false)
+                ),
+            ];
+            if let Some(colors) = &primitive.colors {
+                attributes.push(format!(r#""COLOR_0":{}"#, buffer.push_colors(colors)));
+            }
+            primitives.push(format!(
+                r#"{{"attributes":{{{}}},"indices":{},"material":{}}}"#,
+                attributes.join(","),
+                buffer.push_indices(&primitive.indices),
+                primitive.material
+            ));
+        }
+        let t = node.translation;
+        let mut node_json = format!(
+            r#"{{"name":{},"translation":[{},{},{}]"#,
+            json_string(&node.name),
+            t.x,
+            t.y,
+            t.z
+        );
+        if !primitives.is_empty() {
+            node_json.push_str(&format!(r#","mesh":{}"#, meshes.len()));
+            meshes.push(format!(
+                r#"{{"name":{},"primitives":{}}}"#,
+                json_string(&node.name),
+                json_array(&primitives)
+            ));
+        }
+        node_json.push('}');
+        nodes.push(node_json);
+    }
+    let children: Vec<String> = (1..nodes.len()).map(|i| i.to_string()).collect();
+    nodes[0] = if children.is_empty() {
+        format!(r#"{{"name":{}}}"#, json_string(&scene.name))
+    } else {
+        format!(
+            r#"{{"name":{},"children":{}}}"#,
+            json_string(&scene.name),
+            json_array(&children)
+        )
+    };
+
+    let mut fields = vec![
+        r#""asset":{"version":"2.0","generator":"bevy_marching_cube"}"#.to_string(),
+        r#""scene":0,"scenes":[{"nodes":[0]}]"#.to_string(),
+        format!(r#""nodes":{}"#, json_array(&nodes)),
+    ];
+    // the arrays of glTF can't be empty
+    if !meshes.is_empty() {
+        fields.push(format!(r#""meshes":{}"#, json_array(&meshes)));
+    }
+    if !scene.materials.is_empty() {
+        let materials: Vec<String> = scene.materials.iter().map(material_json).collect();
+        fields.push(format!(r#""materials":{}"#, json_array(&materials)));
+    }
+    if !buffer.bytes.is_empty() {
+        fields.push(format!(
+            r#""buffers":[{{"byteLength":{}}}]"#,
+            buffer.bytes.len()
+        ));
+        fields.push(format!(r#""bufferViews":{}"#, json_array(&buffer.views)));
+        fields.push(format!(r#""accessors":{}"#, json_array(&buffer.accessors)));
+    }
+    let mut json = format!("{{{}}}", fields.join(",")).into_bytes();
+    // the chunks are padded to 4 bytes, with spaces for the JSON
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    let bin = buffer.bytes;
+
+    let bin_chunk_len = if bin.is_empty() { 0 } else { 8 + bin.len() };
+    let total_len = 12 + 8 + json.len() + bin_chunk_len;
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(total_len as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+    if !bin.is_empty() {
+        writer.write_all(&(bin.len() as u32).to_le_bytes())?;
+        writer.write_all(b"BIN\0")?;
+        writer.write_all(&bin)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glb_chunks_are_aligned_and_sized() {
+        let scene = GltfScene {
+            name: "terrain".to_string(),
+            materials: vec![GltfMaterial {
+                name: "Rock \"dark\"".to_string(),
+                base_color: [0.5, 0.4, 0.3, 1.0],
+                metallic: 0.0,
+                roughness: 0.8,
+                double_sided: true,
+                alpha_mode: AlphaMode::Opaque,
+            }],
+            nodes: vec![GltfNode {
+                name: "chunk_0_0_0".to_string(),
+                translation: Vec3::new(16.0, 0.0, -16.0),
+                primitives: vec![GltfPrimitive {
+                    positions: vec![Vec3::ZERO, Vec3::Z, Vec3::X],
+                    normals: vec![Vec3::Y; 3],
+                    colors: Some(vec![[1.0; 4]; 3]),
+                    indices: vec![0, 1, 2],
+                    material: 0,
+                }],
+            }],
+        };
+        let mut glb = Vec::new();
+        write_glb(&scene, &mut glb).unwrap();
+
+        let u32_at = |i: usize| u32::from_le_bytes([glb[i], glb[i + 1], glb[i + 2], glb[i + 3]]);
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(u32_at(8) as usize, glb.len());
+        let json_len = u32_at(12) as usize;
+        assert_eq!(json_len % 4, 0);
+        let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+        assert!(json.contains(r#""COLOR_0":2"#));
+        assert!(json.contains(r#""name":"Rock \"dark\"""#));
+        assert!(json.contains(r#""min":[0,0,0],"max":[1,0,1]"#));
+        // 3 positions, 3 normals, 3 colors and 3 indices
+        let bin_len = u32_at(20 + json_len) as usize;
+        assert_eq!(bin_len, 3 * 12 + 3 * 12 + 3 * 16 + 3 * 4);
+        assert_eq!(&glb[24 + json_len..28 + json_len], b"BIN\0");
+    }
+}
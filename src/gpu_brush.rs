@@ -0,0 +1,283 @@
+use std::borrow::Cow;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{self, RenderGraph},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages,
+            CachedComputePipelineId, ComputePassDescriptor, ComputePipelineDescriptor,
+            PipelineCache, ShaderStages, StorageTextureAccess, TextureFormat, TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderStage,
+    },
+};
+
+use crate::capabilities::GpuCapabilities;
+
+pub const GPU_BRUSH_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x81d4_2fa7_c630_9e5b);
+
+/// Points edited by each workgroup of the shader on each axis
+const WORKGROUP_SIZE: u32 = 4;
+
+/// Brush stroke applied by a compute shader to the [`DensityTexture`] of a
+/// chunk, the same stroke as [`edit_sphere`] applies to the points of the
+/// chunk with the brush falloff.
+///
+/// [`DensityTexture`]: crate::density_texture::DensityTexture
+/// [`edit_sphere`]: crate::brush::edit_sphere
+#[derive(Clone, Debug)]
+pub struct GpuBrushEdit {
+    pub texture: Handle<Image>,
+    /// Center of the brush relative to the origin of the chunk, in world units
+    pub center: Vec3,
+    pub radius: f32,
+    /// Density added at the center of the brush, negative to remove density
+    pub amount: f32,
+    pub cell_size: f32,
+    /// First point of the texture edited
+    pub min: UVec3,
+    /// Last point of the texture edited, inclusive
+    pub max: UVec3,
+}
+
+impl GpuBrushEdit {
+    /// Points of a chunk of `size` cells within `radius` of `center`, relative
+    /// to the origin of the chunk, or `None` when the sphere misses the chunk
+    pub fn region(
+        center: Vec3,
+        radius: f32,
+        cell_size: f32,
+        size: UVec3,
+    ) -> Option<(UVec3, UVec3)> {
+        let first = ((center - Vec3::splat(radius)) / cell_size)
+            .ceil()
+            .max(Vec3::ZERO);
+        let last = ((center + Vec3::splat(radius)) / cell_size)
+            .floor()
+            .min(size.as_vec3());
+        (!first.cmpgt(last).any()).then(|| (first.as_uvec3(), last.as_uvec3()))
+    }
+
+    /// Number of workgroups dispatched to cover the region
+    pub fn workgroups(&self) -> UVec3 {
+        let points = self.max - self.min + UVec3::ONE;
+        (points + UVec3::splat(WORKGROUP_SIZE - 1)) / WORKGROUP_SIZE
+    }
+
+    /// Uniform read by the shader, matches its `BrushEdit` struct
+    fn uniform_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(48);
+        for value in self.center.to_array() {
+            bytes.extend(value.to_ne_bytes());
+        }
+        bytes.extend(self.radius.to_ne_bytes());
+        for value in self.min.to_array() {
+            bytes.extend(value.to_ne_bytes());
+        }
+        bytes.extend(self.amount.to_ne_bytes());
+        for value in self.max.to_array() {
+            bytes.extend(value.to_ne_bytes());
+        }
+        bytes.extend(self.cell_size.to_ne_bytes());
+        bytes
+    }
+}
+
+/// Dispatches the [`GpuBrushEdit`] events before the cameras render, the
+/// events are ignored when the GPU has no compute shaders
+pub struct GpuBrushPlugin;
+
+impl Plugin for GpuBrushPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            GPU_BRUSH_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/gpu_brush.wgsl")),
+        );
+        app.add_event::<GpuBrushEdit>()
+            .init_resource::<GpuCapabilities>();
+        if !app.world.get_resource::<GpuCapabilities>().unwrap().compute {
+            return;
+        }
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<GpuBrushPipeline>()
+            .init_resource::<ExtractedGpuBrushEdits>()
+            .init_resource::<GpuBrushDispatches>()
+            .add_system_to_stage(RenderStage::Extract, extract_gpu_brush_edits)
+            .add_system_to_stage(RenderStage::Queue, queue_gpu_brush_edits);
+
+        let mut render_graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        render_graph.add_node("gpu_brush", GpuBrushNode);
+        render_graph
+            .add_node_edge(
+                "gpu_brush",
+                bevy::core_pipeline::node::MAIN_PASS_DEPENDENCIES,
+            )
+            .unwrap();
+    }
+}
+
+pub struct GpuBrushPipeline {
+    layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for GpuBrushPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("gpu_brush_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(12 * 4),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::ReadWrite,
+                        format: TextureFormat::R32Float,
+                        view_dimension: TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
+        let pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("gpu_brush_pipeline".into()),
+            layout: Some(vec![layout.clone()]),
+            shader: GPU_BRUSH_SHADER_HANDLE.typed(),
+            shader_defs: vec![],
+            entry_point: Cow::from("edit"),
+        });
+        Self { layout, pipeline }
+    }
+}
+
+/// Edits of the frame in the render world
+#[derive(Default)]
+pub struct ExtractedGpuBrushEdits(Vec<GpuBrushEdit>);
+
+/// Bind group and workgroup count of each edit of the frame
+#[derive(Default)]
+pub struct GpuBrushDispatches(Vec<(BindGroup, UVec3)>);
+
+fn extract_gpu_brush_edits(mut commands: Commands, mut edits: EventReader<GpuBrushEdit>) {
+    commands.insert_resource(ExtractedGpuBrushEdits(edits.iter().cloned().collect()));
+}
+
+fn queue_gpu_brush_edits(
+    edits: Res<ExtractedGpuBrushEdits>,
+    pipeline: Res<GpuBrushPipeline>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    mut dispatches: ResMut<GpuBrushDispatches>,
+) {
+    dispatches.0.clear();
+    for edit in &edits.0 {
+        let texture = match images.get(&edit.texture) {
+            Some(texture) => texture,
+            None => {
+                warn!("density texture of a brush edit isn't uploaded yet");
+                continue;
+            }
+        };
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("gpu_brush_uniform"),
+            contents: &edit.uniform_bytes(),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("gpu_brush_bind_group"),
+            layout: &pipeline.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&texture.texture_view),
+                },
+            ],
+        });
+        dispatches.0.push((bind_group, edit.workgroups()));
+    }
+}
+
+struct GpuBrushNode;
+
+impl render_graph::Node for GpuBrushNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let dispatches = world.get_resource::<GpuBrushDispatches>().unwrap();
+        if dispatches.0.is_empty() {
+            return Ok(());
+        }
+        let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
+        let pipeline = world.get_resource::<GpuBrushPipeline>().unwrap();
+        // the pipeline compiles in the background during the first frames
+        let compute_pipeline = match pipeline_cache.get_compute_pipeline(pipeline.pipeline) {
+            Some(compute_pipeline) => compute_pipeline,
+            None => return Ok(()),
+        };
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("gpu_brush"),
+            });
+        pass.set_pipeline(compute_pipeline);
+        for (bind_group, workgroups) in &dispatches.0 {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch(workgroups.x, workgroups.y, workgroups.z);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_is_clamped_to_the_chunk() {
+        let size = UVec3::splat(16);
+        let region = GpuBrushEdit::region(Vec3::new(1.0, 8.0, 15.5), 2.0, 1.0, size);
+        assert_eq!(region, Some((UVec3::new(0, 6, 14), UVec3::new(3, 10, 16))));
+        let outside = GpuBrushEdit::region(Vec3::splat(-10.0), 2.0, 1.0, size);
+        assert_eq!(outside, None);
+
+        let edit = GpuBrushEdit {
+            texture: Handle::default(),
+            center: Vec3::ZERO,
+            radius: 2.0,
+            amount: 1.0,
+            cell_size: 1.0,
+            min: UVec3::ZERO,
+            max: UVec3::new(3, 4, 8),
+        };
+        assert_eq!(edit.workgroups(), UVec3::new(1, 2, 3));
+        assert_eq!(edit.uniform_bytes().len(), 12 * 4);
+    }
+}
@@ -0,0 +1,885 @@
+use std::borrow::Cow;
+
+use bevy::{
+    core_pipeline::Opaque3d,
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MeshPipeline, SetMeshViewBindGroup},
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        render_asset::RenderAssets,
+        render_graph::{self, RenderGraph},
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BlendState, Buffer, BufferBindingType, BufferDescriptor, BufferInitDescriptor,
+            BufferSize, BufferUsages, CachedComputePipelineId, ColorTargetState, ColorWrites,
+            CompareFunction, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor,
+            DepthBiasState, DepthStencilState, Face, FragmentState, FrontFace, MultisampleState,
+            PipelineCache, PolygonMode, PrimitiveState, PrimitiveTopology,
+            RenderPipelineDescriptor, ShaderStages, SpecializedRenderPipeline,
+            SpecializedRenderPipelines, StencilFaceState, StencilState, TextureFormat,
+            TextureSampleType, TextureView, TextureViewDimension, TextureViewId, VertexAttribute,
+            VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::BevyDefault,
+        view::{ExtractedView, Msaa},
+        RenderApp, RenderStage,
+    },
+    utils::{HashMap, HashSet},
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    capabilities::GpuCapabilities,
+    chunk::{Chunk, ChunkIsolevel, MeshedFrom},
+    density_texture::{DensityTexture, TextureRevision},
+    generation::WorldSettings,
+    gpu_brush::GpuBrushEdit,
+    gpu_picking::PICK_CAMERA,
+    marching_cube_tables::{EDGE_CONNECTION, TRIANGLE_TABLE},
+    mesh_parts::{ChunkMeshPart, ChunkMeshParts},
+    terrain_root::TerrainRoot,
+    Data, MarchingCubesSystem, StartMarching,
+};
+
+pub const GPU_MESHING_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5a0e_c39b_71d4_82f6);
+pub const GPU_CHUNK_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0xd27c_4e91_a6b3_0f58);
+
+/// Cells handled by each workgroup of the shader, also the cells of a block
+/// of the prefix sum
+const WORKGROUP_SIZE: u32 = 256;
+/// Bytes of a vertex written by the shader, a position then a normal
+const VERTEX_SIZE: u64 = 6 * 4;
+/// Entry points of `gpu_meshing.wgsl`, in the order they're dispatched
+const PASSES: [&str; 5] = [
+    "classify",
+    "scan_blocks",
+    "scan_block_sums",
+    "add_block_offsets",
+    "generate",
+];
+
+/// Marches the chunks with compute shaders reading their [`DensityTexture`]
+/// instead of on the CPU.
+///
+/// Each cell counts its triangles, an exclusive prefix sum over the counts
+/// gives the first triangle of each cell, then each cell writes its triangles
+/// there. The vertices are tightly packed and the scan writes the vertex count
+/// to an indirect draw buffer, the chunks are drawn with `draw_indirect`
+/// without reading anything back, in the frame they're marched in.
+///
+/// The GPU meshes use linear interpolation and flat normals, without jitter,
+/// smoothing, sharp features, shadows or frustum culling. The CPU meshes of
+/// the chunks are hidden and not marched while it's enabled, so the colliders
+/// don't follow the edits. Ignored when the GPU has no compute shaders.
+#[derive(Inspectable)]
+pub struct GpuMeshing {
+    pub enabled: bool,
+    /// Triangles allocated for each cell of a chunk, the triangles past the
+    /// allocation are dropped. 5 covers every cell but most cells are empty.
+    #[inspectable(min = 0.05, max = 5.0, speed = 0.01)]
+    pub triangles_per_cell: f32,
+    pub color: Color,
+}
+
+impl Default for GpuMeshing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            triangles_per_cell: 0.5,
+            color: Color::rgb(0.45, 0.6, 0.35),
+        }
+    }
+}
+
+impl GpuMeshing {
+    /// Triangles allocated for a chunk of `size` cells
+    pub fn capacity(&self, size: UVec3) -> u32 {
+        let cells = (size.x * size.y * size.z) as f32;
+        (cells * self.triangles_per_cell).ceil().max(1.0) as u32
+    }
+}
+
+/// Chunk marched by the [`GpuMeshing`], its CPU mesh is hidden and not marched
+#[derive(Component)]
+pub struct GpuMeshed;
+
+pub struct GpuMeshingPlugin;
+
+impl Plugin for GpuMeshingPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            GPU_MESHING_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/gpu_meshing.wgsl")),
+        );
+        shaders.set_untracked(
+            GPU_CHUNK_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/gpu_chunk.wgsl")),
+        );
+        app.init_resource::<GpuCapabilities>()
+            .add_system(toggle_gpu_meshing.before(MarchingCubesSystem::Meshing));
+        if !app.world.get_resource::<GpuCapabilities>().unwrap().compute {
+            return;
+        }
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<GpuMeshingPipeline>()
+            .init_resource::<GpuChunkPipeline>()
+            .init_resource::<SpecializedRenderPipelines<GpuChunkPipeline>>()
+            .init_resource::<ExtractedGpuChunks>()
+            .init_resource::<GpuChunkMeshes>()
+            .add_render_command::<Opaque3d, DrawGpuChunk>()
+            .add_system_to_stage(RenderStage::Extract, extract_gpu_chunks)
+            .add_system_to_stage(RenderStage::Prepare, prepare_gpu_chunks)
+            .add_system_to_stage(RenderStage::Queue, queue_gpu_chunks)
+            .add_system_to_stage(RenderStage::Cleanup, finish_gpu_meshing);
+
+        let mut render_graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        render_graph.add_node("gpu_meshing", GpuMeshingNode);
+        // the brush edits of the frame are meshed
+        render_graph
+            .add_node_edge("gpu_brush", "gpu_meshing")
+            .unwrap();
+        render_graph
+            .add_node_edge(
+                "gpu_meshing",
+                bevy::core_pipeline::node::MAIN_PASS_DEPENDENCIES,
+            )
+            .unwrap();
+    }
+}
+
+/// Marks the chunks for the [`GpuMeshing`] and hides their CPU meshes, or
+/// marches them on the CPU again once it's disabled
+fn toggle_gpu_meshing(
+    mut commands: Commands,
+    settings: Res<GpuMeshing>,
+    capabilities: Res<GpuCapabilities>,
+    mut chunks: Query<
+        (
+            Entity,
+            &mut Visibility,
+            &mut MeshedFrom,
+            Option<&ChunkMeshParts>,
+            Option<&GpuMeshed>,
+        ),
+        With<Chunk>,
+    >,
+    mut parts: Query<&mut Visibility, (With<ChunkMeshPart>, Without<Chunk>)>,
+    mut start_marching: EventWriter<StartMarching>,
+) {
+    let enabled = settings.enabled && capabilities.compute;
+    let mut remesh = false;
+    for (entity, mut visibility, mut meshed_from, mesh_parts, gpu_meshed) in chunks.iter_mut() {
+        if enabled == gpu_meshed.is_some() {
+            continue;
+        }
+        if enabled {
+            commands.entity(entity).insert(GpuMeshed);
+        } else {
+            commands.entity(entity).remove::<GpuMeshed>();
+            // the CPU mesh is from before the GPU meshing
+            *meshed_from = MeshedFrom::default();
+            remesh = true;
+        }
+        visibility.is_visible = !enabled;
+        for part in mesh_parts.iter().flat_map(|parts| &parts.0) {
+            if let Ok(mut visibility) = parts.get_mut(*part) {
+                visibility.is_visible = !enabled;
+            }
+        }
+    }
+    if remesh {
+        start_marching.send_default();
+    }
+}
+
+/// Chunk marched on the GPU this frame
+struct ExtractedGpuChunk {
+    entity: Entity,
+    texture: Handle<Image>,
+    size: UVec3,
+    revision: u64,
+    /// A [`GpuBrushEdit`] changed the texture without changing its revision
+    edited: bool,
+    isolevel: f32,
+    cell_size: f32,
+    capacity: u32,
+    transform: Mat4,
+    color: [f32; 4],
+}
+
+impl ExtractedGpuChunk {
+    fn cells(&self) -> u32 {
+        self.size.x * self.size.y * self.size.z
+    }
+
+    fn blocks(&self) -> u32 {
+        (self.cells() + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE
+    }
+
+    /// Uniform read by the compute shader, matches its `Params` struct
+    fn uniform_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32);
+        for value in self.size.to_array() {
+            bytes.extend(value.to_ne_bytes());
+        }
+        bytes.extend(self.isolevel.to_ne_bytes());
+        bytes.extend(self.cells().to_ne_bytes());
+        bytes.extend(self.capacity.to_ne_bytes());
+        bytes.extend(self.cell_size.to_ne_bytes());
+        bytes.extend(self.blocks().to_ne_bytes());
+        bytes
+    }
+
+    /// Uniform read by the draw shader, matches its `GpuChunk` struct
+    fn draw_bytes(&self) -> Vec<u8> {
+        self.transform
+            .to_cols_array()
+            .iter()
+            .chain(&self.color)
+            .flat_map(|value| value.to_ne_bytes())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct ExtractedGpuChunks(Vec<ExtractedGpuChunk>);
+
+fn extract_gpu_chunks(
+    mut commands: Commands,
+    settings: Res<GpuMeshing>,
+    terrains: Query<(&Data, &WorldSettings), With<TerrainRoot>>,
+    mut brush_edits: EventReader<GpuBrushEdit>,
+    chunks: Query<
+        (
+            Entity,
+            &Chunk,
+            &DensityTexture,
+            &TextureRevision,
+            &GlobalTransform,
+            Option<&ChunkIsolevel>,
+            &Parent,
+        ),
+        With<GpuMeshed>,
+    >,
+) {
+    let edited: HashSet<Handle<Image>> = brush_edits
+        .iter()
+        .map(|edit| edit.texture.clone_weak())
+        .collect();
+    let color = settings.color.as_linear_rgba_f32();
+    let chunks = chunks
+        .iter()
+        .filter_map(
+            |(entity, chunk, texture, revision, transform, chunk_isolevel, parent)| {
+                let (data, world_settings) = terrains.get(parent.0).ok()?;
+                Some(ExtractedGpuChunk {
+                    entity,
+                    texture: texture.0.clone_weak(),
+                    size: chunk.size,
+                    revision: revision.0,
+                    edited: edited.contains(&texture.0),
+                    isolevel: chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0),
+                    cell_size: world_settings.cell_size,
+                    capacity: settings.capacity(chunk.size),
+                    transform: transform.compute_matrix(),
+                    color,
+                })
+            },
+        )
+        .collect();
+    commands.insert_resource(ExtractedGpuChunks(chunks));
+}
+
+/// Inputs of the last march of a [`GpuChunkMesh`]
+#[derive(Clone, Copy, PartialEq)]
+struct GpuChunkKey {
+    texture: TextureViewId,
+    size: UVec3,
+    capacity: u32,
+    revision: u64,
+    isolevel: f32,
+    cell_size: f32,
+}
+
+impl GpuChunkKey {
+    /// Whether the buffers of `other` are the same size and read the same
+    /// texture, only the uniform changes
+    fn same_buffers(&self, other: &Self) -> bool {
+        self.texture == other.texture && self.size == other.size && self.capacity == other.capacity
+    }
+}
+
+/// Buffers of a chunk marched on the GPU, kept between the frames and marched
+/// again when its points, its isolevel or the cell size change
+struct GpuChunkMesh {
+    key: GpuChunkKey,
+    params: Buffer,
+    vertices: Buffer,
+    /// Arguments of `draw_indirect` written by the last march
+    draw_args: Buffer,
+    compute_bind_group: BindGroup,
+    blocks: u32,
+    draw_uniform: Buffer,
+    draw_bind_group: BindGroup,
+    /// Waiting to be marched
+    dirty: bool,
+}
+
+impl GpuChunkMesh {
+    fn new(
+        render_device: &RenderDevice,
+        pipelines: (&GpuMeshingPipeline, &GpuChunkPipeline),
+        texture_view: &TextureView,
+        chunk: &ExtractedGpuChunk,
+        key: GpuChunkKey,
+    ) -> Self {
+        let (meshing_pipeline, chunk_pipeline) = pipelines;
+        let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("gpu_meshing_params"),
+            contents: &chunk.uniform_bytes(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let storage = |label, size: u64, usage| {
+            render_device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: BufferUsages::STORAGE | usage,
+                mapped_at_creation: false,
+            })
+        };
+        let counts = storage(
+            "gpu_meshing_counts",
+            chunk.cells() as u64 * 4,
+            BufferUsages::empty(),
+        );
+        let offsets = storage(
+            "gpu_meshing_offsets",
+            chunk.cells() as u64 * 4,
+            BufferUsages::empty(),
+        );
+        let block_sums = storage(
+            "gpu_meshing_block_sums",
+            chunk.blocks() as u64 * 4,
+            BufferUsages::empty(),
+        );
+        let vertices = storage(
+            "gpu_meshing_vertices",
+            chunk.capacity as u64 * 3 * VERTEX_SIZE,
+            BufferUsages::VERTEX,
+        );
+        // zeroed, nothing is drawn until the chunk is marched
+        let draw_args = storage("gpu_meshing_draw_args", 4 * 4, BufferUsages::INDIRECT);
+        let compute_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("gpu_meshing_bind_group"),
+            layout: &meshing_pipeline.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(texture_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: meshing_pipeline.tables.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: counts.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: offsets.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: block_sums.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: vertices.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 7,
+                    resource: draw_args.as_entire_binding(),
+                },
+            ],
+        });
+
+        let draw_uniform = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("gpu_chunk_uniform"),
+            contents: &chunk.draw_bytes(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let draw_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("gpu_chunk_bind_group"),
+            layout: &chunk_pipeline.chunk_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: draw_uniform.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            key,
+            params,
+            vertices,
+            draw_args,
+            compute_bind_group,
+            blocks: chunk.blocks(),
+            draw_uniform,
+            draw_bind_group,
+            dirty: true,
+        }
+    }
+}
+
+/// GPU meshes of the chunks, by chunk entity
+#[derive(Default)]
+pub struct GpuChunkMeshes(HashMap<Entity, GpuChunkMesh>);
+
+fn prepare_gpu_chunks(
+    chunks: Res<ExtractedGpuChunks>,
+    meshing_pipeline: Res<GpuMeshingPipeline>,
+    chunk_pipeline: Res<GpuChunkPipeline>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut meshes: ResMut<GpuChunkMeshes>,
+) {
+    let extracted: HashSet<Entity> = chunks.0.iter().map(|chunk| chunk.entity).collect();
+    meshes.0.retain(|entity, _| extracted.contains(entity));
+
+    for chunk in &chunks.0 {
+        let texture = match images.get(&chunk.texture) {
+            Some(texture) => texture,
+            None => continue,
+        };
+        let key = GpuChunkKey {
+            texture: texture.texture_view.id(),
+            size: chunk.size,
+            capacity: chunk.capacity,
+            revision: chunk.revision,
+            isolevel: chunk.isolevel,
+            cell_size: chunk.cell_size,
+        };
+        let same_buffers = meshes
+            .0
+            .get(&chunk.entity)
+            .map_or(false, |mesh| mesh.key.same_buffers(&key));
+        if !same_buffers {
+            let mesh = GpuChunkMesh::new(
+                &render_device,
+                (&meshing_pipeline, &chunk_pipeline),
+                &texture.texture_view,
+                chunk,
+                key,
+            );
+            meshes.0.insert(chunk.entity, mesh);
+        }
+        let mesh = match meshes.0.get_mut(&chunk.entity) {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        if mesh.key != key || chunk.edited {
+            render_queue.write_buffer(&mesh.params, 0, &chunk.uniform_bytes());
+            mesh.key = key;
+            mesh.dirty = true;
+        }
+        render_queue.write_buffer(&mesh.draw_uniform, 0, &chunk.draw_bytes());
+    }
+}
+
+pub struct GpuMeshingPipeline {
+    layout: BindGroupLayout,
+    /// `TRIANGLE_TABLE` then `EDGE_CONNECTION`
+    tables: Buffer,
+    pipelines: [CachedComputePipelineId; 5],
+}
+
+impl GpuMeshingPipeline {
+    /// Pipelines of the [`PASSES`], once they're all compiled
+    fn compute_pipelines<'a>(
+        &self,
+        pipeline_cache: &'a PipelineCache,
+    ) -> Option<Vec<&'a ComputePipeline>> {
+        self.pipelines
+            .iter()
+            .map(|pipeline| pipeline_cache.get_compute_pipeline(*pipeline))
+            .collect()
+    }
+}
+
+impl FromWorld for GpuMeshingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let storage = |binding, read_only, size| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(size),
+            },
+            count: None,
+        };
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("gpu_meshing_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(8 * 4),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage(2, true, (4096 + 24) * 4),
+                storage(3, false, 4),
+                storage(4, false, 4),
+                storage(5, false, 4),
+                storage(6, false, 4),
+                storage(7, false, 4 * 4),
+            ],
+        });
+        let tables = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("gpu_meshing_tables"),
+            contents: &table_bytes(),
+            usage: BufferUsages::STORAGE,
+        });
+
+        let mut pipeline_cache = world.get_resource_mut::<PipelineCache>().unwrap();
+        let pipelines = PASSES.map(|entry_point| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(format!("gpu_meshing_{entry_point}").into()),
+                layout: Some(vec![layout.clone()]),
+                shader: GPU_MESHING_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: Cow::from(entry_point),
+            })
+        });
+        Self {
+            layout,
+            tables,
+            pipelines,
+        }
+    }
+}
+
+/// Marching cubes tables read by the shader, matches its `Tables` struct
+fn table_bytes() -> Vec<u8> {
+    let triangles = TRIANGLE_TABLE
+        .iter()
+        .flatten()
+        .flat_map(|edge| (*edge as i32).to_ne_bytes());
+    let edges = EDGE_CONNECTION
+        .iter()
+        .flatten()
+        .flat_map(|corner| (*corner as u32).to_ne_bytes());
+    triangles.chain(edges).collect()
+}
+
+struct GpuMeshingNode;
+
+impl render_graph::Node for GpuMeshingNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let meshes = world.get_resource::<GpuChunkMeshes>().unwrap();
+        if !meshes.0.values().any(|mesh| mesh.dirty) {
+            return Ok(());
+        }
+        let pipeline_cache = world.get_resource::<PipelineCache>().unwrap();
+        let pipeline = world.get_resource::<GpuMeshingPipeline>().unwrap();
+        // the pipelines compile in the background during the first frames
+        let compute_pipelines = match pipeline.compute_pipelines(pipeline_cache) {
+            Some(compute_pipelines) => compute_pipelines,
+            None => return Ok(()),
+        };
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("gpu_meshing"),
+            });
+        for mesh in meshes.0.values().filter(|mesh| mesh.dirty) {
+            pass.set_bind_group(0, &mesh.compute_bind_group, &[]);
+            // a single workgroup scans the sums of the blocks
+            let workgroups = [mesh.blocks, mesh.blocks, 1, mesh.blocks, mesh.blocks];
+            for (compute_pipeline, workgroups) in compute_pipelines.iter().zip(workgroups) {
+                pass.set_pipeline(compute_pipeline);
+                pass.dispatch(workgroups, 1, 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Marks the chunks marched by the render graph of this frame as up to date
+fn finish_gpu_meshing(
+    pipeline: Res<GpuMeshingPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    mut meshes: ResMut<GpuChunkMeshes>,
+) {
+    // not marched until the pipelines are compiled
+    if pipeline.compute_pipelines(&pipeline_cache).is_none() {
+        return;
+    }
+    for mesh in meshes.0.values_mut() {
+        mesh.dirty = false;
+    }
+}
+
+pub struct GpuChunkPipeline {
+    view_layout: BindGroupLayout,
+    chunk_layout: BindGroupLayout,
+}
+
+impl FromWorld for GpuChunkPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let view_layout = world
+            .get_resource::<MeshPipeline>()
+            .unwrap()
+            .view_layout
+            .clone();
+        let render_device = world.get_resource::<RenderDevice>().unwrap();
+        let chunk_layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("gpu_chunk_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(20 * 4),
+                },
+                count: None,
+            }],
+        });
+        Self {
+            view_layout,
+            chunk_layout,
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for GpuChunkPipeline {
+    /// MSAA samples
+    type Key = u32;
+
+    fn specialize(&self, samples: u32) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("gpu_chunk_pipeline".into()),
+            layout: Some(vec![self.view_layout.clone(), self.chunk_layout.clone()]),
+            vertex: VertexState {
+                shader: GPU_CHUNK_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: vec![
+                        VertexAttribute {
+                            format: VertexFormat::Float32x3,
+                            offset: 0,
+                            shader_location: 0,
+                        },
+                        VertexAttribute {
+                            format: VertexFormat::Float32x3,
+                            offset: 12,
+                            shader_location: 1,
+                        },
+                    ],
+                }],
+            },
+            fragment: Some(FragmentState {
+                shader: GPU_CHUNK_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            // same depth as the main pass of the 3d cameras
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Greater,
+                stencil: StencilState {
+                    front: StencilFaceState::IGNORE,
+                    back: StencilFaceState::IGNORE,
+                    read_mask: 0,
+                    write_mask: 0,
+                },
+                bias: DepthBiasState {
+                    constant: 0,
+                    slope_scale: 0.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState {
+                count: samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_gpu_chunks(
+    draw_functions: Res<DrawFunctions<Opaque3d>>,
+    pipeline: Res<GpuChunkPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<GpuChunkPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    chunks: Res<ExtractedGpuChunks>,
+    meshes: Res<GpuChunkMeshes>,
+    mut views: Query<(&ExtractedView, &ExtractedCamera, &mut RenderPhase<Opaque3d>)>,
+) {
+    if chunks.0.is_empty() {
+        return;
+    }
+    let draw_function = draw_functions.read().get_id::<DrawGpuChunk>().unwrap();
+    let pipeline_id = pipelines.specialize(&mut pipeline_cache, &pipeline, msaa.samples);
+    for (view, camera, mut phase) in views.iter_mut() {
+        // the pick camera only renders the cell ids of the CPU meshes
+        if camera.name.as_deref() == Some(PICK_CAMERA) {
+            continue;
+        }
+        let view_row_2 = view.transform.compute_matrix().inverse().row(2);
+        for chunk in &chunks.0 {
+            if !meshes.0.contains_key(&chunk.entity) {
+                continue;
+            }
+            phase.add(Opaque3d {
+                distance: view_row_2.dot(chunk.transform.col(3)),
+                pipeline: pipeline_id,
+                entity: chunk.entity,
+                draw_function,
+            });
+        }
+    }
+}
+
+type DrawGpuChunk = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetGpuChunkBindGroup<1>,
+    DrawGpuChunkVertices,
+);
+
+struct SetGpuChunkBindGroup<const I: usize>;
+
+impl<const I: usize> EntityRenderCommand for SetGpuChunkBindGroup<I> {
+    type Param = SRes<GpuChunkMeshes>;
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        meshes: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        match meshes.into_inner().0.get(&item) {
+            Some(mesh) => {
+                pass.set_bind_group(I, &mesh.draw_bind_group, &[]);
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}
+
+struct DrawGpuChunkVertices;
+
+impl EntityRenderCommand for DrawGpuChunkVertices {
+    type Param = SRes<GpuChunkMeshes>;
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        meshes: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        match meshes.into_inner().0.get(&item) {
+            Some(mesh) => {
+                pass.set_vertex_buffer(0, mesh.vertices.slice(..));
+                pass.draw_indirect(&mesh.draw_args, 0);
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_match_the_shader() {
+        let settings = GpuMeshing {
+            triangles_per_cell: 0.5,
+            ..default()
+        };
+        assert_eq!(settings.capacity(UVec3::new(4, 2, 3)), 12);
+        assert_eq!(settings.capacity(UVec3::ZERO), 1);
+
+        let chunk = ExtractedGpuChunk {
+            entity: Entity::from_raw(0),
+            texture: Handle::default(),
+            size: UVec3::new(16, 16, 2),
+            revision: 0,
+            edited: false,
+            isolevel: 0.5,
+            cell_size: 1.0,
+            capacity: 256,
+            transform: Mat4::IDENTITY,
+            color: [1.0; 4],
+        };
+        assert_eq!(chunk.blocks(), 2);
+        assert_eq!(chunk.uniform_bytes().len(), 8 * 4);
+        assert_eq!(chunk.draw_bytes().len(), 20 * 4);
+        assert_eq!(table_bytes().len(), (4096 + 24) * 4);
+    }
+}
@@ -0,0 +1,519 @@
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin, NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        camera::{ActiveCameras, RenderTarget},
+        primitives::Aabb,
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferSize, BufferUsages,
+            CommandEncoderDescriptor, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+            MapMode, Origin3d, ShaderStages, TextureAspect, TextureFormat,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        view::RenderLayers,
+        RenderApp, RenderStage,
+    },
+    utils::HashSet,
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    camera::follow_fly_cam,
+    chunk::{Chunk, ChunkIsolevel},
+    compaction::cube_index,
+    field::DensityField,
+    field_sync::padded_row_len,
+    generation::WorldSettings,
+    inspection_view::{add_image_camera, fit_render_target, render_target_image},
+    mesh_parts::ChunkMeshPart,
+    Data, CELL_CORNERS,
+};
+
+pub const PICK_ID_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x3b9d_51e7_a04c_682f);
+
+pub(crate) const PICK_CAMERA: &str = "pick_camera";
+const PICK_PASS_DRIVER: &str = "pick_pass_driver";
+/// Render layer of the [`PickProxy`]s, only seen by the pick camera
+const PICK_LAYER: u8 = 2;
+
+/// Picks the cell under the cursor by rendering the cell coordinates of the
+/// chunk meshes to an image and reading back the pixel under the cursor,
+/// instead of marching a ray through the density field on the CPU.
+///
+/// The pick is exact to the cell and costs the same on any terrain size, it
+/// arrives one frame late. Pixels on the edges of the meshes are blended by
+/// the MSAA, the picked cells not holding the surface are discarded. The
+/// [`CellInspector`](crate::cell_inspector::CellInspector) uses the
+/// [`GpuPick`] while this is enabled.
+#[derive(Inspectable, Default)]
+pub struct GpuPicking {
+    pub enabled: bool,
+}
+
+/// Chunk and cell under the cursor found by the [`GpuPicking`]
+#[derive(Default)]
+pub struct GpuPick(pub Option<(Entity, UVec3)>);
+
+/// Copy of a chunk mesh, or of one of its parts, rendered with the
+/// [`PickIdMaterial`] by the pick camera
+#[derive(Component)]
+pub struct PickProxy(pub Entity);
+
+/// Camera following the [`FlyCam`](crate::camera::FlyCam) to render the
+/// [`PickProxy`]s
+#[derive(Component)]
+struct PickCamera;
+
+/// Writes the coordinates of the cell of each fragment, see `pick_id.wgsl`
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "8e2b6f14-53c9-4a7d-b0e1-9f4c2d7a6b38"]
+pub struct PickIdMaterial {
+    pub cell_size: f32,
+}
+
+#[derive(Clone)]
+pub struct GpuPickIdMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for PickIdMaterial {
+    type ExtractedAsset = PickIdMaterial;
+    type PreparedAsset = GpuPickIdMaterial;
+    type Param = (SRes<RenderDevice>, SRes<MaterialPipeline<Self>>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        // matches the PickIdMaterial struct of the shader
+        let uniform = [material.cell_size, 0.0, 0.0, 0.0];
+        let contents: Vec<u8> = uniform.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("pick_id_material_uniform"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("pick_id_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(GpuPickIdMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for PickIdMaterial {
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(PICK_ID_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("pick_id_material_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(4 * 4),
+                },
+                count: None,
+            }],
+        })
+    }
+}
+
+/// Material shared by every [`PickProxy`]
+pub struct PickIdMaterialHandle(pub Handle<PickIdMaterial>);
+
+impl FromWorld for PickIdMaterialHandle {
+    fn from_world(world: &mut World) -> Self {
+        let cell_size = world
+            .get_resource_or_insert_with(WorldSettings::default)
+            .cell_size;
+        let mut materials = world.get_resource_mut::<Assets<PickIdMaterial>>().unwrap();
+        Self(materials.add(PickIdMaterial { cell_size }))
+    }
+}
+
+struct PickImage {
+    handle: Handle<Image>,
+    camera: Entity,
+}
+
+#[derive(Default)]
+struct PickTarget(Option<PickImage>);
+
+/// Pixel under the cursor and the format of the image it was read from,
+/// shared between the main and the render world
+#[derive(Default, Clone)]
+pub struct PickReadback(Arc<Mutex<Option<([u8; 4], TextureFormat)>>>);
+
+/// Image of the pick camera and pixel under the cursor to read back
+#[derive(Default)]
+struct ExtractedPickRequest(Option<(Handle<Image>, UVec2)>);
+
+pub struct GpuPickingPlugin;
+
+impl Plugin for GpuPickingPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            PICK_ID_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/pick_id.wgsl")),
+        );
+
+        let readback = PickReadback::default();
+        app.add_plugin(MaterialPlugin::<PickIdMaterial>::default())
+            .init_resource::<PickIdMaterialHandle>()
+            .init_resource::<PickTarget>()
+            .init_resource::<GpuPick>()
+            .insert_resource(readback.clone())
+            .add_system(update_pick_id_material)
+            .add_system(update_pick_camera)
+            .add_system(follow_fly_cam::<PickCamera>.after(update_pick_camera))
+            .add_system(sync_pick_proxies)
+            .add_system(apply_gpu_pick);
+
+        add_image_camera(app, PICK_CAMERA, PICK_PASS_DRIVER);
+        app.sub_app_mut(RenderApp)
+            .insert_resource(readback)
+            .init_resource::<ExtractedPickRequest>()
+            .add_system_to_stage(RenderStage::Extract, extract_pick_request)
+            // after the render graph so the pick pass is read
+            .add_system_to_stage(RenderStage::Cleanup, read_pick_pixel);
+    }
+}
+
+fn update_pick_id_material(
+    world_settings: Res<WorldSettings>,
+    handle: Res<PickIdMaterialHandle>,
+    mut materials: ResMut<Assets<PickIdMaterial>>,
+) {
+    if !world_settings.is_changed() {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        material.cell_size = world_settings.cell_size;
+    }
+}
+
+fn update_pick_camera(
+    mut commands: Commands,
+    settings: Res<GpuPicking>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut target: ResMut<PickTarget>,
+) {
+    if !settings.enabled {
+        if let Some(image) = target.0.take() {
+            commands.entity(image.camera).despawn();
+            images.remove(&image.handle);
+            active_cameras.remove(PICK_CAMERA);
+        }
+        return;
+    }
+
+    // the pixels of the image are the pixels of the window
+    let size = match windows.get_primary() {
+        Some(window) => {
+            UVec2::new(window.physical_width(), window.physical_height()).max(UVec2::ONE)
+        }
+        None => return,
+    };
+    match &target.0 {
+        Some(image) => fit_render_target(&mut images, &image.handle, size),
+        None => {
+            let handle = images.add(render_target_image(size.x, size.y));
+            let mut bundle = PerspectiveCameraBundle::new_3d();
+            bundle.camera.name = Some(PICK_CAMERA.to_string());
+            bundle.camera.target = RenderTarget::Image(handle.clone());
+            let camera = commands
+                .spawn_bundle(bundle)
+                .insert(RenderLayers::layer(PICK_LAYER))
+                .insert(PickCamera)
+                .id();
+            active_cameras.add(PICK_CAMERA);
+            target.0 = Some(PickImage { handle, camera });
+        }
+    }
+}
+
+/// Keeps a [`PickProxy`] of each chunk mesh while the [`GpuPicking`] is enabled
+fn sync_pick_proxies(
+    mut commands: Commands,
+    settings: Res<GpuPicking>,
+    material: Res<PickIdMaterialHandle>,
+    owners: Query<
+        (
+            Entity,
+            &Handle<Mesh>,
+            &GlobalTransform,
+            &Visibility,
+            Option<&Aabb>,
+        ),
+        (Or<(With<Chunk>, With<ChunkMeshPart>)>, Without<PickProxy>),
+    >,
+    mut proxies: Query<
+        (
+            Entity,
+            &PickProxy,
+            &mut Handle<Mesh>,
+            &mut Transform,
+            &mut Visibility,
+            Option<&mut Aabb>,
+        ),
+        With<PickProxy>,
+    >,
+) {
+    if !settings.enabled {
+        for (entity, ..) in proxies.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let mut proxied = HashSet::default();
+    for (entity, proxy, mut mesh, mut transform, mut visibility, aabb) in proxies.iter_mut() {
+        let (_, owner_mesh, owner_transform, owner_visibility, owner_aabb) =
+            match owners.get(proxy.0) {
+                Ok(owner) => owner,
+                Err(_) => {
+                    commands.entity(entity).despawn();
+                    continue;
+                }
+            };
+        proxied.insert(proxy.0);
+        if *mesh != *owner_mesh {
+            *mesh = owner_mesh.clone();
+        }
+        let owner_transform = Transform::from(*owner_transform);
+        if *transform != owner_transform {
+            *transform = owner_transform;
+        }
+        if visibility.is_visible != owner_visibility.is_visible {
+            visibility.is_visible = owner_visibility.is_visible;
+        }
+        // the chunks update their bounds when they're remeshed
+        match (aabb, owner_aabb) {
+            (Some(mut aabb), Some(owner_aabb)) => {
+                if aabb.center != owner_aabb.center || aabb.half_extents != owner_aabb.half_extents
+                {
+                    *aabb = owner_aabb.clone();
+                }
+            }
+            (None, Some(owner_aabb)) => {
+                commands.entity(entity).insert(owner_aabb.clone());
+            }
+            _ => {}
+        }
+    }
+
+    for (owner, mesh, transform, visibility, aabb) in owners.iter() {
+        if proxied.contains(&owner) {
+            continue;
+        }
+        let mut proxy = commands.spawn_bundle(MaterialMeshBundle {
+            mesh: mesh.clone(),
+            material: material.0.clone(),
+            transform: (*transform).into(),
+            visibility: visibility.clone(),
+            ..default()
+        });
+        proxy
+            .insert(RenderLayers::layer(PICK_LAYER))
+            .insert(NotShadowCaster)
+            .insert(NotShadowReceiver)
+            .insert(PickProxy(owner));
+        if let Some(aabb) = aabb {
+            proxy.insert(aabb.clone());
+        }
+    }
+}
+
+fn extract_pick_request(
+    mut commands: Commands,
+    settings: Res<GpuPicking>,
+    target: Res<PickTarget>,
+    windows: Res<Windows>,
+) {
+    let request = target
+        .0
+        .as_ref()
+        .filter(|_| settings.enabled)
+        .and_then(|image| {
+            let window = windows.get_primary()?;
+            let cursor = window.cursor_position()?;
+            // the cursor starts at the bottom left in logical pixels, the
+            // image at the top left in physical pixels
+            let scale = window.scale_factor() as f32;
+            let pixel = Vec2::new(cursor.x, window.height() - cursor.y) * scale;
+            Some((image.handle.clone_weak(), pixel.max(Vec2::ZERO).as_uvec2()))
+        });
+    commands.insert_resource(ExtractedPickRequest(request));
+}
+
+fn read_pick_pixel(
+    request: Res<ExtractedPickRequest>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    readback: Res<PickReadback>,
+) {
+    let (handle, pixel) = match &request.0 {
+        Some(request) => request,
+        None => return,
+    };
+    let image = match images.get(handle) {
+        Some(image) => image,
+        None => return,
+    };
+    // the image is resized a frame after the window
+    if pixel.x as f32 >= image.size.width || pixel.y as f32 >= image.size.height {
+        return;
+    }
+    // a single row of a single pixel
+    let padded_row_len = padded_row_len(4);
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("pick_readback"),
+        size: padded_row_len as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("pick_readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture: &image.texture,
+            mip_level: 0,
+            origin: Origin3d {
+                x: pixel.x,
+                y: pixel.y,
+                z: 0,
+            },
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row_len),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    render_device.map_buffer(&slice, MapMode::Read);
+    let bytes = slice.get_mapped_range();
+    let pixel = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    drop(bytes);
+    buffer.unmap();
+    *readback.0.lock().unwrap() = Some((pixel, image.texture_format));
+}
+
+/// Coordinates of the cell written by `pick_id.wgsl` to a pixel of a
+/// texture of `format`
+fn unpack_cell(pixel: [u8; 4], format: TextureFormat) -> IVec3 {
+    let [r, g, b, a] = match format {
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+            [pixel[2], pixel[1], pixel[0], pixel[3]]
+        }
+        _ => pixel,
+    };
+    let id = u32::from_be_bytes([r, g, b, a]);
+    let biased = IVec3::new(
+        (id >> 21) as i32,
+        ((id >> 11) & 0x3ff) as i32,
+        (id & 0x7ff) as i32,
+    );
+    biased - IVec3::new(1024, 512, 1024)
+}
+
+pub fn apply_gpu_pick(
+    settings: Res<GpuPicking>,
+    readback: Res<PickReadback>,
+    data: Res<Data>,
+    field: DensityField,
+    isolevels: Query<Option<&ChunkIsolevel>>,
+    mut pick: ResMut<GpuPick>,
+) {
+    let read = readback.0.lock().unwrap().take();
+    let picked = read
+        .filter(|_| settings.enabled)
+        .and_then(|(pixel, format)| {
+            let cell = unpack_cell(pixel, format);
+            let center = (cell.as_vec3() + Vec3::splat(0.5)) * field.cell_size();
+            let (entity, chunk, local) = field.cell(center)?;
+            // the background and the pixels blended by the MSAA give cells
+            // without the surface
+            let isolevel = isolevels
+                .get(entity)
+                .ok()
+                .flatten()
+                .map_or(data.isolevel, |isolevel| isolevel.0);
+            let corners = CELL_CORNERS.map(|corner| chunk.get(local.as_vec3() + corner));
+            let index = cube_index(&corners, isolevel);
+            (index != 0 && index != 255).then(|| (entity, local))
+        });
+    if pick.0 != picked {
+        pick.0 = picked;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_unpacked_from_both_byte_orders() {
+        let cell = IVec3::new(-37, 5, 812);
+        let biased = (cell + IVec3::new(1024, 512, 1024)).as_uvec3();
+        let id = (biased.x << 21) | (biased.y << 11) | biased.z;
+        let [r, g, b, a] = id.to_be_bytes();
+        assert_eq!(
+            unpack_cell([r, g, b, a], TextureFormat::Rgba8UnormSrgb),
+            cell
+        );
+        assert_eq!(
+            unpack_cell([b, g, r, a], TextureFormat::Bgra8UnormSrgb),
+            cell
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use image::{ImageBuffer, Luma, Rgb};
+
+use crate::{field::DensityField, generation::WorldSettings, Data};
+
+/// Press F8 to export the terrain as a 16-bit heightmap.
+///
+/// A ray is cast down from the top of the world for every pixel and the
+/// height of the first hit is stored, 0 being the bottom of the world and
+/// 65535 its top.
+#[derive(Inspectable)]
+pub struct HeightmapExport {
+    /// Pixels per world unit
+    #[inspectable(min = 1, max = 16)]
+    pub resolution: u32,
+    /// Also export a tangent space normal map computed from the heights
+    pub normal_map: bool,
+    #[inspectable(ignore)]
+    pub directory: PathBuf,
+}
+
+impl Default for HeightmapExport {
+    fn default() -> Self {
+        Self {
+            resolution: 2,
+            normal_map: true,
+            directory: PathBuf::from("exports"),
+        }
+    }
+}
+
+pub fn export_heightmap(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<HeightmapExport>,
+    data: Res<Data>,
+    world_settings: Res<WorldSettings>,
+    field: DensityField,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F8) {
+        return;
+    }
+
+    let chunk_map = match field.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let chunk_size = world_settings.chunk_extent();
+    let min = chunk_map.min().as_vec3() * chunk_size;
+    let size = chunk_map.dimensions().as_vec3() * chunk_size;
+    let resolution = settings.resolution.max(1);
+    let width = size.x as u32 * resolution;
+    let depth = size.z as u32 * resolution;
+    let top = min.y + size.y;
+
+    let mut heights = vec![min.y; (width * depth) as usize];
+    for z in 0..depth {
+        for x in 0..width {
+            // sample at the center of the pixel
+            let pos = Vec2::new(x as f32 + 0.5, z as f32 + 0.5) / resolution as f32;
+            let origin = Vec3::new(min.x + pos.x, top - 0.001, min.z + pos.y);
+            if let Some(hit) = field.raycast(origin, -Vec3::Y, size.y, data.isolevel) {
+                heights[(z * width + x) as usize] = hit.y;
+            }
+        }
+    }
+
+    if let Err(err) = std::fs::create_dir_all(&settings.directory) {
+        error!("Failed to create export directory: {err}");
+        return;
+    }
+
+    let heightmap = ImageBuffer::from_fn(width, depth, |x, z| {
+        let height = heights[(z * width + x) as usize];
+        let normalized = ((height - min.y) / size.y).clamp(0.0, 1.0);
+        Luma([(normalized * u16::MAX as f32).round() as u16])
+    });
+    let path = settings.directory.join("heightmap.png");
+    match heightmap.save(&path) {
+        Ok(()) => info!("Exported {width}x{depth} heightmap to {path:?}"),
+        Err(err) => error!("Failed to export heightmap: {err}"),
+    }
+
+    if !settings.normal_map {
+        return;
+    }
+
+    let texel = 1.0 / resolution as f32;
+    let height_at = |x: i64, z: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let z = z.clamp(0, depth as i64 - 1) as u32;
+        heights[(z * width + x) as usize]
+    };
+    let normal_map = ImageBuffer::from_fn(width, depth, |x, z| {
+        let (x, z) = (x as i64, z as i64);
+        let dx = (height_at(x + 1, z) - height_at(x - 1, z)) / (2.0 * texel);
+        let dz = (height_at(x, z + 1) - height_at(x, z - 1)) / (2.0 * texel);
+        // x right, y up in the image (towards -z), z out of the surface
+        let normal = Vec3::new(-dx, dz, 1.0).normalize();
+        let encode = |v: f32| ((v * 0.5 + 0.5) * 255.0).round() as u8;
+        Rgb([encode(normal.x), encode(normal.y), encode(normal.z)])
+    });
+    let path = settings.directory.join("normalmap.png");
+    match normal_map.save(&path) {
+        Ok(()) => info!("Exported normal map to {path:?}"),
+        Err(err) => error!("Failed to export normal map: {err}"),
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunk::{is_inside, Chunk, DirtyRegion},
+    dual_contouring::EdgeCrossing,
+};
+
+/// Bisection steps refining a crossing between the 2 points of its edge
+const REFINE_STEPS: u32 = 8;
+/// Step of the central differences of the normals, in cells
+const NORMAL_STEP: f32 = 0.05;
+/// Distance to the grid under which a coordinate of a vertex is on it
+const ON_GRID: f32 = 1e-4;
+
+/// Crossings of the surface on the edges of a chunk, with their normals
+/// (Hermite data), computed from the density source when the chunk is
+/// generated.
+///
+/// The positions are found on the source itself instead of interpolating the
+/// 2 points of the edge and the normals come from the gradient of the source
+/// at the crossing, so they don't depend on the resolution of the grid. The
+/// edges touching points edited after the generation fall back to the points.
+#[derive(Component, Clone, Default)]
+pub struct HermiteData {
+    /// Isolevel the crossings were computed for
+    pub isolevel: f32,
+    /// Revision of the chunk the crossings were computed from
+    pub revision: u64,
+    /// By first point and axis of the edge
+    crossings: HashMap<(UVec3, u8), EdgeCrossing>,
+}
+
+impl HermiteData {
+    /// Finds the crossings of the edges of a chunk of `size` cells whose
+    /// `points` cross the isolevel.
+    ///
+    /// The points are ordered like [`Chunk::points`] and `density` samples
+    /// the source at a position in grid points relative to the chunk. The
+    /// revision is left to the caller.
+    pub fn compute(
+        points: &[f32],
+        size: UVec3,
+        isolevel: f32,
+        density: impl Fn(Vec3) -> f32,
+    ) -> Self {
+        let index =
+            |point: UVec3| ((point.z * (size.y + 1) + point.y) * (size.x + 1) + point.x) as usize;
+        let mut crossings = HashMap::default();
+        for point in Chunk::new_iter_3d(size) {
+            let start_inside = is_inside(points[index(point)], isolevel);
+            for axis in 0..3 {
+                let step = [UVec3::X, UVec3::Y, UVec3::Z][axis];
+                if point[axis] == size[axis]
+                    || is_inside(points[index(point + step)], isolevel) == start_inside
+                {
+                    continue;
+                }
+                let start = point.as_vec3();
+                let direction = step.as_vec3();
+                // keeps the crossing between a point on each side
+                let (mut low, mut high) = (0.0, 1.0);
+                let (mut low_value, mut high_value) =
+                    (points[index(point)], points[index(point + step)]);
+                for _ in 0..REFINE_STEPS {
+                    let middle = (low + high) / 2.0;
+                    let value = density(start + direction * middle);
+                    if is_inside(value, isolevel) == start_inside {
+                        low = middle;
+                        low_value = value;
+                    } else {
+                        high = middle;
+                        high_value = value;
+                    }
+                }
+                let t = if high_value != low_value {
+                    low + (high - low) * ((isolevel - low_value) / (high_value - low_value))
+                } else {
+                    (low + high) / 2.0
+                };
+                let position = start + direction * t.clamp(low, high);
+
+                let gradient = Vec3::new(
+                    density(position + Vec3::X * NORMAL_STEP)
+                        - density(position - Vec3::X * NORMAL_STEP),
+                    density(position + Vec3::Y * NORMAL_STEP)
+                        - density(position - Vec3::Y * NORMAL_STEP),
+                    density(position + Vec3::Z * NORMAL_STEP)
+                        - density(position - Vec3::Z * NORMAL_STEP),
+                );
+                // the density grows towards the inside
+                let outward = direction * if start_inside { 1.0 } else { -1.0 };
+                let normal = match -gradient.normalize_or_zero() {
+                    normal if normal == Vec3::ZERO => outward,
+                    normal => normal,
+                };
+                crossings.insert((point, axis as u8), EdgeCrossing { position, normal });
+            }
+        }
+        Self {
+            isolevel,
+            revision: 0,
+            crossings,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.crossings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.crossings.is_empty()
+    }
+
+    /// Crossings still matching `chunk` marched at `isolevel`, `None` when
+    /// they were computed for another isolevel
+    pub fn current(&self, chunk: &Chunk, isolevel: f32) -> Option<CurrentCrossings<'_>> {
+        if self.crossings.is_empty() || self.isolevel != isolevel {
+            return None;
+        }
+        Some(CurrentCrossings {
+            data: self,
+            edited: chunk.dirty_since(self.revision),
+        })
+    }
+}
+
+/// [`HermiteData`] of a chunk without the edges edited since it was computed
+#[derive(Clone, Copy)]
+pub struct CurrentCrossings<'a> {
+    data: &'a HermiteData,
+    edited: Option<DirtyRegion>,
+}
+
+impl CurrentCrossings<'_> {
+    /// Crossing of the edge from `point` along `axis`, `None` when the edge
+    /// doesn't cross the surface or was edited
+    pub fn get(&self, point: UVec3, axis: usize) -> Option<EdgeCrossing> {
+        if let Some(edited) = self.edited {
+            if edited.contains(point)
+                || edited.contains(point + [UVec3::X, UVec3::Y, UVec3::Z][axis])
+            {
+                return None;
+            }
+        }
+        self.data.crossings.get(&(point, axis as u8)).copied()
+    }
+
+    /// Normal of the crossing under a vertex placed on an edge of the grid,
+    /// `pos` is in grid points relative to the chunk
+    pub fn normal_at(&self, pos: Vec3) -> Option<Vec3> {
+        let rounded = pos.round();
+        let off_grid = (pos - rounded).abs().cmpgt(Vec3::splat(ON_GRID));
+        let axis = match (off_grid.x, off_grid.y, off_grid.z) {
+            (true, false, false) => 0,
+            (false, true, false) => 1,
+            (false, false, true) => 2,
+            _ => return None,
+        };
+        let mut point = rounded;
+        point[axis] = pos[axis].floor();
+        if point.cmplt(Vec3::ZERO).any() {
+            return None;
+        }
+        self.get(point.as_uvec3(), axis)
+            .map(|crossing| crossing.normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossings_follow_the_source() {
+        // a sphere, the points can't describe its curve between them
+        let center = Vec3::splat(4.0);
+        let density = |pos: Vec3| 0.5 + (2.7 - pos.distance(center)) * 0.25;
+        let size = UVec3::splat(8);
+        let chunk = Chunk::from_fn(size, |point| density(point.as_vec3()));
+        let mut hermite = HermiteData::compute(&chunk.points, size, 0.5, density);
+        hermite.revision = chunk.revision();
+        assert!(!hermite.is_empty());
+
+        let current = hermite.current(&chunk, 0.5).unwrap();
+        for point in Chunk::new_iter_3d(size) {
+            for axis in 0..3 {
+                if let Some(crossing) = current.get(point, axis) {
+                    assert!((crossing.position.distance(center) - 2.7).abs() < 1e-3);
+                    let normal = (crossing.position - center).normalize();
+                    assert!(crossing.normal.abs_diff_eq(normal, 1e-3));
+                    assert_eq!(current.normal_at(crossing.position), Some(crossing.normal));
+                }
+            }
+        }
+        assert!(hermite.current(&chunk, 0.6).is_none());
+    }
+
+    #[test]
+    fn edited_edges_are_ignored() {
+        let density = |pos: Vec3| if pos.y < 3.5 { 1.0 } else { 0.0 };
+        let size = UVec3::splat(4);
+        let mut chunk = Chunk::from_fn(size, |point| density(point.as_vec3()));
+        let mut hermite = HermiteData::compute(&chunk.points, size, 0.5, density);
+        hermite.revision = chunk.revision();
+        assert!(hermite
+            .current(&chunk, 0.5)
+            .unwrap()
+            .get(UVec3::new(1, 3, 1), 1)
+            .is_some());
+
+        chunk.set(Vec3::new(1.0, 4.0, 1.0), 1.0);
+        let current = hermite.current(&chunk, 0.5).unwrap();
+        assert!(current.get(UVec3::new(1, 3, 1), 1).is_none());
+        assert!(current.get(UVec3::new(2, 3, 2), 1).is_some());
+    }
+}
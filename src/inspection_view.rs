@@ -0,0 +1,268 @@
+use bevy::{
+    core_pipeline::{draw_3d_graph, node, AlphaMask3d, Opaque3d, Transparent3d},
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, ExtractedCameraNames, RenderTarget},
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotValue},
+        render_phase::RenderPhase,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        renderer::RenderContext,
+        texture::BevyDefault,
+        RenderApp, RenderStage,
+    },
+};
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContext},
+    Inspectable,
+};
+
+use crate::{chunk::Chunk, generation::WorldSettings, terrain_root::MainTerrain};
+
+const INSPECTION_CAMERA: &str = "inspection_camera";
+const INSPECTION_PASS_DRIVER: &str = "inspection_pass_driver";
+
+/// Side the selected chunk is seen from
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InspectionAxis {
+    Top,
+    Front,
+    Side,
+}
+
+impl InspectionAxis {
+    /// Direction from the chunk to the camera and the up direction of the view
+    fn directions(self) -> (Vec3, Vec3) {
+        match self {
+            InspectionAxis::Top => (Vec3::Y, -Vec3::Z),
+            InspectionAxis::Front => (Vec3::Z, Vec3::Y),
+            InspectionAxis::Side => (Vec3::X, Vec3::Y),
+        }
+    }
+}
+
+/// Orthographic view of the selected chunk rendered in its own window, to
+/// inspect its silhouette or a cross-section while flying around
+#[derive(Inspectable)]
+pub struct InspectionView {
+    pub enabled: bool,
+    pub axis: InspectionAxis,
+    /// Cuts the chunk at this fraction of its depth along the axis, 0 shows
+    /// the whole chunk
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub slice: f32,
+    /// Space around the chunk in world units
+    #[inspectable(min = 0.0, max = 16.0)]
+    pub margin: f32,
+    /// Width and height of the rendered image in pixels
+    #[inspectable(min = 64, max = 2048)]
+    pub resolution: u32,
+}
+
+impl Default for InspectionView {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            axis: InspectionAxis::Top,
+            slice: 0.0,
+            margin: 1.0,
+            resolution: 512,
+        }
+    }
+}
+
+/// Camera rendering the [`InspectionView`] to an image
+#[derive(Component)]
+pub struct InspectionCamera;
+
+/// Image the [`InspectionCamera`] renders to and its id in egui
+struct InspectionImage {
+    handle: Handle<Image>,
+    texture_id: egui::TextureId,
+    camera: Entity,
+}
+
+/// Spawned while the [`InspectionView`] is enabled
+#[derive(Default)]
+struct InspectionTarget(Option<InspectionImage>);
+
+pub struct InspectionViewPlugin;
+
+impl Plugin for InspectionViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InspectionTarget>()
+            .add_system(update_inspection_camera)
+            .add_system(inspection_view_ui.after(update_inspection_camera));
+
+        add_image_camera(app, INSPECTION_CAMERA, INSPECTION_PASS_DRIVER);
+    }
+}
+
+/// Renders the 3d camera named `camera` when it's active, for the cameras
+/// rendering to an image instead of a window
+pub(crate) fn add_image_camera(app: &mut App, camera: &'static str, driver: &'static str) {
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app.add_system_to_stage(
+        RenderStage::Extract,
+        move |mut commands: Commands, active_cameras: Res<ActiveCameras>| {
+            if let Some(entity) = active_cameras.get(camera).and_then(|camera| camera.entity) {
+                commands.get_or_spawn(entity).insert_bundle((
+                    RenderPhase::<Opaque3d>::default(),
+                    RenderPhase::<AlphaMask3d>::default(),
+                    RenderPhase::<Transparent3d>::default(),
+                ));
+            }
+        },
+    );
+    let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+    graph.add_node(driver, ImageCameraDriver(camera));
+    graph
+        .add_node_edge(node::MAIN_PASS_DEPENDENCIES, driver)
+        .unwrap();
+    // the image is drawn by egui after the main pass
+    graph.add_node_edge(driver, node::MAIN_PASS_DRIVER).unwrap();
+}
+
+struct ImageCameraDriver(&'static str);
+
+impl Node for ImageCameraDriver {
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let cameras = world.get_resource::<ExtractedCameraNames>().unwrap();
+        if let Some(camera) = cameras.entities.get(self.0) {
+            graph.run_sub_graph(draw_3d_graph::NAME, vec![SlotValue::Entity(*camera)])?;
+        }
+        Ok(())
+    }
+}
+
+/// Image a camera can render to and egui can show
+pub(crate) fn render_target_image(width: u32, height: u32) -> Image {
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::bevy_default(),
+    );
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+        | TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+/// Recreates a [`render_target_image`] when its size isn't `size`
+pub(crate) fn fit_render_target(images: &mut Assets<Image>, handle: &Handle<Image>, size: UVec2) {
+    if let Some(image) = images.get_mut(handle) {
+        let image_size = image.texture_descriptor.size;
+        if image_size.width != size.x || image_size.height != size.y {
+            *image = render_target_image(size.x, size.y);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_inspection_camera(
+    mut commands: Commands,
+    settings: Res<InspectionView>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    chunks: Query<(&Chunk, &GlobalTransform)>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<InspectionCamera>>,
+    mut target: ResMut<InspectionTarget>,
+) {
+    if !settings.enabled {
+        if let Some(image) = target.0.take() {
+            commands.entity(image.camera).despawn();
+            egui_context.remove_image(&image.handle);
+            images.remove(&image.handle);
+            active_cameras.remove(INSPECTION_CAMERA);
+        }
+        return;
+    }
+
+    let resolution = settings.resolution.max(1);
+    let image = target.0.get_or_insert_with(|| {
+        let handle = images.add(render_target_image(resolution, resolution));
+        let mut bundle = OrthographicCameraBundle::new_3d();
+        bundle.camera.name = Some(INSPECTION_CAMERA.to_string());
+        bundle.camera.target = RenderTarget::Image(handle.clone());
+        let camera = commands.spawn_bundle(bundle).insert(InspectionCamera).id();
+        active_cameras.add(INSPECTION_CAMERA);
+        InspectionImage {
+            texture_id: egui_context.add_image(handle.clone()),
+            handle,
+            camera,
+        }
+    });
+    if settings.is_changed() {
+        fit_render_target(&mut images, &image.handle, UVec2::splat(resolution));
+    }
+
+    let (chunk, chunk_transform) = match terrain
+        .selected_chunk()
+        .and_then(|entity| chunks.get(entity).ok())
+    {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let (mut transform, mut projection) = match cameras.get_mut(image.camera) {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let extent = chunk.size.as_vec3() * world_settings.cell_size;
+    let center = chunk_transform.translation + extent * 0.5;
+    let (back, up) = settings.axis.directions();
+    let depth = extent.dot(back);
+    let distance = depth * 0.5 + settings.margin + 1.0;
+    let view = Transform::from_translation(center + back * distance).looking_at(center, up);
+    if *transform != view {
+        *transform = view;
+    }
+    // the near plane cuts the chunk for the cross-sections
+    let near = distance - depth * 0.5 + depth * settings.slice;
+    // the projection is 2 units high at a scale of 1
+    let scale = (extent - back * depth).max_element() * 0.5 + settings.margin;
+    if projection.near != near || projection.scale != scale {
+        projection.near = near;
+        projection.far = distance + depth * 0.5 + settings.margin;
+        projection.scale = scale;
+    }
+}
+
+fn inspection_view_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut settings: ResMut<InspectionView>,
+    terrain: MainTerrain,
+    target: Res<InspectionTarget>,
+) {
+    let texture_id = match &target.0 {
+        Some(image) if settings.enabled => image.texture_id,
+        _ => return,
+    };
+    let size = settings.resolution.min(512) as f32;
+    let mut open = true;
+    egui::Window::new("Inspection view")
+        .open(&mut open)
+        .show(egui_context.ctx_mut(), |ui| {
+            if terrain.selected_chunk().is_none() {
+                ui.label("Click a chunk to inspect it");
+            } else {
+                ui.image(texture_id, [size, size]);
+            }
+        });
+    if !open {
+        settings.enabled = false;
+    }
+}
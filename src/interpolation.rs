@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+/// How the position of a vertex is chosen along a cube edge crossing the isolevel
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub enum Interpolation {
+    /// Places the vertex where the linear interpolation of the 2 values
+    /// reaches the isolevel
+    Linear,
+    /// Always places the vertex in the middle of the edge, gives a blocky look
+    Midpoint,
+    /// Like `Linear`, but snaps the vertex to an end of the edge when its value
+    /// is within `epsilon` of the isolevel
+    Snapped {
+        #[inspectable(min = 0.0, max = 0.1, speed = 0.0001)]
+        epsilon: f32,
+    },
+}
+
+impl Default for Interpolation {
+    fn default() -> Self {
+        Interpolation::Snapped { epsilon: 0.00001 }
+    }
+}
+
+impl Interpolation {
+    /// Interpolates between 2 vertices proportional to isolevel
+    pub fn interpolate(self, isolevel: f32, p1: Vec3, p2: Vec3, valp1: f32, valp2: f32) -> Vec3 {
+        match self {
+            Interpolation::Linear => linear(isolevel, p1, p2, valp1, valp2),
+            Interpolation::Midpoint => (p1 + p2) / 2.0,
+            Interpolation::Snapped { epsilon } => {
+                if (isolevel - valp1).abs() <= epsilon {
+                    p1
+                } else if (isolevel - valp2).abs() <= epsilon {
+                    p2
+                } else {
+                    linear(isolevel, p1, p2, valp1, valp2)
+                }
+            }
+        }
+    }
+}
+
+fn linear(isolevel: f32, p1: Vec3, p2: Vec3, valp1: f32, valp2: f32) -> Vec3 {
+    // a point on the isolevel is the vertex, `p1 + (p2 - p1)` can round away from `p2`
+    if valp1 == isolevel {
+        return p1;
+    }
+    if valp2 == isolevel {
+        return p2;
+    }
+    let mu = (isolevel - valp1) / (valp2 - valp1);
+    if !mu.is_finite() {
+        // both values are equal, the surface could be anywhere on the edge
+        return (p1 + p2) / 2.0;
+    }
+    p1 + mu.clamp(0.0, 1.0) * (p2 - p1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P1: Vec3 = Vec3::ZERO;
+    const P2: Vec3 = Vec3::X;
+
+    const MODES: [Interpolation; 4] = [
+        Interpolation::Linear,
+        Interpolation::Midpoint,
+        Interpolation::Snapped { epsilon: 0.00001 },
+        Interpolation::Snapped { epsilon: 0.0 },
+    ];
+
+    #[test]
+    fn linear_crossing() {
+        let p = Interpolation::Linear.interpolate(0.5, P1, P2, 0.0, 2.0);
+        assert_eq!(p, Vec3::new(0.25, 0.0, 0.0));
+        let p = Interpolation::Linear.interpolate(0.5, P1, P2, 2.0, 0.0);
+        assert_eq!(p, Vec3::new(0.75, 0.0, 0.0));
+    }
+
+    #[test]
+    fn midpoint_ignores_values() {
+        let p = Interpolation::Midpoint.interpolate(0.5, P1, P2, 0.0, 2.0);
+        assert_eq!(p, Vec3::new(0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn snapped_to_close_end() {
+        let mode = Interpolation::Snapped { epsilon: 0.01 };
+        assert_eq!(mode.interpolate(0.5, P1, P2, 0.505, 0.0), P1);
+        assert_eq!(mode.interpolate(0.5, P1, P2, 0.0, 0.495), P2);
+    }
+
+    #[test]
+    fn degenerate_values_stay_on_edge() {
+        let pairs = [
+            // both values equal to the isolevel
+            (0.5, 0.5),
+            // equal values on the same side of the isolevel
+            (0.2, 0.2),
+            // one value exactly on the isolevel
+            (0.5, 0.0),
+            (0.0, 0.5),
+            // values that don't straddle the isolevel
+            (0.7, 0.9),
+        ];
+        for mode in MODES {
+            for (valp1, valp2) in pairs {
+                let p = mode.interpolate(0.5, P1, P2, valp1, valp2);
+                assert!(p.is_finite(), "{:?} {:?} gave {}", mode, (valp1, valp2), p);
+                assert!(
+                    (0.0..=1.0).contains(&p.x) && p.y == 0.0 && p.z == 0.0,
+                    "{:?} {:?} gave {}",
+                    mode,
+                    (valp1, valp2),
+                    p
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exact_isolevel_snaps_to_vertex() {
+        for mode in [
+            Interpolation::Linear,
+            Interpolation::Snapped { epsilon: 0.0 },
+        ] {
+            assert_eq!(mode.interpolate(0.5, P1, P2, 0.5, 0.0), P1);
+            assert_eq!(mode.interpolate(0.5, P1, P2, 0.0, 0.5), P2);
+            // exact even where the difference of the ends rounds
+            let (p1, p2) = (Vec3::splat(0.7), Vec3::splat(0.1));
+            assert_eq!(mode.interpolate(0.5, p1, p2, 0.0, 0.5), p2);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+/// Stable offset in [-1, 1] on each axis of the grid point `point`, in world
+/// grid coordinates so the chunks sharing a point move it the same way.
+///
+/// Scaled below half a cell, each point stays in its own stratum of the grid
+/// and the points never get closer than the jitter allows, which spreads
+/// them like blue noise instead of clumping them like white noise.
+pub fn jitter_offset(point: IVec3) -> Vec3 {
+    let mut z = (point.x as u32 as u64).wrapping_mul(0x9e37_79b9)
+        ^ (point.y as u32 as u64).wrapping_mul(0x85eb_ca77) << 21
+        ^ (point.z as u32 as u64).wrapping_mul(0xc2b2_ae3d) << 42;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    // 21 bits for each axis
+    let axis = |shift: u32| ((z >> shift) & 0x1f_ffff) as f32 / 0x1f_ffff as f32 * 2.0 - 1.0;
+    Vec3::new(axis(0), axis(21), axis(42))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_are_stable_and_bounded() {
+        let mut sum = Vec3::ZERO;
+        let mut count = 0.0;
+        for x in -8..8 {
+            for y in -8..8 {
+                for z in -8..8 {
+                    let point = IVec3::new(x, y, z);
+                    let offset = jitter_offset(point);
+                    assert_eq!(offset, jitter_offset(point));
+                    assert!(offset.abs().max_element() <= 1.0);
+                    assert_ne!(offset, jitter_offset(point + IVec3::X));
+                    sum += offset;
+                    count += 1.0;
+                }
+            }
+        }
+        // centered, the surface doesn't drift
+        assert!((sum / count).abs().max_element() < 0.05, "{}", sum / count);
+    }
+}
@@ -0,0 +1,1649 @@
+use bevy::{
+    ecs::schedule::ShouldRun,
+    pbr::wireframe::{Wireframe, WireframeConfig, WireframePlugin},
+    prelude::*,
+    render::{primitives::Aabb, view::VisibilitySystems},
+    tasks::ComputeTaskPool,
+    utils::{HashMap, HashSet, Instant},
+};
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+use bevy_mod_picking::*;
+use brush::{Brush, BrushTarget};
+use capabilities::GpuCapabilities;
+use capture::{CapturePlugin, TurntableSettings};
+use caves::{CaveNetwork, CaveSettings};
+use cell_inspector::{CellInspector, HoveredCell};
+use cellular::{CellularRocks, CellularSettings};
+use chunk::{
+    is_inside, ActiveCells, Chunk, ChunkCoord, ChunkIsolevel, ChunkMap, ChunkMesh, ChunkStatus,
+    MeshedFrom, NonIndexed, NormalMode, RegenerateChunk,
+};
+use chunk_transform::{ChunkTransformTool, MoveChunk};
+use clipboard::Clipboard;
+use collider::{ColliderQueue, ColliderSettings};
+use compaction::cube_index;
+use compare::{CompareMode, CompareModePlugin};
+use debug_camera::{DebugCamera, DebugCameraPlugin};
+use debug_points::PointColors;
+use drivable::DrivableSmoothing;
+use dual_contouring::dual_contour;
+use environment::EnvironmentPlugin;
+use event_log::{Activity, EventLog};
+use field::DensityField;
+use field_sync::FieldSyncPlugin;
+use flatten::{FlattenPad, FlattenTool};
+use frame_guard::FrameTimeGuard;
+use generation::{
+    fill_points, sample_density_at, GenerationWorkers, NoiseSettings, NoiseStack, WorldSettings,
+    WrapPeriod,
+};
+use gpu_brush::GpuBrushPlugin;
+use gpu_meshing::{GpuMeshed, GpuMeshing, GpuMeshingPlugin};
+use gpu_picking::{GpuPicking, GpuPickingPlugin};
+use heightmap::HeightmapExport;
+use hermite::HermiteData;
+use inspection_view::{InspectionView, InspectionViewPlugin};
+use interpolation::Interpolation;
+use iters::Iter3d;
+use jitter::jitter_offset;
+use lod::LodSettings;
+use log_levels::LogLevels;
+use marching_cube_tables::{EDGE_CONNECTION, EDGE_TABLE, TRIANGLE_TABLE};
+use materials::{ChunkMaterial, MaterialLibrary, SetChunkMaterial, TerrainMaterial, TERRAIN};
+use measure::{Measurement, ScaleReference};
+use merge::MergedWorld;
+use mesh_export::MeshExport;
+use mesh_parts::{spawn_mesh_parts, split_mesh, ChunkMeshParts};
+use ore::{OreLayer, OrePlugin, OreSettings};
+use pipeline::GenerationPipeline;
+use placement::{Placement, PlacementAssets};
+use presets::SelectedCellPreset;
+use projectile::{ProjectileImpact, ProjectileSettings};
+use ramp::{BuildRamp, RampTool};
+use ray_debug::RayDebug;
+use save::{AutosaveSettings, ChunkVersion, LoadRegion, SaveMigrations, SaveSettings};
+use skirt::{append_skirts, skirt_triangles};
+use slope_material::SlopeColoringPlugin;
+use snapshot::FieldSnapshot;
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use terrain_instance::TerrainInstance;
+use terrain_root::{MainTerrain, TerrainRoot};
+use transition::{ChunkTransition, EditTransition};
+use validation::MeshValidation;
+use viewport_orientation_gizmo::{TrackedRotator, ViewportOrientationGizmoPlugin};
+use volume::{VolumePreview, VolumePreviewPlugin};
+use voxelize::ModelImport;
+use xray::XRayPlugin;
+
+pub mod brush;
+pub mod calibration;
+mod camera;
+pub mod capabilities;
+mod capture;
+pub mod caves;
+pub mod cell_inspector;
+pub mod cellular;
+pub mod character;
+pub mod chunk;
+#[cfg(feature = "world_inspector")]
+mod chunk_inspector;
+pub mod chunk_transform;
+pub mod clipboard;
+pub mod collider;
+pub mod compaction;
+pub mod compare;
+pub mod debug_camera;
+mod debug_points;
+pub mod density_texture;
+pub mod drivable;
+pub mod dual_contouring;
+mod environment;
+pub mod event_log;
+pub mod field;
+pub mod field_sync;
+pub mod flatten;
+pub mod frame_guard;
+pub mod generation;
+pub mod gltf_export;
+pub mod gpu_brush;
+pub mod gpu_meshing;
+pub mod gpu_picking;
+mod heightmap;
+pub mod hermite;
+pub mod inspection_view;
+pub mod interpolation;
+mod iters;
+pub mod jitter;
+mod lines;
+pub mod lod;
+pub mod log_levels;
+mod marching_cube_tables;
+pub mod marching_squares;
+pub mod materials;
+mod measure;
+pub mod merge;
+pub mod mesh_export;
+pub mod mesh_parts;
+mod minimap;
+pub mod ore;
+pub mod pipeline;
+pub mod placement;
+mod point_editor;
+pub mod presets;
+pub mod projectile;
+pub mod ramp;
+pub mod ray_debug;
+pub mod regression;
+pub mod save;
+pub mod skirt;
+mod slope_material;
+pub mod snapshot;
+mod stats;
+pub mod stress;
+mod svo;
+pub mod terrain_instance;
+pub mod terrain_root;
+mod transition;
+mod validation;
+mod vertex_cache;
+mod volume;
+pub mod voxelize;
+mod xray;
+
+/// Default number of cells on each axis of the chunks, see [`WorldSettings::chunk_size`]
+pub const CHUNK_SIZE: usize = 16;
+const CHUNK_COUNT: usize = 2;
+
+/// Marches every chunk again, sent when the settings change or when R is pressed
+#[derive(Default)]
+pub struct StartMarching;
+
+#[derive(Component)]
+struct Point(f32);
+
+#[derive(Component)]
+struct MarchCubeIndicator;
+
+#[derive(Component)]
+struct DebugPoint;
+
+/// Meshing and debug view settings, the resource is the inspector window of
+/// the main terrain and the component the settings of each terrain, on its
+/// [`TerrainRoot`]
+#[derive(Inspectable, Component, Clone)]
+#[non_exhaustive]
+pub struct Data {
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub isolevel: f32,
+    /// Which debug points of the selected chunk are visible
+    pub point_filter: PointFilter,
+    /// Only show every Nth debug point on each axis
+    #[inspectable(min = 1, max = 16)]
+    pub point_stride: u32,
+    /// Maximum distance to the isolevel of the points shown by the `NearIsolevel` filter
+    #[inspectable(min = 0.0, max = 0.5, speed = 0.005)]
+    pub near_isolevel_range: f32,
+    /// Where vertices are placed along the edges crossing the isolevel
+    pub interpolation: Interpolation,
+    /// How the normals of the meshes are computed
+    pub normals: NormalMode,
+    /// With smooth normals, the vertices on the faces of the chunks use the
+    /// gradient of the density field, which the neighboring chunks share, so
+    /// the shading is continuous across the seams
+    pub smooth_seams: bool,
+    /// Depth of the walls hanging below the edges of the meshes on the sides
+    /// of the chunks, they hide the cracks between chunks. 0 disables them.
+    #[inspectable(min = 0.0, max = 8.0, speed = 0.05)]
+    pub skirt_depth: f32,
+    /// When only the isolevel changed, march outward from the previous surface
+    /// instead of every cell. Surfaces appearing away from the previous one,
+    /// like a new pocket, are missed until the next full march.
+    pub incremental_isolevel: bool,
+    /// Reorders the triangles of the meshes to reuse the vertices in the GPU
+    /// vertex cache, meshing is slower but large meshes render faster
+    pub optimize_index_order: bool,
+    /// Chunks with at most this many triangles skip the welding and upload a
+    /// non indexed mesh, which is faster to build. Ignored by smooth normals.
+    #[inspectable(min = 0, max = 4096)]
+    pub non_indexed_max_triangles: usize,
+    /// Meshes with more vertices are split into parts spawned as children of
+    /// the chunk. 65536 keeps every index in 16 bits, 0 disables the limit.
+    #[inspectable(min = 0, max = 1048576)]
+    pub max_mesh_vertices: usize,
+    /// Moves the grid points of the meshes by stable offsets hashed from
+    /// their position, breaking up the axis aligned ridges of low resolution
+    /// chunks. Toggle it to compare with the regular grid.
+    pub jitter: bool,
+    /// Largest offset of the jittered points, in cells
+    #[inspectable(min = 0.0, max = 0.45, speed = 0.01)]
+    pub jitter_amount: f32,
+    /// Places the vertices with dual contouring instead of marching cubes, so
+    /// the stamped shapes like the ramps and the imported models keep their
+    /// edges and corners. The seams between chunks are left open, the skirts
+    /// hide them. Ignores the jitter.
+    pub sharp_features: bool,
+    /// Smooths the near horizontal ground for vehicles
+    pub drivable: DrivableSmoothing,
+    #[inspectable()]
+    pub show_wireframe: bool,
+}
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub enum PointFilter {
+    All,
+    /// Points with a value above the isolevel
+    Inside,
+    /// Points close to the isolevel, where the surface crosses the grid
+    NearIsolevel,
+}
+
+impl PointFilter {
+    fn is_visible(self, val: f32, isolevel: f32, data: &Data) -> bool {
+        match self {
+            PointFilter::All => true,
+            PointFilter::Inside => is_inside(val, isolevel),
+            PointFilter::NearIsolevel => (val - isolevel).abs() <= data.near_isolevel_range,
+        }
+    }
+}
+
+impl Default for Data {
+    fn default() -> Self {
+        Self {
+            isolevel: 0.5,
+            point_filter: PointFilter::Inside,
+            point_stride: 1,
+            near_isolevel_range: 0.05,
+            interpolation: Interpolation::default(),
+            normals: NormalMode::default(),
+            smooth_seams: true,
+            skirt_depth: 0.0,
+            incremental_isolevel: true,
+            optimize_index_order: false,
+            non_indexed_max_triangles: 64,
+            max_mesh_vertices: 0,
+            jitter: false,
+            jitter_amount: 0.25,
+            sharp_features: false,
+            drivable: DrivableSmoothing::default(),
+            show_wireframe: false,
+        }
+    }
+}
+
+impl Data {
+    pub fn with_isolevel(mut self, isolevel: f32) -> Self {
+        self.isolevel = isolevel;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn with_normals(mut self, normals: NormalMode) -> Self {
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_wireframe(mut self, show_wireframe: bool) -> Self {
+        self.show_wireframe = show_wireframe;
+        self
+    }
+}
+
+/// Chunk of the terrain clicked last, on its [`TerrainRoot`]
+#[derive(Component, Default)]
+pub struct SelectedChunk(pub Option<Entity>);
+
+/// Stages of the chunk pipeline, use them to order systems relative to the
+/// generation and the meshing of the chunks
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MarchingCubesSystem {
+    /// Samples the noise into the points of the chunks
+    DensityGeneration,
+    /// Marches the changed chunks into triangles
+    Meshing,
+    /// Uploads the triangles of the marched chunks to their meshes
+    MeshApply,
+}
+
+/// Pauses the generation and the meshing of the chunks so many settings can
+/// be changed before remeshing once, press P to toggle it.
+///
+/// Changes made while paused are applied when running again.
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshingState {
+    Running,
+    Paused,
+}
+
+impl Default for MeshingState {
+    fn default() -> Self {
+        MeshingState::Running
+    }
+}
+
+fn meshing_running(state: Res<MeshingState>) -> ShouldRun {
+    match *state {
+        MeshingState::Running => ShouldRun::Yes,
+        MeshingState::Paused => ShouldRun::No,
+    }
+}
+
+fn toggle_meshing(keyboard_input: Res<Input<KeyCode>>, mut state: ResMut<MeshingState>) {
+    if keyboard_input.just_pressed(KeyCode::P) {
+        *state = match *state {
+            MeshingState::Running => MeshingState::Paused,
+            MeshingState::Paused => MeshingState::Running,
+        };
+    }
+}
+
+/// The [`StartMarching`] events sent while paused expired before the meshing
+/// could read them, a march is started when it runs again and the chunks
+/// that changed since they were meshed are marched
+fn resume_meshing(
+    state: Res<MeshingState>,
+    mut previous: Local<Option<MeshingState>>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    if *previous == Some(MeshingState::Paused) && *state == MeshingState::Running {
+        start_marching_events.send_default();
+    }
+    *previous = Some(*state);
+}
+
+/// Sets the [`ChunkIsolevel`] of a chunk, `None` removes the override and the
+/// chunk goes back to the global isolevel
+pub struct SetChunkIsolevel {
+    pub chunk: Entity,
+    pub isolevel: Option<f32>,
+}
+
+/// Marches again the chunks overlapping a box in world space, even if their
+/// points didn't change.
+///
+/// Lets gameplay systems refresh the terrain around an explosion or a
+/// building without knowing the chunk layout.
+pub struct RemeshRegion {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl RemeshRegion {
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        Self {
+            min: Vec3::from(aabb.min()),
+            max: Vec3::from(aabb.max()),
+        }
+    }
+}
+
+/// Sent when a chunk is clicked, after [`SelectedChunk`] is updated
+pub struct SelectChunk;
+
+/// The plugin, the chunks and their density field, the brushes and the
+/// events, the rest is in the modules
+pub mod prelude {
+    pub use crate::{
+        brush::{edit_box, edit_sphere, Brush, BrushMode, BrushTarget, BrushTargeting},
+        chunk::Chunk,
+        chunk_transform::MoveChunk,
+        event_log::Activity,
+        field::DensityField,
+        field_sync::{DensityTextureRead, ReadDensityTexture},
+        flatten::FlattenPad,
+        gpu_brush::GpuBrushEdit,
+        materials::SetChunkMaterial,
+        projectile::ProjectileImpact,
+        ramp::BuildRamp,
+        save::LoadRegion,
+        MarchingCubesPlugin, RemeshRegion, SelectChunk, SetChunkIsolevel, StartMarching,
+    };
+}
+
+/// Generates, marches and renders the chunks, with the debug tools and the
+/// inspector windows used to tweak them.
+///
+/// Requires the `DefaultPlugins`, the tools needing an optional GPU feature
+/// are disabled when it's missing, see [`GpuCapabilities`]. Settings
+/// resources inserted before adding the plugin, like
+/// `NoiseSettings::default().with_seed(42)`, are kept.
+pub struct MarchingCubesPlugin;
+
+impl Plugin for MarchingCubesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GpuCapabilities>();
+        let capabilities = *app.world.get_resource::<GpuCapabilities>().unwrap();
+        if capabilities.wireframe {
+            app.add_plugin(WireframePlugin)
+                .insert_resource(WireframeConfig { global: false });
+        }
+
+        app.add_plugin(PickingPlugin)
+            .add_plugin(InteractablePickingPlugin)
+            .add_plugin(DebugCursorPickingPlugin)
+            .add_plugin(InspectorPlugin::<Data>::new())
+            .add_plugin(InspectorPlugin::<MeshingState>::new())
+            .add_plugin(InspectorPlugin::<EditTransition>::new())
+            .add_plugin(InspectorPlugin::<TerrainMaterial>::new())
+            .add_plugin(InspectorPlugin::<NoiseSettings>::new())
+            .add_plugin(InspectorPlugin::<CellularSettings>::new())
+            .add_plugin(InspectorPlugin::<CaveSettings>::new())
+            .add_plugin(InspectorPlugin::<PointColors>::new())
+            .add_plugin(InspectorPlugin::<WorldSettings>::new())
+            .add_plugin(InspectorPlugin::<GenerationWorkers>::new())
+            .add_plugin(InspectorPlugin::<TurntableSettings>::new())
+            .add_plugin(CapturePlugin)
+            .add_plugin(InspectorPlugin::<ScaleReference>::new())
+            .add_plugin(ViewportOrientationGizmoPlugin::new())
+            .add_plugin(VolumePreviewPlugin)
+            .add_plugin(EnvironmentPlugin)
+            .add_plugin(GpuBrushPlugin)
+            .add_plugin(FieldSyncPlugin)
+            .add_plugin(SlopeColoringPlugin)
+            .add_plugin(OrePlugin)
+            .add_plugin(XRayPlugin)
+            .add_plugin(InspectorPlugin::<VolumePreview>::new())
+            .add_plugin(InspectorPlugin::<MeshValidation>::new())
+            .add_plugin(InspectorPlugin::<MergedWorld>::new())
+            .add_plugin(InspectorPlugin::<Brush>::new())
+            .add_plugin(InspectorPlugin::<ProjectileSettings>::new())
+            .add_plugin(InspectorPlugin::<Placement>::new())
+            .add_plugin(InspectorPlugin::<FlattenTool>::new())
+            .add_plugin(InspectorPlugin::<RampTool>::new())
+            .add_plugin(InspectorPlugin::<LodSettings>::new())
+            .add_plugin(InspectorPlugin::<ColliderSettings>::new())
+            .init_resource::<ColliderQueue>()
+            .add_plugin(InspectorPlugin::<FrameTimeGuard>::new())
+            .add_plugin(InspectorPlugin::<CellInspector>::new())
+            .add_plugin(InspectorPlugin::<RayDebug>::new())
+            .add_plugin(InspectorPlugin::<InspectionView>::new())
+            .add_plugin(InspectionViewPlugin)
+            .add_plugin(InspectorPlugin::<CompareMode>::new())
+            .add_plugin(CompareModePlugin)
+            .add_plugin(InspectorPlugin::<GpuPicking>::new())
+            .add_plugin(GpuPickingPlugin)
+            .add_plugin(InspectorPlugin::<GpuMeshing>::new())
+            .add_plugin(GpuMeshingPlugin)
+            .add_plugin(InspectorPlugin::<DebugCamera>::new())
+            .add_plugin(DebugCameraPlugin)
+            .add_event::<StartMarching>()
+            .add_event::<SelectChunk>()
+            .add_event::<SetChunkIsolevel>()
+            .add_event::<MoveChunk>()
+            .add_event::<RemeshRegion>()
+            .add_event::<ProjectileImpact>()
+            .add_event::<FlattenPad>()
+            .add_event::<BuildRamp>()
+            .add_event::<SetChunkMaterial>()
+            .add_event::<Activity>()
+            .init_resource::<EventLog>()
+            .init_resource::<MaterialLibrary>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, terrain_root::spawn_terrain_root)
+            .add_startup_system(setup)
+            .add_startup_system(spawn_debug_points)
+            .add_startup_system(stats::setup_memory_diagnostics)
+            .add_system(terrain_root::sync_main_terrain.before(spawn_chunks))
+            .add_system(spawn_chunks.before(MarchingCubesSystem::DensityGeneration))
+            .add_system_set(
+                SystemSet::new()
+                    .label(MarchingCubesSystem::DensityGeneration)
+                    .with_run_criteria(meshing_running)
+                    .with_system(update_noise_values),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .label(MarchingCubesSystem::Meshing)
+                    .after(MarchingCubesSystem::DensityGeneration)
+                    .with_run_criteria(meshing_running)
+                    .with_system(mark_dirty_chunks.before(update_chunks))
+                    .with_system(transition::start_transitions.before(update_chunks))
+                    .with_system(
+                        transition::advance_transitions
+                            .after(transition::start_transitions)
+                            .before(update_chunks),
+                    )
+                    .with_system(update_chunks),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .label(MarchingCubesSystem::MeshApply)
+                    .after(MarchingCubesSystem::Meshing)
+                    .with_system(update_chunks_meshes),
+            )
+            .add_system(validation::validate_meshes.after(MarchingCubesSystem::Meshing))
+            .add_system(merge::update_merged_world.after(MarchingCubesSystem::Meshing))
+            .add_system(lod::update_lod_impostors.after(merge::update_merged_world))
+            .add_system(collider::rebuild_colliders.after(MarchingCubesSystem::Meshing))
+            .add_system(camera::fly_camera)
+            .add_system(measure::update_scale_reference)
+            .add_system(measure::measure)
+            .add_system(measure::measure_ui)
+            .add_system(minimap::minimap)
+            .add_system(stats::chunk_stats_ui)
+            .add_system(stats::memory_diagnostics)
+            .add_system(clipboard::clipboard_ui.before(MarchingCubesSystem::Meshing))
+            .add_system(snapshot::snapshot_field.before(MarchingCubesSystem::Meshing))
+            .add_system(flatten::pick_flatten_pad)
+            .add_system(
+                flatten::flatten_pads
+                    .after(flatten::pick_flatten_pad)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(ramp::pick_ramp_points)
+            .add_system(
+                ramp::build_ramps
+                    .after(ramp::pick_ramp_points)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(placement::update_placement)
+            .add_system(placement::settle_placed_objects.after(MarchingCubesSystem::Meshing))
+            .add_system(projectile::fire_projectile)
+            .add_system(projectile::move_projectiles.after(projectile::fire_projectile))
+            .add_system(
+                projectile::carve_craters
+                    .after(projectile::move_projectiles)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(projectile::update_debris)
+            .add_system(brush::toggle_brush)
+            .add_system(brush::update_brush_target.after(brush::toggle_brush))
+            .add_system(
+                brush::apply_brush
+                    .after(brush::update_brush_target)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(point_editor::point_editor_ui.before(MarchingCubesSystem::Meshing))
+            .add_system(chunk_transform::chunk_transform_ui)
+            .add_system(
+                chunk_transform::move_chunks
+                    .after(chunk_transform::chunk_transform_ui)
+                    .before(MarchingCubesSystem::DensityGeneration),
+            )
+            .add_system(chunk_transform::update_transform_gizmo.after(chunk_transform::move_chunks))
+            .add_system(cell_inspector::update_hovered_cell.after(gpu_picking::apply_gpu_pick))
+            .add_system(
+                cell_inspector::cell_inspector_ui.after(cell_inspector::update_hovered_cell),
+            )
+            .add_system(capabilities::capabilities_notice_ui)
+            .add_system(event_log::record_activity)
+            .add_system(event_log::event_log_ui.after(event_log::record_activity))
+            .add_system(ray_debug::draw_click_rays)
+            .add_system(ray_debug::expire_ray_gizmos)
+            .add_system(presets::cell_presets_ui)
+            .add_system(
+                presets::update_preset_cell
+                    .after(presets::cell_presets_ui)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(toggle_meshing)
+            .add_system(
+                resume_meshing
+                    .after(toggle_meshing)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(materials::set_chunk_materials)
+            .add_system(materials::update_terrain_material)
+            .add_system(materials::apply_render_mode)
+            .add_system(materials::sync_mesh_part_materials.after(materials::apply_render_mode))
+            .add_system(set_chunk_isolevel.before(MarchingCubesSystem::Meshing))
+            .add_system(remesh_regions.before(MarchingCubesSystem::Meshing))
+            .add_system(
+                start_march
+                    .with_run_criteria(meshing_running)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(
+                update_data
+                    .with_run_criteria(meshing_running)
+                    .after(terrain_root::sync_main_terrain)
+                    .before(MarchingCubesSystem::Meshing),
+            )
+            .add_system(
+                density_texture::sync_density_textures
+                    .after(MarchingCubesSystem::DensityGeneration),
+            )
+            .add_system(select_event)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                terrain_root::hide_terrain.after(VisibilitySystems::CheckVisibility),
+            )
+            .add_system(
+                frame_guard::guard_frame_time
+                    .before(update_points_color)
+                    .before(toggle_wireframe),
+            )
+            .add_system(update_points_color.after(select_event))
+            .add_system(debug_points::point_colors_legend)
+            .add_system(toggle_wireframe)
+            .init_resource::<Clipboard>()
+            .init_resource::<ChunkTransformTool>()
+            .init_resource::<FieldSnapshot>()
+            .init_resource::<SelectedCellPreset>()
+            .init_resource::<BrushTarget>()
+            .init_resource::<PlacementAssets>()
+            .init_resource::<Measurement>()
+            .init_resource::<HoveredCell>();
+
+        #[cfg(feature = "world_inspector")]
+        app.add_plugin(chunk_inspector::ChunkInspectorPlugin);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            app.init_resource::<SaveSettings>()
+                .init_resource::<SaveMigrations>()
+                .add_plugin(InspectorPlugin::<AutosaveSettings>::new())
+                .add_plugin(InspectorPlugin::<HeightmapExport>::new())
+                .add_system(heightmap::export_heightmap)
+                .add_plugin(InspectorPlugin::<MeshExport>::new())
+                .add_system(mesh_export::export_meshes)
+                .add_plugin(InspectorPlugin::<ModelImport>::new())
+                .init_resource::<ImportedModel>()
+                .add_system(voxelize::import_model.before(MarchingCubesSystem::Meshing))
+                .add_plugin(InspectorPlugin::<LogLevels>::new())
+                .add_system(log_levels::save_log_levels)
+                .add_system(save::save_world)
+                .add_event::<LoadRegion>()
+                .add_system(save::load_world)
+                .add_system(save::load_regions)
+                .add_system(save::autosave);
+        }
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    world_settings: Res<WorldSettings>,
+) {
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_translation(world_settings.chunk_extent() * 1.5)
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert_bundle(PickingCameraBundle::default())
+        .insert(TrackedRotator)
+        .insert(camera::FlyCam);
+
+    let plane_size = world_settings.chunk_extent().x * CHUNK_COUNT as f32;
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size: plane_size })),
+        material: materials.add(Color::GREEN.into()),
+        transform: Transform::from_xyz(plane_size / 2.0, 0.0, plane_size / 2.0),
+        ..default()
+    });
+}
+
+/// Spawns the chunks of the new terrains as children of their root
+fn spawn_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material_library: Res<MaterialLibrary>,
+    mut roots: Query<(Entity, &Data, &WorldSettings, &mut ChunkMap), Added<TerrainRoot>>,
+) {
+    for (root, data, world_settings, mut chunk_map) in roots.iter_mut() {
+        let mut chunks = Vec::new();
+        let count = world_settings.chunk_count.as_ivec3();
+        let min = IVec3::new(-count.x / 2, 0, -count.z / 2);
+        for offset in Iter3d::new(UVec3::ZERO, world_settings.chunk_count - UVec3::ONE) {
+            let coord = min + offset.as_ivec3();
+            let pos = coord.as_vec3() * world_settings.chunk_extent();
+            debug!("Spawning chunk at {pos:?}");
+            let size = world_settings.chunk_size;
+            let points = vec![0.0; Chunk::points_len(size)];
+            let chunk_mesh = ChunkMesh::default();
+            let entity = commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(Mesh::from(chunk_mesh.clone())),
+                    material: material_library.get(TERRAIN).unwrap().clone(),
+                    transform: Transform::from_translation(pos),
+
+                    ..default()
+                })
+                .insert(Chunk::new(points, size))
+                .insert(Chunk::new_iter_3d(size - UVec3::ONE))
+                .insert(chunk_mesh)
+                .insert_bundle(PickableBundle::default())
+                .insert(ChunkCoord(coord))
+                .insert(ChunkVersion::default())
+                .insert(ChunkStatus::default())
+                .insert(MeshedFrom::default())
+                .insert(HermiteData::default())
+                .insert(ActiveCells::default())
+                .insert(ChunkTransition::default())
+                .insert(ChunkMaterial::default())
+                .id();
+            if data.show_wireframe {
+                commands.entity(entity).insert(Wireframe);
+            }
+            chunk_map.insert(coord, entity);
+            chunks.push(entity);
+        }
+        commands.entity(root).push_children(&chunks);
+    }
+}
+
+pub fn unlit_material(color: Color) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..default()
+    }
+}
+
+fn spawn_debug_points(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    world_settings: Res<WorldSettings>,
+    root: Query<Entity, (With<TerrainRoot>, Without<TerrainInstance>)>,
+) {
+    let icosphere = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 0.05,
+        ..default()
+    }));
+
+    let black = materials.add(unlit_material(Color::BLACK));
+
+    let points: Vec<Entity> = Chunk::new_iter_3d(world_settings.chunk_size)
+        .map(|point| {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: icosphere.clone(),
+                    material: black.clone(),
+                    transform: Transform::from_translation(point.as_vec3()),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(DebugPoint)
+                .id()
+        })
+        .collect();
+    commands.entity(root.single()).push_children(&points);
+}
+
+fn select_event(
+    mut events: EventReader<PickingEvent>,
+    transforms: Query<&Transform>,
+    chunks: Query<&Parent, With<Chunk>>,
+    mut roots: Query<&mut SelectedChunk, With<TerrainRoot>>,
+    mut select_chunk_event: EventWriter<SelectChunk>,
+) {
+    for event in events.iter() {
+        if let PickingEvent::Clicked(entity) = event {
+            // selected in its own terrain
+            let selected = chunks
+                .get(*entity)
+                .ok()
+                .and_then(|parent| roots.get_mut(parent.0).ok());
+            if let Some(mut selected) = selected {
+                selected.0 = Some(*entity);
+            }
+            select_chunk_event.send(SelectChunk);
+            if let Ok(transform) = transforms.get(*entity) {
+                info!(
+                    "selected chunk {:?} at {:?}",
+                    *entity, transform.translation
+                )
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_points_color(
+    chunks: Query<(&Chunk, &Transform, Option<&ChunkIsolevel>), Without<DebugPoint>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut q: Query<
+        (
+            &mut Transform,
+            &mut Handle<StandardMaterial>,
+            &mut Visibility,
+        ),
+        With<DebugPoint>,
+    >,
+    data: Res<Data>,
+    point_colors: Res<PointColors>,
+    noise_settings: Res<NoiseSettings>,
+    world_settings: Res<WorldSettings>,
+    frame_guard: Res<FrameTimeGuard>,
+    terrain: MainTerrain,
+    mut start_event: EventReader<SelectChunk>,
+) {
+    let chunk_entity = match terrain.selected_chunk() {
+        Some(e) => e,
+        _ => return,
+    };
+
+    if !(start_event.iter().count() > 0
+        || data.is_changed()
+        || point_colors.is_changed()
+        || noise_settings.is_changed()
+        || world_settings.is_changed()
+        || frame_guard.is_changed())
+    {
+        return;
+    }
+
+    debug!("updating points");
+    if let Ok((chunk, chunk_transform, chunk_isolevel)) = chunks.get(chunk_entity) {
+        let isolevel = chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0);
+        let mut iter_3d = Chunk::new_iter_3d(chunk.size);
+        for (mut transform, mut mat, mut visibility) in q.iter_mut() {
+            if let Some(point) = iter_3d.next() {
+                let on_stride = (point % data.point_stride.max(1)) == UVec3::ZERO;
+                let point = point.as_vec3();
+                let val = chunk.get(point);
+                transform.translation =
+                    point * world_settings.cell_size + chunk_transform.translation;
+                transform.scale = point_colors.scale(val, isolevel) * world_settings.cell_size;
+                visibility.is_visible = !frame_guard.degraded
+                    && on_stride
+                    && data.point_filter.is_visible(val, isolevel, &data);
+                if visibility.is_visible {
+                    let color = point_colors.color(val, isolevel);
+                    *mat = materials.add(unlit_material(color));
+                }
+            }
+        }
+    }
+}
+
+/// Generator of the points of a terrain, shared by the workers
+struct TerrainGenerator<'a> {
+    noise: CellularRocks<NoiseStack>,
+    caves: Option<CaveNetwork>,
+    wrap: WrapPeriod,
+    noise_settings: &'a NoiseSettings,
+    world_settings: &'a WorldSettings,
+    isolevel: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_noise_values(
+    mut commands: Commands,
+    mut chunks: Query<(
+        Entity,
+        &mut Chunk,
+        &ChunkCoord,
+        &mut Transform,
+        &Parent,
+        Option<&GenerationPipeline>,
+        Option<&mut HermiteData>,
+    )>,
+    changed_pipelines: Query<Entity, Changed<GenerationPipeline>>,
+    removed_pipelines: RemovedComponents<GenerationPipeline>,
+    regenerate: Query<Entity, With<RegenerateChunk>>,
+    new_chunks: Query<Entity, Added<Chunk>>,
+    terrains: Query<
+        (
+            Entity,
+            &Data,
+            &NoiseSettings,
+            &WorldSettings,
+            &ChunkMap,
+            ChangeTrackers<NoiseSettings>,
+            ChangeTrackers<WorldSettings>,
+        ),
+        With<TerrainRoot>,
+    >,
+    cellular_settings: Res<CellularSettings>,
+    cave_settings: Res<CaveSettings>,
+    workers: Res<GenerationWorkers>,
+    pool: Res<ComputeTaskPool>,
+    mut activity: EventWriter<Activity>,
+    mut scratch: Local<Vec<Vec<f32>>>,
+) {
+    let modifiers_changed = cellular_settings.is_changed() || cave_settings.is_changed();
+    // every chunk of a terrain is generated again when its settings change
+    let changed_terrains: HashSet<Entity> = terrains
+        .iter()
+        .filter(|(.., noise_tracker, world_tracker)| {
+            modifiers_changed || noise_tracker.is_changed() || world_tracker.is_changed()
+        })
+        .map(|(root, ..)| root)
+        .collect();
+    // new chunks, chunks whose pipeline changed or that were moved are
+    // generated again on their own
+    let requested: HashSet<Entity> = changed_pipelines
+        .iter()
+        .chain(removed_pipelines.iter())
+        .chain(regenerate.iter())
+        .chain(new_chunks.iter())
+        .collect();
+    for entity in regenerate.iter() {
+        commands.entity(entity).remove::<RegenerateChunk>();
+    }
+    if changed_terrains.is_empty() && requested.is_empty() {
+        return;
+    }
+    debug!(target: "bevy_marching_cube::generation", "update noise");
+    let start = Instant::now();
+
+    let mut jobs = Vec::new();
+    for (entity, chunk, coord, mut transform, parent, pipeline, _) in chunks.iter_mut() {
+        if !changed_terrains.contains(&parent.0) && !requested.contains(&entity) {
+            continue;
+        }
+        let world_settings = match terrains.get(parent.0) {
+            Ok((_, _, _, world_settings, ..)) => world_settings,
+            Err(_) => continue,
+        };
+        let origin = coord.0 * chunk.size.as_ivec3();
+        // follow the cell size
+        transform.translation = origin.as_vec3() * world_settings.cell_size;
+        jobs.push((
+            entity,
+            parent.0,
+            coord.0,
+            origin,
+            chunk.size,
+            pipeline.cloned(),
+        ));
+    }
+
+    let generators: HashMap<Entity, TerrainGenerator> = jobs
+        .iter()
+        .map(|(_, root, ..)| *root)
+        .collect::<HashSet<Entity>>()
+        .into_iter()
+        .filter_map(|root| {
+            let (_, data, noise_settings, world_settings, chunk_map, ..) =
+                terrains.get(root).ok()?;
+            let chunk_size = world_settings.chunk_size.as_ivec3();
+            let min = chunk_map.min() * chunk_size;
+            let dimensions = chunk_map.dimensions() * chunk_size;
+            let caves = cave_settings.enabled.then(|| {
+                let cell_size = world_settings.cell_size;
+                CaveNetwork::generate(
+                    &cave_settings,
+                    min.as_vec3() * cell_size,
+                    (min + dimensions).as_vec3() * cell_size,
+                )
+            });
+            let generator = TerrainGenerator {
+                noise: cellular_settings.compose(noise_settings.stack()),
+                caves,
+                wrap: WrapPeriod {
+                    origin: IVec2::new(min.x, min.z),
+                    size: IVec2::new(dimensions.x, dimensions.z),
+                },
+                noise_settings,
+                world_settings,
+                isolevel: data.isolevel,
+            };
+            Some((root, generator))
+        })
+        .collect();
+
+    // one scratch buffer per worker, reused for every batch
+    scratch.resize_with(workers.max_in_flight.max(1), Vec::new);
+    let generators = &generators;
+    for batch in jobs.chunks(scratch.len()) {
+        let hermite = pool.scope(|scope| {
+            for (buffer, (_, root, coord, origin, size, pipeline)) in scratch.iter_mut().zip(batch)
+            {
+                let (coord, origin, size) = (*coord, *origin, *size);
+                let generator = &generators[root];
+                scope.spawn(async move {
+                    let _span = info_span!("noise_fill", chunk = ?coord).entered();
+                    let TerrainGenerator {
+                        noise,
+                        caves,
+                        wrap,
+                        noise_settings,
+                        world_settings,
+                        isolevel,
+                    } = generator;
+                    let world = |buffer: &mut Vec<f32>| {
+                        fill_points(
+                            buffer,
+                            noise,
+                            origin,
+                            size,
+                            noise_settings,
+                            world_settings,
+                            Some(*wrap),
+                        );
+                        if let Some(caves) = caves {
+                            caves.carve_points(buffer, origin, size, world_settings.cell_size);
+                        }
+                    };
+                    match pipeline {
+                        Some(pipeline) => {
+                            pipeline.fill(buffer, origin, size, world_settings, Some(*wrap), world);
+                            // the stages can't be sampled between the points
+                            None
+                        }
+                        None => {
+                            world(buffer);
+                            world_settings.hermite_data.then(|| {
+                                let density = |local: Vec3| {
+                                    let pos = origin.as_vec3() + local;
+                                    let value = sample_density_at(
+                                        noise,
+                                        pos.as_dvec3(),
+                                        noise_settings,
+                                        world_settings,
+                                        Some(*wrap),
+                                    );
+                                    caves.as_ref().map_or(value, |caves| {
+                                        caves.carve(pos * world_settings.cell_size, value)
+                                    })
+                                };
+                                HermiteData::compute(buffer, size, *isolevel, density)
+                            })
+                        }
+                    }
+                });
+            }
+        });
+        for ((buffer, (entity, ..)), hermite) in scratch.iter().zip(batch).zip(hermite) {
+            if let Ok((_, mut chunk, .., stored)) = chunks.get_mut(*entity) {
+                chunk.points.copy_from_slice(buffer);
+                let size = chunk.size;
+                chunk.mark_dirty(UVec3::ZERO, size);
+                if let Some(mut stored) = stored {
+                    *stored = hermite
+                        .map(|mut hermite| {
+                            hermite.revision = chunk.revision();
+                            hermite
+                        })
+                        .unwrap_or_default();
+                }
+            }
+        }
+    }
+    activity.send(Activity::ChunksGenerated {
+        chunks: jobs.len(),
+        duration: start.elapsed(),
+    });
+}
+
+fn start_march(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    if keyboard_input.just_pressed(KeyCode::R) {
+        start_marching_events.send_default();
+    }
+}
+
+fn set_chunk_isolevel(
+    mut commands: Commands,
+    mut events: EventReader<SetChunkIsolevel>,
+    mut start_marching_events: EventWriter<StartMarching>,
+    removed: RemovedComponents<ChunkIsolevel>,
+    changed: Query<(), Changed<ChunkIsolevel>>,
+) {
+    for event in events.iter() {
+        match event.isolevel {
+            Some(isolevel) => commands.entity(event.chunk).insert(ChunkIsolevel(isolevel)),
+            None => commands.entity(event.chunk).remove::<ChunkIsolevel>(),
+        };
+    }
+    // also catch the overrides inserted or removed directly
+    if !changed.is_empty() || removed.iter().next().is_some() {
+        start_marching_events.send_default();
+    }
+}
+
+fn remesh_regions(
+    mut events: EventReader<RemeshRegion>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut meshed_from: Query<&mut MeshedFrom>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let mut remesh = false;
+    for event in events.iter() {
+        let chunks = chunk_map.in_world_box(event.min, event.max, world_settings.chunk_extent());
+        for (_, entity) in chunks {
+            if let Ok(mut meshed_from) = meshed_from.get_mut(entity) {
+                // can't match the points anymore so the chunk is marched
+                *meshed_from = MeshedFrom::default();
+                remesh = true;
+            }
+        }
+    }
+    if remesh {
+        start_marching_events.send_default();
+    }
+}
+
+fn update_data(
+    changed_terrains: Query<
+        (),
+        (
+            With<TerrainRoot>,
+            Or<(
+                Changed<Data>,
+                Changed<NoiseSettings>,
+                Changed<WorldSettings>,
+            )>,
+        ),
+    >,
+    new_chunks: Query<(), Added<Chunk>>,
+    cellular_settings: Res<CellularSettings>,
+    cave_settings: Res<CaveSettings>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    if !changed_terrains.is_empty()
+        || !new_chunks.is_empty()
+        || cellular_settings.is_changed()
+        || cave_settings.is_changed()
+    {
+        start_marching_events.send_default();
+    }
+}
+
+fn mark_dirty_chunks(mut chunks: Query<&mut ChunkStatus, Changed<Chunk>>) {
+    for mut status in chunks.iter_mut() {
+        *status = ChunkStatus::Dirty;
+    }
+}
+
+fn update_chunks(
+    mut chunks: Query<
+        (
+            &Chunk,
+            Option<&ChunkCoord>,
+            &mut Iter3d,
+            &mut ChunkMesh,
+            &mut ChunkStatus,
+            &mut MeshedFrom,
+            &mut ActiveCells,
+            Option<&ChunkIsolevel>,
+            &mut ChunkTransition,
+            Option<&HermiteData>,
+            Option<&Parent>,
+        ),
+        Without<GpuMeshed>,
+    >,
+    terrains: Query<(&Data, &WorldSettings), With<TerrainRoot>>,
+    main: MainTerrain,
+    mut start_event: EventReader<StartMarching>,
+    edit_transition: Res<EditTransition>,
+    pool: Res<ComputeTaskPool>,
+    mut activity: EventWriter<Activity>,
+) {
+    if start_event.iter().count() == 0 {
+        return;
+    }
+    let start = Instant::now();
+    let skipped = AtomicUsize::new(0);
+    let main_root = main.root();
+
+    chunks.par_for_each_mut(
+        &pool,
+        1,
+        |(
+            chunk,
+            coord,
+            mut chunk_iter,
+            mut chunk_mesh,
+            mut status,
+            mut last_meshed_from,
+            mut active_cells,
+            chunk_isolevel,
+            mut transition,
+            hermite_data,
+            parent,
+        )| {
+            // meshed with the settings of its terrain, or of the main terrain
+            // for the chunks outside of a terrain like the preset cell
+            let root = parent.map_or(main_root, |parent| Some(parent.0));
+            let (data, world_settings) = match root.and_then(|root| terrains.get(root).ok()) {
+                Some(terrain) => terrain,
+                None => return,
+            };
+            let isolevel = chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0);
+            let cell_size = world_settings.cell_size;
+            let blended = transition.blended(chunk, edit_transition.duration);
+            let hermite = hermite_data.and_then(|hermite| hermite.current(chunk, isolevel));
+            let jitter = if data.jitter {
+                data.jitter_amount * cell_size
+            } else {
+                0.0
+            };
+            let meshed_from = MeshedFrom {
+                points_hash: chunk.content_hash(),
+                isolevel,
+                interpolation: data.interpolation,
+                cell_size,
+                jitter,
+                sharp_features: data.sharp_features,
+                drivable: data.drivable,
+                hermite_revision: hermite.and(hermite_data).map(|hermite| hermite.revision),
+            };
+            if blended.is_none() && *last_meshed_from == meshed_from {
+                // the mesh is already up to date, don't trigger a mesh upload
+                if *status == ChunkStatus::Dirty {
+                    *status = if chunk_mesh.triangles.is_empty() {
+                        ChunkStatus::Empty
+                    } else {
+                        ChunkStatus::Loaded
+                    };
+                }
+                return;
+            }
+            let isolevel_only = blended.is_none()
+                && data.incremental_isolevel
+                && !active_cells.0.is_empty()
+                && *last_meshed_from
+                    == MeshedFrom {
+                        isolevel: last_meshed_from.isolevel,
+                        ..meshed_from
+                    };
+            // chunks entirely solid or empty, like the ones deep underground,
+            // have no surface whatever their neighbors are since a cell only
+            // samples the points of its chunk. An edit changes the hash of
+            // the points so they are checked again.
+            let uniform = !blended.as_ref().unwrap_or(chunk).crosses(isolevel);
+            let flooded = if isolevel_only && !uniform {
+                let _span = info_span!("classification").entered();
+                Some(flood_active_cells(chunk, &active_cells.0, isolevel))
+            } else {
+                None
+            };
+            // a transitioning chunk doesn't match its points yet, the march
+            // at the end of the transition must not be skipped
+            *last_meshed_from = if blended.is_some() {
+                MeshedFrom::default()
+            } else {
+                meshed_from
+            };
+            let chunk = blended.as_ref().unwrap_or(chunk);
+
+            // a full march classifies every cell while triangulating
+            let _span = info_span!("triangulation", full = flooded.is_none()).entered();
+            chunk_mesh.triangles.clear();
+            active_cells.0.clear();
+
+            // the preset cell is outside of the chunk grid
+            let first_point = coord.map_or(IVec3::ZERO, |coord| coord.0) * chunk.size.as_ivec3();
+            let mut march_cell = |pos: UVec3| {
+                let mut grid_cell = GridCell::sample(pos.as_vec3(), cell_size, chunk);
+                grid_cell.jitter(first_point + pos.as_ivec3(), jitter);
+                if let Some(triangles) = march_cube(&grid_cell, isolevel, data.interpolation) {
+                    chunk_mesh.triangles.extend(triangles);
+                    active_cells.0.push(pos);
+                }
+            };
+            match flooded {
+                _ if uniform => {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                }
+                // without active cells the next isolevel change marches everything
+                _ if data.sharp_features => {
+                    chunk_mesh.triangles = dual_contour(chunk, isolevel, cell_size, hermite);
+                }
+                Some(cells) => cells.into_iter().for_each(&mut march_cell),
+                None => {
+                    chunk_iter.reset();
+                    chunk_iter.by_ref().for_each(&mut march_cell);
+                }
+            }
+            if data.drivable.enabled && !chunk_mesh.triangles.is_empty() {
+                let _span = info_span!("drivable_smoothing").entered();
+                let extent = chunk.size.as_vec3() * cell_size;
+                data.drivable
+                    .smooth(&mut chunk_mesh.triangles, extent, cell_size);
+            }
+            chunk_iter.reset();
+            *status = ChunkStatus::Meshing;
+
+            if edit_transition.enabled {
+                transition.meshed.clone_from(&chunk.points);
+            } else if !transition.meshed.is_empty() {
+                transition.meshed = Vec::new();
+            }
+        },
+    );
+
+    activity.send(Activity::ChunksMarched {
+        skipped: skipped.into_inner(),
+        duration: start.elapsed(),
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_chunks_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    terrains: Query<(Entity, &Data), With<TerrainRoot>>,
+    main: MainTerrain,
+    field: DensityField,
+    ore: Res<OreSettings>,
+    ore_layer: Res<OreLayer>,
+    mut activity: EventWriter<Activity>,
+    mut last_options: Local<HashMap<Entity, (NormalMode, bool, f32, bool, usize, usize)>>,
+    mut chunks: Query<(
+        Entity,
+        ChangeTrackers<ChunkMesh>,
+        &ChunkMesh,
+        &Chunk,
+        &Transform,
+        &Handle<Mesh>,
+        Option<&mut Aabb>,
+        &mut ChunkStatus,
+        Option<&NonIndexed>,
+        Option<&ChunkMeshParts>,
+        Option<&ChunkIsolevel>,
+        Option<&HermiteData>,
+        Option<&Parent>,
+    )>,
+) {
+    // every mesh of a terrain is rebuilt when its normal mode or its mesh
+    // layout changes
+    let mut options_changed = HashSet::default();
+    for (root, data) in terrains.iter() {
+        let options = (
+            data.normals,
+            data.smooth_seams,
+            data.skirt_depth,
+            data.optimize_index_order,
+            data.non_indexed_max_triangles,
+            data.max_mesh_vertices,
+        );
+        if last_options.insert(root, options) != Some(options) || ore.is_changed() {
+            options_changed.insert(root);
+        }
+    }
+
+    // the chunks outside of a terrain, like the preset cell, use the main one
+    let main_root = main.root();
+    // TODO create meshes in parallel then update the handles and aabb
+    for (
+        entity,
+        mesh_tracker,
+        chunk_mesh,
+        chunk,
+        transform,
+        mesh_handle,
+        chunk_aabb,
+        mut status,
+        non_indexed,
+        mesh_parts,
+        chunk_isolevel,
+        hermite,
+        parent,
+    ) in chunks.iter_mut()
+    {
+        let root = match parent.map_or(main_root, |parent| Some(parent.0)) {
+            Some(root) => root,
+            None => continue,
+        };
+        if !(mesh_tracker.is_changed() || options_changed.contains(&root)) {
+            continue;
+        }
+        let (data, field) = match (terrains.get(root), field.terrain(root)) {
+            (Ok((_, data)), Some(field)) => (data, field),
+            _ => continue,
+        };
+        *status = if chunk_mesh.triangles.is_empty() {
+            ChunkStatus::Empty
+        } else {
+            ChunkStatus::Loaded
+        };
+        let _span = info_span!("mesh_upload", triangles = chunk_mesh.triangles.len()).entered();
+        activity.send(Activity::ChunkRemeshed {
+            chunk: entity,
+            triangles: chunk_mesh.triangles.len(),
+        });
+        let origin = transform.translation;
+        let extent = chunk.size.as_vec3() * field.cell_size();
+        // the normals of the vertices on the crossings of the Hermite data
+        // come from the source instead of the points
+        let isolevel = chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0);
+        let hermite = hermite.and_then(|hermite| hermite.current(chunk, isolevel));
+        let normal = |pos: Vec3| {
+            hermite
+                .and_then(|hermite| hermite.normal_at(pos / field.cell_size()))
+                .or_else(|| field.normal(origin + pos))
+        };
+        let non_indexed = data.normals != NormalMode::Smooth
+            && (non_indexed.is_some()
+                || chunk_mesh.triangles.len() <= data.non_indexed_max_triangles);
+        let mut mesh = if non_indexed {
+            let gradient = data.normals == NormalMode::Gradient;
+            chunk_mesh.to_non_indexed_mesh(|pos| if gradient { normal(pos) } else { None })
+        } else {
+            let mut indexed = chunk_mesh.indexed(data.normals, |pos| match data.normals {
+                NormalMode::Smooth if !(data.smooth_seams && on_chunk_face(pos, extent)) => None,
+                _ => normal(pos),
+            });
+            if data.optimize_index_order {
+                indexed.optimize_vertex_cache();
+            }
+            Mesh::from(indexed)
+        };
+        if data.skirt_depth > 0.0 {
+            let skirts = skirt_triangles(chunk_mesh, extent, data.skirt_depth);
+            append_skirts(&mut mesh, &skirts);
+        }
+        if ore.show_veins {
+            ore_layer.write_concentrations(&mut mesh, origin);
+        }
+        let mut parts = if data.max_mesh_vertices > 0 {
+            split_mesh(mesh, data.max_mesh_vertices)
+        } else {
+            vec![mesh]
+        };
+        let mesh = parts.remove(0);
+        if !parts.is_empty() || mesh_parts.is_some() {
+            spawn_mesh_parts(&mut commands, &mut meshes, entity, mesh_parts, parts);
+        }
+        if let Some(mut chunk_aabb) = chunk_aabb {
+            if let Some(aabb) = mesh.compute_aabb() {
+                *chunk_aabb = aabb;
+            }
+        }
+        meshes.set_untracked(mesh_handle, mesh);
+    }
+}
+
+/// Whether a vertex at `pos` in a chunk of `extent` world units lies on one
+/// of its faces, shared with the neighboring chunk
+fn on_chunk_face(pos: Vec3, extent: Vec3) -> bool {
+    // the vertices on a face are interpolated along edges of that face, so
+    // they are exactly on it
+    pos.cmple(Vec3::ZERO).any() || pos.cmpge(extent).any()
+}
+
+fn toggle_wireframe(
+    mut commands: Commands,
+    changed_terrains: Query<&Data, (With<TerrainRoot>, Changed<Data>)>,
+    wireframes: Query<&Wireframe>,
+    chunks: Query<(Entity, &Parent), With<Chunk>>,
+) {
+    if changed_terrains.is_empty() {
+        return;
+    }
+
+    for (chunk, parent) in chunks.iter() {
+        let data = match changed_terrains.get(parent.0) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        if data.show_wireframe {
+            if wireframes.get(chunk).is_err() {
+                commands.entity(chunk).insert(Wireframe);
+            }
+        } else {
+            if wireframes.get(chunk).is_ok() {
+                commands.entity(chunk).remove::<Wireframe>();
+            }
+        }
+    }
+}
+
+/// March a single cube
+//     4--------5     *-----4------*
+//    /|       /|    /|           /|
+//   / |      / |   7 |          5 |
+//  /  |     /  |  /  8         /  9
+// 7--------6   | *------6-----*   |
+// |   |    |   | |   |        |   |
+// |   0----|---1 |   *-----0--|---*
+// |  /     |  /  11 /         10 /
+// | /      | /   | 3          | 1
+// |/       |/    |/           |/
+// 3--------2     *-----2------*
+fn march_cube(
+    grid: &GridCell,
+    isolevel: f32,
+    interpolation: Interpolation,
+) -> Option<Vec<Triangle>> {
+    let cube_index = cube_index(&grid.value, isolevel);
+    let edge = EDGE_TABLE[cube_index];
+    if edge == 0 {
+        return None;
+    }
+
+    let mut vertices = [Vec3::ZERO; 12];
+    for i in 0..12 {
+        if edge & 1 << i != 0 {
+            let [mut u, mut v] = EDGE_CONNECTION[i];
+            // neighboring cells must compute the exact same vertex for the
+            // edge they share, always interpolate in the direction of the axis
+            if grid.vertex_position[u].cmpgt(grid.vertex_position[v]).any() {
+                std::mem::swap(&mut u, &mut v);
+            }
+            vertices[i] = interpolation.interpolate(
+                isolevel,
+                grid.vertex_position[u],
+                grid.vertex_position[v],
+                grid.value[u],
+                grid.value[v],
+            );
+        }
+    }
+
+    let mut triangles = Vec::new();
+    let triangulation = TRIANGLE_TABLE[cube_index];
+    for i in (0..16).step_by(3) {
+        if triangulation[i] < 0 {
+            break;
+        }
+        triangles.push([
+            vertices[triangulation[i + 2] as usize],
+            vertices[triangulation[i + 1] as usize],
+            vertices[triangulation[i] as usize],
+        ]);
+    }
+    Some(triangles)
+}
+
+/// Whether the surface crosses the cell, the cells `march_cube` generates triangles for
+fn crosses_surface(grid: &GridCell, isolevel: f32) -> bool {
+    let below = grid
+        .value
+        .iter()
+        .filter(|value| !is_inside(**value, isolevel))
+        .count();
+    below != 0 && below != 8
+}
+
+/// Finds the cells of `chunk` crossed by the surface at `isolevel` by flooding
+/// from the cells around `seeds`, the cells crossed at the previous isolevel.
+///
+/// The cells are returned in the order of a full march so the triangles are
+/// the same as marching every cell.
+fn flood_active_cells(chunk: &Chunk, seeds: &[UVec3], isolevel: f32) -> Vec<UVec3> {
+    let max = (chunk.size - UVec3::ONE).as_ivec3();
+    let in_chunk = |cell: IVec3| cell.cmpge(IVec3::ZERO).all() && cell.cmple(max).all();
+
+    let mut visited = HashSet::default();
+    let mut queue = VecDeque::new();
+    // the surface moves by less than a cell for small isolevel changes
+    for seed in seeds {
+        for offset in Iter3d::new(UVec3::ZERO, UVec3::splat(2)) {
+            let cell = seed.as_ivec3() + offset.as_ivec3() - IVec3::ONE;
+            if in_chunk(cell) && visited.insert(cell) {
+                queue.push_back(cell);
+            }
+        }
+    }
+
+    let mut active = Vec::new();
+    while let Some(cell) = queue.pop_front() {
+        if !crosses_surface(&GridCell::sample(cell.as_vec3(), 1.0, chunk), isolevel) {
+            continue;
+        }
+        active.push(cell.as_uvec3());
+        // the surface continues through the faces of the cell
+        for offset in [IVec3::X, IVec3::Y, IVec3::Z] {
+            for neighbor in [cell - offset, cell + offset] {
+                if in_chunk(neighbor) && visited.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+    active.sort_by_key(|cell| (cell.z, cell.y, cell.x));
+    active
+}
+
+type Triangle = [Vec3; 3];
+
+/// Offsets of the corners of a cell in grid points, in the order used by [`march_cube`]
+const CELL_CORNERS: [Vec3; 8] = [
+    Vec3::new(0.0, 0.0, 0.0),
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(1.0, 0.0, 1.0),
+    Vec3::new(0.0, 0.0, 1.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(1.0, 1.0, 1.0),
+    Vec3::new(0.0, 1.0, 1.0),
+];
+
+#[derive(Clone, Copy)]
+struct GridCell {
+    /// Positions of the corners in the local space of the chunk, in world units
+    vertex_position: [Vec3; 8],
+    value: [f32; 8],
+}
+
+impl GridCell {
+    /// Cell at the grid point `pos`, the positions of its corners are scaled by `cell_size`
+    fn new(pos: Vec3, cell_size: f32) -> Self {
+        GridCell {
+            vertex_position: CELL_CORNERS.map(|corner| (pos + corner) * cell_size),
+            value: [0.0; 8],
+        }
+    }
+
+    /// Samples the values of the corners from the points of `chunk`
+    fn sample(pos: Vec3, cell_size: f32, chunk: &Chunk) -> Self {
+        let mut grid_cell = GridCell::new(pos, cell_size);
+        for (value, corner) in grid_cell.value.iter_mut().zip(CELL_CORNERS) {
+            *value = chunk.get(pos + corner);
+        }
+        grid_cell
+    }
+
+    /// Moves the corners by the offsets of their points, `point` is the first
+    /// corner in world grid coordinates and `amount` the largest offset
+    fn jitter(&mut self, point: IVec3, amount: f32) {
+        if amount <= 0.0 {
+            return;
+        }
+        for (position, corner) in self.vertex_position.iter_mut().zip(CELL_CORNERS) {
+            *position += jitter_offset(point + corner.as_ivec3()) * amount;
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use bevy::{prelude::*, render::mesh::PrimitiveTopology};
+
+/// Builds a mesh drawing one line per segment
+pub fn line_mesh(segments: &[(Vec3, Vec3)]) -> Mesh {
+    let positions: Vec<[f32; 3]> = segments
+        .iter()
+        .flat_map(|(a, b)| [a.to_array(), b.to_array()])
+        .collect();
+    // the mesh pipeline needs normals and uvs even if the material is unlit
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
@@ -0,0 +1,430 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    camera::FlyCam,
+    chunk::{compute_vertex_normals, ChunkCoord, ChunkMesh, IndexedMesh},
+    debug_camera::CullingView,
+    generation::WorldSettings,
+    materials::{MaterialLibrary, TERRAIN},
+    merge::{merge_chunk_meshes, MergedWorld},
+    terrain_root::TerrainRoot,
+};
+
+/// Replaces the chunks of the clusters far from the camera by a single
+/// simplified mesh, the chunks of each terrain are clustered on its own grid.
+///
+/// An impostor is baked when its cluster moves past the distance, and baked
+/// again the next time it's shown after one of its chunks was remeshed.
+/// Impostors are disabled while the world is merged.
+///
+/// A cluster only switches once its distance leaves the `hysteresis` band
+/// around `distance`, so moving the camera along the threshold doesn't swap
+/// the meshes every frame. The impostor fades in over the chunks and out
+/// over them for `cross_fade` seconds.
+#[derive(Inspectable)]
+pub struct LodSettings {
+    pub enabled: bool,
+    /// Chunks per axis in a cluster
+    #[inspectable(min = 1, max = 16)]
+    pub cluster_size: u32,
+    /// Distance from the camera to the center of a cluster beyond which its
+    /// chunks are replaced by the impostor
+    #[inspectable(min = 0.0, speed = 1.0)]
+    pub distance: f32,
+    /// Width of the band around `distance` where the clusters keep their
+    /// current mesh
+    #[inspectable(min = 0.0, speed = 1.0)]
+    pub hysteresis: f32,
+    /// Seconds the impostors take to fade in and out, 0 to swap at once
+    #[inspectable(min = 0.0, max = 5.0, speed = 0.05)]
+    pub cross_fade: f32,
+    /// Size in cells of the grid the vertices of the impostors are merged on
+    #[inspectable(min = 1.0, max = 32.0)]
+    pub simplification: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cluster_size: 4,
+            distance: 150.0,
+            hysteresis: 16.0,
+            cross_fade: 0.5,
+            simplification: 4.0,
+        }
+    }
+}
+
+/// Simplified mesh of the chunks of a cluster
+#[derive(Component)]
+pub struct LodImpostor {
+    pub cluster: IVec3,
+}
+
+/// Root of a terrain and a cluster of its chunk grid
+type ClusterKey = (Entity, IVec3);
+
+#[derive(Default)]
+pub struct LodState {
+    impostors: HashMap<ClusterKey, Entity>,
+    /// Clusters with a chunk remeshed since their impostor was baked
+    stale: HashSet<ClusterKey>,
+    /// Clusters showing their impostor
+    far: HashSet<ClusterKey>,
+    fades: HashMap<ClusterKey, LodFade>,
+}
+
+/// Impostor blended over the chunks of its cluster while it switches
+struct LodFade {
+    /// Fading in when the cluster moved away
+    to_far: bool,
+    elapsed: f32,
+    material: Handle<StandardMaterial>,
+}
+
+impl LodFade {
+    fn alpha(&self, duration: f32) -> f32 {
+        let t = (self.elapsed / duration).clamp(0.0, 1.0);
+        if self.to_far {
+            t
+        } else {
+            1.0 - t
+        }
+    }
+}
+
+/// Whether a cluster at `distance` from the camera shows its impostor, it
+/// switches only once out of the band of `hysteresis` around `threshold`
+pub fn is_far(was_far: bool, distance: f32, threshold: f32, hysteresis: f32) -> bool {
+    let half_band = hysteresis.max(0.0) / 2.0;
+    if was_far {
+        distance > threshold - half_band
+    } else {
+        distance > threshold + half_band
+    }
+}
+
+/// Cluster of `cluster_size` chunks per axis containing the chunk at `coord`
+pub fn cluster_of(coord: IVec3, cluster_size: u32) -> IVec3 {
+    let size = cluster_size.max(1) as i32;
+    IVec3::new(
+        coord.x.div_euclid(size),
+        coord.y.div_euclid(size),
+        coord.z.div_euclid(size),
+    )
+}
+
+/// Simplifies a mesh by merging the vertices in each cell of a grid of
+/// `cell` world units at their mean position.
+///
+/// Triangles collapsed by the merge are dropped, as well as the duplicates
+/// left by thin walls.
+pub fn cluster_vertices(mesh: &IndexedMesh, cell: f32) -> IndexedMesh {
+    let mut cluster_ids = HashMap::default();
+    let mut sums: Vec<(Vec3, f32)> = Vec::new();
+    let remap: Vec<u32> = mesh
+        .positions
+        .iter()
+        .map(|position| {
+            let key = (*position / cell).floor().as_ivec3();
+            let id = *cluster_ids.entry(key).or_insert_with(|| {
+                sums.push((Vec3::ZERO, 0.0));
+                sums.len() as u32 - 1
+            });
+            let (sum, count) = &mut sums[id as usize];
+            *sum += *position;
+            *count += 1.0;
+            id
+        })
+        .collect();
+    let positions: Vec<Vec3> = sums.iter().map(|(sum, count)| *sum / *count).collect();
+
+    let mut kept = HashSet::default();
+    let mut indices = Vec::new();
+    for triangle in mesh.indices.chunks_exact(3) {
+        let ids = [0, 1, 2].map(|i| remap[triangle[i] as usize]);
+        if ids[0] == ids[1] || ids[1] == ids[2] || ids[2] == ids[0] {
+            continue;
+        }
+        let mut key = ids;
+        key.sort_unstable();
+        if kept.insert(key) {
+            indices.extend(ids);
+        }
+    }
+    let normals = compute_vertex_normals(&positions, &indices);
+    IndexedMesh {
+        positions,
+        normals,
+        indices,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_lod_impostors(
+    mut commands: Commands,
+    settings: Res<LodSettings>,
+    merged_world: Res<MergedWorld>,
+    terrains: Query<&WorldSettings, With<TerrainRoot>>,
+    mut state: Local<LodState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    material_library: Res<MaterialLibrary>,
+    time: Res<Time>,
+    camera: Query<&GlobalTransform, With<FlyCam>>,
+    culling_view: Res<CullingView>,
+    changed_chunks: Query<(&ChunkCoord, &Parent), Changed<ChunkMesh>>,
+    mut chunks: Query<(
+        Entity,
+        &ChunkCoord,
+        &ChunkMesh,
+        &GlobalTransform,
+        &Transform,
+        &mut Visibility,
+        &Parent,
+    )>,
+    mut impostors: Query<
+        (
+            &mut Visibility,
+            &Handle<Mesh>,
+            &mut Handle<StandardMaterial>,
+        ),
+        (With<LodImpostor>, Without<ChunkCoord>),
+    >,
+) {
+    let active = settings.enabled && !merged_world.enabled;
+    if settings.is_changed() || !active {
+        // the clusters or the simplification may have changed, bake everything again
+        let had_impostors = !state.impostors.is_empty();
+        for (_, entity) in state.impostors.drain() {
+            commands.entity(entity).despawn_recursive();
+        }
+        state.stale.clear();
+        state.far.clear();
+        state.fades.clear();
+        if had_impostors && !merged_world.enabled {
+            for (_, _, _, _, _, mut visibility, _) in chunks.iter_mut() {
+                visibility.is_visible = true;
+            }
+        }
+    }
+    if !active {
+        return;
+    }
+
+    for (coord, parent) in changed_chunks.iter() {
+        state
+            .stale
+            .insert((parent.0, cluster_of(coord.0, settings.cluster_size)));
+    }
+    let camera_position = match camera.get_single() {
+        Ok(transform) => culling_view.position(transform.translation),
+        Err(_) => return,
+    };
+
+    let mut members: HashMap<ClusterKey, Vec<Entity>> = HashMap::default();
+    let mut centers: HashMap<ClusterKey, Vec3> = HashMap::default();
+    for (entity, coord, _, transform, _, _, parent) in chunks.iter() {
+        let world_settings = match terrains.get(parent.0) {
+            Ok(world_settings) => world_settings,
+            Err(_) => continue,
+        };
+        let key = (parent.0, cluster_of(coord.0, settings.cluster_size));
+        members.entry(key).or_default().push(entity);
+        // the terrains can be moved, the distance is measured in world space
+        *centers.entry(key).or_default() += transform.mul_vec3(world_settings.chunk_extent() / 2.0);
+    }
+    let far: HashSet<ClusterKey> = members
+        .iter()
+        .filter(|(cluster, entities)| {
+            let center = centers[*cluster] / entities.len() as f32;
+            is_far(
+                state.far.contains(*cluster),
+                center.distance(camera_position),
+                settings.distance,
+                settings.hysteresis,
+            )
+        })
+        .map(|(cluster, _)| *cluster)
+        .collect();
+
+    let terrain_material = material_library.get(TERRAIN).unwrap().clone();
+    let fade_duration = settings.cross_fade;
+    for fade in state.fades.values_mut() {
+        fade.elapsed += time.delta_seconds();
+    }
+    let switched: Vec<ClusterKey> = members
+        .keys()
+        .filter(|cluster| far.contains(*cluster) != state.far.contains(*cluster))
+        .copied()
+        .collect();
+    for cluster in switched {
+        let to_far = far.contains(&cluster);
+        if fade_duration <= 0.0 {
+            state.fades.remove(&cluster);
+            continue;
+        }
+        match state.fades.get_mut(&cluster) {
+            // turned back halfway, continues from the same opacity
+            Some(fade) => {
+                fade.to_far = to_far;
+                fade.elapsed = (fade_duration - fade.elapsed).max(0.0);
+            }
+            None => {
+                let mut material = materials
+                    .get(&terrain_material)
+                    .cloned()
+                    .unwrap_or_default();
+                material.alpha_mode = AlphaMode::Blend;
+                state.fades.insert(
+                    cluster,
+                    LodFade {
+                        to_far,
+                        elapsed: 0.0,
+                        material: materials.add(material),
+                    },
+                );
+            }
+        }
+    }
+    state
+        .fades
+        .retain(|cluster, fade| fade.elapsed < fade_duration && members.contains_key(cluster));
+    for fade in state.fades.values() {
+        if let Some(material) = materials.get_mut(&fade.material) {
+            material.base_color.set_a(fade.alpha(fade_duration));
+        }
+    }
+
+    for cluster in &far {
+        if state.impostors.contains_key(cluster) && !state.stale.contains(cluster) {
+            continue;
+        }
+        let (root, cluster_coord) = *cluster;
+        let world_settings = match terrains.get(root) {
+            Ok(world_settings) => world_settings,
+            Err(_) => continue,
+        };
+        let merged = merge_chunk_meshes(members[cluster].iter().filter_map(|entity| {
+            // baked in the space of the terrain root, the impostor is its child
+            let (_, _, chunk_mesh, _, transform, _, _) = chunks.get(*entity).ok()?;
+            Some((transform.translation, chunk_mesh))
+        }));
+        let cell = world_settings.cell_size * settings.simplification;
+        let mesh = Mesh::from(cluster_vertices(&merged, cell));
+        state.stale.remove(cluster);
+
+        match state.impostors.get(cluster).copied() {
+            Some(entity) => {
+                if let Some(impostor_mesh) = impostors
+                    .get(entity)
+                    .ok()
+                    .and_then(|(_, handle, _)| meshes.get_mut(handle))
+                {
+                    *impostor_mesh = mesh;
+                }
+            }
+            None => {
+                let entity = commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: state
+                            .fades
+                            .get(cluster)
+                            .map_or(terrain_material.clone(), |fade| fade.material.clone()),
+                        ..default()
+                    })
+                    .insert(LodImpostor {
+                        cluster: cluster_coord,
+                    })
+                    .id();
+                commands.entity(root).push_children(&[entity]);
+                state.impostors.insert(*cluster, entity);
+            }
+        }
+    }
+
+    // the chunks stay under the impostors while they fade
+    for (_, coord, _, _, _, mut visibility, parent) in chunks.iter_mut() {
+        let cluster = (parent.0, cluster_of(coord.0, settings.cluster_size));
+        let hidden = far.contains(&cluster) && !state.fades.contains_key(&cluster);
+        if visibility.is_visible == hidden {
+            visibility.is_visible = !hidden;
+        }
+    }
+    for (cluster, entity) in &state.impostors {
+        if let Ok((mut visibility, _, mut material)) = impostors.get_mut(*entity) {
+            let fade = state.fades.get(cluster);
+            let shown = far.contains(cluster) || fade.is_some();
+            if visibility.is_visible != shown {
+                visibility.is_visible = shown;
+            }
+            let handle = fade.map_or(&terrain_material, |fade| &fade.material);
+            if *material != *handle {
+                *material = handle.clone();
+            }
+        }
+    }
+    state.far = far;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clusters_round_down() {
+        assert_eq!(cluster_of(IVec3::new(0, 3, 4), 4), IVec3::new(0, 0, 1));
+        assert_eq!(
+            cluster_of(IVec3::new(-1, -4, -5), 4),
+            IVec3::new(-1, -1, -2)
+        );
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_current_mesh_near_the_threshold() {
+        assert!(!is_far(false, 105.0, 100.0, 20.0));
+        assert!(is_far(false, 111.0, 100.0, 20.0));
+        assert!(is_far(true, 95.0, 100.0, 20.0));
+        assert!(!is_far(true, 89.0, 100.0, 20.0));
+        assert!(is_far(false, 100.5, 100.0, 0.0));
+    }
+
+    #[test]
+    fn clustering_simplifies_a_plane() {
+        // 8 x 8 quads on the y = 0.5 plane
+        let mut positions = Vec::new();
+        for z in 0..=8 {
+            for x in 0..=8 {
+                positions.push(Vec3::new(x as f32, 0.5, z as f32));
+            }
+        }
+        let mut indices = Vec::new();
+        for z in 0..8 {
+            for x in 0..8 {
+                let a = z * 9 + x;
+                indices.extend([a, a + 9, a + 10, a, a + 10, a + 1]);
+            }
+        }
+        let normals = compute_vertex_normals(&positions, &indices);
+        let mesh = IndexedMesh {
+            positions,
+            normals,
+            indices,
+        };
+
+        let simplified = cluster_vertices(&mesh, 4.0);
+        assert!(simplified.indices.len() < mesh.indices.len() / 4);
+        assert!(!simplified.indices.is_empty());
+        for &index in &simplified.indices {
+            let vertex = index as usize;
+            assert_eq!(simplified.positions[vertex].y, 0.5);
+            assert!(simplified.normals[vertex].abs_diff_eq(Vec3::Y, 1e-5));
+        }
+    }
+}
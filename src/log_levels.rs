@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use bevy::{log::LogSettings, prelude::*};
+use bevy_inspector_egui::Inspectable;
+
+/// File the levels edited in the inspector are saved to
+pub const LOG_LEVELS_FILE: &str = "log_levels.txt";
+/// Environment variable read by [`LogLevels::load`], like `meshing=debug,io=warn`
+pub const LOG_LEVELS_ENV: &str = "MARCHING_CUBES_LOG";
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verbosity {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Verbosity {
+    const ALL: [Verbosity; 6] = [
+        Verbosity::Off,
+        Verbosity::Error,
+        Verbosity::Warn,
+        Verbosity::Info,
+        Verbosity::Debug,
+        Verbosity::Trace,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Verbosity::Off => "off",
+            Verbosity::Error => "error",
+            Verbosity::Warn => "warn",
+            Verbosity::Info => "info",
+            Verbosity::Debug => "debug",
+            Verbosity::Trace => "trace",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|verbosity| verbosity.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Crate root and modules logging for each subsystem
+const MESHING: &[&str] = &[
+    "",
+    "chunk",
+    "compaction",
+    "gpu_meshing",
+    "interpolation",
+    "merge",
+    "mesh_parts",
+    "skirt",
+    "transition",
+    "validation",
+    "vertex_cache",
+];
+const NOISE: &[&str] = &["caves", "cellular", "generation", "ore", "pipeline"];
+const STREAMING: &[&str] = &["density_texture", "field_sync", "gpu_brush", "lod"];
+const IO: &[&str] = &[
+    "calibration",
+    "capture",
+    "clipboard",
+    "heightmap",
+    "mesh_export",
+    "regression",
+    "save",
+    "snapshot",
+];
+
+/// Log level of each subsystem of the crate, the other crates keep the
+/// level of the [`LogSettings`].
+///
+/// The levels are read by the log plugin when the app starts, so they must
+/// be inserted as [`LogSettings`] before the `DefaultPlugins`. The levels
+/// edited in the inspector are saved to [`LOG_LEVELS_FILE`] and used from
+/// the next start. `RUST_LOG` replaces all of them.
+#[derive(Inspectable, Clone, PartialEq, Debug)]
+pub struct LogLevels {
+    /// Marching, welding and uploading the meshes
+    pub meshing: Verbosity,
+    /// Density generation from the noise, the caves and the pipelines
+    pub noise: Verbosity,
+    /// Density textures and edits streamed to the GPU, distant impostors
+    pub streaming: Verbosity,
+    /// Saves, loads and exports
+    pub io: Verbosity,
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        Self {
+            meshing: Verbosity::Info,
+            noise: Verbosity::Info,
+            streaming: Verbosity::Info,
+            io: Verbosity::Info,
+        }
+    }
+}
+
+impl LogLevels {
+    fn subsystems(&self) -> [(&'static str, Verbosity, &'static [&'static str]); 4] {
+        [
+            ("meshing", self.meshing, MESHING),
+            ("noise", self.noise, NOISE),
+            ("streaming", self.streaming, STREAMING),
+            ("io", self.io, IO),
+        ]
+    }
+
+    /// Applies directives like `meshing=debug,io=warn`
+    pub fn apply(&mut self, directives: &str) -> Result<(), String> {
+        for directive in directives
+            .split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+        {
+            let (subsystem, level) = directive
+                .split_once('=')
+                .ok_or_else(|| format!("expected subsystem=level, found {directive:?}"))?;
+            let level = Verbosity::parse(level.trim())
+                .ok_or_else(|| format!("unknown log level {level:?}"))?;
+            let verbosity = match subsystem.trim() {
+                "meshing" => &mut self.meshing,
+                "noise" => &mut self.noise,
+                "streaming" => &mut self.streaming,
+                "io" => &mut self.io,
+                other => return Err(format!("unknown subsystem {other:?}")),
+            };
+            *verbosity = level;
+        }
+        Ok(())
+    }
+
+    /// Directives accepted by [`LogLevels::apply`]
+    pub fn directives(&self) -> String {
+        self.subsystems()
+            .iter()
+            .map(|(name, verbosity, _)| format!("{name}={}", verbosity.name()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Default levels, then [`LOG_LEVELS_FILE`], [`LOG_LEVELS_ENV`] and the
+    /// `--log` argument, each replacing the levels set before
+    pub fn load(args: &[String]) -> Self {
+        let mut levels = Self::default();
+        let mut sources = Vec::new();
+        if let Ok(saved) = std::fs::read_to_string(LOG_LEVELS_FILE) {
+            sources.push((LOG_LEVELS_FILE.to_string(), saved));
+        }
+        if let Ok(env) = std::env::var(LOG_LEVELS_ENV) {
+            sources.push((LOG_LEVELS_ENV.to_string(), env));
+        }
+        if let Some(index) = args.iter().position(|arg| arg == "--log") {
+            if let Some(arg) = args.get(index + 1) {
+                sources.push(("--log".to_string(), arg.clone()));
+            }
+        }
+        for (source, directives) in sources {
+            // nothing is logged before the log plugin is added
+            if let Err(err) = levels.apply(directives.trim()) {
+                eprintln!("Ignoring the log levels of {source}: {err}");
+            }
+        }
+        levels
+    }
+
+    /// Settings of the log plugin with the level of every module
+    pub fn log_settings(&self) -> LogSettings {
+        let mut filter = LogSettings::default().filter;
+        for (_, verbosity, modules) in self.subsystems() {
+            for module in modules {
+                let target = if module.is_empty() {
+                    "bevy_marching_cube".to_string()
+                } else {
+                    format!("bevy_marching_cube::{module}")
+                };
+                filter.push_str(&format!(",{target}={}", verbosity.name()));
+            }
+        }
+        LogSettings {
+            filter,
+            ..default()
+        }
+    }
+}
+
+/// Saves the levels edited in the inspector for the next start
+pub fn save_log_levels(levels: Res<LogLevels>) {
+    if !levels.is_changed() || levels.is_added() {
+        return;
+    }
+    let path = Path::new(LOG_LEVELS_FILE);
+    match std::fs::write(path, levels.directives()) {
+        Ok(()) => info!("Saved the log levels to {path:?}, they apply from the next start"),
+        Err(err) => error!("Failed to save the log levels: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directives_round_trip() {
+        let mut levels = LogLevels::default();
+        levels.apply("meshing=debug, io=OFF").unwrap();
+        assert_eq!(levels.meshing, Verbosity::Debug);
+        assert_eq!(levels.io, Verbosity::Off);
+        assert!(levels.apply("physics=debug").is_err());
+        assert!(levels.apply("noise").is_err());
+
+        let mut parsed = LogLevels::default();
+        parsed.apply(&levels.directives()).unwrap();
+        assert_eq!(parsed, levels);
+
+        let filter = levels.log_settings().filter;
+        assert!(filter.contains(",bevy_marching_cube=debug"));
+        assert!(filter.contains(",bevy_marching_cube::save=off"));
+        assert!(filter.contains(",bevy_marching_cube::generation=info"));
+    }
+}
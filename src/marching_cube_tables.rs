@@ -295,3 +295,79 @@ pub const TRIANGLE_TABLE: [[i8; 16]; 256] = [
     [0, 3, 8, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
     [-1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1],
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Edges crossing the surface when the corners set in `case` are outside
+    fn crossed_edges(case: usize) -> u32 {
+        let mut edges = 0;
+        for (i, [u, v]) in EDGE_CONNECTION.iter().enumerate() {
+            if (case >> u) & 1 != (case >> v) & 1 {
+                edges |= 1 << i;
+            }
+        }
+        edges
+    }
+
+    /// Edges referenced by the triangles of `case`
+    fn triangle_edges(case: usize) -> u32 {
+        TRIANGLE_TABLE[case]
+            .iter()
+            .take_while(|e| **e >= 0)
+            .fold(0, |edges, e| edges | 1 << e)
+    }
+
+    #[test]
+    fn edge_table_matches_corners() {
+        for case in 0..256 {
+            assert_eq!(EDGE_TABLE[case], crossed_edges(case), "case {}", case);
+        }
+    }
+
+    #[test]
+    fn triangles_use_every_crossed_edge() {
+        for case in 0..256 {
+            assert_eq!(triangle_edges(case), EDGE_TABLE[case], "case {}", case);
+        }
+    }
+
+    #[test]
+    fn triangle_lists_are_well_formed() {
+        for (case, triangulation) in TRIANGLE_TABLE.iter().enumerate() {
+            let len = triangulation.iter().take_while(|e| **e >= 0).count();
+            assert_eq!(len % 3, 0, "case {}", case);
+            assert!(len < 16, "case {} isn't terminated", case);
+            assert!(
+                triangulation[len..].iter().all(|e| *e == -1),
+                "case {} has edges after the terminator",
+                case
+            );
+            for triangle in triangulation[..len].chunks(3) {
+                assert!(triangle.iter().all(|e| *e < 12), "case {}", case);
+                assert!(
+                    triangle[0] != triangle[1]
+                        && triangle[1] != triangle[2]
+                        && triangle[2] != triangle[0],
+                    "case {} has a degenerate triangle",
+                    case
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn complementary_cases_cross_the_same_edges() {
+        for case in 0..256 {
+            let complement = 255 - case;
+            assert_eq!(EDGE_TABLE[case], EDGE_TABLE[complement], "case {}", case);
+            assert_eq!(
+                triangle_edges(case),
+                triangle_edges(complement),
+                "case {}",
+                case
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+
+use crate::{chunk::is_inside, field::DensityField, generation::EMPTY};
+
+/// Corners of a square, counterclockwise from its origin
+pub const SQUARE_CORNERS: [Vec2; 4] = [
+    Vec2::new(0.0, 0.0),
+    Vec2::new(1.0, 0.0),
+    Vec2::new(1.0, 1.0),
+    Vec2::new(0.0, 1.0),
+];
+
+/// Corners joined by each edge of a square
+pub const SQUARE_EDGE_CONNECTION: [[usize; 2]; 4] = [[0, 1], [1, 2], [2, 3], [3, 0]];
+
+/// Edges joined by the segments of each configuration of a square, indexed
+/// by the corners inside the surface like the cubes and ended by -1.
+///
+/// The segments keep the inside on their left, so the contours go
+/// counterclockwise around the solid parts. The saddles 5 and 10 keep the
+/// inside corners apart, see [`square_segments`].
+pub const SEGMENT_TABLE: [[i8; 4]; 16] = [
+    [-1, -1, -1, -1],
+    [0, 3, -1, -1],
+    [1, 0, -1, -1],
+    [1, 3, -1, -1],
+    [2, 1, -1, -1],
+    [0, 3, 2, 1],
+    [2, 0, -1, -1],
+    [2, 3, -1, -1],
+    [3, 2, -1, -1],
+    [0, 2, -1, -1],
+    [1, 0, 3, 2],
+    [1, 2, -1, -1],
+    [3, 1, -1, -1],
+    [0, 1, -1, -1],
+    [3, 0, -1, -1],
+    [-1, -1, -1, -1],
+];
+
+/// Configuration of a square, bit `i` is set when corner `i` is inside
+pub fn square_index(values: &[f32; 4], isolevel: f32) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| is_inside(**value, isolevel))
+        .fold(0, |index, (i, _)| index | 1 << i)
+}
+
+/// Segments of the contour crossing a square with `values` at its corners,
+/// in the local space of the square.
+///
+/// The saddles are resolved with the mean of the corners, when it is inside
+/// the outside corners are the ones kept apart.
+pub fn square_segments(values: &[f32; 4], isolevel: f32) -> Vec<(Vec2, Vec2)> {
+    let index = square_index(values, isolevel);
+    let center_inside = is_inside(values.iter().sum::<f32>() / 4.0, isolevel);
+    let (index, reversed) = match index {
+        5 | 10 if center_inside => (15 ^ index, true),
+        _ => (index, false),
+    };
+    let crossing = |edge: i8| {
+        let [a, b] = SQUARE_EDGE_CONNECTION[edge as usize];
+        let t = (isolevel - values[a]) / (values[b] - values[a]);
+        SQUARE_CORNERS[a].lerp(SQUARE_CORNERS[b], t)
+    };
+    SEGMENT_TABLE[index]
+        .chunks(2)
+        .take_while(|edges| edges[0] != -1)
+        .map(|edges| {
+            let (start, end) = (crossing(edges[0]), crossing(edges[1]));
+            if reversed {
+                (end, start)
+            } else {
+                (start, end)
+            }
+        })
+        .collect()
+}
+
+/// Contour of a 2D grid of `size` squares where `value` crosses the
+/// isolevel, in grid points
+pub fn march_squares(
+    size: UVec2,
+    isolevel: f32,
+    value: impl Fn(UVec2) -> f32,
+) -> Vec<(Vec2, Vec2)> {
+    let width = size.x as usize + 1;
+    let mut points = Vec::with_capacity(width * (size.y as usize + 1));
+    for y in 0..=size.y {
+        for x in 0..=size.x {
+            points.push(value(UVec2::new(x, y)));
+        }
+    }
+
+    let mut segments = Vec::new();
+    for y in 0..size.y as usize {
+        for x in 0..size.x as usize {
+            let values = SQUARE_CORNERS
+                .map(|corner| points[(y + corner.y as usize) * width + x + corner.x as usize]);
+            let origin = Vec2::new(x as f32, y as f32);
+            segments.extend(
+                square_segments(&values, isolevel)
+                    .into_iter()
+                    .map(|(start, end)| (origin + start, origin + end)),
+            );
+        }
+    }
+    segments
+}
+
+/// Outline of the terrain cut by the horizontal plane at `height`, sampled
+/// every `spacing` world units over the loaded chunks.
+///
+/// The points outside of the chunks are empty so the outline is closed on
+/// the edges of the world.
+pub fn slice_contour(
+    field: &DensityField,
+    height: f32,
+    isolevel: f32,
+    spacing: f32,
+) -> Vec<(Vec3, Vec3)> {
+    let chunk_map = match field.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return Vec::new(),
+    };
+    let extent = field.chunk_extent();
+    let min = chunk_map.min().as_vec3() * extent;
+    let world_size = chunk_map.dimensions().as_vec3() * extent;
+    // one more square on each side to close the outline
+    let size = (Vec2::new(world_size.x, world_size.z) / spacing)
+        .ceil()
+        .as_uvec2()
+        + UVec2::splat(2);
+    let origin = Vec2::new(min.x, min.z) - Vec2::splat(spacing);
+    let to_world = |point: Vec2| {
+        let pos = origin + point * spacing;
+        Vec3::new(pos.x, height, pos.y)
+    };
+    march_squares(size, isolevel, |point| {
+        field.density(to_world(point.as_vec2())).unwrap_or(EMPTY)
+    })
+    .into_iter()
+    .map(|(start, end)| (to_world(start), to_world(end)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circle_is_closed_counterclockwise() {
+        let center = Vec2::splat(5.0);
+        let segments = march_squares(UVec2::splat(10), 0.5, |point| {
+            0.5 + (3.2 - point.as_vec2().distance(center)) * 0.2
+        });
+        assert!(!segments.is_empty());
+        for (start, end) in &segments {
+            // every segment ends where another one starts
+            assert!(segments
+                .iter()
+                .any(|(next, _)| next.abs_diff_eq(*end, 1e-5)));
+            // the inside on the left
+            let left = Vec2::new(start.y - end.y, end.x - start.x);
+            assert!(left.dot(center - (*start + *end) / 2.0) > 0.0);
+            assert!((start.distance(center) - 3.2).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn saddles_follow_the_center() {
+        // corners 0 and 2 inside
+        let apart = square_segments(&[0.6, 0.0, 0.6, 0.0], 0.5);
+        let joined = square_segments(&[1.0, 0.4, 1.0, 0.4], 0.5);
+        assert_eq!(apart.len(), 2);
+        assert_eq!(joined.len(), 2);
+        let middle = Vec2::splat(0.5);
+        for (start, end) in apart {
+            // cut around the inside corners, away from the center
+            let left = Vec2::new(start.y - end.y, end.x - start.x);
+            assert!(left.dot(middle - (start + end) / 2.0) < 0.0);
+        }
+        for (start, end) in joined {
+            let left = Vec2::new(start.y - end.y, end.x - start.x);
+            assert!(left.dot(middle - (start + end) / 2.0) > 0.0);
+        }
+    }
+}
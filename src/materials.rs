@@ -0,0 +1,285 @@
+use bevy::{asset::Asset, ecs::system::EntityCommands, prelude::*, render::render_resource::Face};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    chunk::{Chunk, ChunkCoord},
+    mesh_parts::ChunkMeshPart,
+    ore::{OreMaterial, OreMaterialHandle, OreSettings},
+    slope_material::{SlopeColoring, SlopeMaterial, SlopeMaterialHandle},
+    terrain_root::MainTerrain,
+    xray::{XRay, XRayMaterial, XRayMaterialHandle},
+};
+
+/// Faces of the terrain that aren't rendered
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub enum TerrainCulling {
+    /// Render both sides, the inside of the surface is visible when the
+    /// camera goes through it
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Debug)]
+pub enum TerrainAlpha {
+    Opaque,
+    /// Discards the fragments with an alpha below `cutoff`
+    Mask {
+        #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+        cutoff: f32,
+    },
+    Blend,
+}
+
+/// Material of the "Terrain" entry of the [`MaterialLibrary`], used by the
+/// chunks when they are spawned
+#[derive(Inspectable)]
+pub struct TerrainMaterial {
+    pub base_color: Color,
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub metallic: f32,
+    #[inspectable(min = 0.089, max = 1.0, speed = 0.01)]
+    pub perceptual_roughness: f32,
+    pub culling: TerrainCulling,
+    pub alpha: TerrainAlpha,
+}
+
+impl Default for TerrainMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: Color::rgb(0.55, 0.45, 0.35),
+            metallic: 0.0,
+            perceptual_roughness: 0.85,
+            culling: TerrainCulling::None,
+            alpha: TerrainAlpha::Opaque,
+        }
+    }
+}
+
+impl TerrainMaterial {
+    pub fn to_standard_material(&self) -> StandardMaterial {
+        StandardMaterial {
+            base_color: self.base_color,
+            metallic: self.metallic,
+            perceptual_roughness: self.perceptual_roughness,
+            cull_mode: match self.culling {
+                TerrainCulling::None => None,
+                TerrainCulling::Back => Some(Face::Back),
+                TerrainCulling::Front => Some(Face::Front),
+            },
+            alpha_mode: match self.alpha {
+                TerrainAlpha::Opaque => AlphaMode::Opaque,
+                TerrainAlpha::Mask { cutoff } => AlphaMode::Mask(cutoff),
+                TerrainAlpha::Blend => AlphaMode::Blend,
+            },
+            ..default()
+        }
+    }
+}
+
+/// Named materials that can be assigned to chunks
+pub struct MaterialLibrary {
+    pub materials: Vec<(String, Handle<StandardMaterial>)>,
+}
+
+impl FromWorld for MaterialLibrary {
+    fn from_world(world: &mut World) -> Self {
+        let terrain = world
+            .get_resource_or_insert_with(TerrainMaterial::default)
+            .to_standard_material();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        let terrain = materials.add(terrain);
+        let mut add = |name: &str, base_color: Color, perceptual_roughness: f32| {
+            let material = materials.add(StandardMaterial {
+                base_color,
+                perceptual_roughness,
+                cull_mode: None,
+                ..default()
+            });
+            (name.to_string(), material)
+        };
+        Self {
+            materials: vec![
+                ("Terrain".to_string(), terrain),
+                add("Rock", Color::rgb(0.45, 0.43, 0.4), 0.9),
+                add("Sand", Color::rgb(0.76, 0.7, 0.5), 0.8),
+                add("Grass", Color::rgb(0.3, 0.55, 0.2), 0.7),
+                add("Snow", Color::rgb(0.95, 0.95, 0.97), 0.5),
+            ],
+        }
+    }
+}
+
+impl MaterialLibrary {
+    pub fn get(&self, index: usize) -> Option<&Handle<StandardMaterial>> {
+        self.materials.get(index).map(|(_, material)| material)
+    }
+
+    pub fn name(&self, index: usize) -> &str {
+        self.materials
+            .get(index)
+            .map_or("Unknown", |(name, _)| name.as_str())
+    }
+}
+
+/// Index of the "Terrain" material in the [`MaterialLibrary`]
+pub const TERRAIN: usize = 0;
+
+/// Index of the material of a chunk in the [`MaterialLibrary`]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ChunkMaterial(pub usize);
+
+/// Assigns a material of the [`MaterialLibrary`] to every chunk within
+/// `radius` chunks of `center`, a radius of 0 only changes the center chunk
+pub struct SetChunkMaterial {
+    pub center: IVec3,
+    pub radius: u32,
+    pub material: usize,
+}
+
+/// Updates the terrain material of the library when its settings change
+pub fn update_terrain_material(
+    settings: Res<TerrainMaterial>,
+    library: Res<MaterialLibrary>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(material) = library
+        .get(TERRAIN)
+        .and_then(|handle| materials.get_mut(handle))
+    {
+        *material = settings.to_standard_material();
+    }
+}
+
+pub fn set_chunk_materials(
+    mut events: EventReader<SetChunkMaterial>,
+    library: Res<MaterialLibrary>,
+    terrain: MainTerrain,
+    mut chunks: Query<(
+        &ChunkCoord,
+        &mut ChunkMaterial,
+        Option<&mut Handle<StandardMaterial>>,
+        &Parent,
+    )>,
+) {
+    // the center is on the grid of the main terrain
+    let root = terrain.root();
+    for event in events.iter() {
+        let handle = match library.get(event.material) {
+            Some(handle) => handle,
+            None => {
+                warn!("no material {} in the library", event.material);
+                continue;
+            }
+        };
+        let radius = event.radius as i32;
+        for (coord, mut chunk_material, mut material, parent) in chunks.iter_mut() {
+            let offset = (coord.0 - event.center).abs();
+            if Some(parent.0) == root && offset.max_element() <= radius {
+                *chunk_material = ChunkMaterial(event.material);
+                // chunks using another kind of material get it back when it's disabled
+                if let Some(mut material) = material {
+                    *material = handle.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Gives every chunk the material of the enabled render mode, the x-ray mode
+/// wins over the ore veins which win over the slope coloring, and the chunks go back to their material
+/// from the library when both are disabled
+#[allow(clippy::too_many_arguments)]
+pub fn apply_render_mode(
+    mut commands: Commands,
+    xray: Res<XRay>,
+    ore: Res<OreSettings>,
+    slope_coloring: Res<SlopeColoring>,
+    library: Res<MaterialLibrary>,
+    xray_material: Res<XRayMaterialHandle>,
+    ore_material: Res<OreMaterialHandle>,
+    slope_material: Res<SlopeMaterialHandle>,
+    chunks: Query<(Entity, &ChunkMaterial), With<Chunk>>,
+) {
+    if !(xray.is_changed() || ore.is_changed() || slope_coloring.is_changed()) {
+        return;
+    }
+    for (entity, chunk_material) in chunks.iter() {
+        let mut entity = commands.entity(entity);
+        entity
+            .remove::<Handle<StandardMaterial>>()
+            .remove::<Handle<OreMaterial>>()
+            .remove::<Handle<SlopeMaterial>>()
+            .remove::<Handle<XRayMaterial>>();
+        if xray.enabled {
+            entity.insert(xray_material.0.clone());
+        } else if ore.show_veins {
+            entity.insert(ore_material.0.clone());
+        } else if slope_coloring.enabled {
+            entity.insert(slope_material.0.clone());
+        } else if let Some(material) = library.get(chunk_material.0) {
+            entity.insert(material.clone());
+        }
+    }
+}
+
+/// Gives the [`ChunkMeshPart`]s the material of their chunk
+pub fn sync_mesh_part_materials(
+    mut commands: Commands,
+    parts: Query<
+        (
+            Entity,
+            &Parent,
+            (
+                Option<&Handle<StandardMaterial>>,
+                Option<&Handle<OreMaterial>>,
+                Option<&Handle<SlopeMaterial>>,
+                Option<&Handle<XRayMaterial>>,
+            ),
+        ),
+        With<ChunkMeshPart>,
+    >,
+    chunks: Query<
+        (
+            Option<&Handle<StandardMaterial>>,
+            Option<&Handle<OreMaterial>>,
+            Option<&Handle<SlopeMaterial>>,
+            Option<&Handle<XRayMaterial>>,
+        ),
+        With<Chunk>,
+    >,
+) {
+    for (entity, parent, (standard, ore, slope, xray)) in parts.iter() {
+        let (chunk_standard, chunk_ore, chunk_slope, chunk_xray) = match chunks.get(parent.0) {
+            Ok(materials) => materials,
+            Err(_) => continue,
+        };
+        let mut entity = commands.entity(entity);
+        sync_handle(&mut entity, standard, chunk_standard);
+        sync_handle(&mut entity, ore, chunk_ore);
+        sync_handle(&mut entity, slope, chunk_slope);
+        sync_handle(&mut entity, xray, chunk_xray);
+    }
+}
+
+fn sync_handle<T: Asset>(
+    entity: &mut EntityCommands,
+    part: Option<&Handle<T>>,
+    chunk: Option<&Handle<T>>,
+) {
+    match (part, chunk) {
+        (Some(part), Some(chunk)) if part == chunk => {}
+        (_, Some(chunk)) => {
+            entity.insert(chunk.clone());
+        }
+        (Some(_), None) => {
+            entity.remove::<Handle<T>>();
+        }
+        (None, None) => {}
+    }
+}
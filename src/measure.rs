@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContext},
+    Inspectable,
+};
+use bevy_mod_picking::PickingCamera;
+
+use crate::{lines::line_mesh, unlit_material};
+
+/// Visual references to judge the scale of the chunks and voxels
+#[derive(Inspectable)]
+pub struct ScaleReference {
+    /// Grid with one line per world unit on the ground plane
+    pub show_grid: bool,
+    /// The grid covers -extent..extent on the X and Z axis
+    #[inspectable(min = 1, max = 256)]
+    pub grid_extent: i32,
+    pub show_axes: bool,
+}
+
+impl Default for ScaleReference {
+    fn default() -> Self {
+        Self {
+            show_grid: false,
+            grid_extent: 32,
+            show_axes: false,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ReferenceGrid;
+
+#[derive(Component)]
+pub struct ReferenceAxes;
+
+/// Press M to toggle the measure mode, then click two points on the terrain
+#[derive(Default)]
+pub struct Measurement {
+    pub active: bool,
+    pub points: Vec<Vec3>,
+    markers: Vec<Entity>,
+}
+
+impl Measurement {
+    pub fn distance(&self) -> Option<f32> {
+        match self.points.as_slice() {
+            [a, b] => Some(a.distance(*b)),
+            _ => None,
+        }
+    }
+}
+
+pub fn update_scale_reference(
+    mut commands: Commands,
+    settings: Res<ScaleReference>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    grids: Query<Entity, With<ReferenceGrid>>,
+    axes: Query<Entity, With<ReferenceAxes>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for entity in grids.iter().chain(axes.iter()) {
+        commands.entity(entity).despawn();
+    }
+
+    if settings.show_grid {
+        let extent = settings.grid_extent;
+        let mut segments = Vec::new();
+        for i in -extent..=extent {
+            let i = i as f32;
+            let e = extent as f32;
+            segments.push((Vec3::new(i, 0.0, -e), Vec3::new(i, 0.0, e)));
+            segments.push((Vec3::new(-e, 0.0, i), Vec3::new(e, 0.0, i)));
+        }
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(line_mesh(&segments)),
+                material: materials.add(unlit_material(Color::rgb(0.2, 0.2, 0.2))),
+                // slightly above the ground plane to avoid z-fighting
+                transform: Transform::from_xyz(0.0, 0.01, 0.0),
+                ..default()
+            })
+            .insert(ReferenceGrid);
+    }
+
+    if settings.show_axes {
+        let axes = [
+            (Vec3::X, Color::RED),
+            (Vec3::Y, Color::GREEN),
+            (Vec3::Z, Color::BLUE),
+        ];
+        for (axis, color) in axes {
+            let size = axis * 2.0 + Vec3::splat(0.05);
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z))),
+                    material: materials.add(unlit_material(color)),
+                    transform: Transform::from_translation(axis),
+                    ..default()
+                })
+                .insert(ReferenceAxes);
+        }
+    }
+}
+
+pub fn measure(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut measurement: ResMut<Measurement>,
+    picking_cameras: Query<&PickingCamera>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        measurement.active = !measurement.active;
+    }
+
+    if !measurement.active || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let hit = picking_cameras
+        .iter()
+        .find_map(|camera| camera.intersect_top())
+        .map(|(_, intersection)| intersection.position());
+    let hit = match hit {
+        Some(hit) => hit,
+        None => return,
+    };
+
+    // start a new measurement after the previous one was completed
+    if measurement.points.len() == 2 {
+        measurement.points.clear();
+        for entity in measurement.markers.drain(..) {
+            commands.entity(entity).despawn();
+        }
+    }
+    measurement.points.push(hit);
+
+    let material = materials.add(unlit_material(Color::YELLOW));
+    let marker = commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: 0.1,
+                ..default()
+            })),
+            material: material.clone(),
+            transform: Transform::from_translation(hit),
+            ..default()
+        })
+        .id();
+    measurement.markers.push(marker);
+
+    if let [a, b] = measurement.points[..] {
+        let line = commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(line_mesh(&[(a, b)])),
+                material,
+                ..default()
+            })
+            .id();
+        measurement.markers.push(line);
+        info!("Measured distance: {}", a.distance(b));
+    }
+}
+
+pub fn measure_ui(mut egui_context: ResMut<EguiContext>, measurement: Res<Measurement>) {
+    if !measurement.active {
+        return;
+    }
+
+    egui::Window::new("Measure").show(egui_context.ctx_mut(), |ui| {
+        match measurement.distance() {
+            Some(distance) => ui.label(format!("Distance: {distance:.3}")),
+            None => ui.label(format!(
+                "Click {} more point(s) on the terrain",
+                2 - measurement.points.len()
+            )),
+        };
+    });
+}
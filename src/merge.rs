@@ -0,0 +1,154 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    chunk::{compute_vertex_normals, Chunk, ChunkMesh, IndexedMesh},
+    materials::{MaterialLibrary, TERRAIN},
+    terrain_instance::TerrainInstance,
+    terrain_root::TerrainRoot,
+    Data,
+};
+
+/// Renders every chunk of the main terrain as a single mesh, rebuilt when a
+/// chunk is remeshed.
+///
+/// Useful for small worlds where the draw calls cost more than the rebuilds.
+/// The merged mesh uses the terrain material and can't be picked, disable it
+/// to select chunks.
+#[derive(Inspectable, Default)]
+pub struct MergedWorld {
+    pub enabled: bool,
+}
+
+/// Entity rendering the merged meshes of the chunks
+#[derive(Component)]
+pub struct MergedMesh;
+
+/// Merges the meshes of chunks into a single mesh in world space.
+///
+/// Each chunk is given with the world position of its origin. Vertices closer
+/// than 1/4096 are welded so the seams between chunks share their vertices and
+/// the normals are smooth across them. Degenerate triangles are skipped.
+pub fn merge_chunk_meshes<'a>(
+    chunks: impl IntoIterator<Item = (Vec3, &'a ChunkMesh)>,
+) -> IndexedMesh {
+    let mut vertex_ids = HashMap::default();
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    for (origin, chunk_mesh) in chunks {
+        for triangle in chunk_mesh.iter_triangles() {
+            let ids = triangle.map(|vertex| {
+                let position = origin + vertex;
+                let key = (position * 4096.0).round().as_ivec3();
+                *vertex_ids.entry(key).or_insert_with(|| {
+                    positions.push(position);
+                    positions.len() as u32 - 1
+                })
+            });
+            // welding can collapse thin triangles
+            if ids[0] != ids[1] && ids[1] != ids[2] && ids[2] != ids[0] {
+                indices.extend(ids);
+            }
+        }
+    }
+    let normals = compute_vertex_normals(&positions, &indices);
+    IndexedMesh {
+        positions,
+        normals,
+        indices,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_merged_world(
+    mut commands: Commands,
+    settings: Res<MergedWorld>,
+    data: Res<Data>,
+    mut last_optimized: Local<bool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material_library: Res<MaterialLibrary>,
+    changed_chunks: Query<&Parent, (With<Chunk>, Changed<ChunkMesh>)>,
+    // in the space of the terrain root, like the merged mesh
+    mut chunks: Query<(&ChunkMesh, &Transform, &mut Visibility, &Parent), With<Chunk>>,
+    merged: Query<(Entity, &Handle<Mesh>), With<MergedMesh>>,
+    root: Query<Entity, (With<TerrainRoot>, Without<TerrainInstance>)>,
+) {
+    let root = root.get_single().ok();
+    let in_main_terrain = |parent: &Parent| Some(parent.0) == root;
+    if settings.is_changed() {
+        for (_, _, mut visibility, parent) in chunks.iter_mut() {
+            if in_main_terrain(parent) {
+                visibility.is_visible = !settings.enabled;
+            }
+        }
+        if !settings.enabled {
+            for (entity, _) in merged.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+    } else if !settings.enabled
+        || (!changed_chunks.iter().any(in_main_terrain)
+            && *last_optimized == data.optimize_index_order)
+    {
+        return;
+    }
+    *last_optimized = data.optimize_index_order;
+
+    let mut merged_mesh = merge_chunk_meshes(
+        chunks
+            .iter()
+            .filter(|(.., parent)| in_main_terrain(parent))
+            .map(|(chunk_mesh, transform, ..)| (transform.translation, chunk_mesh)),
+    );
+    if data.optimize_index_order {
+        merged_mesh.optimize_vertex_cache();
+    }
+    let mesh = Mesh::from(merged_mesh);
+    match merged.get_single() {
+        Ok((_, handle)) => {
+            if let Some(merged_mesh) = meshes.get_mut(handle) {
+                *merged_mesh = mesh;
+            }
+        }
+        Err(_) => {
+            let entity = commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: material_library.get(TERRAIN).unwrap().clone(),
+                    ..default()
+                })
+                .insert(MergedMesh)
+                .id();
+            if let Some(root) = root {
+                commands.entity(root).push_children(&[entity]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seams_are_welded() {
+        // 2 quads sharing an edge at x = 1, each in the local space of its chunk
+        let [a, b, c, d] = [
+            Vec3::new(0.0, 0.5, 0.0),
+            Vec3::new(0.0, 0.5, 1.0),
+            Vec3::new(1.0, 0.5, 1.0),
+            Vec3::new(1.0, 0.5, 0.0),
+        ];
+        let quad = ChunkMesh {
+            triangles: vec![[a, b, c], [a, c, d]],
+        };
+        let merged = merge_chunk_meshes([(Vec3::ZERO, &quad), (Vec3::X, &quad)]);
+        assert_eq!(merged.positions.len(), 6);
+        assert_eq!(merged.indices.len(), 12);
+        assert!(merged
+            .normals
+            .iter()
+            .all(|normal| normal.abs_diff_eq(Vec3::Y, 1e-6)));
+    }
+}
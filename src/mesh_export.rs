@@ -0,0 +1,322 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+    utils::HashMap,
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    chunk::{Chunk, ChunkCoord, ChunkMesh, IndexedMesh},
+    gltf_export::{write_glb, GltfMaterial, GltfNode, GltfPrimitive, GltfScene},
+    materials::{ChunkMaterial, MaterialLibrary, TERRAIN},
+    merge::merge_chunk_meshes,
+    mesh_parts::{ChunkMeshPart, ChunkMeshParts},
+    slope_material::SlopeColoring,
+};
+
+/// Order of the vertices of the front faces seen from the outside of the surface
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Winding {
+    /// Used by bevy, OpenGL and glTF
+    CounterClockwise,
+    /// Used by DirectX and Unity
+    Clockwise,
+}
+
+/// Axis pointing up, both are right handed
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpAxis {
+    /// Used by bevy, glTF and Maya
+    Y,
+    /// Used by Blender and 3ds Max
+    Z,
+}
+
+/// Conversion of the meshes from the conventions of bevy, counter clockwise
+/// and Y up, to the conventions of another engine or tool
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MeshOrientation {
+    pub winding: Winding,
+    pub up: UpAxis,
+}
+
+impl Default for MeshOrientation {
+    fn default() -> Self {
+        Self {
+            winding: Winding::CounterClockwise,
+            up: UpAxis::Y,
+        }
+    }
+}
+
+impl MeshOrientation {
+    /// Converts a position or a normal from Y up
+    pub fn convert(&self, v: Vec3) -> Vec3 {
+        match self.up {
+            UpAxis::Y => v,
+            // rotation around X, the forward axis of Y up becomes -Y
+            UpAxis::Z => Vec3::new(v.x, -v.z, v.y),
+        }
+    }
+
+    /// Converts the positions and the normals of `mesh` and reorders the
+    /// indices of its triangles for the winding
+    pub fn apply(&self, mesh: &mut IndexedMesh) {
+        for position in &mut mesh.positions {
+            *position = self.convert(*position);
+        }
+        for normal in &mut mesh.normals {
+            *normal = self.convert(*normal);
+        }
+        if self.winding == Winding::Clockwise {
+            for triangle in mesh.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+}
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    /// `terrain.obj`, a single mesh welded and with smooth normals like the
+    /// [`MergedWorld`](crate::merge::MergedWorld)
+    Obj,
+    /// `terrain.glb`, a node per chunk with the rendered meshes, their normals
+    /// and materials. With the [`SlopeColoring`] its colors are baked in the
+    /// vertex colors.
+    Gltf,
+}
+
+/// Press F10 to export the meshes of the chunks to `directory`
+#[derive(Inspectable)]
+pub struct MeshExport {
+    pub format: ExportFormat,
+    /// Only used by the OBJ export, glTF is always Y up and counter clockwise
+    pub orientation: MeshOrientation,
+    #[inspectable(ignore)]
+    pub directory: PathBuf,
+}
+
+impl Default for MeshExport {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Obj,
+            orientation: MeshOrientation::default(),
+            directory: PathBuf::from("exports"),
+        }
+    }
+}
+
+/// Writes `mesh` in the Wavefront OBJ format
+pub fn write_obj(mesh: &IndexedMesh, mut writer: impl Write) -> io::Result<()> {
+    for position in &mesh.positions {
+        writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+    }
+    for normal in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+    // indices start at 1
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+    writer.flush()
+}
+
+/// Triangles of a rendered mesh, `None` without positions or normals
+fn gltf_primitive(mesh: &Mesh, material: usize) -> Option<GltfPrimitive> {
+    let vec3s = |name: &'static str| match mesh.attribute(name) {
+        Some(VertexAttributeValues::Float32x3(values)) => {
+            Some(values.iter().copied().map(Vec3::from).collect::<Vec<_>>())
+        }
+        _ => None,
+    };
+    let positions = vec3s(Mesh::ATTRIBUTE_POSITION)?;
+    let normals = vec3s(Mesh::ATTRIBUTE_NORMAL)?;
+    let indices = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|i| *i as u32).collect(),
+        Some(Indices::U32(indices)) => indices.clone(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    Some(GltfPrimitive {
+        positions,
+        normals,
+        colors: None,
+        indices,
+        material,
+    })
+}
+
+fn gltf_material(name: &str, material: &StandardMaterial) -> GltfMaterial {
+    GltfMaterial {
+        name: name.to_string(),
+        base_color: material.base_color.as_linear_rgba_f32(),
+        metallic: material.metallic,
+        roughness: material.perceptual_roughness,
+        double_sided: material.cull_mode.is_none(),
+        alpha_mode: material.alpha_mode,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn export_meshes(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<MeshExport>,
+    chunks: Query<(&ChunkMesh, &GlobalTransform), With<Chunk>>,
+    rendered_chunks: Query<(
+        &ChunkCoord,
+        &GlobalTransform,
+        &Handle<Mesh>,
+        Option<&ChunkMaterial>,
+        Option<&ChunkMeshParts>,
+    )>,
+    parts: Query<&Handle<Mesh>, With<ChunkMeshPart>>,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<StandardMaterial>>,
+    library: Res<MaterialLibrary>,
+    slope_coloring: Res<SlopeColoring>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F10) {
+        return;
+    }
+    if let Err(err) = std::fs::create_dir_all(&settings.directory) {
+        error!("Failed to create export directory: {err}");
+        return;
+    }
+
+    if settings.format == ExportFormat::Obj {
+        let mut mesh = merge_chunk_meshes(
+            chunks
+                .iter()
+                .map(|(chunk_mesh, transform)| (transform.translation, chunk_mesh)),
+        );
+        settings.orientation.apply(&mut mesh);
+
+        let path = settings.directory.join("terrain.obj");
+        let result = File::create(&path).and_then(|file| write_obj(&mesh, BufWriter::new(file)));
+        match result {
+            Ok(()) => info!("Exported {} triangles to {path:?}", mesh.indices.len() / 3),
+            Err(err) => error!("Failed to export the meshes: {err}"),
+        }
+        return;
+    }
+
+    let mut scene = GltfScene {
+        name: "terrain".to_string(),
+        ..default()
+    };
+    // the slope coloring replaces the materials of the chunks
+    if slope_coloring.enabled {
+        scene.materials.push(GltfMaterial {
+            name: "Slope coloring".to_string(),
+            base_color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 1.0,
+            double_sided: true,
+            alpha_mode: AlphaMode::Opaque,
+        });
+    }
+    let mut material_indices: HashMap<usize, usize> = HashMap::default();
+    let mut triangles = 0;
+    for (coord, transform, mesh, chunk_material, mesh_parts) in rendered_chunks.iter() {
+        let material = if slope_coloring.enabled {
+            0
+        } else {
+            let index = chunk_material.map_or(TERRAIN, |material| material.0);
+            *material_indices.entry(index).or_insert_with(|| {
+                let material = library
+                    .get(index)
+                    .and_then(|handle| materials.get(handle))
+                    .cloned()
+                    .unwrap_or_default();
+                let name = library
+                    .materials
+                    .get(index)
+                    .map_or("Default", |(name, _)| name.as_str());
+                scene.materials.push(gltf_material(name, &material));
+                scene.materials.len() - 1
+            })
+        };
+        let mut handles = vec![mesh];
+        if let Some(mesh_parts) = mesh_parts {
+            handles.extend(mesh_parts.0.iter().filter_map(|part| parts.get(*part).ok()));
+        }
+        let origin = transform.translation;
+        let primitives: Vec<GltfPrimitive> = handles
+            .into_iter()
+            .filter_map(|handle| meshes.get(handle))
+            .filter_map(|mesh| gltf_primitive(mesh, material))
+            .map(|mut primitive| {
+                if slope_coloring.enabled {
+                    let colors = primitive
+                        .positions
+                        .iter()
+                        .zip(&primitive.normals)
+                        .map(|(position, normal)| {
+                            slope_coloring.albedo(*normal, origin.y + position.y)
+                        })
+                        .collect();
+                    primitive.colors = Some(colors);
+                }
+                triangles += primitive.indices.len() / 3;
+                primitive
+            })
+            .collect();
+        let IVec3 { x, y, z } = coord.0;
+        scene.nodes.push(GltfNode {
+            name: format!("chunk_{x}_{y}_{z}"),
+            translation: origin,
+            primitives,
+        });
+    }
+    // stable order for the diffs between exports
+    scene.nodes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let path = settings.directory.join("terrain.glb");
+    let result = File::create(&path).and_then(|file| write_glb(&scene, BufWriter::new(file)));
+    match result {
+        Ok(()) => info!(
+            "Exported {} chunks and {triangles} triangles to {path:?}",
+            scene.nodes.len()
+        ),
+        Err(err) => error!("Failed to export the meshes: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_keep_the_surface_facing_out() {
+        // triangle facing up
+        let mut mesh = IndexedMesh {
+            positions: vec![Vec3::ZERO, Vec3::Z, Vec3::X],
+            normals: vec![Vec3::Y; 3],
+            indices: vec![0, 1, 2],
+        };
+        let orientation = MeshOrientation {
+            winding: Winding::Clockwise,
+            up: UpAxis::Z,
+        };
+        orientation.apply(&mut mesh);
+        assert_eq!(mesh.normals, vec![Vec3::Z; 3]);
+        assert_eq!(mesh.positions[1], -Vec3::Y);
+        assert_eq!(mesh.indices, vec![0, 2, 1]);
+        // seen from above the vertices now turn clockwise
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[mesh.indices[i] as usize]);
+        assert!((b - a).cross(c - a).z < 0.0);
+
+        let mut obj = Vec::new();
+        write_obj(&mesh, &mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(obj.lines().count(), 7);
+        assert_eq!(obj.lines().last(), Some("f 1//1 3//3 2//2"));
+    }
+}
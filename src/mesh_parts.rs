@@ -0,0 +1,170 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        primitives::Aabb,
+    },
+    utils::HashMap,
+};
+
+/// Mesh holding the triangles of a chunk past the vertex limit of its own
+/// mesh, spawned as a child of the chunk. See [`split_mesh`].
+#[derive(Component)]
+pub struct ChunkMeshPart;
+
+/// Child entities holding the [`ChunkMeshPart`]s of a chunk
+#[derive(Component, Default)]
+pub struct ChunkMeshParts(pub Vec<Entity>);
+
+/// Splits a triangle list into meshes of at most `max_vertices` vertices,
+/// or returns it unchanged when it fits.
+///
+/// The triangles stay in their order and each part only keeps the vertices
+/// it uses. Parts of at most 65536 vertices use 16 bit indices, the limit
+/// of some WebGL devices.
+pub fn split_mesh(mesh: Mesh, max_vertices: usize) -> Vec<Mesh> {
+    let max_vertices = max_vertices.max(3);
+    let vertex_count = mesh.count_vertices();
+    if vertex_count <= max_vertices {
+        return vec![mesh];
+    }
+    let positions = float32x3(&mesh, Mesh::ATTRIBUTE_POSITION);
+    let normals = float32x3(&mesh, Mesh::ATTRIBUTE_NORMAL);
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+        _ => vec![[0.0, 0.0]; vertex_count],
+    };
+    let indices: Vec<u32> = match mesh.indices() {
+        Some(indices) => indices.iter().map(|i| i as u32).collect(),
+        None => (0..vertex_count as u32).collect(),
+    };
+
+    let mut parts = Vec::new();
+    let mut part = MeshPart::default();
+    for triangle in indices.chunks_exact(3) {
+        let new_vertices = triangle
+            .iter()
+            .filter(|i| !part.remap.contains_key(*i))
+            .count();
+        if part.remap.len() + new_vertices > max_vertices {
+            parts.push(std::mem::take(&mut part).into_mesh(max_vertices));
+        }
+        for &i in triangle {
+            let index = *part.remap.entry(i).or_insert_with(|| {
+                let i = i as usize;
+                part.positions.push(positions[i]);
+                part.normals.push(normals[i]);
+                part.uvs.push(uvs[i]);
+                part.positions.len() as u32 - 1
+            });
+            part.indices.push(index);
+        }
+    }
+    if !part.indices.is_empty() {
+        parts.push(part.into_mesh(max_vertices));
+    }
+    parts
+}
+
+fn float32x3(mesh: &Mesh, name: &'static str) -> Vec<[f32; 3]> {
+    match mesh.attribute(name) {
+        Some(VertexAttributeValues::Float32x3(values)) => values.clone(),
+        _ => vec![[0.0; 3]; mesh.count_vertices()],
+    }
+}
+
+#[derive(Default)]
+struct MeshPart {
+    /// Index of each vertex of the split mesh in the part
+    remap: HashMap<u32, u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshPart {
+    fn into_mesh(self, max_vertices: usize) -> Mesh {
+        let indices = if max_vertices <= u16::MAX as usize + 1 {
+            Indices::U16(self.indices.iter().map(|i| *i as u16).collect())
+        } else {
+            Indices::U32(self.indices)
+        };
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_indices(Some(indices));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh
+    }
+}
+
+/// Replaces the [`ChunkMeshPart`]s of a chunk with children holding `parts`
+pub fn spawn_mesh_parts(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    chunk: Entity,
+    old_parts: Option<&ChunkMeshParts>,
+    parts: Vec<Mesh>,
+) {
+    for part in old_parts.iter().flat_map(|parts| &parts.0) {
+        commands.entity(*part).despawn_recursive();
+    }
+    if parts.is_empty() {
+        if old_parts.is_some() {
+            commands.entity(chunk).remove::<ChunkMeshParts>();
+        }
+        return;
+    }
+    let mut entities = Vec::with_capacity(parts.len());
+    commands.entity(chunk).with_children(|children| {
+        for part in parts {
+            let aabb = part.compute_aabb().unwrap_or_default();
+            let entity = children
+                .spawn_bundle((
+                    meshes.add(part),
+                    aabb,
+                    Transform::default(),
+                    GlobalTransform::default(),
+                    Visibility::default(),
+                    ComputedVisibility::default(),
+                ))
+                .insert(ChunkMeshPart)
+                .id();
+            entities.push(entity);
+        }
+    });
+    commands.entity(chunk).insert(ChunkMeshParts(entities));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{ChunkMesh, NormalMode};
+
+    #[test]
+    fn parts_respect_the_vertex_limit() {
+        // a strip of 10 quads sharing their edges, 22 vertices
+        let triangles = (0..10)
+            .flat_map(|i| {
+                let x = i as f32;
+                let (a, b) = (Vec3::new(x, 0.0, 0.0), Vec3::new(x + 1.0, 0.0, 0.0));
+                let (c, d) = (Vec3::new(x, 0.0, 1.0), Vec3::new(x + 1.0, 0.0, 1.0));
+                [[a, c, b], [b, c, d]]
+            })
+            .collect();
+        let mesh = Mesh::from(ChunkMesh { triangles }.indexed(NormalMode::Smooth, |_| None));
+        assert_eq!(mesh.count_vertices(), 22);
+        assert_eq!(split_mesh(mesh.clone(), 64).len(), 1);
+
+        let parts = split_mesh(mesh, 8);
+        assert!(parts.len() > 1);
+        let mut triangles = 0;
+        for part in &parts {
+            assert!(part.count_vertices() <= 8);
+            assert!(matches!(part.indices(), Some(Indices::U16(_))));
+            triangles += part.indices().unwrap().len() / 3;
+        }
+        assert_eq!(triangles, 20);
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    camera::FlyCam, chunk::ChunkStatus, field::DensityField, generation::WorldSettings,
+    marching_squares::slice_contour, terrain_root::MainTerrain, Data,
+};
+
+const MINIMAP_SIZE: f32 = 200.0;
+/// Samples of the terrain outline along the longest side of the map
+const OUTLINE_RESOLUTION: f32 = 64.0;
+
+fn status_color(status: ChunkStatus) -> egui::Color32 {
+    match status {
+        ChunkStatus::Dirty => egui::Color32::from_rgb(230, 160, 30),
+        ChunkStatus::Meshing => egui::Color32::from_rgb(60, 120, 230),
+        ChunkStatus::Loaded => egui::Color32::from_rgb(60, 180, 60),
+        ChunkStatus::Empty => egui::Color32::from_gray(60),
+    }
+}
+
+/// Chunks stacked on the Y axis share a cell, show the one that needs the most work
+fn status_priority(status: ChunkStatus) -> u8 {
+    match status {
+        ChunkStatus::Dirty => 3,
+        ChunkStatus::Meshing => 2,
+        ChunkStatus::Loaded => 1,
+        ChunkStatus::Empty => 0,
+    }
+}
+
+/// Top-down view of the chunk grid colored by chunk status with the camera
+/// position, and the outline of the terrain at the height of the camera
+pub fn minimap(
+    mut egui_context: ResMut<EguiContext>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    data: Res<Data>,
+    field: DensityField,
+    chunks: Query<&ChunkStatus>,
+    camera: Query<&GlobalTransform, With<FlyCam>>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let min = chunk_map.min();
+    let dimensions = chunk_map.dimensions();
+    let cell_size = MINIMAP_SIZE / dimensions.x.max(dimensions.z).max(1) as f32;
+
+    let mut cells = bevy::utils::HashMap::<IVec2, ChunkStatus>::default();
+    for (coord, entity) in chunk_map.iter() {
+        let status = match chunks.get(entity) {
+            Ok(status) => status,
+            Err(_) => continue,
+        };
+        let cell = IVec2::new(coord.x - min.x, coord.z - min.z);
+        let entry = cells.entry(cell).or_insert(*status);
+        if status_priority(*status) > status_priority(*entry) {
+            *entry = *status;
+        }
+    }
+
+    egui::Window::new("Minimap")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            let (response, painter) = ui.allocate_painter(
+                egui::Vec2::new(
+                    cell_size * dimensions.x as f32,
+                    cell_size * dimensions.z as f32,
+                ),
+                egui::Sense::hover(),
+            );
+            let origin = response.rect.min;
+
+            for (cell, status) in &cells {
+                let rect = egui::Rect::from_min_size(
+                    origin + egui::Vec2::new(cell.x as f32, cell.y as f32) * cell_size,
+                    egui::Vec2::splat(cell_size),
+                );
+                painter.rect_filled(rect.shrink(1.0), 0.0, status_color(*status));
+            }
+
+            if let Ok(transform) = camera.get_single() {
+                let extent = world_settings.chunk_extent();
+                let chunk_size = Vec2::new(extent.x, extent.z);
+                let world_min = Vec2::new(min.x as f32, min.z as f32) * chunk_size;
+                let to_map = |pos: Vec3| {
+                    let p = (Vec2::new(pos.x, pos.z) - world_min) / chunk_size * cell_size;
+                    origin + egui::Vec2::new(p.x, p.y)
+                };
+
+                let world_size = dimensions.as_vec3() * extent;
+                let spacing = (world_size.x.max(world_size.z) / OUTLINE_RESOLUTION)
+                    .max(world_settings.cell_size);
+                let height = transform.translation.y;
+                for (start, end) in slice_contour(&field, height, data.isolevel, spacing) {
+                    painter.line_segment([to_map(start), to_map(end)], (1.0, egui::Color32::WHITE));
+                }
+
+                let position = to_map(transform.translation);
+                let forward = transform.forward();
+                let facing = to_map(transform.translation + forward.normalize_or_zero() * 8.0);
+                painter.line_segment([position, facing], (2.0, egui::Color32::WHITE));
+                painter.circle_filled(position, 4.0, egui::Color32::RED);
+            }
+
+            ui.horizontal(|ui| {
+                for status in [
+                    ChunkStatus::Loaded,
+                    ChunkStatus::Dirty,
+                    ChunkStatus::Meshing,
+                    ChunkStatus::Empty,
+                ] {
+                    ui.colored_label(status_color(status), format!("{status:?}"));
+                }
+            });
+        });
+}
@@ -0,0 +1,275 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::VertexAttributeValues,
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, ShaderStages,
+        },
+        renderer::RenderDevice,
+    },
+};
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
+
+use crate::MarchingCubesSystem;
+
+pub const ORE_VEINS_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x6c1e_93a0_d427_b58f);
+
+/// Secondary scalar field of the world, independent of the density, that
+/// places resource veins in the terrain.
+///
+/// The concentration at a point is read with [`OreLayer::resource_at`].
+#[derive(Inspectable)]
+pub struct OreSettings {
+    /// Colors the terrain by the concentration of the veins instead of
+    /// using the material assigned to the chunks
+    pub show_veins: bool,
+    /// Seed of the ore noise, separate from the seed of the terrain
+    pub seed: u32,
+    #[inspectable(min = 1, max = 8)]
+    pub octaves: usize,
+    /// Number of noise cycles per world unit
+    #[inspectable(min = 0.001, max = 1.0, speed = 0.005)]
+    pub frequency: f64,
+    /// Points where the ridged noise is below the threshold have no ore,
+    /// higher thresholds give thinner and rarer veins
+    #[inspectable(min = 0.0, max = 0.99, speed = 0.01)]
+    pub threshold: f32,
+    /// Color of the terrain without ore
+    pub rock: Color,
+    /// Color of the terrain at the full concentration
+    pub vein: Color,
+}
+
+impl Default for OreSettings {
+    fn default() -> Self {
+        Self {
+            show_veins: false,
+            seed: 7,
+            octaves: 3,
+            frequency: 0.05,
+            threshold: 0.85,
+            rock: Color::rgb(0.45, 0.43, 0.4),
+            vein: Color::rgb(0.85, 0.55, 0.15),
+        }
+    }
+}
+
+impl OreSettings {
+    pub fn layer(&self) -> OreLayer {
+        OreLayer {
+            fbm: Fbm::new()
+                .set_seed(self.seed)
+                .set_octaves(self.octaves)
+                .set_frequency(self.frequency),
+            threshold: self.threshold,
+        }
+    }
+}
+
+/// Ore field built from the [`OreSettings`], kept up to date as a resource
+/// so games can query it
+pub struct OreLayer {
+    fbm: Fbm,
+    threshold: f32,
+}
+
+impl FromWorld for OreLayer {
+    fn from_world(world: &mut World) -> Self {
+        world
+            .get_resource_or_insert_with(OreSettings::default)
+            .layer()
+    }
+}
+
+impl OreLayer {
+    /// Concentration of ore at `pos` in world space, from 0 outside of the
+    /// veins to 1 at their center.
+    ///
+    /// The concentration doesn't depend on the density, check the density
+    /// field to know if the point is solid.
+    pub fn resource_at(&self, pos: Vec3) -> f32 {
+        if self.threshold >= 1.0 {
+            return 0.0;
+        }
+        let p = pos.as_dvec3();
+        let noise = self.fbm.get([p.x, p.y, p.z]) as f32;
+        // ridges where the noise crosses zero form thin sheets
+        let ridge = 1.0 - noise.abs();
+        ((ridge - self.threshold) / (1.0 - self.threshold)).clamp(0.0, 1.0)
+    }
+
+    /// Stores the concentration at each vertex of a chunk mesh in the U
+    /// coordinate, where the [`OreMaterial`] reads it
+    pub fn write_concentrations(&self, mesh: &mut Mesh, origin: Vec3) {
+        let uvs: Vec<[f32; 2]> = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions
+                .iter()
+                .map(|position| [self.resource_at(origin + Vec3::from(*position)), 0.0])
+                .collect(),
+            _ => return,
+        };
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    }
+}
+
+pub struct OrePlugin;
+
+impl Plugin for OrePlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            ORE_VEINS_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/ore_veins.wgsl")),
+        );
+
+        app.add_plugin(MaterialPlugin::<OreMaterial>::default())
+            .add_plugin(InspectorPlugin::<OreSettings>::new())
+            .init_resource::<OreLayer>()
+            .init_resource::<OreMaterialHandle>()
+            .add_system(update_ore_layer.before(MarchingCubesSystem::MeshApply));
+    }
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "3f5b8e21-7ad4-4c96-9e0b-d21c47a8f653"]
+pub struct OreMaterial {
+    pub rock: Color,
+    pub vein: Color,
+}
+
+impl From<&OreSettings> for OreMaterial {
+    fn from(settings: &OreSettings) -> Self {
+        Self {
+            rock: settings.rock,
+            vein: settings.vein,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GpuOreMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for OreMaterial {
+    type ExtractedAsset = OreMaterial;
+    type PreparedAsset = GpuOreMaterial;
+    type Param = (SRes<RenderDevice>, SRes<MaterialPipeline<Self>>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        // matches the OreMaterial struct of the shader
+        let uniform: Vec<f32> = [material.rock, material.vein]
+            .iter()
+            .flat_map(|color| color.as_linear_rgba_f32())
+            .collect();
+        let contents: Vec<u8> = uniform.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("ore_material_uniform"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("ore_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(GpuOreMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for OreMaterial {
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(ORE_VEINS_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("ore_material_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(8 * 4),
+                },
+                count: None,
+            }],
+        })
+    }
+}
+
+/// Material shared by every chunk while the veins are shown
+pub struct OreMaterialHandle(pub Handle<OreMaterial>);
+
+impl FromWorld for OreMaterialHandle {
+    fn from_world(world: &mut World) -> Self {
+        let material = OreMaterial::from(&*world.get_resource_or_insert_with(OreSettings::default));
+        let mut materials = world.get_resource_mut::<Assets<OreMaterial>>().unwrap();
+        Self(materials.add(material))
+    }
+}
+
+fn update_ore_layer(
+    settings: Res<OreSettings>,
+    handle: Res<OreMaterialHandle>,
+    mut layer: ResMut<OreLayer>,
+    mut materials: ResMut<Assets<OreMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    *layer = settings.layer();
+    if let Some(material) = materials.get_mut(&handle.0) {
+        *material = OreMaterial::from(&*settings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concentration_is_normalized() {
+        let layer = OreSettings::default().layer();
+        let mut veins = 0;
+        for i in 0..1000 {
+            let pos = Vec3::new(i as f32 * 0.37, (i % 17) as f32, (i % 31) as f32 * 1.3);
+            let concentration = layer.resource_at(pos);
+            assert!((0.0..=1.0).contains(&concentration));
+            assert_eq!(concentration, layer.resource_at(pos));
+            if concentration > 0.0 {
+                veins += 1;
+            }
+        }
+        // veins are rare but present
+        assert!(veins > 0 && veins < 500);
+    }
+}
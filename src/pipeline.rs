@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    field::DensitySource,
+    generation::{fill_points, NoiseSettings, WorldSettings, WrapPeriod, EMPTY},
+};
+
+/// One step of a [`GenerationPipeline`]
+#[derive(Clone)]
+pub enum GenerationStage {
+    /// Density of the world generation, the noise layers, cells and caves
+    /// of the global settings
+    World,
+    /// Replaces the density with noise layers of their own, shaped by the
+    /// [`WorldSettings`] like the world generation
+    Noise(NoiseSettings),
+    /// Replaces the density where the source has a value, sampled at the
+    /// world position of each point
+    Density(Arc<dyn DensitySource + Send + Sync>),
+    /// Maps the density of each point, given its world position
+    PostProcess(Arc<dyn Fn(Vec3, f32) -> f32 + Send + Sync>),
+}
+
+/// Generates the points of a chunk with its own stages instead of the world
+/// generation, so special regions like an arena or a spawn area can use
+/// different generators inside one world.
+///
+/// The stages run in order starting from empty points. Adding, changing or
+/// removing the component generates the points of the chunk again.
+#[derive(Component, Clone, Default)]
+pub struct GenerationPipeline {
+    pub stages: Vec<GenerationStage>,
+}
+
+impl GenerationPipeline {
+    pub fn with_stage(mut self, stage: GenerationStage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn with_world(self) -> Self {
+        self.with_stage(GenerationStage::World)
+    }
+
+    pub fn with_noise(self, settings: NoiseSettings) -> Self {
+        self.with_stage(GenerationStage::Noise(settings))
+    }
+
+    pub fn with_density(self, source: impl DensitySource + Send + Sync + 'static) -> Self {
+        self.with_stage(GenerationStage::Density(Arc::new(source)))
+    }
+
+    pub fn with_post_process(
+        self,
+        process: impl Fn(Vec3, f32) -> f32 + Send + Sync + 'static,
+    ) -> Self {
+        self.with_stage(GenerationStage::PostProcess(Arc::new(process)))
+    }
+
+    /// Fills `buffer` with the points of a chunk of `size` cells starting at
+    /// the grid point `origin`, `world` fills it with the world generation
+    pub fn fill(
+        &self,
+        buffer: &mut Vec<f32>,
+        origin: IVec3,
+        size: UVec3,
+        world_settings: &WorldSettings,
+        wrap: Option<WrapPeriod>,
+        world: impl Fn(&mut Vec<f32>),
+    ) {
+        buffer.clear();
+        buffer.resize(Chunk::points_len(size), EMPTY);
+        let cell_size = world_settings.cell_size;
+        let positions = || {
+            Chunk::new_iter_3d(size)
+                .map(move |point| (origin + point.as_ivec3()).as_vec3() * cell_size)
+        };
+        for stage in &self.stages {
+            match stage {
+                GenerationStage::World => world(buffer),
+                GenerationStage::Noise(settings) => fill_points(
+                    buffer,
+                    &settings.stack(),
+                    origin,
+                    size,
+                    settings,
+                    world_settings,
+                    wrap,
+                ),
+                GenerationStage::Density(source) => {
+                    for (value, pos) in buffer.iter_mut().zip(positions()) {
+                        if let Some(density) = source.density_at(pos) {
+                            *value = density;
+                        }
+                    }
+                }
+                GenerationStage::PostProcess(process) => {
+                    for (value, pos) in buffer.iter_mut().zip(positions()) {
+                        *value = process(pos, *value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_run_in_order() {
+        let size = UVec3::splat(2);
+        let world_settings = WorldSettings::default();
+        let pipeline = GenerationPipeline::default()
+            .with_world()
+            .with_density(|pos: Vec3| pos.x)
+            .with_post_process(|pos, value| if pos.y > 0.0 { value * 2.0 } else { value });
+        let mut buffer = Vec::new();
+        pipeline.fill(
+            &mut buffer,
+            IVec3::new(1, 0, 0),
+            size,
+            &world_settings,
+            None,
+            |buffer| buffer.fill(-1.0),
+        );
+        let cell_size = world_settings.cell_size;
+        for (value, point) in buffer.iter().zip(Chunk::new_iter_3d(size)) {
+            let x = (point.x + 1) as f32 * cell_size;
+            let expected = if point.y > 0 { x * 2.0 } else { x };
+            assert_eq!(*value, expected);
+        }
+
+        // the world generation doesn't run without its stage
+        GenerationPipeline::default().fill(
+            &mut buffer,
+            IVec3::ZERO,
+            size,
+            &world_settings,
+            None,
+            |_| panic!("the world generation ran"),
+        );
+        assert!(buffer.iter().all(|value| *value == EMPTY));
+    }
+}
@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContext, Inspectable};
+
+use crate::{
+    brush::cursor_hit, camera::FlyCam, chunk::Chunk, field::DensityField,
+    generation::WorldSettings, Data,
+};
+
+/// Longest ray marched in the field to find the surface under the cursor
+const MAX_DISTANCE: f32 = 500.0;
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlacementKind {
+    Crate,
+    Tree,
+    Marker,
+}
+
+impl PlacementKind {
+    pub const ALL: [PlacementKind; 3] = [
+        PlacementKind::Crate,
+        PlacementKind::Tree,
+        PlacementKind::Marker,
+    ];
+
+    /// Distance from the surface to the center of the object
+    pub fn height(self) -> f32 {
+        match self {
+            PlacementKind::Crate => 0.5,
+            PlacementKind::Tree => 1.3,
+            PlacementKind::Marker => 0.25,
+        }
+    }
+
+    fn mesh(self) -> Mesh {
+        match self {
+            PlacementKind::Crate => Mesh::from(shape::Cube { size: 1.0 }),
+            PlacementKind::Tree => Mesh::from(shape::Capsule {
+                radius: 0.3,
+                depth: 2.0,
+                ..default()
+            }),
+            PlacementKind::Marker => Mesh::from(shape::Icosphere {
+                radius: 0.25,
+                subdivisions: 2,
+            }),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PlacementKind::Crate => Color::rgb(0.55, 0.4, 0.2),
+            PlacementKind::Tree => Color::rgb(0.2, 0.5, 0.2),
+            PlacementKind::Marker => Color::RED,
+        }
+    }
+}
+
+/// Press G to toggle the placement mode, then click the terrain to place the
+/// object previewed under the cursor.
+///
+/// Placed objects are children of the chunk under them. They follow the
+/// surface when the chunk is edited and are removed when the surface under
+/// them is carved away.
+#[derive(Inspectable)]
+pub struct Placement {
+    pub enabled: bool,
+    pub kind: PlacementKind,
+    /// Objects follow the surface when it moves less than this distance
+    /// along their normal, farther they are removed
+    #[inspectable(min = 0.1, max = 16.0)]
+    pub max_snap: f32,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: PlacementKind::Crate,
+            max_snap: 2.0,
+        }
+    }
+}
+
+/// Object placed on the terrain
+#[derive(Component)]
+pub struct PlacedObject {
+    pub kind: PlacementKind,
+    /// Normal of the surface under the object
+    pub normal: Vec3,
+}
+
+/// Translucent copy of the object to place, shown under the cursor
+#[derive(Component)]
+pub struct PlacementPreview;
+
+/// Meshes and materials of each [`PlacementKind`], in the order of [`PlacementKind::ALL`]
+pub struct PlacementAssets {
+    meshes: [Handle<Mesh>; 3],
+    materials: [Handle<StandardMaterial>; 3],
+    preview: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PlacementAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut mesh_assets = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+        let meshes = PlacementKind::ALL.map(|kind| mesh_assets.add(kind.mesh()));
+        let mut material_assets = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        let materials = PlacementKind::ALL.map(|kind| material_assets.add(kind.color().into()));
+        let preview = material_assets.add(StandardMaterial {
+            base_color: Color::rgba(1.0, 1.0, 1.0, 0.4),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        Self {
+            meshes,
+            materials,
+            preview,
+        }
+    }
+}
+
+/// Transform of an object standing on the surface at `point`, with its up
+/// axis along the surface normal
+pub fn surface_transform(kind: PlacementKind, point: Vec3, normal: Vec3) -> Transform {
+    Transform::from_translation(point + normal * kind.height())
+        .with_rotation(Quat::from_rotation_arc(Vec3::Y, normal))
+}
+
+fn surface_normal(field: &DensityField, point: Vec3) -> Vec3 {
+    field
+        .normal(point)
+        .filter(|normal| *normal != Vec3::ZERO)
+        .unwrap_or(Vec3::Y)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_placement(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut placement: ResMut<Placement>,
+    assets: Res<PlacementAssets>,
+    data: Res<Data>,
+    world_settings: Res<WorldSettings>,
+    windows: Res<Windows>,
+    mut egui_context: ResMut<EguiContext>,
+    field: DensityField,
+    cameras: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    chunk_transforms: Query<&GlobalTransform, With<Chunk>>,
+    mut previews: Query<
+        (&mut Transform, &mut Visibility, &mut Handle<Mesh>),
+        With<PlacementPreview>,
+    >,
+    mut preview_entity: Local<Option<Entity>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::G) {
+        placement.enabled = !placement.enabled;
+    }
+
+    let hit = if placement.enabled {
+        cursor_hit(&windows, &cameras, &field, data.isolevel, MAX_DISTANCE)
+    } else {
+        None
+    };
+    let kind_index = PlacementKind::ALL
+        .iter()
+        .position(|kind| *kind == placement.kind)
+        .unwrap();
+    let target = hit.map(|hit| surface_transform(placement.kind, hit, surface_normal(&field, hit)));
+
+    match preview_entity.and_then(|entity| previews.get_mut(entity).ok()) {
+        Some((mut transform, mut visibility, mut mesh)) => {
+            if visibility.is_visible != target.is_some() {
+                visibility.is_visible = target.is_some();
+            }
+            if let Some(target) = target {
+                *transform = target;
+            }
+            if *mesh != assets.meshes[kind_index] {
+                *mesh = assets.meshes[kind_index].clone();
+            }
+        }
+        None => {
+            *preview_entity = Some(
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: assets.meshes[kind_index].clone(),
+                        material: assets.preview.clone(),
+                        visibility: Visibility { is_visible: false },
+                        ..default()
+                    })
+                    .insert(PlacementPreview)
+                    .id(),
+            );
+        }
+    }
+
+    let (hit, target) = match (hit, target) {
+        (Some(hit), Some(target)) if mouse_input.just_pressed(MouseButton::Left) => (hit, target),
+        _ => return,
+    };
+    if egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let coord = (hit / world_settings.chunk_extent()).floor().as_ivec3();
+    let chunk = match field.chunk_map().and_then(|chunk_map| chunk_map.get(coord)) {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let chunk_translation = match chunk_transforms.get(chunk) {
+        Ok(transform) => transform.translation,
+        Err(_) => return,
+    };
+
+    let object = commands
+        .spawn_bundle(PbrBundle {
+            mesh: assets.meshes[kind_index].clone(),
+            material: assets.materials[kind_index].clone(),
+            transform: Transform {
+                translation: target.translation - chunk_translation,
+                ..target
+            },
+            ..default()
+        })
+        .insert(PlacedObject {
+            kind: placement.kind,
+            normal: target.rotation * Vec3::Y,
+        })
+        .id();
+    commands.entity(chunk).push_children(&[object]);
+}
+
+/// Moves the objects of the edited chunks back onto the surface, or removes
+/// them when there is no surface within `max_snap` of where they were
+pub fn settle_placed_objects(
+    mut commands: Commands,
+    placement: Res<Placement>,
+    data: Res<Data>,
+    field: DensityField,
+    changed_chunks: Query<(&Children, &GlobalTransform), Changed<Chunk>>,
+    mut objects: Query<(&mut Transform, &mut PlacedObject)>,
+) {
+    for (children, chunk_transform) in changed_chunks.iter() {
+        for &child in children.iter() {
+            let (mut transform, mut object) = match objects.get_mut(child) {
+                Ok(object) => object,
+                Err(_) => continue,
+            };
+            let surface = chunk_transform.translation + transform.translation
+                - object.normal * object.kind.height();
+            // search the surface on both sides of where it was
+            let start = surface + object.normal * placement.max_snap;
+            let hit = if field.is_solid(start, data.isolevel) {
+                // buried deeper than the snap distance
+                None
+            } else {
+                field.raycast(
+                    start,
+                    -object.normal,
+                    2.0 * placement.max_snap,
+                    data.isolevel,
+                )
+            };
+            match hit {
+                Some(hit) => {
+                    let normal = surface_normal(&field, hit);
+                    let target = surface_transform(object.kind, hit, normal);
+                    transform.translation = target.translation - chunk_transform.translation;
+                    transform.rotation = target.rotation;
+                    object.normal = normal;
+                }
+                None => commands.entity(child).despawn_recursive(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_stand_on_the_surface() {
+        let point = Vec3::new(1.0, 2.0, 3.0);
+        let normal = Vec3::new(0.0, 1.0, 1.0).normalize();
+        for kind in PlacementKind::ALL {
+            let transform = surface_transform(kind, point, normal);
+            assert!((transform.rotation * Vec3::Y).abs_diff_eq(normal, 1e-5));
+            let bottom = transform.translation - transform.rotation * Vec3::Y * kind.height();
+            assert!(bottom.abs_diff_eq(point, 1e-5));
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    chunk::{is_inside, Chunk},
+    terrain_root::MainTerrain,
+    Data, StartMarching,
+};
+
+/// Table of the point values of a Z slice of the selected chunk, edits are
+/// applied to the chunk and remeshed right away.
+///
+/// Handy to build specific marching cube cases by hand.
+pub fn point_editor_ui(
+    mut egui_context: ResMut<EguiContext>,
+    terrain: MainTerrain,
+    data: Res<Data>,
+    mut chunks: Query<&mut Chunk>,
+    mut slice: Local<u32>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let mut chunk = match terrain
+        .selected_chunk()
+        .and_then(|entity| chunks.get_mut(entity).ok())
+    {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let size = chunk.size;
+    *slice = (*slice).min(size.z);
+
+    let mut edits = Vec::new();
+    egui::Window::new("Point editor")
+        .default_open(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut *slice, 0..=size.z).text("Z slice"));
+                if ui.button("Fill empty").clicked() {
+                    edits.extend(slice_points(size, *slice).map(|point| (point, 0.0)));
+                }
+                if ui.button("Fill solid").clicked() {
+                    edits.extend(slice_points(size, *slice).map(|point| (point, 1.0)));
+                }
+            });
+            ui.label(
+                "Solid points are at or above the isolevel, X grows to the right and Y upward",
+            );
+
+            egui::ScrollArea::both().show(ui, |ui| {
+                egui::Grid::new("point_editor").show(ui, |ui| {
+                    ui.label("");
+                    for x in 0..=size.x {
+                        ui.strong(format!("x {x}"));
+                    }
+                    ui.end_row();
+
+                    for y in (0..=size.y).rev() {
+                        ui.strong(format!("y {y}"));
+                        for x in 0..=size.x {
+                            let point = UVec3::new(x, y, *slice);
+                            let mut value = chunk.get(point.as_vec3());
+                            let solid = is_inside(value, data.isolevel);
+                            let response = ui.add(
+                                egui::DragValue::new(&mut value)
+                                    .speed(0.01)
+                                    .clamp_range(0.0..=1.0)
+                                    .max_decimals(3),
+                            );
+                            let response = if solid {
+                                response.highlight()
+                            } else {
+                                response
+                            };
+                            if response.changed() {
+                                edits.push((point, value));
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+
+    if !edits.is_empty() {
+        for (point, value) in edits {
+            chunk.set(point.as_vec3(), value);
+        }
+        start_marching_events.send_default();
+    }
+}
+
+fn slice_points(size: UVec3, z: u32) -> impl Iterator<Item = UVec3> {
+    (0..=size.y).flat_map(move |y| (0..=size.x).map(move |x| UVec3::new(x, y, z)))
+}
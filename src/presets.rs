@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    chunk::{ActiveCells, Chunk, ChunkMesh, ChunkStatus, MeshedFrom},
+    generation::WorldSettings,
+    materials::{MaterialLibrary, TERRAIN},
+    transition::ChunkTransition,
+    StartMarching, CELL_CORNERS,
+};
+
+/// Corner values of a single cell, for the classic marching cube configurations
+#[derive(Clone, Copy, Debug)]
+pub struct CellPreset {
+    pub name: &'static str,
+    /// Bit `i` is set when the corner `i` of [`CELL_CORNERS`] is solid
+    pub solid: u8,
+}
+
+impl CellPreset {
+    const fn new(name: &'static str, corners: &[u8]) -> Self {
+        let mut solid = 0;
+        let mut i = 0;
+        while i < corners.len() {
+            solid |= 1 << corners[i];
+            i += 1;
+        }
+        Self { name, solid }
+    }
+
+    /// Same configuration with the solid and empty corners swapped
+    const fn complement(name: &'static str, preset: CellPreset) -> Self {
+        Self {
+            name,
+            solid: !preset.solid,
+        }
+    }
+
+    /// Chunk of a single cell with the solid corners at 1 and the empty ones at 0
+    pub fn chunk(&self) -> Chunk {
+        Chunk::from_fn(UVec3::ONE, |point| {
+            let corner = CELL_CORNERS
+                .iter()
+                .position(|corner| *corner == point.as_vec3())
+                .unwrap();
+            if self.solid & 1 << corner != 0 {
+                1.0
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+const CASE_3: CellPreset = CellPreset::new("3: face diagonal", &[0, 2]);
+const CASE_4: CellPreset = CellPreset::new("4: body diagonal", &[0, 6]);
+const CASE_6: CellPreset = CellPreset::new("6: edge and far corner", &[0, 1, 6]);
+const CASE_7: CellPreset = CellPreset::new("7: three face diagonals", &[1, 3, 4]);
+const CASE_10: CellPreset = CellPreset::new("10: opposite edges", &[0, 2, 4, 6]);
+const CASE_12: CellPreset = CellPreset::new("12: face corners and corner", &[1, 2, 3, 4]);
+const CASE_13: CellPreset = CellPreset::new("13: alternating corners", &[0, 2, 5, 7]);
+
+/// The 15 base configurations of Lorensen and Cline, then the complements of
+/// the configurations with an ambiguous face or interior. The complements of
+/// 10 and 12 are rotations of the configurations themselves, they have no
+/// preset of their own.
+pub const CELL_PRESETS: [CellPreset; 19] = [
+    CellPreset::new("0: empty", &[]),
+    CellPreset::new("1: corner", &[0]),
+    CellPreset::new("2: edge", &[0, 1]),
+    CASE_3,
+    CASE_4,
+    CellPreset::new("5: three face corners", &[0, 1, 2]),
+    CASE_6,
+    CASE_7,
+    CellPreset::new("8: face", &[0, 1, 2, 3]),
+    CellPreset::new("9: corner and neighbors", &[0, 1, 3, 4]),
+    CASE_10,
+    CellPreset::new("11: twisted chain", &[0, 1, 2, 6]),
+    CASE_12,
+    CASE_13,
+    CellPreset::new("14: mirrored twisted chain", &[0, 1, 3, 7]),
+    CellPreset::complement("3 complement (ambiguous)", CASE_3),
+    CellPreset::complement("4 complement (ambiguous)", CASE_4),
+    CellPreset::complement("6 complement (ambiguous)", CASE_6),
+    CellPreset::complement("7 complement (ambiguous)", CASE_7),
+];
+
+/// Index in [`CELL_PRESETS`] of the configuration of a cell with the `solid`
+/// corners, the same up to a rotation of the cell or with the solid and the
+/// empty corners swapped
+pub fn classify(solid: u8) -> usize {
+    let rotations = cell_rotations();
+    let rotate = |solid: u8, rotation: &[usize; 8]| {
+        (0..8)
+            .filter(|i| solid & 1 << i != 0)
+            .fold(0u8, |rotated, i| rotated | 1 << rotation[i])
+    };
+    // the complements of the ambiguous configurations are presets of their own
+    [solid, !solid]
+        .into_iter()
+        .find_map(|solid| {
+            CELL_PRESETS.iter().position(|preset| {
+                rotations
+                    .iter()
+                    .any(|rotation| rotate(solid, rotation) == preset.solid)
+            })
+        })
+        .expect("every configuration is a rotation of a preset or of its complement")
+}
+
+/// The 24 rotations of a cell, as the corner each corner moves to
+fn cell_rotations() -> Vec<[usize; 8]> {
+    let corner = |position: Vec3| CELL_CORNERS.iter().position(|c| *c == position).unwrap();
+    // quarter turns around the X and the Y axis through the center of the cell
+    let generators = [
+        CELL_CORNERS.map(|c| corner(Vec3::new(c.x, 1.0 - c.z, c.y))),
+        CELL_CORNERS.map(|c| corner(Vec3::new(c.z, c.y, 1.0 - c.x))),
+    ];
+    let mut rotations = vec![[0, 1, 2, 3, 4, 5, 6, 7]];
+    let mut next = 0;
+    while next < rotations.len() {
+        let rotation = rotations[next];
+        for generator in &generators {
+            let composed = rotation.map(|i| generator[i]);
+            if !rotations.contains(&composed) {
+                rotations.push(composed);
+            }
+        }
+        next += 1;
+    }
+    rotations
+}
+
+/// Index in [`CELL_PRESETS`] of the preset shown above the terrain
+#[derive(Default)]
+pub struct SelectedCellPreset(pub Option<usize>);
+
+/// Chunk of a single cell showing a [`CellPreset`]
+#[derive(Component)]
+pub struct PresetCell;
+
+pub fn cell_presets_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut selected: ResMut<SelectedCellPreset>,
+) {
+    egui::Window::new("Cell presets")
+        .default_open(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            let mut preset = selected.0;
+            let name = preset.map_or("None", |index| CELL_PRESETS[index].name);
+            egui::ComboBox::from_id_source("cell_preset")
+                .selected_text(name)
+                .width(220.0)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut preset, None, "None");
+                    for (index, cell_preset) in CELL_PRESETS.iter().enumerate() {
+                        ui.selectable_value(&mut preset, Some(index), cell_preset.name);
+                    }
+                });
+            if preset != selected.0 {
+                selected.0 = preset;
+            }
+        });
+}
+
+/// Spawns the preset cell above the terrain, or updates its corners
+pub fn update_preset_cell(
+    mut commands: Commands,
+    selected: Res<SelectedCellPreset>,
+    world_settings: Res<WorldSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material_library: Res<MaterialLibrary>,
+    mut cells: Query<(Entity, &mut Chunk), With<PresetCell>>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    if !selected.is_changed() {
+        return;
+    }
+    let preset = match selected.0 {
+        Some(index) => CELL_PRESETS[index],
+        None => {
+            for (entity, _) in cells.iter() {
+                commands.entity(entity).despawn();
+            }
+            return;
+        }
+    };
+    info!("Showing the cell preset {}", preset.name);
+
+    match cells.get_single_mut() {
+        Ok((_, mut chunk)) => {
+            let cell = preset.chunk();
+            chunk.replace_points(cell.points, cell.size);
+        }
+        Err(_) => {
+            // scaled up to be visible next to the terrain
+            let scale = 4.0;
+            let extent = world_settings.chunk_extent() * world_settings.chunk_count.as_vec3();
+            let position = Vec3::new(-scale / 2.0, extent.y + scale, -scale / 2.0);
+            let chunk_mesh = ChunkMesh::default();
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(Mesh::from(chunk_mesh.clone())),
+                    material: material_library.get(TERRAIN).unwrap().clone(),
+                    transform: Transform::from_translation(position)
+                        .with_scale(Vec3::splat(scale / world_settings.cell_size)),
+                    ..default()
+                })
+                .insert(preset.chunk())
+                .insert(Chunk::new_iter_3d(UVec3::ZERO))
+                .insert(chunk_mesh)
+                .insert(ChunkStatus::default())
+                .insert(MeshedFrom::default())
+                .insert(ActiveCells::default())
+                .insert(ChunkTransition::default())
+                .insert(PresetCell);
+        }
+    }
+    start_marching_events.send_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpolation::Interpolation, march_cube, GridCell};
+
+    #[test]
+    fn presets_are_distinct() {
+        for (i, a) in CELL_PRESETS.iter().enumerate() {
+            for b in &CELL_PRESETS[i + 1..] {
+                assert_ne!(a.solid, b.solid, "{} and {}", a.name, b.name);
+            }
+        }
+    }
+
+    #[test]
+    fn every_configuration_has_a_preset() {
+        assert_eq!(cell_rotations().len(), 24);
+        for (index, preset) in CELL_PRESETS.iter().enumerate() {
+            assert_eq!(classify(preset.solid), index, "{}", preset.name);
+        }
+        // the complements of 10 and 12 are rotations of the configurations themselves
+        assert_eq!(classify(!CASE_10.solid), 10);
+        assert_eq!(classify(!CASE_12.solid), 12);
+        for solid in 0..=255 {
+            classify(solid);
+        }
+        // a single empty corner is the complement of a corner
+        assert_eq!(classify(!0b10), 1);
+    }
+
+    #[test]
+    fn base_cases_triangle_count() {
+        // the table joins the solid corners of cases 3, 6 and 7 across their ambiguous faces
+        let expected = [0, 1, 2, 4, 2, 3, 5, 5, 2, 4, 4, 4, 4, 4, 4];
+        for (preset, expected) in CELL_PRESETS.iter().zip(expected) {
+            let chunk = preset.chunk();
+            let grid_cell = GridCell::sample(Vec3::ZERO, 1.0, &chunk);
+            let triangles = march_cube(&grid_cell, 0.5, Interpolation::Linear)
+                .map_or(0, |triangles| triangles.len());
+            assert_eq!(triangles, expected, "{}", preset.name);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    brush::edit_sphere, camera::FlyCam, chunk::Chunk, field::DensityField,
+    generation::WorldSettings, terrain_root::MainTerrain, Data, StartMarching,
+};
+
+/// Seconds before a projectile that didn't hit anything is despawned
+const PROJECTILE_LIFETIME: f32 = 10.0;
+const PROJECTILE_RADIUS: f32 = 0.3;
+
+/// Press F to fire a ball from the camera, it carves a crater where it hits
+/// the terrain and throws debris around.
+///
+/// The ball is moved by raycasts in the density field and the crater is
+/// carved with [`edit_sphere`], so it shows the destruction of the terrain
+/// from end to end.
+#[derive(Inspectable)]
+pub struct ProjectileSettings {
+    #[inspectable(min = 1.0, max = 200.0)]
+    pub speed: f32,
+    #[inspectable(min = 0.0, max = 50.0)]
+    pub gravity: f32,
+    #[inspectable(min = 0.5, max = 16.0)]
+    pub crater_radius: f32,
+    #[inspectable(min = 0, max = 256)]
+    pub debris_count: usize,
+    #[inspectable(min = 0.0, max = 50.0)]
+    pub debris_speed: f32,
+    /// Seconds before the debris disappear
+    #[inspectable(min = 0.1, max = 10.0)]
+    pub debris_lifetime: f32,
+}
+
+impl Default for ProjectileSettings {
+    fn default() -> Self {
+        Self {
+            speed: 40.0,
+            gravity: 9.81,
+            crater_radius: 3.0,
+            debris_count: 24,
+            debris_speed: 8.0,
+            debris_lifetime: 2.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub velocity: Vec3,
+    pub age: f32,
+}
+
+#[derive(Component)]
+pub struct Debris {
+    pub velocity: Vec3,
+    pub age: f32,
+}
+
+/// Sent when a projectile hits the terrain
+pub struct ProjectileImpact {
+    pub position: Vec3,
+    /// Normal of the surface at the impact
+    pub normal: Vec3,
+}
+
+/// Position and velocity after `dt` seconds of ballistic motion
+pub fn ballistic_step(position: Vec3, velocity: Vec3, gravity: f32, dt: f32) -> (Vec3, Vec3) {
+    let acceleration = Vec3::new(0.0, -gravity, 0.0);
+    (
+        position + velocity * dt + 0.5 * acceleration * dt * dt,
+        velocity + acceleration * dt,
+    )
+}
+
+/// `count` directions spread evenly over the hemisphere around `normal`
+pub fn debris_directions(normal: Vec3, count: usize) -> impl Iterator<Item = Vec3> {
+    let mut normal = normal.normalize_or_zero();
+    if normal == Vec3::ZERO {
+        normal = Vec3::Y;
+    }
+    let rotation = Quat::from_rotation_arc(Vec3::Y, normal);
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    (0..count).map(move |i| {
+        // from the top of the hemisphere down to the horizon
+        let y = 1.0 - (i as f32 + 0.5) / count as f32;
+        let radius = (1.0 - y * y).sqrt();
+        let angle = golden_angle * i as f32;
+        rotation * Vec3::new(radius * angle.cos(), y, radius * angle.sin())
+    })
+}
+
+pub fn fire_projectile(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<ProjectileSettings>,
+    camera: Query<&GlobalTransform, With<FlyCam>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F) {
+        return;
+    }
+    let transform = match camera.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let (mesh, material) = assets
+        .get_or_insert_with(|| {
+            let mesh = meshes.add(Mesh::from(shape::Icosphere {
+                radius: PROJECTILE_RADIUS,
+                ..default()
+            }));
+            (mesh, materials.add(Color::rgb(0.9, 0.6, 0.2).into()))
+        })
+        .clone();
+    let forward = transform.rotation * -Vec3::Z;
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(transform.translation + forward),
+            ..default()
+        })
+        .insert(Projectile {
+            velocity: forward * settings.speed,
+            age: 0.0,
+        });
+}
+
+pub fn move_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<ProjectileSettings>,
+    data: Res<Data>,
+    field: DensityField,
+    mut projectiles: Query<(Entity, &mut Transform, &mut Projectile)>,
+    mut impacts: EventWriter<ProjectileImpact>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut transform, mut projectile) in projectiles.iter_mut() {
+        let start = transform.translation;
+        let (end, velocity) = ballistic_step(start, projectile.velocity, settings.gravity, dt);
+        projectile.velocity = velocity;
+        projectile.age += dt;
+
+        let travel = end - start;
+        match field.raycast(start, travel, travel.length(), data.isolevel) {
+            Some(position) => {
+                impacts.send(ProjectileImpact {
+                    position,
+                    normal: field.normal(position).unwrap_or(Vec3::Y),
+                });
+                commands.entity(entity).despawn();
+            }
+            None if projectile.age > PROJECTILE_LIFETIME => commands.entity(entity).despawn(),
+            None => transform.translation = end,
+        }
+    }
+}
+
+/// Carves a crater at each impact and spawns the debris
+#[allow(clippy::too_many_arguments)]
+pub fn carve_craters(
+    mut commands: Commands,
+    settings: Res<ProjectileSettings>,
+    data: Res<Data>,
+    mut impacts: EventReader<ProjectileImpact>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut chunks: Query<&mut Chunk>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let mut carved = false;
+    for impact in impacts.iter() {
+        // below the isolevel everywhere in the radius so the crater edge is at the radius
+        carved |= edit_sphere(
+            chunk_map,
+            &mut chunks,
+            &world_settings,
+            impact.position,
+            settings.crater_radius,
+            |value, falloff| value.min(data.isolevel * (1.0 - falloff)),
+        );
+
+        let (mesh, material) = assets
+            .get_or_insert_with(|| {
+                let mesh = meshes.add(Mesh::from(shape::Cube { size: 0.2 }));
+                (mesh, materials.add(Color::rgb(0.45, 0.35, 0.25).into()))
+            })
+            .clone();
+        for direction in debris_directions(impact.normal, settings.debris_count) {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_translation(impact.position + direction * 0.3),
+                    ..default()
+                })
+                .insert(Debris {
+                    velocity: direction * settings.debris_speed,
+                    age: 0.0,
+                });
+        }
+    }
+    if carved {
+        start_marching_events.send_default();
+    }
+}
+
+/// Debris fall through the terrain and shrink until they disappear
+pub fn update_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<ProjectileSettings>,
+    mut debris: Query<(Entity, &mut Transform, &mut Debris)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut transform, mut piece) in debris.iter_mut() {
+        piece.age += dt;
+        if piece.age > settings.debris_lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let (position, velocity) =
+            ballistic_step(transform.translation, piece.velocity, settings.gravity, dt);
+        transform.translation = position;
+        transform.scale = Vec3::splat(1.0 - piece.age / settings.debris_lifetime);
+        piece.velocity = velocity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ballistic_motion_matches_closed_form() {
+        let velocity = Vec3::new(3.0, 10.0, 0.0);
+        let (mut position, mut current) = (Vec3::ZERO, velocity);
+        for _ in 0..100 {
+            (position, current) = ballistic_step(position, current, 9.81, 0.01);
+        }
+        let expected = velocity - Vec3::new(0.0, 9.81 / 2.0, 0.0);
+        assert!(position.abs_diff_eq(expected, 1e-3));
+        assert!(current.abs_diff_eq(Vec3::new(3.0, 10.0 - 9.81, 0.0), 1e-3));
+    }
+
+    #[test]
+    fn debris_leave_the_surface() {
+        let normal = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let directions: Vec<Vec3> = debris_directions(normal, 16).collect();
+        assert_eq!(directions.len(), 16);
+        for direction in directions {
+            assert!((direction.length() - 1.0).abs() < 1e-5);
+            assert!(direction.dot(normal) > 0.0);
+        }
+    }
+}
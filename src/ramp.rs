@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContext, Inspectable};
+
+use crate::{
+    brush::{cursor_hit, edit_box},
+    camera::FlyCam,
+    chunk::Chunk,
+    field::DensityField,
+    flatten::{edge_weight, surface_density},
+    generation::WorldSettings,
+    terrain_root::MainTerrain,
+    Data, StartMarching,
+};
+
+/// Longest ray marched in the field to find the clicked points
+const MAX_DISTANCE: f32 = 500.0;
+
+/// Press J to toggle the ramp tool, then click the start and the end of the
+/// ramp on the terrain.
+///
+/// The terrain above the ramp is carved and the terrain below it is filled,
+/// across every chunk on the way. The end is lowered or raised when the ramp
+/// would be steeper than `max_slope`.
+#[derive(Inspectable)]
+pub struct RampTool {
+    pub enabled: bool,
+    #[inspectable(min = 0.5, max = 64.0)]
+    pub width: f32,
+    /// Width of the strip on each side blending the ramp into the terrain
+    #[inspectable(min = 0.0, max = 32.0)]
+    pub blend: f32,
+    /// Steepest slope in degrees
+    #[inspectable(min = 0.0, max = 60.0)]
+    pub max_slope: f32,
+    /// Height carved above the ramp
+    #[inspectable(min = 0.0, max = 64.0)]
+    pub clearance: f32,
+    /// Depth filled below the ramp
+    #[inspectable(min = 0.0, max = 64.0)]
+    pub depth: f32,
+}
+
+impl Default for RampTool {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 3.0,
+            blend: 1.5,
+            max_slope: 30.0,
+            clearance: 6.0,
+            depth: 4.0,
+        }
+    }
+}
+
+impl RampTool {
+    /// End of a ramp from `start` toward `end` that doesn't exceed the max slope
+    pub fn clamped_end(&self, start: Vec3, end: Vec3) -> Vec3 {
+        let run = Vec2::new(end.x - start.x, end.z - start.z).length();
+        let max_rise = run * self.max_slope.to_radians().tan();
+        Vec3::new(
+            end.x,
+            start.y + (end.y - start.y).clamp(-max_rise, max_rise),
+            end.z,
+        )
+    }
+
+    /// Density of the point at `pos` once the ramp from `start` to `end` is built
+    pub fn ramp_density(
+        &self,
+        start: Vec3,
+        end: Vec3,
+        pos: Vec3,
+        value: f32,
+        isolevel: f32,
+        cell_size: f32,
+    ) -> f32 {
+        let run = Vec2::new(end.x - start.x, end.z - start.z);
+        let offset = Vec2::new(pos.x - start.x, pos.z - start.z);
+        // closest point of the center line, seen from above
+        let t = if run.length_squared() > 0.0 {
+            (offset.dot(run) / run.length_squared()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let height = pos.y - (start.y + (end.y - start.y) * t);
+        if height > self.clearance || height < -self.depth {
+            return value;
+        }
+        let weight = edge_weight(offset.distance(run * t), self.width / 2.0, self.blend);
+        value + (surface_density(height, isolevel, cell_size) - value) * weight
+    }
+}
+
+/// Builds a ramp from `start` to `end` with the [`RampTool`] settings
+pub struct BuildRamp {
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pick_ramp_points(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut tool: ResMut<RampTool>,
+    data: Res<Data>,
+    windows: Res<Windows>,
+    mut egui_context: ResMut<EguiContext>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    field: DensityField,
+    mut start: Local<Option<Vec3>>,
+    mut ramps: EventWriter<BuildRamp>,
+) {
+    if keyboard_input.just_pressed(KeyCode::J) {
+        tool.enabled = !tool.enabled;
+        *start = None;
+    }
+    if !tool.enabled
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || egui_context.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+    let hit = match cursor_hit(&windows, &cameras, &field, data.isolevel, MAX_DISTANCE) {
+        Some(hit) => hit,
+        None => return,
+    };
+    match start.take() {
+        Some(start) => ramps.send(BuildRamp { start, end: hit }),
+        None => {
+            info!("Ramp start at {hit}, click the end of the ramp");
+            *start = Some(hit);
+        }
+    }
+}
+
+pub fn build_ramps(
+    tool: Res<RampTool>,
+    data: Res<Data>,
+    mut ramps: EventReader<BuildRamp>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut chunks: Query<&mut Chunk>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let mut edited = false;
+    for ramp in ramps.iter() {
+        let start = ramp.start;
+        let end = tool.clamped_end(start, ramp.end);
+        let reach = Vec3::new(
+            tool.width / 2.0 + tool.blend,
+            0.0,
+            tool.width / 2.0 + tool.blend,
+        );
+        let min = start.min(end) - reach - Vec3::Y * tool.depth;
+        let max = start.max(end) + reach + Vec3::Y * tool.clearance;
+        edited |= edit_box(
+            chunk_map,
+            &mut chunks,
+            &world_settings,
+            min,
+            max,
+            |pos, value| {
+                tool.ramp_density(
+                    start,
+                    end,
+                    pos,
+                    value,
+                    data.isolevel,
+                    world_settings.cell_size,
+                )
+            },
+        );
+        info!("Built a ramp from {start} to {end}");
+    }
+    if edited {
+        start_marching_events.send_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steep_ramps_are_clamped() {
+        let tool = RampTool {
+            max_slope: 45.0,
+            ..default()
+        };
+        let start = Vec3::ZERO;
+        let end = tool.clamped_end(start, Vec3::new(4.0, 10.0, 0.0));
+        assert!((end.y - 4.0).abs() < 1e-4);
+        let gentle = Vec3::new(4.0, -2.0, 0.0);
+        assert_eq!(tool.clamped_end(start, gentle), gentle);
+    }
+
+    #[test]
+    fn ramp_follows_the_center_line() {
+        let tool = RampTool::default();
+        let (start, end) = (Vec3::ZERO, Vec3::new(10.0, 2.0, 0.0));
+        let density = |pos: Vec3, value: f32| tool.ramp_density(start, end, pos, value, 0.5, 1.0);
+        // halfway up the ramp the surface is at y = 1
+        assert_eq!(density(Vec3::new(5.0, 1.0, 0.0), 0.0), 0.5);
+        assert!(density(Vec3::new(5.0, 2.0, 0.0), 1.0) < 0.5);
+        assert!(density(Vec3::new(5.0, 0.0, 0.0), 0.0) > 0.5);
+        // beside the ramp and its blend
+        let side = tool.width / 2.0 + tool.blend + 0.1;
+        assert_eq!(density(Vec3::new(5.0, 1.0, side), 0.3), 0.3);
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContext, Inspectable};
+use bevy_mod_picking::PickingCamera;
+
+use crate::{
+    brush::{cursor_ray, Brush},
+    camera::FlyCam,
+    field::DensityField,
+    lines::line_mesh,
+    unlit_material, Data,
+};
+
+/// Draws the rays of the clicks to see where they hit the terrain.
+///
+/// Each click draws the ray from the camera to the hit of the picking plugin
+/// in yellow and to the hit of the density field raycast used by the brush
+/// in cyan, with a cross on the hit point and the normal of the surface
+/// there. A ray hitting nothing is drawn in red up to the brush distance.
+/// The rays start at the camera, move it to see them.
+#[derive(Inspectable)]
+pub struct RayDebug {
+    pub enabled: bool,
+    /// Seconds the rays stay visible
+    #[inspectable(min = 0.1, max = 60.0)]
+    pub duration: f32,
+    /// Length of the normals and of the hit crosses in world units
+    #[inspectable(min = 0.05, max = 8.0)]
+    pub normal_length: f32,
+}
+
+impl Default for RayDebug {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration: 5.0,
+            normal_length: 1.0,
+        }
+    }
+}
+
+/// Lines of a ray, despawned when its timer finishes
+#[derive(Component)]
+pub struct RayGizmo(pub Timer);
+
+/// Segments of a ray from `origin` to its `hit`, with the normal at the hit
+fn ray_segments(
+    origin: Vec3,
+    hit: Vec3,
+    normal: Option<Vec3>,
+    normal_length: f32,
+) -> Vec<(Vec3, Vec3)> {
+    let mut segments = vec![(origin, hit)];
+    let half = normal_length * 0.1;
+    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+        segments.push((hit - axis * half, hit + axis * half));
+    }
+    if let Some(normal) = normal {
+        segments.push((hit, hit + normal * normal_length));
+    }
+    segments
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_click_rays(
+    mut commands: Commands,
+    settings: Res<RayDebug>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut egui_context: ResMut<EguiContext>,
+    windows: Res<Windows>,
+    data: Res<Data>,
+    brush: Res<Brush>,
+    field: DensityField,
+    cameras: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    picking_cameras: Query<&PickingCamera>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !settings.enabled || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let ray = windows.get_primary().and_then(|window| {
+        let cursor = window.cursor_position()?;
+        let (camera, transform) = cameras.get_single().ok()?;
+        let size = Vec2::new(window.width(), window.height());
+        cursor_ray(camera, transform, size, cursor)
+    });
+    let (origin, direction) = match ray {
+        Some(ray) => ray,
+        None => return,
+    };
+
+    let picking_hit = picking_cameras
+        .iter()
+        .find_map(|camera| camera.intersect_top())
+        .map(|(_, intersection)| (intersection.position(), Some(intersection.normal())));
+    let field_hit = field
+        .raycast(origin, direction, brush.max_distance, data.isolevel)
+        .map(|hit| (hit, field.normal(hit)));
+
+    let rays = [(picking_hit, Color::YELLOW), (field_hit, Color::CYAN)];
+    for (hit, color) in rays {
+        let segments = match hit {
+            Some((hit, normal)) => ray_segments(origin, hit, normal, settings.normal_length),
+            None => vec![(origin, origin + direction * brush.max_distance)],
+        };
+        let color = if hit.is_some() { color } else { Color::RED };
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(line_mesh(&segments)),
+                material: materials.add(unlit_material(color)),
+                ..default()
+            })
+            .insert(RayGizmo(Timer::from_seconds(settings.duration, false)));
+    }
+    if let (Some((picked, _)), Some((marched, _))) = (picking_hit, field_hit) {
+        debug!(
+            "click ray hits {picked} with picking and {marched} in the field, {} apart",
+            picked.distance(marched)
+        );
+    }
+}
+
+pub fn expire_ray_gizmos(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut gizmos: Query<(Entity, &mut RayGizmo)>,
+) {
+    for (entity, mut gizmo) in gizmos.iter_mut() {
+        if gizmo.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
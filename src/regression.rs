@@ -0,0 +1,414 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    app::AppExit,
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, RenderTarget},
+        primitives::Aabb,
+        render_asset::RenderAssets,
+        render_resource::Face,
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp, RenderStage,
+    },
+    winit::WinitConfig,
+};
+use image::{DynamicImage, RgbImage, RgbaImage};
+
+use crate::{
+    capture::read_image,
+    chunk::{ChunkMesh, IndexedMesh, NormalMode},
+    inspection_view::{add_image_camera, render_target_image},
+    interpolation::Interpolation,
+    march_cube,
+    presets::CELL_PRESETS,
+    GridCell,
+};
+
+const REGRESSION_CAMERA: &str = "regression_camera";
+const REGRESSION_PASS_DRIVER: &str = "regression_pass_driver";
+/// Frames rendered with the mesh of a case before its image is read, the
+/// mesh is uploaded and the image drawn in the first ones
+const RENDER_FRAMES: u32 = 3;
+
+const BACKGROUND: Color = Color::rgb(0.157, 0.157, 0.18);
+const FRONT_COLOR: Color = Color::rgb(0.85, 0.85, 0.8);
+/// Back faces are red so winding regressions stand out
+const BACK_COLOR: Color = Color::rgb(0.9, 0.15, 0.1);
+
+/// Renders every cell preset with a fixed camera, writes the images and
+/// compares them to stored references.
+///
+/// The presets are drawn by bevy to an image with the same lighting for every
+/// run, the image is read back from the GPU once it's rendered. Each preset is
+/// drawn with flat and smooth normals.
+#[derive(Clone)]
+pub struct RegressionSettings {
+    /// Where the rendered images are written
+    pub output: PathBuf,
+    /// Reference images with the same file names
+    pub references: PathBuf,
+    /// Overwrite the references with the rendered images instead of comparing
+    pub bless: bool,
+    /// Width and height of the images in pixels
+    pub resolution: u32,
+    /// Maximum mean difference of the color channels, from 0 to 1
+    pub tolerance: f32,
+}
+
+impl Default for RegressionSettings {
+    fn default() -> Self {
+        Self {
+            output: PathBuf::from("regression/output"),
+            references: PathBuf::from("regression/references"),
+            bless: false,
+            resolution: 128,
+            tolerance: 0.01,
+        }
+    }
+}
+
+/// Mesh of the cell preset at `index` in [`CELL_PRESETS`]
+pub fn preset_mesh(index: usize, normals: NormalMode) -> IndexedMesh {
+    let chunk = CELL_PRESETS[index].chunk();
+    let grid_cell = GridCell::sample(Vec3::ZERO, 1.0, &chunk);
+    let chunk_mesh = ChunkMesh {
+        triangles: march_cube(&grid_cell, 0.5, Interpolation::Linear).unwrap_or_default(),
+    };
+    chunk_mesh.indexed(normals, |_| None)
+}
+
+/// Mean difference of the color channels of two images of the same size, from 0 to 1
+pub fn image_difference(a: &RgbImage, b: &RgbImage) -> Option<f32> {
+    if a.dimensions() != b.dimensions() {
+        return None;
+    }
+    let total: u64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(a, b)| (*a as i32 - *b as i32).unsigned_abs() as u64)
+        .sum();
+    Some(total as f32 / (a.as_raw().len().max(1) as f32 * 255.0))
+}
+
+/// Renders and checks every preset in an app of its own, returns false if an
+/// image doesn't match its reference or couldn't be written
+pub fn run_regression(settings: &RegressionSettings) -> bool {
+    let directory = if settings.bless {
+        &settings.references
+    } else {
+        &settings.output
+    };
+    if let Err(err) = std::fs::create_dir_all(directory) {
+        eprintln!("Failed to create {}: {err}", directory.display());
+        return false;
+    }
+
+    let passed = RegressionPassed::default();
+    App::new()
+        .insert_resource(WindowDescriptor {
+            title: "Regression".to_string(),
+            width: settings.resolution as f32,
+            height: settings.resolution as f32,
+            resizable: false,
+            ..default()
+        })
+        .insert_resource(WinitConfig {
+            return_from_run: true,
+        })
+        .insert_resource(ClearColor(BACKGROUND))
+        .insert_resource(AmbientLight {
+            color: Color::WHITE,
+            brightness: 0.25,
+        })
+        // the edges are the same on every GPU without multisampling
+        .insert_resource(Msaa { samples: 1 })
+        .insert_resource(settings.clone())
+        .insert_resource(passed.clone())
+        .add_plugins(DefaultPlugins)
+        .add_plugin(RegressionPlugin)
+        .run();
+    passed.0.load(Ordering::Relaxed)
+}
+
+/// Set once every image matched its reference, read after the app exits
+#[derive(Default, Clone)]
+struct RegressionPassed(Arc<AtomicBool>);
+
+/// Image of a case read back from the GPU, shared between the main and the
+/// render world
+#[derive(Default, Clone)]
+struct RegressionReadback(Arc<Mutex<Option<(usize, RgbaImage)>>>);
+
+/// Image of the regression camera to read back and the case it shows
+#[derive(Default)]
+struct ExtractedRegressionRequest(Option<(Handle<Image>, usize)>);
+
+struct RegressionCase {
+    name: String,
+    mesh: Handle<Mesh>,
+}
+
+struct RegressionRun {
+    cases: Vec<RegressionCase>,
+    /// Case shown by the camera
+    current: usize,
+    /// Frames rendered with the mesh of the current case
+    frames: u32,
+    /// Case whose image is read at the end of this frame
+    request: Option<usize>,
+    image: Handle<Image>,
+    /// Front and back faces of the shown mesh
+    entities: [Entity; 2],
+    checked: usize,
+    failures: usize,
+}
+
+struct RegressionPlugin;
+
+impl Plugin for RegressionPlugin {
+    fn build(&self, app: &mut App) {
+        let readback = RegressionReadback::default();
+        app.insert_resource(readback.clone())
+            .add_startup_system(setup_regression)
+            .add_system(advance_regression);
+
+        add_image_camera(app, REGRESSION_CAMERA, REGRESSION_PASS_DRIVER);
+        app.sub_app_mut(RenderApp)
+            .insert_resource(readback)
+            .init_resource::<ExtractedRegressionRequest>()
+            .add_system_to_stage(RenderStage::Extract, extract_regression_request)
+            // after the render graph so the case is read
+            .add_system_to_stage(RenderStage::Cleanup, read_regression_image);
+    }
+}
+
+fn setup_regression(
+    mut commands: Commands,
+    settings: Res<RegressionSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut active_cameras: ResMut<ActiveCameras>,
+) {
+    let mut cases = Vec::new();
+    for index in 0..CELL_PRESETS.len() {
+        for normals in [NormalMode::Flat, NormalMode::Smooth] {
+            cases.push(RegressionCase {
+                name: format!("case_{index:02}_{normals:?}.png").to_lowercase(),
+                mesh: meshes.add(preset_mesh(index, normals).into()),
+            });
+        }
+    }
+
+    let image = images.add(render_target_image(
+        settings.resolution,
+        settings.resolution,
+    ));
+    // looks down at the unit cell from the front left
+    let center = Vec3::splat(0.5);
+    let rotation = (Quat::from_rotation_x(-0.5) * Quat::from_rotation_y(0.6)).inverse();
+    let mut camera = OrthographicCameraBundle::new_3d();
+    camera.camera.name = Some(REGRESSION_CAMERA.to_string());
+    camera.camera.target = RenderTarget::Image(image.clone());
+    camera.transform =
+        Transform::from_translation(center + rotation * Vec3::Z * 2.0).with_rotation(rotation);
+    // the projection is 2 units high at a scale of 1
+    camera.orthographic_projection.scale = 0.95;
+    commands.spawn_bundle(camera);
+    active_cameras.add(REGRESSION_CAMERA);
+
+    let light = Vec3::new(0.4, 1.0, 0.6).normalize();
+    commands.spawn_bundle(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 10000.0,
+            ..default()
+        },
+        transform: Transform::identity().looking_at(-light, Vec3::Y),
+        ..default()
+    });
+
+    let front = materials.add(StandardMaterial {
+        base_color: FRONT_COLOR,
+        perceptual_roughness: 1.0,
+        reflectance: 0.0,
+        ..default()
+    });
+    // only the back faces, lit from their side
+    let back = materials.add(StandardMaterial {
+        base_color: BACK_COLOR,
+        perceptual_roughness: 1.0,
+        reflectance: 0.0,
+        cull_mode: Some(Face::Front),
+        double_sided: true,
+        ..default()
+    });
+    let entities = [front, back].map(|material| {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: cases[0].mesh.clone(),
+                material,
+                ..default()
+            })
+            // the bounds of the first mesh would cull the next ones
+            .insert(Aabb::from_min_max(Vec3::ZERO, Vec3::ONE))
+            .insert(NotShadowCaster)
+            .id()
+    });
+
+    commands.insert_resource(RegressionRun {
+        cases,
+        current: 0,
+        frames: 0,
+        request: None,
+        image,
+        entities,
+        checked: 0,
+        failures: 0,
+    });
+}
+
+fn advance_regression(
+    settings: Res<RegressionSettings>,
+    readback: Res<RegressionReadback>,
+    passed: Res<RegressionPassed>,
+    mut run: ResMut<RegressionRun>,
+    mut meshes: Query<&mut Handle<Mesh>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let read = readback.0.lock().unwrap().take();
+    if let Some((case, image)) = read {
+        if case == run.current {
+            check_image(&settings, &mut run, image);
+            run.current += 1;
+            run.frames = 0;
+            if let Some(case) = run.cases.get(run.current) {
+                let mesh = case.mesh.clone();
+                for entity in run.entities {
+                    if let Ok(mut handle) = meshes.get_mut(entity) {
+                        *handle = mesh.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    if run.current >= run.cases.len() {
+        let directory = if settings.bless {
+            &settings.references
+        } else {
+            &settings.output
+        };
+        if settings.bless {
+            println!("Wrote the references to {}", directory.display());
+        } else {
+            println!(
+                "{} of {} images match their reference, the renders are in {}",
+                run.checked - run.failures.min(run.checked),
+                run.checked,
+                directory.display()
+            );
+        }
+        passed.0.store(run.failures == 0, Ordering::Relaxed);
+        app_exit.send(AppExit);
+        return;
+    }
+
+    run.frames += 1;
+    // asked again if the image wasn't ready
+    if run.frames % RENDER_FRAMES == 0 {
+        run.request = Some(run.current);
+    }
+}
+
+/// Writes the image of the current case and compares it to its reference
+fn check_image(settings: &RegressionSettings, run: &mut RegressionRun, image: RgbaImage) {
+    let image = DynamicImage::ImageRgba8(image).to_rgb8();
+    let name = run.cases[run.current].name.clone();
+    let directory = if settings.bless {
+        &settings.references
+    } else {
+        &settings.output
+    };
+    if let Err(err) = image.save(directory.join(&name)) {
+        eprintln!("Failed to write {name}: {err}");
+        run.failures += 1;
+        return;
+    }
+    if settings.bless {
+        return;
+    }
+    run.checked += 1;
+    match compare(&image, &settings.references.join(&name)) {
+        Ok(difference) if difference <= settings.tolerance => {}
+        Ok(difference) => {
+            eprintln!("{name} differs from its reference by {difference:.4}");
+            run.failures += 1;
+        }
+        Err(err) => {
+            eprintln!("{name}: {err}");
+            run.failures += 1;
+        }
+    }
+}
+
+fn extract_regression_request(mut commands: Commands, mut run: ResMut<RegressionRun>) {
+    let request = run
+        .request
+        .take()
+        .map(|case| (run.image.clone_weak(), case));
+    commands.insert_resource(ExtractedRegressionRequest(request));
+}
+
+fn read_regression_image(
+    request: Res<ExtractedRegressionRequest>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    readback: Res<RegressionReadback>,
+) {
+    let (handle, case) = match &request.0 {
+        Some(request) => request,
+        None => return,
+    };
+    let image = match images.get(handle) {
+        Some(image) => image,
+        None => return,
+    };
+    if let Some(image) = read_image(image, &render_device, &render_queue) {
+        *readback.0.lock().unwrap() = Some((*case, image));
+    }
+}
+
+fn compare(image: &RgbImage, reference: &Path) -> Result<f32, String> {
+    let reference = image::open(reference)
+        .map_err(|err| format!("can't open the reference {}: {err}", reference.display()))?
+        .to_rgb8();
+    image_difference(image, &reference).ok_or_else(|| "the reference has another size".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn differences_are_normalized() {
+        let black = RgbImage::from_pixel(4, 4, Rgb([0, 0, 0]));
+        let white = RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]));
+        assert_eq!(image_difference(&black, &black), Some(0.0));
+        assert_eq!(image_difference(&black, &white), Some(1.0));
+        assert_eq!(image_difference(&black, &RgbImage::new(2, 4)), None);
+        // the corner preset has a surface, the empty one doesn't
+        assert!(!preset_mesh(1, NormalMode::Smooth).indices.is_empty());
+        assert!(preset_mesh(0, NormalMode::Smooth).indices.is_empty());
+    }
+}
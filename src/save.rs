@@ -0,0 +1,743 @@
+use std::{
+    fs,
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task},
+    utils::HashMap,
+};
+use bevy_inspector_egui::Inspectable;
+use futures_lite::future;
+
+use crate::{
+    chunk::{Chunk, ChunkCoord},
+    event_log::Activity,
+    svo::{self, SVO_MAGIC},
+    terrain_root::MainTerrain,
+    StartMarching,
+};
+
+pub(crate) const CHUNK_MAGIC: &[u8; 4] = b"MCCK";
+const MANIFEST_FILE: &str = "world.manifest";
+/// Version of the on-disk world format, written in the manifest and in the
+/// header of every chunk file.
+///
+/// Bump it whenever the chunk storage changes and register a migration from
+/// the previous version in [`SaveMigrations`].
+pub const FORMAT_VERSION: u32 = 3;
+/// magic + format version + chunk version + size on each axis
+const HEADER_LEN: usize = 4 + 4 + 8 + 3 * 4;
+
+/// Directory the world is saved to and how the chunk files are written
+pub struct SaveSettings {
+    pub directory: PathBuf,
+    pub format: SaveFormat,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("saves/world"),
+            format: SaveFormat::Dense,
+        }
+    }
+}
+
+/// Storage of the points in the chunk files.
+///
+/// Both formats can be loaded whatever the current setting is, a chunk is
+/// written in the new format the next time it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// Every point, see [`encode_chunk`]
+    Dense,
+    /// Sparse voxel octree storing uniform regions as a single value, much
+    /// smaller for worlds with large empty or solid areas, see [`svo::write_svo`]
+    Octree,
+}
+
+impl SaveFormat {
+    pub fn encode(self, chunk: &Chunk, version: u64) -> Vec<u8> {
+        match self {
+            SaveFormat::Dense => encode_chunk(chunk, version),
+            SaveFormat::Octree => svo::encode_svo(chunk, version),
+        }
+    }
+}
+
+/// Loads the saved chunks whose coordinates are from `min` to `max` included,
+/// so a large world can be loaded region by region
+pub struct LoadRegion {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+#[derive(Inspectable)]
+pub struct AutosaveSettings {
+    /// Disabling it cancels the autosave in progress
+    pub enabled: bool,
+    /// Seconds between two autosaves
+    #[inspectable(min = 5.0, max = 3600.0, speed = 1.0)]
+    pub interval: f32,
+    /// Number of autosave slots, each autosave goes to the next slot so a
+    /// crash while writing never loses every copy of the world
+    #[inspectable(min = 1, max = 10)]
+    pub slot_count: usize,
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: 60.0,
+            slot_count: 3,
+        }
+    }
+}
+
+/// Tracks how many times a chunk was written to disk and what was written last.
+///
+/// A chunk is only written when its content hash differs from the hash of
+/// the last saved or loaded data.
+#[derive(Component, Default)]
+pub struct ChunkVersion {
+    pub version: u64,
+    pub saved_hash: Option<u64>,
+}
+
+pub fn chunk_path(directory: &Path, coord: ChunkCoord) -> PathBuf {
+    let IVec3 { x, y, z } = coord.0;
+    directory.join(format!("chunk_{x}_{y}_{z}.bin"))
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it over
+/// `path`, so a crash while writing leaves either the old or the new file
+/// but never a truncated one
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Encodes a chunk file in the current format.
+///
+/// The file starts with a header containing the magic, the format version,
+/// the chunk version and the chunk size, followed by the points.
+pub fn encode_chunk(chunk: &Chunk, version: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + chunk.points.len() * 4);
+    bytes.extend_from_slice(CHUNK_MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&version.to_le_bytes());
+    for size in chunk.size.to_array() {
+        bytes.extend_from_slice(&size.to_le_bytes());
+    }
+    for point in &chunk.points {
+        bytes.extend_from_slice(&point.to_le_bytes());
+    }
+    bytes
+}
+
+/// Decodes a chunk file in the current format, returns the chunk and its version
+pub fn decode_chunk(bytes: &[u8]) -> io::Result<(Chunk, u64)> {
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != CHUNK_MAGIC {
+        return Err(invalid_data("not a chunk file"));
+    }
+    let format_version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if format_version != FORMAT_VERSION {
+        return Err(invalid_data(
+            "chunk file wasn't migrated to the current format",
+        ));
+    }
+    let version = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    let size_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let size = UVec3::new(size_at(16), size_at(20), size_at(24));
+    let points: Vec<f32> = bytes[HEADER_LEN..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    if points.len() != Chunk::points_len(size) {
+        return Err(invalid_data(
+            "chunk size doesn't match the number of points",
+        ));
+    }
+    Ok((Chunk::new(points, size), version))
+}
+
+/// Writes a chunk file in the given format, returns the checksum of the file
+pub fn write_chunk(
+    path: &Path,
+    chunk: &Chunk,
+    version: u64,
+    format: SaveFormat,
+) -> io::Result<u32> {
+    let bytes = format.encode(chunk, version);
+    write_atomic(path, &bytes)?;
+    Ok(crc32fast::hash(&bytes))
+}
+
+/// Hashes the bytes as they are read, so a file can be checked without
+/// buffering it whole
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Reads a chunk file written with the given format version, migrating it to
+/// the current format if needed. Returns the chunk and its version.
+///
+/// Octree files are decoded while they are read. Fails if `checksum` is set
+/// and doesn't match the content of the file.
+pub fn read_chunk(
+    path: &Path,
+    checksum: Option<u32>,
+    format_version: u32,
+    migrations: &SaveMigrations,
+) -> io::Result<(Chunk, u64)> {
+    let mut file = io::BufReader::new(fs::File::open(path)?);
+    if file.fill_buf()?.starts_with(SVO_MAGIC) {
+        let mut reader = ChecksumReader {
+            inner: file,
+            hasher: crc32fast::Hasher::new(),
+        };
+        let chunk = svo::read_svo(&mut reader, migrations)?;
+        // the checksum covers the whole file
+        io::copy(&mut reader, &mut io::sink())?;
+        if checksum.map_or(false, |checksum| reader.hasher.finalize() != checksum) {
+            return Err(invalid_data("checksum mismatch"));
+        }
+        return Ok(chunk);
+    }
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    if let Some(checksum) = checksum {
+        if crc32fast::hash(&bytes) != checksum {
+            return Err(invalid_data("checksum mismatch"));
+        }
+    }
+    let bytes = migrations.migrate(bytes, format_version)?;
+    decode_chunk(&bytes)
+}
+
+/// Upgrades the bytes of a chunk file from one format version to the next
+pub type ChunkMigration = fn(Vec<u8>) -> io::Result<Vec<u8>>;
+
+/// Registry of chunk file migrations, keyed by the format version they upgrade from.
+///
+/// Old worlds are loaded by applying every migration from their version up
+/// to [`FORMAT_VERSION`] in order.
+pub struct SaveMigrations {
+    migrations: HashMap<u32, ChunkMigration>,
+}
+
+impl Default for SaveMigrations {
+    fn default() -> Self {
+        let mut migrations = Self {
+            migrations: HashMap::default(),
+        };
+        migrations.register(1, migrate_v1_to_v2);
+        migrations.register(2, migrate_v2_to_v3);
+        migrations
+    }
+}
+
+impl SaveMigrations {
+    pub fn register(&mut self, from_version: u32, migration: ChunkMigration) {
+        self.migrations.insert(from_version, migration);
+    }
+
+    pub fn migrate(&self, mut bytes: Vec<u8>, from_version: u32) -> io::Result<Vec<u8>> {
+        if from_version > FORMAT_VERSION {
+            return Err(invalid_data(
+                "chunk file is newer than this version supports",
+            ));
+        }
+        for version in from_version..FORMAT_VERSION {
+            let migration = self
+                .migrations
+                .get(&version)
+                .ok_or_else(|| invalid_data("missing save migration"))?;
+            bytes = migration(bytes)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Version 1 files didn't store the format version in the chunk header
+fn migrate_v1_to_v2(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    if bytes.len() < 4 || &bytes[0..4] != CHUNK_MAGIC {
+        return Err(invalid_data("not a chunk file"));
+    }
+    let mut migrated = Vec::with_capacity(bytes.len() + 4);
+    migrated.extend_from_slice(CHUNK_MAGIC);
+    migrated.extend_from_slice(&2u32.to_le_bytes());
+    migrated.extend_from_slice(&bytes[4..]);
+    Ok(migrated)
+}
+
+/// Version 2 chunks were cubes and stored a single size
+fn migrate_v2_to_v3(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    // magic + format version + chunk version + size
+    const V2_HEADER_LEN: usize = 4 + 4 + 8 + 4;
+    if bytes.len() < V2_HEADER_LEN || &bytes[0..4] != CHUNK_MAGIC {
+        return Err(invalid_data("not a chunk file"));
+    }
+    let size = &bytes[16..V2_HEADER_LEN];
+    let mut migrated = Vec::with_capacity(bytes.len() + 8);
+    migrated.extend_from_slice(CHUNK_MAGIC);
+    migrated.extend_from_slice(&3u32.to_le_bytes());
+    migrated.extend_from_slice(&bytes[8..16]);
+    for _ in 0..3 {
+        migrated.extend_from_slice(size);
+    }
+    migrated.extend_from_slice(&bytes[V2_HEADER_LEN..]);
+    Ok(migrated)
+}
+
+pub struct ManifestEntry {
+    pub version: u64,
+    pub checksum: u32,
+}
+
+/// Lists every chunk file of a saved world with its version and checksum.
+///
+/// The manifest is written after every chunk file has been renamed in place
+/// so it only ever references complete files. Chunks whose file doesn't
+/// match the manifest are skipped on load instead of failing the whole world.
+pub struct Manifest {
+    pub format_version: u32,
+    pub chunks: HashMap<ChunkCoord, ManifestEntry>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            chunks: HashMap::default(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Reads the manifest of `directory`, a missing manifest is an empty world
+    pub fn read(directory: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(directory.join(MANIFEST_FILE)) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+
+        let mut manifest = Self::default();
+        for line in text.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["format_version", version] => {
+                    manifest.format_version = version
+                        .parse()
+                        .map_err(|_| invalid_data("invalid format version"))?;
+                }
+                ["chunk", x, y, z, version, checksum] => {
+                    let parse_i32 = |s: &str| -> io::Result<i32> {
+                        s.parse()
+                            .map_err(|_| invalid_data("invalid chunk coordinate"))
+                    };
+                    let coord = ChunkCoord(IVec3::new(parse_i32(x)?, parse_i32(y)?, parse_i32(z)?));
+                    let entry = ManifestEntry {
+                        version: version
+                            .parse()
+                            .map_err(|_| invalid_data("invalid version"))?,
+                        checksum: u32::from_str_radix(checksum, 16)
+                            .map_err(|_| invalid_data("invalid checksum"))?,
+                    };
+                    manifest.chunks.insert(coord, entry);
+                }
+                [] => {}
+                _ => return Err(invalid_data("invalid manifest line")),
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn write(&self, directory: &Path) -> io::Result<()> {
+        let mut text = format!("format_version {}\n", self.format_version);
+        for (coord, entry) in &self.chunks {
+            let IVec3 { x, y, z } = coord.0;
+            text += &format!(
+                "chunk {x} {y} {z} {} {:08x}\n",
+                entry.version, entry.checksum
+            );
+        }
+        write_atomic(&directory.join(MANIFEST_FILE), text.as_bytes())
+    }
+}
+
+/// Press F5 to write every chunk of the main terrain that changed since it
+/// was last saved
+pub fn save_world(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<SaveSettings>,
+    terrain: MainTerrain,
+    mut chunks: Query<(&Chunk, &ChunkCoord, &mut ChunkVersion, &Parent)>,
+    mut activity: EventWriter<Activity>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    if let Err(err) = fs::create_dir_all(&settings.directory) {
+        error!(
+            "Failed to create save directory {:?}: {err}",
+            settings.directory
+        );
+        return;
+    }
+
+    let mut manifest = Manifest::read(&settings.directory).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable manifest, every chunk will be saved: {err}");
+        Manifest::default()
+    });
+    if manifest.format_version != FORMAT_VERSION {
+        // chunk files of different versions can't share a manifest, rewrite everything
+        manifest = Manifest::default();
+    }
+
+    let root = terrain.root();
+    let mut written = 0;
+    let mut skipped = 0;
+    for (chunk, coord, mut version, parent) in chunks.iter_mut() {
+        if Some(parent.0) != root {
+            continue;
+        }
+        let hash = chunk.content_hash();
+        if version.saved_hash == Some(hash) && manifest.chunks.contains_key(coord) {
+            skipped += 1;
+            continue;
+        }
+
+        let path = chunk_path(&settings.directory, *coord);
+        match write_chunk(&path, chunk, version.version + 1, settings.format) {
+            Ok(checksum) => {
+                version.version += 1;
+                manifest.chunks.insert(
+                    *coord,
+                    ManifestEntry {
+                        version: version.version,
+                        checksum,
+                    },
+                );
+                version.saved_hash = Some(hash);
+                written += 1;
+            }
+            Err(err) => error!("Failed to save chunk {:?}: {err}", coord.0),
+        }
+    }
+    if let Err(err) = manifest.write(&settings.directory) {
+        error!("Failed to write world manifest: {err}");
+        return;
+    }
+    activity.send(Activity::WorldSaved {
+        written,
+        unchanged: skipped,
+    });
+}
+
+/// Press F9 to load every chunk of the main terrain that has a file in the
+/// save directory
+pub fn load_world(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<SaveSettings>,
+    migrations: Res<SaveMigrations>,
+    terrain: MainTerrain,
+    mut chunks: Query<(&mut Chunk, &ChunkCoord, &mut ChunkVersion, &Parent)>,
+    mut start_marching_events: EventWriter<StartMarching>,
+    mut activity: EventWriter<Activity>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F9) {
+        return;
+    }
+
+    let root = terrain.root();
+    if let Some(loaded) = load_chunks(&settings.directory, &migrations, &mut chunks, root, |_| {
+        true
+    }) {
+        activity.send(Activity::ChunksLoaded { chunks: loaded });
+        start_marching_events.send_default();
+    }
+}
+
+/// Loads the chunks of every [`LoadRegion`] event
+pub fn load_regions(
+    mut events: EventReader<LoadRegion>,
+    settings: Res<SaveSettings>,
+    migrations: Res<SaveMigrations>,
+    terrain: MainTerrain,
+    mut chunks: Query<(&mut Chunk, &ChunkCoord, &mut ChunkVersion, &Parent)>,
+    mut start_marching_events: EventWriter<StartMarching>,
+    mut activity: EventWriter<Activity>,
+) {
+    let root = terrain.root();
+    let mut loaded = 0;
+    for LoadRegion { min, max } in events.iter() {
+        let in_region = |coord: IVec3| coord.cmpge(*min).all() && coord.cmple(*max).all();
+        loaded += load_chunks(
+            &settings.directory,
+            &migrations,
+            &mut chunks,
+            root,
+            in_region,
+        )
+        .unwrap_or_default();
+    }
+    if loaded > 0 {
+        activity.send(Activity::ChunksLoaded { chunks: loaded });
+        start_marching_events.send_default();
+    }
+}
+
+/// Loads the chunks of the terrain of `root` in `directory` accepted by
+/// `filter`, only their files are read. Returns how many were loaded, or
+/// `None` if the manifest can't be used.
+fn load_chunks(
+    directory: &Path,
+    migrations: &SaveMigrations,
+    chunks: &mut Query<(&mut Chunk, &ChunkCoord, &mut ChunkVersion, &Parent)>,
+    root: Option<Entity>,
+    filter: impl Fn(IVec3) -> bool,
+) -> Option<usize> {
+    let manifest = match Manifest::read(directory) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            error!("Failed to read world manifest: {err}");
+            return None;
+        }
+    };
+    if manifest.format_version > FORMAT_VERSION {
+        error!(
+            "World format version {} is newer than the supported version {FORMAT_VERSION}",
+            manifest.format_version
+        );
+        return None;
+    }
+    if manifest.format_version < FORMAT_VERSION {
+        info!(
+            "Migrating world from format version {} to {FORMAT_VERSION}",
+            manifest.format_version
+        );
+    }
+
+    let mut loaded = 0;
+    for (mut chunk, coord, mut version, parent) in chunks.iter_mut() {
+        let entry = match manifest.chunks.get(coord) {
+            Some(entry) if Some(parent.0) == root && filter(coord.0) => entry,
+            _ => continue,
+        };
+        let path = chunk_path(directory, *coord);
+        let checksum = Some(entry.checksum);
+        match read_chunk(&path, checksum, manifest.format_version, migrations) {
+            Ok((saved, saved_version)) => {
+                version.version = saved_version;
+                version.saved_hash = Some(saved.content_hash());
+                chunk.replace_points(saved.points, saved.size);
+                loaded += 1;
+            }
+            Err(err) => error!("Failed to load chunk {:?}: {err}", coord.0),
+        }
+    }
+    Some(loaded)
+}
+
+struct ChunkSnapshot {
+    coord: ChunkCoord,
+    chunk: Chunk,
+    version: u64,
+    hash: u64,
+}
+
+#[derive(Default)]
+pub struct AutosaveState {
+    elapsed: f32,
+    next_slot: usize,
+    /// Hash of every chunk written to each slot, so a slot only receives the
+    /// chunks that changed since that slot was last written
+    slot_hashes: Vec<HashMap<ChunkCoord, u64>>,
+    task: Option<Task<AutosaveResult>>,
+}
+
+struct AutosaveResult {
+    slot: usize,
+    written: Vec<(ChunkCoord, u64)>,
+}
+
+pub fn autosave_slot_directory(settings: &SaveSettings, slot: usize) -> PathBuf {
+    settings.directory.join(format!("autosave_{slot}"))
+}
+
+/// Periodically snapshots the chunks of the main terrain that changed since
+/// the last autosave to the current slot and writes them on the io task pool
+#[allow(clippy::too_many_arguments)]
+pub fn autosave(
+    time: Res<Time>,
+    settings: Res<SaveSettings>,
+    autosave_settings: Res<AutosaveSettings>,
+    mut state: Local<AutosaveState>,
+    terrain: MainTerrain,
+    chunks: Query<(&Chunk, &ChunkCoord, &ChunkVersion, &Parent)>,
+    pool: Res<IoTaskPool>,
+    mut activity: EventWriter<Activity>,
+) {
+    if !autosave_settings.enabled {
+        // dropping the task cancels the autosave, or discards its result if
+        // it is already writing
+        if state.task.take().is_some() {
+            activity.send(Activity::TaskCancelled { task: "autosave" });
+        }
+        return;
+    }
+
+    if let Some(task) = &mut state.task {
+        if let Some(result) = future::block_on(future::poll_once(task)) {
+            let hashes = &mut state.slot_hashes[result.slot];
+            hashes.extend(result.written.iter().copied());
+            activity.send(Activity::Autosaved {
+                slot: result.slot,
+                chunks: result.written.len(),
+            });
+            state.task = None;
+        } else {
+            // Wait for the previous autosave to finish before starting a new one
+            return;
+        }
+    }
+
+    state.elapsed += time.delta_seconds();
+    if state.elapsed < autosave_settings.interval {
+        return;
+    }
+    state.elapsed = 0.0;
+
+    let slot_count = autosave_settings.slot_count.max(1);
+    state.slot_hashes.resize_with(slot_count, HashMap::default);
+    let slot = state.next_slot % slot_count;
+    state.next_slot = (slot + 1) % slot_count;
+
+    let slot_hashes = &state.slot_hashes[slot];
+    let root = terrain.root();
+    let snapshots: Vec<ChunkSnapshot> = chunks
+        .iter()
+        .filter(|(.., parent)| Some(parent.0) == root)
+        .filter_map(|(chunk, coord, version, _)| {
+            let hash = chunk.content_hash();
+            if slot_hashes.get(coord) == Some(&hash) {
+                return None;
+            }
+            Some(ChunkSnapshot {
+                coord: *coord,
+                chunk: chunk.clone(),
+                version: version.version,
+                hash,
+            })
+        })
+        .collect();
+
+    if snapshots.is_empty() {
+        return;
+    }
+
+    let directory = autosave_slot_directory(&settings, slot);
+    let format = settings.format;
+    state.task = Some(pool.spawn(async move {
+        let mut written = Vec::with_capacity(snapshots.len());
+        if let Err(err) = fs::create_dir_all(&directory) {
+            error!("Failed to create autosave directory {directory:?}: {err}");
+            return AutosaveResult { slot, written };
+        }
+        let mut manifest = Manifest::read(&directory)
+            .ok()
+            .filter(|manifest| manifest.format_version == FORMAT_VERSION)
+            .unwrap_or_default();
+        for snapshot in snapshots {
+            let path = chunk_path(&directory, snapshot.coord);
+            match write_chunk(&path, &snapshot.chunk, snapshot.version, format) {
+                Ok(checksum) => {
+                    manifest.chunks.insert(
+                        snapshot.coord,
+                        ManifestEntry {
+                            version: snapshot.version,
+                            checksum,
+                        },
+                    );
+                    written.push((snapshot.coord, snapshot.hash));
+                }
+                Err(err) => error!("Failed to autosave chunk {:?}: {err}", snapshot.coord.0),
+            }
+        }
+        if let Err(err) = manifest.write(&directory) {
+            error!("Failed to write autosave manifest: {err}");
+            // the chunks aren't referenced by the manifest, write them again next time
+            written.clear();
+        }
+        AutosaveResult { slot, written }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_v1_chunk() {
+        let chunk = Chunk::new((0..27).map(|i| i as f32).collect(), UVec3::splat(2));
+
+        // v1 header: magic + chunk version + size
+        let mut v1 = Vec::new();
+        v1.extend_from_slice(CHUNK_MAGIC);
+        v1.extend_from_slice(&5u64.to_le_bytes());
+        v1.extend_from_slice(&2u32.to_le_bytes());
+        for point in &chunk.points {
+            v1.extend_from_slice(&point.to_le_bytes());
+        }
+
+        let migrated = SaveMigrations::default().migrate(v1, 1).unwrap();
+        assert_eq!(migrated, encode_chunk(&chunk, 5));
+        let (decoded, version) = decode_chunk(&migrated).unwrap();
+        assert_eq!(version, 5);
+        assert_eq!(decoded.points, chunk.points);
+    }
+
+    #[test]
+    fn non_cubic_chunk_roundtrip() {
+        let size = UVec3::new(1, 3, 2);
+        let points: Vec<f32> = (0..Chunk::points_len(size)).map(|i| i as f32).collect();
+        let chunk = Chunk::new(points, size);
+        let (decoded, version) = decode_chunk(&encode_chunk(&chunk, 3)).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(decoded.size, size);
+        assert_eq!(decoded.points, chunk.points);
+    }
+
+    #[test]
+    fn rejects_newer_format() {
+        let bytes = encode_chunk(&Chunk::new(vec![0.0; 8], UVec3::ONE), 0);
+        assert!(SaveMigrations::default()
+            .migrate(bytes, FORMAT_VERSION + 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,37 @@
+// Applies a brush stroke to the density texture of a chunk, the same edit as
+// edit_sphere does on the points of the chunk
+
+struct BrushEdit {
+    // relative to the origin of the chunk, in world units
+    center: vec3<f32>;
+    radius: f32;
+    // first point edited
+    min: vec3<u32>;
+    amount: f32;
+    // last point edited, inclusive
+    max: vec3<u32>;
+    cell_size: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> brush: BrushEdit;
+
+[[group(0), binding(1)]]
+var density: texture_storage_3d<r32float, read_write>;
+
+[[stage(compute), workgroup_size(4, 4, 4)]]
+fn edit([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let point = brush.min + id;
+    if (any(point > brush.max)) {
+        return;
+    }
+    let dist = distance(vec3<f32>(point) * brush.cell_size, brush.center);
+    if (dist > brush.radius) {
+        return;
+    }
+    let falloff = 1.0 - dist / brush.radius;
+    let coords = vec3<i32>(point);
+    let value = textureLoad(density, coords).x;
+    let edited = clamp(value + brush.amount * falloff, 0.0, 1.0);
+    textureStore(density, coords, vec4<f32>(edited, 0.0, 0.0, 1.0));
+}
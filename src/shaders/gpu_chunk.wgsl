@@ -0,0 +1,50 @@
+// Draws the vertices written by gpu_meshing.wgsl
+
+#import bevy_pbr::mesh_view_bind_group
+
+struct GpuChunk {
+    model: mat4x4<f32>;
+    color: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> chunk: GpuChunk;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_normal: vec3<f32>;
+};
+
+let PI: f32 = 3.141592653589793;
+
+fn luminance(v: vec3<f32>) -> f32 {
+    return dot(v, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_position = chunk.model * vec4<f32>(vertex.position, 1.0);
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_position;
+    out.world_normal = (chunk.model * vec4<f32>(vertex.normal, 0.0)).xyz;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fragment(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.world_normal);
+    // simple lambert lighting from the first directional light
+    var light = lights.ambient_color.rgb;
+    if (lights.n_directional_lights > 0u) {
+        let sun = lights.directional_lights[0];
+        light = light + sun.color.rgb * max(dot(normal, sun.direction_to_light), 0.0) / PI;
+    }
+    let lit = chunk.color.rgb * light;
+    // same reinhard tonemapping as the pbr shader
+    return vec4<f32>(lit / (1.0 + luminance(lit)), 1.0);
+}
@@ -0,0 +1,270 @@
+// Marches the cells of a chunk from its density texture in three steps: each
+// cell counts its triangles, an exclusive prefix sum over the counts gives the
+// first triangle of each cell, then each cell writes its triangles there so
+// the vertices are tightly packed. The number of vertices is written to the
+// arguments of the indirect draw. Same classification, edges and winding as
+// march_cube with linear interpolation.
+
+struct Params {
+    // cells of the chunk
+    size: vec3<u32>;
+    isolevel: f32;
+    cell_count: u32;
+    // triangles the vertex buffer can hold
+    capacity: u32;
+    cell_size: f32;
+    // blocks of 256 cells scanned by a workgroup
+    block_count: u32;
+};
+
+// TRIANGLE_TABLE then EDGE_CONNECTION
+struct Tables {
+    triangles: array<i32, 4096>;
+    edges: array<u32, 24>;
+};
+
+struct Values {
+    values: array<u32>;
+};
+
+// position then normal of each vertex
+struct Vertices {
+    values: array<f32>;
+};
+
+// arguments of draw_indirect
+struct DrawArgs {
+    vertex_count: u32;
+    instance_count: u32;
+    first_vertex: u32;
+    first_instance: u32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+[[group(0), binding(1)]]
+var density: texture_3d<f32>;
+
+[[group(0), binding(2)]]
+var<storage, read> tables: Tables;
+
+// triangles of each cell
+[[group(0), binding(3)]]
+var<storage, read_write> counts: Values;
+
+// first triangle of each cell
+[[group(0), binding(4)]]
+var<storage, read_write> offsets: Values;
+
+// triangles of each block, then first triangle of each block
+[[group(0), binding(5)]]
+var<storage, read_write> block_sums: Values;
+
+[[group(0), binding(6)]]
+var<storage, read_write> vertices: Vertices;
+
+[[group(0), binding(7)]]
+var<storage, read_write> draw_args: DrawArgs;
+
+var<workgroup> scratch: array<u32, 256>;
+
+// order of CELL_CORNERS
+fn corner(i: u32) -> vec3<u32> {
+    let low = i & 3u;
+    let x = select(0u, 1u, low == 1u || low == 2u);
+    let z = select(0u, 1u, low >= 2u);
+    return vec3<u32>(x, i >> 2u, z);
+}
+
+fn cell_position(cell: u32) -> vec3<u32> {
+    let x = cell % params.size.x;
+    let y = cell / params.size.x % params.size.y;
+    let z = cell / (params.size.x * params.size.y);
+    return vec3<u32>(x, y, z);
+}
+
+fn value_at(point: vec3<u32>) -> f32 {
+    return textureLoad(density, vec3<i32>(point), 0).x;
+}
+
+fn cube_index(cell: vec3<u32>) -> u32 {
+    var index = 0u;
+    var i = 0u;
+    loop {
+        if (i >= 8u) {
+            break;
+        }
+        // the points on the isolevel are inside
+        if (!(value_at(cell + corner(i)) >= params.isolevel)) {
+            index = index | (1u << i);
+        }
+        i = i + 1u;
+    }
+    return index;
+}
+
+fn triangle_count(index: u32) -> u32 {
+    var count = 0u;
+    loop {
+        if (count >= 5u || tables.triangles[index * 16u + count * 3u] < 0) {
+            break;
+        }
+        count = count + 1u;
+    }
+    return count;
+}
+
+// Inclusive prefix sum of the scratch values of the workgroup
+fn scan_scratch(index: u32) {
+    var stride = 1u;
+    loop {
+        if (stride >= 256u) {
+            break;
+        }
+        var value = scratch[index];
+        if (index >= stride) {
+            value = value + scratch[index - stride];
+        }
+        workgroupBarrier();
+        scratch[index] = value;
+        workgroupBarrier();
+        stride = stride * 2u;
+    }
+}
+
+[[stage(compute), workgroup_size(256)]]
+fn classify([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= params.cell_count) {
+        return;
+    }
+    counts.values[id.x] = triangle_count(cube_index(cell_position(id.x)));
+}
+
+[[stage(compute), workgroup_size(256)]]
+fn scan_blocks(
+    [[builtin(global_invocation_id)]] id: vec3<u32>,
+    [[builtin(local_invocation_id)]] local: vec3<u32>,
+    [[builtin(workgroup_id)]] group: vec3<u32>,
+) {
+    var count = 0u;
+    if (id.x < params.cell_count) {
+        count = counts.values[id.x];
+    }
+    scratch[local.x] = count;
+    workgroupBarrier();
+    scan_scratch(local.x);
+    if (id.x < params.cell_count) {
+        offsets.values[id.x] = scratch[local.x] - count;
+    }
+    if (local.x == 255u) {
+        block_sums.values[group.x] = scratch[255u];
+    }
+}
+
+// Dispatched with a single workgroup, which scans 256 blocks at a time
+[[stage(compute), workgroup_size(256)]]
+fn scan_block_sums([[builtin(local_invocation_id)]] local: vec3<u32>) {
+    var carry = 0u;
+    var first = 0u;
+    loop {
+        if (first >= params.block_count) {
+            break;
+        }
+        let block = first + local.x;
+        var sum = 0u;
+        if (block < params.block_count) {
+            sum = block_sums.values[block];
+        }
+        scratch[local.x] = sum;
+        workgroupBarrier();
+        scan_scratch(local.x);
+        if (block < params.block_count) {
+            block_sums.values[block] = carry + scratch[local.x] - sum;
+        }
+        carry = carry + scratch[255u];
+        workgroupBarrier();
+        first = first + 256u;
+    }
+    if (local.x == 0u) {
+        // the triangles past the allocation aren't written
+        draw_args.vertex_count = min(carry, params.capacity) * 3u;
+        draw_args.instance_count = 1u;
+        draw_args.first_vertex = 0u;
+        draw_args.first_instance = 0u;
+    }
+}
+
+[[stage(compute), workgroup_size(256)]]
+fn add_block_offsets(
+    [[builtin(global_invocation_id)]] id: vec3<u32>,
+    [[builtin(workgroup_id)]] group: vec3<u32>,
+) {
+    if (id.x >= params.cell_count) {
+        return;
+    }
+    offsets.values[id.x] = offsets.values[id.x] + block_sums.values[group.x];
+}
+
+fn edge_vertex(cell: vec3<u32>, edge: u32) -> vec3<f32> {
+    var u = tables.edges[edge * 2u];
+    var v = tables.edges[edge * 2u + 1u];
+    // neighboring cells interpolate their shared edges in the same direction
+    if (any(corner(u) > corner(v))) {
+        let swapped = u;
+        u = v;
+        v = swapped;
+    }
+    let p1 = vec3<f32>(cell + corner(u)) * params.cell_size;
+    let p2 = vec3<f32>(cell + corner(v)) * params.cell_size;
+    let value1 = value_at(cell + corner(u));
+    let value2 = value_at(cell + corner(v));
+    if (value1 == params.isolevel) {
+        return p1;
+    }
+    if (value2 == params.isolevel) {
+        return p2;
+    }
+    if (value1 == value2) {
+        return (p1 + p2) * 0.5;
+    }
+    let mu = clamp((params.isolevel - value1) / (value2 - value1), 0.0, 1.0);
+    return p1 + mu * (p2 - p1);
+}
+
+fn write_vertex(index: u32, position: vec3<f32>, normal: vec3<f32>) {
+    let base = index * 6u;
+    vertices.values[base] = position.x;
+    vertices.values[base + 1u] = position.y;
+    vertices.values[base + 2u] = position.z;
+    vertices.values[base + 3u] = normal.x;
+    vertices.values[base + 4u] = normal.y;
+    vertices.values[base + 5u] = normal.z;
+}
+
+[[stage(compute), workgroup_size(256)]]
+fn generate([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= params.cell_count) {
+        return;
+    }
+    let count = counts.values[id.x];
+    let cell = cell_position(id.x);
+    let row = cube_index(cell) * 16u;
+    var i = 0u;
+    loop {
+        let triangle = offsets.values[id.x] + i;
+        // past the allocation
+        if (i >= count || triangle >= params.capacity) {
+            break;
+        }
+        let a = edge_vertex(cell, u32(tables.triangles[row + i * 3u + 2u]));
+        let b = edge_vertex(cell, u32(tables.triangles[row + i * 3u + 1u]));
+        let c = edge_vertex(cell, u32(tables.triangles[row + i * 3u]));
+        let face = cross(b - a, c - a);
+        let normal = select(vec3<f32>(0.0, 1.0, 0.0), normalize(face), length(face) > 0.0);
+        write_vertex(triangle * 3u, a, normal);
+        write_vertex(triangle * 3u + 1u, b, normal);
+        write_vertex(triangle * 3u + 2u, c, normal);
+        i = i + 1u;
+    }
+}
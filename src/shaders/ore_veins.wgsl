@@ -0,0 +1,43 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct OreMaterial {
+    rock: vec4<f32>;
+    vein: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: OreMaterial;
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    // x: ore concentration written by the OreLayer
+    [[location(2)]] uv: vec2<f32>;
+};
+
+let PI: f32 = 3.141592653589793;
+
+fn luminance(v: vec3<f32>) -> f32 {
+    return dot(v, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    var normal = normalize(in.world_normal);
+    if (!in.is_front) {
+        normal = -normal;
+    }
+
+    let albedo = mix(material.rock.rgb, material.vein.rgb, clamp(in.uv.x, 0.0, 1.0));
+
+    // simple lambert lighting from the first directional light
+    var light = lights.ambient_color.rgb;
+    if (lights.n_directional_lights > 0u) {
+        let sun = lights.directional_lights[0];
+        light = light + sun.color.rgb * max(dot(normal, sun.direction_to_light), 0.0) / PI;
+    }
+    let color = albedo * light;
+    // same reinhard tonemapping as the pbr shader
+    return vec4<f32>(color / (1.0 + luminance(color)), 1.0);
+}
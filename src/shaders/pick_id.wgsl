@@ -0,0 +1,37 @@
+struct PickIdMaterial {
+    // x: cell size
+    params: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: PickIdMaterial;
+
+struct FragmentInput {
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+// the target is sRGB, the bytes written must be the bytes of the id
+fn id_channel(id: u32, shift: u32) -> f32 {
+    let c = f32((id >> shift) & 255u) / 255.0;
+    if (c <= 0.04045) {
+        return c / 12.92;
+    }
+    return pow((c + 0.055) / 1.055, 2.4);
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    // matches unpack_cell of gpu_picking.rs
+    let biased = vec3<i32>(floor(in.world_position.xyz / material.params.x)) + vec3<i32>(1024, 512, 1024);
+    let cell = vec3<u32>(clamp(biased, vec3<i32>(0, 0, 0), vec3<i32>(2047, 1023, 2047)));
+    let id = (cell.x << 21u) | (cell.y << 11u) | cell.z;
+    // alpha isn't sRGB encoded
+    return vec4<f32>(
+        id_channel(id, 24u),
+        id_channel(id, 16u),
+        id_channel(id, 8u),
+        f32(id & 255u) / 255.0
+    );
+}
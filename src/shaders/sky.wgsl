@@ -0,0 +1,28 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct SkyMaterial {
+    zenith: vec4<f32>;
+    horizon: vec4<f32>;
+    ground: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: SkyMaterial;
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    let dir = normalize(in.world_position.xyz - view.world_position.xyz);
+    // sharper transition below the horizon so the ground reads as a flat plane
+    let up = pow(clamp(dir.y, 0.0, 1.0), 0.5);
+    let down = pow(clamp(-dir.y * 4.0, 0.0, 1.0), 0.5);
+    var color = mix(material.horizon.rgb, material.zenith.rgb, up);
+    color = mix(color, material.ground.rgb, down);
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,60 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct SlopeMaterial {
+    flat: vec4<f32>;
+    steep: vec4<f32>;
+    snow: vec4<f32>;
+    // x: steep slope start, y: slope blend, both in radians,
+    // z: snow height, w: snow blend
+    params: vec4<f32>;
+    fog_color: vec4<f32>;
+    // x: fog start, y: fog end, z: 1.0 when the fog is enabled
+    fog: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: SlopeMaterial;
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+let PI: f32 = 3.141592653589793;
+
+fn luminance(v: vec3<f32>) -> f32 {
+    return dot(v, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    var normal = normalize(in.world_normal);
+    if (!in.is_front) {
+        normal = -normal;
+    }
+
+    // floors and ceilings are both flat
+    let slope = acos(clamp(abs(normal.y), 0.0, 1.0));
+    let steep = smoothstep(material.params.x, material.params.x + material.params.y, slope);
+    var albedo = mix(material.flat.rgb, material.steep.rgb, steep);
+    let snow_blend = max(material.params.w, 0.001);
+    let snow = smoothstep(material.params.z - snow_blend, material.params.z + snow_blend, in.world_position.y);
+    // snow doesn't stick to steep slopes
+    albedo = mix(albedo, material.snow.rgb, snow * (1.0 - steep));
+
+    // simple lambert lighting from the first directional light
+    var light = lights.ambient_color.rgb;
+    if (lights.n_directional_lights > 0u) {
+        let sun = lights.directional_lights[0];
+        light = light + sun.color.rgb * max(dot(normal, sun.direction_to_light), 0.0) / PI;
+    }
+    let lit = albedo * light;
+    // same reinhard tonemapping as the pbr shader
+    let color = lit / (1.0 + luminance(lit));
+
+    let distance = length(in.world_position.xyz - view.world_position.xyz);
+    let fog = material.fog.z * smoothstep(material.fog.x, material.fog.y, distance);
+    return vec4<f32>(mix(color, material.fog_color.rgb, fog), 1.0);
+}
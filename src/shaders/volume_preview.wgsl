@@ -0,0 +1,90 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct VolumeMaterial {
+    // xyz: world position of the chunk, w: size of a cell in world units
+    origin_cell_size: vec4<f32>;
+    // xyz: number of cells on each axis
+    size: vec4<f32>;
+    // x: isolevel, y: opacity, z: number of steps
+    params: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: VolumeMaterial;
+[[group(1), binding(1)]]
+var density: texture_3d<f32>;
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+fn load(p: vec3<i32>) -> f32 {
+    return textureLoad(density, p, 0).r;
+}
+
+// R32Float textures can't be filtered, interpolate manually
+fn sample_density(p: vec3<f32>) -> f32 {
+    let max_index = vec3<f32>(textureDimensions(density)) - vec3<f32>(1.0);
+    let q = clamp(p, vec3<f32>(0.0), max_index);
+    let base = min(floor(q), max_index - vec3<f32>(1.0));
+    let t = q - base;
+    let i = vec3<i32>(base);
+
+    let x00 = mix(load(i), load(i + vec3<i32>(1, 0, 0)), t.x);
+    let x10 = mix(load(i + vec3<i32>(0, 1, 0)), load(i + vec3<i32>(1, 1, 0)), t.x);
+    let x01 = mix(load(i + vec3<i32>(0, 0, 1)), load(i + vec3<i32>(1, 0, 1)), t.x);
+    let x11 = mix(load(i + vec3<i32>(0, 1, 1)), load(i + vec3<i32>(1, 1, 1)), t.x);
+    return mix(mix(x00, x10, t.y), mix(x01, x11, t.y), t.z);
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    let origin = material.origin_cell_size.xyz;
+    let cell_size = material.origin_cell_size.w;
+    let size = material.size.xyz;
+    let isolevel = material.params.x;
+    let opacity = material.params.y;
+    let steps = i32(material.params.z);
+
+    // march in the local space of the chunk, one unit per cell
+    let camera = (view.world_position.xyz - origin) / cell_size;
+    let dir = normalize(in.world_position.xyz - view.world_position.xyz);
+
+    let inv_dir = 1.0 / dir;
+    let t0 = (vec3<f32>(0.0) - camera) * inv_dir;
+    let t1 = (size - camera) * inv_dir;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    let t_enter = max(max(max(t_min.x, t_min.y), t_min.z), 0.0);
+    let t_exit = min(min(t_max.x, t_max.y), t_max.z);
+    if (t_exit <= t_enter) {
+        discard;
+    }
+
+    let step_size = (t_exit - t_enter) / f32(steps);
+    var color = vec3<f32>(0.0);
+    var alpha = 0.0;
+    var i = 0;
+    loop {
+        if (i >= steps || alpha > 0.99) {
+            break;
+        }
+        let p = camera + dir * (t_enter + (f32(i) + 0.5) * step_size);
+        let d = sample_density(p);
+
+        // blue below the isolevel, red above and white close to it
+        let side = mix(vec3<f32>(0.1, 0.3, 1.0), vec3<f32>(1.0, 0.2, 0.1), step(isolevel, d));
+        let near = 1.0 - clamp(abs(d - isolevel) * 20.0, 0.0, 1.0);
+        let c = mix(side, vec3<f32>(1.0), near);
+        let a = clamp(d * opacity * step_size * cell_size, 0.0, 1.0);
+
+        color = color + (1.0 - alpha) * a * c;
+        alpha = alpha + (1.0 - alpha) * a;
+        i = i + 1;
+    }
+
+    return vec4<f32>(color / max(alpha, 0.0001), alpha);
+}
@@ -0,0 +1,37 @@
+#import bevy_pbr::mesh_view_bind_group
+
+struct XRayMaterial {
+    color: vec4<f32>;
+    // x: opacity, y: rim power
+    params: vec4<f32>;
+    // x: slice enabled, y: slice height, z: front faces only
+    slice: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: XRayMaterial;
+
+struct FragmentInput {
+    [[builtin(front_facing)]] is_front: bool;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+};
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    if (material.slice.x > 0.5 && in.world_position.y > material.slice.y) {
+        discard;
+    }
+    if (material.slice.z > 0.5 && !in.is_front) {
+        discard;
+    }
+
+    // surfaces seen edge-on are more opaque so the outline of the cavities stands out
+    let view_dir = normalize(view.world_position.xyz - in.world_position.xyz);
+    let facing = abs(dot(normalize(in.world_normal), view_dir));
+    let rim = pow(1.0 - facing, material.params.y);
+    let opacity = material.params.x;
+    let alpha = clamp(opacity + rim * (1.0 - opacity), 0.0, 1.0);
+    return vec4<f32>(material.color.rgb, alpha * material.color.a);
+}
@@ -0,0 +1,96 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+
+use crate::chunk::ChunkMesh;
+
+/// Vertical walls hanging `depth` world units below the edges of `mesh` on
+/// the X and Z faces of a chunk of `extent` world units, with the normal of
+/// the triangle of each edge.
+///
+/// The skirts hide the small cracks between chunks meshed at different
+/// isolevels or levels of detail without stitching their meshes. They're
+/// only added to the render meshes, the [`ChunkMesh`] stays closed.
+pub fn skirt_triangles(mesh: &ChunkMesh, extent: Vec3, depth: f32) -> Vec<([Vec3; 3], Vec3)> {
+    let down = Vec3::new(0.0, -depth, 0.0);
+    // the vertices on a face are interpolated along edges of that face, so
+    // they are exactly on it
+    let same_face = |a: Vec3, b: Vec3| {
+        (a.x <= 0.0 && b.x <= 0.0)
+            || (a.x >= extent.x && b.x >= extent.x)
+            || (a.z <= 0.0 && b.z <= 0.0)
+            || (a.z >= extent.z && b.z >= extent.z)
+    };
+    let mut skirts = Vec::new();
+    for [a, b, c] in mesh.iter_triangles() {
+        let normal = (b - a).cross(c - a).normalize();
+        for (p, q) in [(a, b), (b, c), (c, a)] {
+            if same_face(p, q) {
+                // walk the edge backward so the wall faces the same side as the triangle
+                skirts.push(([q, p, p + down], normal));
+                skirts.push(([q, p + down, q + down], normal));
+            }
+        }
+    }
+    skirts
+}
+
+/// Appends the `skirts` to a render mesh built from a [`ChunkMesh`]
+pub fn append_skirts(mesh: &mut Mesh, skirts: &[([Vec3; 3], Vec3)]) {
+    let first = mesh.count_vertices();
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        positions.extend(
+            skirts
+                .iter()
+                .flat_map(|(triangle, _)| triangle.map(|v| v.to_array())),
+        );
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+    {
+        normals.extend(skirts.iter().flat_map(|(_, normal)| [normal.to_array(); 3]));
+    }
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        uvs.extend(std::iter::repeat([0.0, 0.0]).take(skirts.len() * 3));
+    }
+    let added = first..first + skirts.len() * 3;
+    match mesh.indices_mut() {
+        Some(Indices::U32(indices)) => indices.extend(added.map(|i| i as u32)),
+        Some(Indices::U16(indices)) => indices.extend(added.map(|i| i as u16)),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skirts_hang_from_the_faces() {
+        let extent = Vec3::splat(4.0);
+        // horizontal triangle facing up, with an edge on the face at x = 0
+        let mesh = ChunkMesh {
+            triangles: vec![[
+                Vec3::new(0.0, 2.0, 1.0),
+                Vec3::new(0.0, 2.0, 2.0),
+                Vec3::new(1.0, 2.0, 1.0),
+            ]],
+        };
+        let skirts = skirt_triangles(&mesh, extent, 0.5);
+        assert_eq!(skirts.len(), 2);
+        for ([a, b, c], normal) in &skirts {
+            assert_eq!(*normal, Vec3::Y);
+            assert!([a, b, c].iter().all(|v| v.x == 0.0 && v.y >= 1.5));
+            // facing out of the chunk
+            assert!((*b - *a).cross(*c - *a).x < 0.0);
+        }
+
+        let mut render_mesh = Mesh::from(mesh.indexed(crate::chunk::NormalMode::Flat, |_| None));
+        append_skirts(&mut render_mesh, &skirts);
+        assert_eq!(render_mesh.count_vertices(), 9);
+        assert_eq!(render_mesh.indices().unwrap().len(), 9);
+    }
+}
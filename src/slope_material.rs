@@ -0,0 +1,260 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, ShaderStages,
+        },
+        renderer::RenderDevice,
+    },
+};
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+
+use crate::environment::RenderSettings;
+
+pub const SLOPE_COLORING_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x2b96_f1c7_58de_0a43);
+
+/// Colors the terrain by the slope and the altitude of the surface instead of
+/// using the material assigned to the chunks
+#[derive(Inspectable)]
+pub struct SlopeColoring {
+    pub enabled: bool,
+    /// Color of the flat surfaces
+    pub flat: Color,
+    /// Color of the surfaces steeper than `steep_slope`
+    pub steep: Color,
+    /// Color of the flat surfaces above `snow_height`
+    pub snow: Color,
+    /// Angle in degrees where the surface starts to be steep
+    #[inspectable(min = 0.0, max = 90.0)]
+    pub steep_slope: f32,
+    /// Angle in degrees over which the flat color blends into the steep color
+    #[inspectable(min = 0.0, max = 45.0)]
+    pub slope_blend: f32,
+    #[inspectable(speed = 0.1)]
+    pub snow_height: f32,
+    #[inspectable(min = 0.0, max = 8.0, speed = 0.05)]
+    pub snow_blend: f32,
+}
+
+impl Default for SlopeColoring {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            flat: Color::rgb(0.3, 0.55, 0.2),
+            steep: Color::rgb(0.45, 0.43, 0.4),
+            snow: Color::rgb(0.95, 0.95, 0.97),
+            steep_slope: 35.0,
+            slope_blend: 10.0,
+            snow_height: 12.0,
+            snow_blend: 1.0,
+        }
+    }
+}
+
+impl SlopeColoring {
+    /// Linear color of the surface with the `normal` at `height`, before the
+    /// lighting, like `slope_coloring.wgsl`
+    pub fn albedo(&self, normal: Vec3, height: f32) -> [f32; 4] {
+        let steep_slope = self.steep_slope.to_radians();
+        let slope_blend = self.slope_blend.to_radians();
+        // floors and ceilings are both flat
+        let slope = normal.normalize_or_zero().y.abs().clamp(0.0, 1.0).acos();
+        let steep = smoothstep(steep_slope, steep_slope + slope_blend, slope);
+        let snow_blend = self.snow_blend.max(0.001);
+        let snow = smoothstep(
+            self.snow_height - snow_blend,
+            self.snow_height + snow_blend,
+            height,
+        );
+        let [flat, steep_color, snow_color] =
+            [self.flat, self.steep, self.snow].map(|color| Vec4::from(color.as_linear_rgba_f32()));
+        let albedo = flat.lerp(steep_color, steep);
+        // snow doesn't stick to steep slopes
+        let albedo = albedo.lerp(snow_color, snow * (1.0 - steep));
+        [albedo.x, albedo.y, albedo.z, 1.0]
+    }
+}
+
+/// Same as the WGSL builtin
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+pub struct SlopeColoringPlugin;
+
+impl Plugin for SlopeColoringPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            SLOPE_COLORING_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/slope_coloring.wgsl")),
+        );
+
+        app.add_plugin(MaterialPlugin::<SlopeMaterial>::default())
+            .add_plugin(InspectorPlugin::<SlopeColoring>::new())
+            .init_resource::<SlopeMaterialHandle>()
+            .add_system(update_slope_material);
+    }
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "9a4d2c68-1e7b-4f35-b0c9-58e3d6a1f2b7"]
+pub struct SlopeMaterial {
+    pub flat: Color,
+    pub steep: Color,
+    pub snow: Color,
+    /// In radians
+    pub steep_slope: f32,
+    /// In radians
+    pub slope_blend: f32,
+    pub snow_height: f32,
+    pub snow_blend: f32,
+    pub fog_color: Color,
+    pub fog_start: f32,
+    pub fog_end: f32,
+    pub fog_enabled: bool,
+}
+
+impl SlopeMaterial {
+    pub fn new(settings: &SlopeColoring, render_settings: &RenderSettings) -> Self {
+        Self {
+            flat: settings.flat,
+            steep: settings.steep,
+            snow: settings.snow,
+            steep_slope: settings.steep_slope.to_radians(),
+            slope_blend: settings.slope_blend.to_radians(),
+            snow_height: settings.snow_height,
+            snow_blend: settings.snow_blend,
+            fog_color: render_settings.fog_color,
+            fog_start: render_settings.fog_start,
+            fog_end: render_settings.fog_end,
+            fog_enabled: render_settings.fog_enabled,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GpuSlopeMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for SlopeMaterial {
+    type ExtractedAsset = SlopeMaterial;
+    type PreparedAsset = GpuSlopeMaterial;
+    type Param = (SRes<RenderDevice>, SRes<MaterialPipeline<Self>>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        // matches the SlopeMaterial struct of the shader
+        let mut uniform = Vec::with_capacity(24);
+        for color in [material.flat, material.steep, material.snow] {
+            uniform.extend(color.as_linear_rgba_f32());
+        }
+        uniform.extend([
+            material.steep_slope,
+            material.slope_blend,
+            material.snow_height,
+            material.snow_blend,
+        ]);
+        uniform.extend(material.fog_color.as_linear_rgba_f32());
+        uniform.extend([
+            material.fog_start,
+            material.fog_end,
+            if material.fog_enabled { 1.0 } else { 0.0 },
+            0.0,
+        ]);
+        let contents: Vec<u8> = uniform.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("slope_material_uniform"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("slope_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(GpuSlopeMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for SlopeMaterial {
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(SLOPE_COLORING_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("slope_material_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(24 * 4),
+                },
+                count: None,
+            }],
+        })
+    }
+}
+
+/// Material shared by every chunk while the slope coloring is enabled
+pub struct SlopeMaterialHandle(pub Handle<SlopeMaterial>);
+
+impl FromWorld for SlopeMaterialHandle {
+    fn from_world(world: &mut World) -> Self {
+        world.get_resource_or_insert_with(SlopeColoring::default);
+        world.get_resource_or_insert_with(RenderSettings::default);
+        let material = SlopeMaterial::new(
+            world.get_resource::<SlopeColoring>().unwrap(),
+            world.get_resource::<RenderSettings>().unwrap(),
+        );
+        let mut materials = world.get_resource_mut::<Assets<SlopeMaterial>>().unwrap();
+        Self(materials.add(material))
+    }
+}
+
+fn update_slope_material(
+    settings: Res<SlopeColoring>,
+    render_settings: Res<RenderSettings>,
+    handle: Res<SlopeMaterialHandle>,
+    mut materials: ResMut<Assets<SlopeMaterial>>,
+) {
+    if !settings.is_changed() && !render_settings.is_changed() {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        *material = SlopeMaterial::new(&settings, &render_settings);
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunk::{Chunk, ChunkCoord},
+    terrain_root::MainTerrain,
+    StartMarching,
+};
+
+/// In-memory copy of the points of every chunk of the main terrain, press
+/// F6 to take it and F7 to restore it.
+///
+/// Restoring replaces the points of the chunks and remeshes them, the
+/// materials and isolevel overrides are kept. Chunks spawned after the
+/// snapshot are left untouched.
+#[derive(Default)]
+pub struct FieldSnapshot {
+    chunks: HashMap<IVec3, Chunk>,
+}
+
+impl FieldSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Bytes used by the points of the snapshot
+    pub fn memory_bytes(&self) -> usize {
+        self.chunks.values().map(Chunk::memory_bytes).sum()
+    }
+
+    pub fn take<'a>(&mut self, chunks: impl IntoIterator<Item = (IVec3, &'a Chunk)>) {
+        self.chunks.clear();
+        self.chunks.extend(
+            chunks
+                .into_iter()
+                .map(|(coord, chunk)| (coord, chunk.clone())),
+        );
+    }
+
+    /// Restores the snapshot into the given chunks, returns how many were restored
+    pub fn restore<'a>(&self, chunks: impl IntoIterator<Item = (IVec3, Mut<'a, Chunk>)>) -> usize {
+        let mut restored = 0;
+        for (coord, mut chunk) in chunks {
+            if let Some(saved) = self.chunks.get(&coord) {
+                // don't flag unchanged chunks for meshing
+                if saved.size != chunk.size || saved.points != chunk.points {
+                    chunk.replace_points(saved.points.clone(), saved.size);
+                }
+                restored += 1;
+            }
+        }
+        restored
+    }
+}
+
+pub fn snapshot_field(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut snapshot: ResMut<FieldSnapshot>,
+    terrain: MainTerrain,
+    mut chunks: Query<(&ChunkCoord, &mut Chunk, &Parent)>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let root = terrain.root();
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        snapshot.take(
+            chunks
+                .iter()
+                .filter(|(.., parent)| Some(parent.0) == root)
+                .map(|(coord, chunk, _)| (coord.0, chunk)),
+        );
+        info!(
+            "Took a snapshot of the field, {} KiB",
+            snapshot.memory_bytes() / 1024
+        );
+    } else if keyboard_input.just_pressed(KeyCode::F7) {
+        if snapshot.is_empty() {
+            warn!("No snapshot to restore, press F6 to take one");
+            return;
+        }
+        let restored = snapshot.restore(
+            chunks
+                .iter_mut()
+                .filter(|(.., parent)| Some(parent.0) == root)
+                .map(|(coord, chunk, _)| (coord.0, chunk)),
+        );
+        info!("Restored {restored} chunks from the snapshot");
+        start_marching_events.send_default();
+    }
+}
@@ -0,0 +1,249 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    render::mesh::Indices,
+};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    chunk::{Chunk, ChunkCoord, ChunkIsolevel, ChunkMesh, ChunkStatus},
+    generation::WorldSettings,
+    materials::{ChunkMaterial, MaterialLibrary, SetChunkMaterial},
+    terrain_root::MainTerrain,
+    vertex_cache::{acmr, CACHE_SIZE},
+    Data, SetChunkIsolevel,
+};
+
+/// Shows information about the selected chunk
+#[allow(clippy::too_many_arguments)]
+pub fn chunk_stats_ui(
+    mut egui_context: ResMut<EguiContext>,
+    terrain: MainTerrain,
+    data: Res<Data>,
+    world_settings: Res<WorldSettings>,
+    mut set_isolevel_events: EventWriter<SetChunkIsolevel>,
+    material_library: Res<MaterialLibrary>,
+    mut set_material_events: EventWriter<SetChunkMaterial>,
+    mut material_radius: Local<u32>,
+    meshes: Res<Assets<Mesh>>,
+    chunks: Query<(
+        Entity,
+        &Chunk,
+        &ChunkMesh,
+        &ChunkCoord,
+        &ChunkStatus,
+        Option<&ChunkIsolevel>,
+        &ChunkMaterial,
+        &Handle<Mesh>,
+    )>,
+) {
+    let (entity, chunk, chunk_mesh, coord, status, chunk_isolevel, chunk_material, mesh) =
+        match terrain
+            .selected_chunk()
+            .and_then(|entity| chunks.get(entity).ok())
+        {
+            Some(chunk) => chunk,
+            None => return,
+        };
+
+    egui::Window::new("Selected chunk").show(egui_context.ctx_mut(), |ui| {
+        egui::Grid::new("chunk_stats").show(ui, |ui| {
+            ui.label("Coordinate");
+            ui.label(format!("{:?}", coord.0));
+            ui.end_row();
+
+            ui.label("Status");
+            ui.label(format!("{status:?}"));
+            ui.end_row();
+
+            ui.label("Triangles");
+            ui.label(chunk_mesh.triangles.len().to_string());
+            ui.end_row();
+
+            // depends on the normal mode, flat normals can't share vertices between faces
+            ui.label("Vertices");
+            let vertices = meshes.get(mesh).map_or(0, |mesh| mesh.count_vertices());
+            ui.label(vertices.to_string());
+            ui.end_row();
+
+            // vertices transformed per triangle by the GPU, lower is better
+            ui.label("ACMR");
+            match meshes.get(mesh).and_then(|mesh| mesh.indices()) {
+                Some(Indices::U32(indices)) => {
+                    ui.label(format!("{:.3}", acmr(indices, CACHE_SIZE)))
+                }
+                _ => ui.label("-"),
+            };
+            ui.end_row();
+
+            ui.label("Surface area");
+            ui.label(format!("{:.2}", chunk_mesh.surface_area()));
+            ui.end_row();
+
+            // closed by the solid faces of the chunk
+            ui.label("Enclosed volume");
+            let isolevel = chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0);
+            let volume = chunk.enclosed_volume(
+                chunk_mesh,
+                isolevel,
+                data.interpolation,
+                world_settings.cell_size,
+            );
+            ui.label(format!("{volume:.2}"));
+            ui.end_row();
+
+            ui.label("Points hash");
+            ui.monospace(format!("{:016x}", chunk.content_hash()));
+            ui.end_row();
+
+            ui.label("Mesh hash");
+            ui.monospace(format!("{:016x}", chunk_mesh.content_hash()));
+            ui.end_row();
+
+            ui.label("Isolevel");
+            ui.horizontal(|ui| {
+                let mut overridden = chunk_isolevel.is_some();
+                let mut isolevel = chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0);
+                let mut changed = ui.checkbox(&mut overridden, "Override").changed();
+                if overridden {
+                    changed |= ui
+                        .add(egui::Slider::new(&mut isolevel, 0.0..=1.0))
+                        .changed();
+                }
+                if changed {
+                    let isolevel = if overridden { Some(isolevel) } else { None };
+                    set_isolevel_events.send(SetChunkIsolevel {
+                        chunk: entity,
+                        isolevel,
+                    });
+                }
+            });
+            ui.end_row();
+
+            ui.label("Material");
+            ui.horizontal(|ui| {
+                let mut material = chunk_material.0;
+                egui::ComboBox::from_id_source("chunk_material")
+                    .selected_text(material_library.name(material))
+                    .show_ui(ui, |ui| {
+                        for (index, (name, _)) in material_library.materials.iter().enumerate() {
+                            ui.selectable_value(&mut material, index, name.as_str());
+                        }
+                    });
+                // also apply it to the chunks around the selected one
+                ui.add(egui::DragValue::new(&mut *material_radius).clamp_range(0..=8))
+                    .on_hover_text("Radius in chunks");
+                let apply = ui.button("Apply").clicked();
+                if apply || material != chunk_material.0 {
+                    set_material_events.send(SetChunkMaterial {
+                        center: coord.0,
+                        radius: *material_radius,
+                        material,
+                    });
+                }
+            });
+            ui.end_row();
+        });
+    });
+}
+
+pub const CHUNK_POINTS_MEMORY: DiagnosticId =
+    DiagnosticId::from_u128(0x9a3c_81d2_4f1e_4b7a_8e55_0c6d_2b19_f4a1);
+pub const CHUNK_TRIANGLES_MEMORY: DiagnosticId =
+    DiagnosticId::from_u128(0x5d27_c04e_93b8_4e61_a1f2_7e34_8c90_1b5d);
+pub const CHUNK_MESH_ASSETS_MEMORY: DiagnosticId =
+    DiagnosticId::from_u128(0x1fe8_6b53_2ac7_4d90_b3e6_45a1_d07c_9e28);
+
+/// Bytes used by the vertex and index buffers of a mesh
+pub fn mesh_memory_bytes(mesh: &Mesh) -> usize {
+    let vertices = mesh.count_vertices() * mesh.get_vertex_size() as usize;
+    let indices = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.len() * 2,
+        Some(Indices::U32(indices)) => indices.len() * 4,
+        None => 0,
+    };
+    vertices + indices
+}
+
+fn format_bytes(bytes: usize) -> String {
+    let bytes = bytes as f64;
+    if bytes >= 1024.0 * 1024.0 {
+        format!("{:.2} MiB", bytes / (1024.0 * 1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.1} KiB", bytes / 1024.0)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+pub fn setup_memory_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(
+        CHUNK_POINTS_MEMORY,
+        "chunk_points_bytes",
+        20,
+    ));
+    diagnostics.add(Diagnostic::new(
+        CHUNK_TRIANGLES_MEMORY,
+        "chunk_triangles_bytes",
+        20,
+    ));
+    diagnostics.add(Diagnostic::new(
+        CHUNK_MESH_ASSETS_MEMORY,
+        "chunk_mesh_assets_bytes",
+        20,
+    ));
+}
+
+/// Reports the memory used by the chunk points, the triangle buffers and the
+/// mesh assets, and shows the breakdown per chunk
+pub fn memory_diagnostics(
+    mut egui_context: ResMut<EguiContext>,
+    mut diagnostics: ResMut<Diagnostics>,
+    meshes: Res<Assets<Mesh>>,
+    chunks: Query<(&Chunk, &ChunkMesh, &ChunkCoord, &Handle<Mesh>)>,
+) {
+    let mut rows = Vec::new();
+    let (mut total_points, mut total_triangles, mut total_meshes) = (0, 0, 0);
+    for (chunk, chunk_mesh, coord, mesh_handle) in chunks.iter() {
+        let points = chunk.memory_bytes();
+        let triangles = chunk_mesh.memory_bytes();
+        let mesh = meshes.get(mesh_handle).map_or(0, mesh_memory_bytes);
+        total_points += points;
+        total_triangles += triangles;
+        total_meshes += mesh;
+        rows.push((coord.0, points, triangles, mesh));
+    }
+    rows.sort_by_key(|(coord, ..)| (coord.x, coord.y, coord.z));
+
+    diagnostics.add_measurement(CHUNK_POINTS_MEMORY, total_points as f64);
+    diagnostics.add_measurement(CHUNK_TRIANGLES_MEMORY, total_triangles as f64);
+    diagnostics.add_measurement(CHUNK_MESH_ASSETS_MEMORY, total_meshes as f64);
+
+    egui::Window::new("Memory")
+        .default_open(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::Grid::new("memory_stats")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Chunk");
+                    ui.strong("Points");
+                    ui.strong("Triangles");
+                    ui.strong("Mesh");
+                    ui.end_row();
+
+                    ui.strong("Total");
+                    ui.strong(format_bytes(total_points));
+                    ui.strong(format_bytes(total_triangles));
+                    ui.strong(format_bytes(total_meshes));
+                    ui.end_row();
+
+                    for (coord, points, triangles, mesh) in rows {
+                        ui.label(format!("{} {} {}", coord.x, coord.y, coord.z));
+                        ui.label(format_bytes(points));
+                        ui.label(format_bytes(triangles));
+                        ui.label(format_bytes(mesh));
+                        ui.end_row();
+                    }
+                });
+        });
+}
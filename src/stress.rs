@@ -0,0 +1,128 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{chunk::ChunkMesh, generation::WorldSettings, Data};
+
+/// Built-in stress scenario: a large world remeshed every frame by animating
+/// the isolevel. A performance report is logged at the end of the run and the
+/// app exits, run it in release with `--stress` to compare commits.
+#[derive(Clone, Debug)]
+pub struct StressTest {
+    pub chunk_count: UVec3,
+    pub chunk_size: UVec3,
+    /// Seconds to wait for the first generation before measuring
+    pub warmup: f32,
+    /// Seconds measured after the warmup
+    pub duration: f32,
+    /// The isolevel oscillates by this much around its initial value
+    pub isolevel_amplitude: f32,
+    /// Seconds for a full oscillation of the isolevel
+    pub isolevel_period: f32,
+}
+
+impl Default for StressTest {
+    fn default() -> Self {
+        Self {
+            chunk_count: UVec3::new(16, 4, 16),
+            chunk_size: UVec3::splat(32),
+            warmup: 3.0,
+            duration: 30.0,
+            isolevel_amplitude: 0.1,
+            isolevel_period: 4.0,
+        }
+    }
+}
+
+/// Runs the [`StressTest`] inserted before adding the plugin, or the default one.
+///
+/// The chunk dimensions of the [`WorldSettings`] are replaced by the ones of
+/// the test.
+pub struct StressTestPlugin;
+
+impl Plugin for StressTestPlugin {
+    fn build(&self, app: &mut App) {
+        let test = app
+            .world
+            .get_resource::<StressTest>()
+            .cloned()
+            .unwrap_or_default();
+        info!("Running stress test {test:?}");
+        app.insert_resource(
+            WorldSettings::default()
+                .with_chunk_count(test.chunk_count)
+                .with_chunk_size(test.chunk_size),
+        )
+        .insert_resource(test)
+        .init_resource::<StressTestRun>()
+        .add_system(run_stress_test);
+    }
+}
+
+#[derive(Default)]
+struct StressTestRun {
+    elapsed: f32,
+    base_isolevel: Option<f32>,
+    /// Seconds of every measured frame
+    frame_times: Vec<f32>,
+    remeshes: usize,
+    triangles: usize,
+}
+
+fn run_stress_test(
+    time: Res<Time>,
+    test: Res<StressTest>,
+    mut run: ResMut<StressTestRun>,
+    mut data: ResMut<Data>,
+    remeshed: Query<&ChunkMesh, Changed<ChunkMesh>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    run.elapsed += time.delta_seconds();
+    if run.elapsed < test.warmup {
+        return;
+    }
+    let base_isolevel = *run.base_isolevel.get_or_insert(data.isolevel);
+
+    let t = (run.elapsed - test.warmup) / test.isolevel_period.max(0.001);
+    data.isolevel = base_isolevel + (t * std::f32::consts::TAU).sin() * test.isolevel_amplitude;
+
+    run.frame_times.push(time.delta_seconds());
+    for chunk_mesh in remeshed.iter() {
+        run.remeshes += 1;
+        run.triangles += chunk_mesh.triangles.len();
+    }
+
+    if run.elapsed >= test.warmup + test.duration {
+        report(&run, test.duration);
+        app_exit.send(AppExit);
+    }
+}
+
+fn report(run: &StressTestRun, duration: f32) {
+    let mut frame_times = run.frame_times.clone();
+    frame_times.sort_by(|a, b| a.total_cmp(b));
+    let frames = frame_times.len().max(1);
+    let percentile = |p: f32| {
+        let index = ((frames - 1) as f32 * p).round() as usize;
+        frame_times.get(index).copied().unwrap_or(0.0) * 1000.0
+    };
+    let mean = frame_times.iter().sum::<f32>() / frames as f32 * 1000.0;
+
+    info!("Stress test report");
+    info!(
+        "  {} frames in {duration:.1}s, {:.1} fps",
+        frame_times.len(),
+        frame_times.len() as f32 / duration
+    );
+    info!(
+        "  frame time: mean {mean:.2} ms, p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+        percentile(0.5),
+        percentile(0.95),
+        percentile(0.99),
+        percentile(1.0)
+    );
+    info!(
+        "  {} chunk remeshes, {:.1} per second, {:.0} triangles per second",
+        run.remeshes,
+        run.remeshes as f32 / duration,
+        run.triangles as f32 / duration
+    );
+}
@@ -0,0 +1,262 @@
+use std::io::{self, Read, Write};
+
+use bevy::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    save::{decode_chunk, invalid_data, SaveMigrations, CHUNK_MAGIC, FORMAT_VERSION},
+};
+
+pub const SVO_MAGIC: &[u8; 4] = b"MCSV";
+/// Nodes covering at most this many points per axis store their points
+/// instead of being split further
+pub const BRICK_SIZE: u32 = 4;
+/// Refuse to allocate chunks larger than this when reading a corrupted header
+const MAX_POINTS: usize = 1 << 28;
+
+const UNIFORM: u8 = 0;
+const BRICK: u8 = 1;
+const BRANCH: u8 = 2;
+
+/// Encodes a chunk as a sparse voxel octree, see [`write_svo`]
+pub fn encode_svo(chunk: &Chunk, version: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_svo(&mut bytes, chunk, version).expect("writing to a Vec can't fail");
+    bytes
+}
+
+/// Writes a chunk as a sparse voxel octree.
+///
+/// The header is the same as the dense chunk files with another magic. The
+/// root node covers the points of the chunk rounded up to a power of two,
+/// nodes where every point has the same value store it once, the other
+/// nodes are split in 8 until they are small enough to be stored as bricks.
+/// Nodes entirely outside the chunk aren't written.
+pub fn write_svo(writer: &mut impl Write, chunk: &Chunk, version: u64) -> io::Result<()> {
+    writer.write_all(SVO_MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&version.to_le_bytes())?;
+    for size in chunk.size.to_array() {
+        writer.write_all(&size.to_le_bytes())?;
+    }
+    write_node(writer, chunk, UVec3::ZERO, root_side(chunk.size))
+}
+
+/// Reads a chunk written by [`write_svo`] node by node, without buffering
+/// the file. Returns the chunk and its version.
+///
+/// The points of files written with an older format version are decoded
+/// into a dense chunk file of that version, then upgraded by `migrations`
+/// like the dense files.
+pub fn read_svo(reader: &mut impl Read, migrations: &SaveMigrations) -> io::Result<(Chunk, u64)> {
+    let mut header = [0; 4 + 4 + 8 + 3 * 4];
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != SVO_MAGIC {
+        return Err(invalid_data("not an octree chunk file"));
+    }
+    let format_version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if format_version > FORMAT_VERSION {
+        return Err(invalid_data(
+            "chunk file is newer than this version supports",
+        ));
+    }
+    let version = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let size_at =
+        |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let size = UVec3::new(size_at(16), size_at(20), size_at(24));
+    if size.max_element() >= 1 << 16 || Chunk::points_len(size) > MAX_POINTS {
+        return Err(invalid_data("chunk is too large"));
+    }
+
+    let mut points = vec![0.0; Chunk::points_len(size)];
+    read_node(
+        reader,
+        &mut points,
+        size + UVec3::ONE,
+        UVec3::ZERO,
+        root_side(size),
+    )?;
+    if format_version == FORMAT_VERSION {
+        return Ok((Chunk::new(points, size), version));
+    }
+
+    // same header as the dense files, with the other magic
+    let mut dense = Vec::with_capacity(header.len() + points.len() * 4);
+    dense.extend_from_slice(CHUNK_MAGIC);
+    dense.extend_from_slice(&header[4..]);
+    for point in &points {
+        dense.extend_from_slice(&point.to_le_bytes());
+    }
+    decode_chunk(&migrations.migrate(dense, format_version)?)
+}
+
+fn root_side(size: UVec3) -> u32 {
+    (size + UVec3::ONE)
+        .max_element()
+        .next_power_of_two()
+        .max(BRICK_SIZE)
+}
+
+/// End of the points covered by a node, exclusive and clamped to the chunk,
+/// `None` if the node is outside the chunk
+fn clip(min: UVec3, side: u32, dims: UVec3) -> Option<UVec3> {
+    let max = (min + UVec3::splat(side)).min(dims);
+    max.cmpgt(min).all().then(|| max)
+}
+
+/// Indices of the points from `min` to `max` exclusive, in storage order
+fn region_indices(dims: UVec3, min: UVec3, max: UVec3) -> impl Iterator<Item = usize> {
+    (min.z..max.z).flat_map(move |z| {
+        (min.y..max.y).flat_map(move |y| {
+            (min.x..max.x).map(move |x| (x + dims.x * (y + dims.y * z)) as usize)
+        })
+    })
+}
+
+fn child_min(min: UVec3, half: u32, child: u32) -> UVec3 {
+    min + UVec3::new(child & 1, child >> 1 & 1, child >> 2 & 1) * half
+}
+
+fn write_node(writer: &mut impl Write, chunk: &Chunk, min: UVec3, side: u32) -> io::Result<()> {
+    let dims = chunk.size + UVec3::ONE;
+    let max = match clip(min, side, dims) {
+        Some(max) => max,
+        None => return Ok(()),
+    };
+
+    let mut values = region_indices(dims, min, max).map(|i| chunk.points[i]);
+    let first = values.next().unwrap();
+    // compare the bits so the values are restored exactly
+    if values.all(|value| value.to_bits() == first.to_bits()) {
+        writer.write_all(&[UNIFORM])?;
+        writer.write_all(&first.to_le_bytes())
+    } else if side <= BRICK_SIZE {
+        writer.write_all(&[BRICK])?;
+        for i in region_indices(dims, min, max) {
+            writer.write_all(&chunk.points[i].to_le_bytes())?;
+        }
+        Ok(())
+    } else {
+        writer.write_all(&[BRANCH])?;
+        let half = side / 2;
+        for child in 0..8 {
+            write_node(writer, chunk, child_min(min, half, child), half)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_node(
+    reader: &mut impl Read,
+    points: &mut [f32],
+    dims: UVec3,
+    min: UVec3,
+    side: u32,
+) -> io::Result<()> {
+    let max = match clip(min, side, dims) {
+        Some(max) => max,
+        None => return Ok(()),
+    };
+
+    let mut tag = [0; 1];
+    reader.read_exact(&mut tag)?;
+    match tag[0] {
+        UNIFORM => {
+            let value = read_f32(reader)?;
+            for i in region_indices(dims, min, max) {
+                points[i] = value;
+            }
+        }
+        BRICK if side <= BRICK_SIZE => {
+            for i in region_indices(dims, min, max) {
+                points[i] = read_f32(reader)?;
+            }
+        }
+        BRANCH if side > BRICK_SIZE => {
+            let half = side / 2;
+            for child in 0..8 {
+                read_node(reader, points, dims, child_min(min, half, child), half)?;
+            }
+        }
+        _ => return Err(invalid_data("invalid octree node")),
+    }
+    Ok(())
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::encode_chunk;
+
+    fn read(bytes: &[u8]) -> io::Result<(Chunk, u64)> {
+        read_svo(&mut &bytes[..], &SaveMigrations::default())
+    }
+
+    #[test]
+    fn octree_roundtrip() {
+        let size = UVec3::new(13, 6, 9);
+        // a flat ground with a bump, so there are uniform and mixed nodes
+        let chunk = Chunk::from_fn(size, |point| {
+            if point.y < 3 || (point.x == 5 && point.z == 2) {
+                1.0
+            } else {
+                point.y as f32 * 0.01
+            }
+        });
+        let bytes = encode_svo(&chunk, 7);
+        let (decoded, version) = read(&bytes).unwrap();
+        assert_eq!(version, 7);
+        assert_eq!(decoded.size, size);
+        assert_eq!(decoded.points, chunk.points);
+    }
+
+    #[test]
+    fn migrates_older_format_version() {
+        let size = UVec3::new(4, 2, 3);
+        let chunk = Chunk::from_fn(size, |point| if point.y == 0 { 1.0 } else { 0.25 });
+        let mut bytes = encode_svo(&chunk, 9);
+        let previous = FORMAT_VERSION - 1;
+        bytes[4..8].copy_from_slice(&previous.to_le_bytes());
+
+        // stands for the migration of the next format bump, the points are
+        // already decoded to the dense layout
+        let mut migrations = SaveMigrations::default();
+        migrations.register(previous, |mut bytes| {
+            assert_eq!(&bytes[0..4], CHUNK_MAGIC);
+            bytes[4..8].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+            Ok(bytes)
+        });
+        let (decoded, version) = read_svo(&mut bytes.as_slice(), &migrations).unwrap();
+        assert_eq!(version, 9);
+        assert_eq!(decoded.size, size);
+        assert_eq!(decoded.points, chunk.points);
+
+        bytes[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(read(&bytes).is_err());
+    }
+
+    #[test]
+    fn uniform_regions_are_smaller() {
+        let size = UVec3::splat(32);
+        let chunk = Chunk::from_fn(size, |point| if point.y < 8 { 1.0 } else { 0.0 });
+        let octree = encode_svo(&chunk, 0);
+        assert!(octree.len() * 10 < encode_chunk(&chunk, 0).len());
+
+        let empty = Chunk::from_fn(size, |_| 0.0);
+        // header, tag and value
+        assert_eq!(encode_svo(&empty, 0).len(), 28 + 1 + 4);
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let chunk = Chunk::from_fn(UVec3::splat(5), |point| point.x as f32);
+        let bytes = encode_svo(&chunk, 0);
+        assert!(read(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
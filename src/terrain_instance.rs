@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+
+use crate::{
+    chunk::ChunkMap,
+    generation::{NoiseSettings, WorldSettings},
+    terrain_root::TerrainRoot,
+    Data, SelectedChunk,
+};
+
+/// Marks the root of a terrain generated next to the main one with its own
+/// settings, like an asteroid orbiting a planet.
+///
+/// Its chunks are generated and marched by the same systems as the main
+/// terrain from the settings on its [`TerrainRoot`], edit them to generate
+/// or mesh it again. The editing tools and the inspector windows only work
+/// on the main terrain.
+#[derive(Component, Default)]
+pub struct TerrainInstance;
+
+/// Spawns the root of a new terrain at `transform`, its chunks are spawned
+/// as its children on the next frame
+pub fn spawn_terrain_instance(
+    commands: &mut Commands,
+    data: Data,
+    noise_settings: NoiseSettings,
+    world_settings: WorldSettings,
+    transform: Transform,
+) -> Entity {
+    commands
+        .spawn()
+        .insert(transform)
+        .insert(GlobalTransform::identity())
+        .insert(Visibility::default())
+        .insert(TerrainRoot)
+        .insert(TerrainInstance)
+        .insert(data)
+        .insert(noise_settings)
+        .insert(world_settings)
+        .insert(ChunkMap::default())
+        .insert(SelectedChunk::default())
+        .id()
+}
@@ -0,0 +1,122 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    chunk::ChunkMap,
+    generation::{NoiseSettings, WorldSettings},
+    terrain_instance::TerrainInstance,
+    Data, SelectedChunk,
+};
+
+/// Parent of every entity of the terrain: the chunks with the objects placed
+/// on them, the debug points, the LOD impostors and the merged mesh.
+///
+/// Transforming, hiding or despawning it, with `despawn_recursive`, applies
+/// to the whole terrain. The chunks are placed in its local space and the
+/// editing tools, like the brush or the picking, expect the root of the main
+/// terrain at the origin. The roots of the other terrains have a
+/// [`TerrainInstance`].
+///
+/// The root holds the state of its terrain: its [`Data`], [`NoiseSettings`]
+/// and [`WorldSettings`], its [`ChunkMap`] and its [`SelectedChunk`]. The
+/// settings of the main terrain are copied from the resources of the same
+/// type, edited in the inspector windows.
+#[derive(Component, Default)]
+pub struct TerrainRoot;
+
+pub fn spawn_terrain_root(
+    mut commands: Commands,
+    data: Res<Data>,
+    noise_settings: Res<NoiseSettings>,
+    world_settings: Res<WorldSettings>,
+) {
+    commands
+        .spawn()
+        .insert(Transform::identity())
+        .insert(GlobalTransform::identity())
+        .insert(Visibility::default())
+        .insert(TerrainRoot)
+        .insert(data.clone())
+        .insert(noise_settings.clone())
+        .insert(world_settings.clone())
+        .insert(ChunkMap::default())
+        .insert(SelectedChunk::default());
+}
+
+/// The main terrain, the one the editing tools and the inspector windows work on
+#[derive(SystemParam)]
+pub struct MainTerrain<'w, 's> {
+    roots: Query<
+        'w,
+        's,
+        (Entity, &'static ChunkMap, &'static SelectedChunk),
+        (With<TerrainRoot>, Without<TerrainInstance>),
+    >,
+}
+
+impl<'w, 's> MainTerrain<'w, 's> {
+    /// Root of the main terrain, `None` once despawned
+    pub fn root(&self) -> Option<Entity> {
+        self.roots.get_single().ok().map(|(root, ..)| root)
+    }
+
+    pub fn chunk_map(&self) -> Option<&ChunkMap> {
+        self.roots
+            .get_single()
+            .ok()
+            .map(|(_, chunk_map, _)| chunk_map)
+    }
+
+    /// Chunk of the main terrain clicked last
+    pub fn selected_chunk(&self) -> Option<Entity> {
+        self.roots
+            .get_single()
+            .ok()
+            .and_then(|(.., selected)| selected.0)
+    }
+}
+
+/// Copies the settings edited in the inspector windows on the root of the
+/// main terrain
+pub fn sync_main_terrain(
+    data: Res<Data>,
+    noise_settings: Res<NoiseSettings>,
+    world_settings: Res<WorldSettings>,
+    mut roots: Query<
+        (&mut Data, &mut NoiseSettings, &mut WorldSettings),
+        (With<TerrainRoot>, Without<TerrainInstance>),
+    >,
+) {
+    for (mut root_data, mut root_noise, mut root_world) in roots.iter_mut() {
+        if data.is_changed() {
+            *root_data = data.clone();
+        }
+        if noise_settings.is_changed() {
+            *root_noise = noise_settings.clone();
+        }
+        if world_settings.is_changed() {
+            *root_world = world_settings.clone();
+        }
+    }
+}
+
+/// Hides the descendants of the hidden roots, [`Visibility`] isn't inherited
+pub fn hide_terrain(
+    roots: Query<(&Visibility, &Children), With<TerrainRoot>>,
+    children: Query<&Children>,
+    mut visibilities: Query<&mut ComputedVisibility>,
+) {
+    for (visibility, root_children) in roots.iter() {
+        if visibility.is_visible {
+            continue;
+        }
+        let mut stack: Vec<Entity> = root_children.iter().copied().collect();
+        while let Some(entity) = stack.pop() {
+            if let Ok(mut computed) = visibilities.get_mut(entity) {
+                computed.is_visible = false;
+            }
+            if let Ok(children) = children.get(entity) {
+                stack.extend(children.iter().copied());
+            }
+        }
+    }
+}
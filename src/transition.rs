@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{chunk::Chunk, generation::EMPTY, StartMarching};
+
+/// Animates the surface of a chunk when its points change instead of popping
+/// to the new mesh.
+///
+/// The chunk is marched every frame of the transition with its points blended
+/// from the ones of the previous mesh to the new ones. Chunks that were never
+/// meshed are blended from an empty field so they grow in.
+#[derive(Inspectable)]
+pub struct EditTransition {
+    pub enabled: bool,
+    /// Duration of the transition in seconds
+    #[inspectable(min = 0.05, max = 2.0, speed = 0.01)]
+    pub duration: f32,
+}
+
+impl Default for EditTransition {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration: 0.3,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct ChunkTransition {
+    /// Points used for the last march of the chunk, only kept when the
+    /// transitions are enabled
+    pub meshed: Vec<f32>,
+    from: Vec<f32>,
+    /// Seconds since the start of the transition, `None` when the chunk isn't
+    /// transitioning
+    elapsed: Option<f32>,
+}
+
+impl ChunkTransition {
+    /// Points of the chunk at the current step of the transition,
+    /// `None` when the chunk isn't transitioning
+    pub fn blended(&self, chunk: &Chunk, duration: f32) -> Option<Chunk> {
+        let elapsed = self.elapsed?;
+        if self.from.len() != chunk.points.len() {
+            return None;
+        }
+        let t = (elapsed / duration).clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        let points = self
+            .from
+            .iter()
+            .zip(&chunk.points)
+            .map(|(from, to)| from + (to - from) * t)
+            .collect();
+        Some(Chunk::new(points, chunk.size))
+    }
+}
+
+/// Starts a transition from the last meshed points when the points of a chunk change
+pub fn start_transitions(
+    settings: Res<EditTransition>,
+    mut chunks: Query<(&Chunk, &mut ChunkTransition), Changed<Chunk>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    for (chunk, mut transition) in chunks.iter_mut() {
+        transition.from = if transition.meshed.len() == chunk.points.len() {
+            transition.meshed.clone()
+        } else {
+            vec![EMPTY; chunk.points.len()]
+        };
+        transition.elapsed = Some(0.0);
+    }
+}
+
+/// Advances the transitions and marches the transitioning chunks again
+pub fn advance_transitions(
+    time: Res<Time>,
+    settings: Res<EditTransition>,
+    mut transitions: Query<&mut ChunkTransition>,
+    mut start_marching_events: EventWriter<StartMarching>,
+) {
+    let mut transitioning = false;
+    for mut transition in transitions.iter_mut() {
+        let elapsed = match transition.elapsed {
+            Some(elapsed) => elapsed + time.delta_seconds(),
+            None => continue,
+        };
+        // the last march after the end of the transition uses the real points
+        transition.elapsed = if settings.enabled && elapsed < settings.duration {
+            Some(elapsed)
+        } else {
+            transition.from = Vec::new();
+            None
+        };
+        transitioning = true;
+    }
+    if transitioning {
+        start_marching_events.send_default();
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    chunk::{Chunk, ChunkCoord, ChunkMesh},
+    generation::WorldSettings,
+    terrain_root::TerrainRoot,
+};
+
+/// Checks the marched meshes for winding, manifold and hole errors.
+///
+/// Press V to validate every chunk on demand.
+#[derive(Inspectable)]
+pub struct MeshValidation {
+    /// Validate every chunk after it's remeshed, enabled by default in debug builds
+    pub on_remesh: bool,
+}
+
+impl Default for MeshValidation {
+    fn default() -> Self {
+        Self {
+            on_remesh: cfg!(debug_assertions),
+        }
+    }
+}
+
+/// Cells of a chunk where the marched mesh has errors
+#[derive(Default, Debug)]
+pub struct ValidationReport {
+    /// Triangles with at least 2 identical vertices. They are a side effect of
+    /// snapping vertices to the grid and are reported without being an error.
+    pub degenerate: Vec<UVec3>,
+    /// Edges shared by 2 triangles using it in the same direction
+    pub inconsistent_winding: Vec<UVec3>,
+    /// Edges shared by more than 2 triangles
+    pub non_manifold: Vec<UVec3>,
+    /// Edges used by a single triangle that aren't on the border of the chunk
+    pub holes: Vec<UVec3>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.inconsistent_winding.is_empty()
+            && self.non_manifold.is_empty()
+            && self.holes.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct EdgeUses {
+    /// Number of triangles using the edge from the lower to the higher vertex id
+    forward: u32,
+    backward: u32,
+}
+
+/// Validates the `triangles` of a chunk of the given `size`, in chunk local coordinates
+/// scaled by `cell_size`
+pub fn validate(triangles: &[[Vec3; 3]], size: UVec3, cell_size: f32) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    // vertices are shared between triangles by position, quantized to absorb
+    // rounding differences between the cells sharing an edge
+    let mut vertex_ids = HashMap::default();
+    let mut positions = Vec::new();
+    let mut vertex_id = |position: Vec3| {
+        let key = (position * 4096.0).round().as_ivec3();
+        *vertex_ids.entry(key).or_insert_with(|| {
+            positions.push(position);
+            positions.len() - 1
+        })
+    };
+
+    let mut edges: HashMap<(usize, usize), EdgeUses> = HashMap::default();
+    for triangle in triangles {
+        let ids = triangle.map(&mut vertex_id);
+        if ids[0] == ids[1] || ids[1] == ids[2] || ids[2] == ids[0] {
+            let center = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+            report.degenerate.push(cell(center / cell_size, size));
+            continue;
+        }
+        for i in 0..3 {
+            let (a, b) = (ids[i], ids[(i + 1) % 3]);
+            let uses = edges.entry((a.min(b), a.max(b))).or_default();
+            if a < b {
+                uses.forward += 1;
+            } else {
+                uses.backward += 1;
+            }
+        }
+    }
+
+    for ((a, b), uses) in edges {
+        let (a, b) = (positions[a], positions[b]);
+        let cell = cell((a + b) / (2.0 * cell_size), size);
+        match uses.forward + uses.backward {
+            1 if !on_border(a, b, size.as_vec3() * cell_size) => report.holes.push(cell),
+            2 if uses.forward != 1 => report.inconsistent_winding.push(cell),
+            n if n > 2 => report.non_manifold.push(cell),
+            _ => {}
+        }
+    }
+
+    for cells in [
+        &mut report.degenerate,
+        &mut report.inconsistent_winding,
+        &mut report.non_manifold,
+        &mut report.holes,
+    ] {
+        cells.sort_by_key(|c| (c.x, c.y, c.z));
+        cells.dedup();
+    }
+    report
+}
+
+/// Cell of the chunk containing `position`
+fn cell(position: Vec3, size: UVec3) -> UVec3 {
+    position
+        .floor()
+        .clamp(Vec3::ZERO, (size - UVec3::ONE).as_vec3())
+        .as_uvec3()
+}
+
+/// Whether the edge lies on one of the faces of the chunk, where the surface
+/// continues in the neighboring chunk. `extent` is the size of the chunk in
+/// world units, the vertices on its far faces are exactly at this coordinate.
+fn on_border(a: Vec3, b: Vec3, extent: Vec3) -> bool {
+    let on_face =
+        |a: f32, b: f32, extent: f32| (a == 0.0 && b == 0.0) || (a == extent && b == extent);
+    on_face(a.x, b.x, extent.x) || on_face(a.y, b.y, extent.y) || on_face(a.z, b.z, extent.z)
+}
+
+pub fn validate_meshes(
+    settings: Res<MeshValidation>,
+    keyboard_input: Res<Input<KeyCode>>,
+    terrains: Query<&WorldSettings, With<TerrainRoot>>,
+    chunks: Query<(
+        ChangeTrackers<ChunkMesh>,
+        &ChunkMesh,
+        &Chunk,
+        &ChunkCoord,
+        &Parent,
+    )>,
+) {
+    let on_demand = keyboard_input.just_pressed(KeyCode::V);
+    for (mesh_tracker, mesh, chunk, coord, parent) in chunks.iter() {
+        if !(on_demand || (settings.on_remesh && mesh_tracker.is_changed())) {
+            continue;
+        }
+        let world_settings = match terrains.get(parent.0) {
+            Ok(world_settings) => world_settings,
+            Err(_) => continue,
+        };
+        let report = validate(&mesh.triangles, chunk.size, world_settings.cell_size);
+        if report.is_valid() {
+            if on_demand {
+                info!(
+                    "chunk {} mesh is valid, {} degenerate triangles",
+                    coord.0,
+                    report.degenerate.len()
+                );
+            }
+            continue;
+        }
+        warn!("chunk {} mesh is invalid", coord.0);
+        if !report.inconsistent_winding.is_empty() {
+            warn!(
+                "  inconsistent winding in cells {:?}",
+                report.inconsistent_winding
+            );
+        }
+        if !report.non_manifold.is_empty() {
+            warn!("  non-manifold edges in cells {:?}", report.non_manifold);
+        }
+        if !report.holes.is_empty() {
+            warn!("  holes in cells {:?}", report.holes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpolation::Interpolation, march_cube, GridCell};
+
+    /// Marches a sphere fully contained in a chunk, the mesh must be closed
+    fn sphere(interpolation: Interpolation) -> Vec<[Vec3; 3]> {
+        let size = 9;
+        let center = Vec3::new(4.3, 4.6, 4.4);
+        let mut triangles = Vec::new();
+        for pos in Chunk::new_iter_3d(UVec3::splat(size - 1)) {
+            let mut grid_cell = GridCell::new(pos.as_vec3(), 1.0);
+            for (i, v_pos) in grid_cell.vertex_position.iter().enumerate() {
+                grid_cell.value[i] = 2.7 - v_pos.distance(center) + 0.5;
+            }
+            if let Some(cell_triangles) = march_cube(&grid_cell, 0.5, interpolation) {
+                triangles.extend(cell_triangles);
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn marched_sphere_is_valid() {
+        for interpolation in [Interpolation::Linear, Interpolation::Midpoint] {
+            let triangles = sphere(interpolation);
+            assert!(!triangles.is_empty());
+            let report = validate(&triangles, UVec3::splat(9), 1.0);
+            assert!(report.is_valid(), "{:?}: {:?}", interpolation, report);
+        }
+    }
+
+    #[test]
+    fn flipped_triangle_is_reported() {
+        let mut triangles = sphere(Interpolation::Linear);
+        triangles[0].swap(1, 2);
+        let report = validate(&triangles, UVec3::splat(9), 1.0);
+        assert!(!report.inconsistent_winding.is_empty());
+    }
+
+    #[test]
+    fn missing_triangle_is_reported() {
+        let mut triangles = sphere(Interpolation::Linear);
+        triangles.pop();
+        let report = validate(&triangles, UVec3::splat(9), 1.0);
+        assert!(!report.holes.is_empty());
+    }
+
+    #[test]
+    fn chunk_border_is_not_a_hole() {
+        let triangles = [[Vec3::ZERO, Vec3::X, Vec3::Y]];
+        let report = validate(&triangles, UVec3::ONE, 1.0);
+        assert!(report.holes.is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+/// Number of vertices the scoring assumes the GPU keeps in its post transform cache
+pub const CACHE_SIZE: usize = 32;
+
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Score of a vertex in Tom Forsyth's "Linear-Speed Vertex Cache Optimisation".
+///
+/// Vertices recently used score higher so the next triangles reuse them, and
+/// vertices with few remaining triangles score higher so they are finished
+/// before being evicted.
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: u32) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        // the vertices of the last triangle are penalized a bit so the strip
+        // doesn't turn back on itself
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scaler = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scaler).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    let valence_boost =
+        VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER);
+    cache_score + valence_boost
+}
+
+/// Reorders the triangles of an indexed triangle list to reuse the vertices in
+/// the GPU vertex cache, with the algorithm of Tom Forsyth.
+///
+/// Only the order of the triangles changes, their winding is kept. The
+/// vertex buffer isn't touched.
+pub fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // triangles using each vertex, the first `remaining[v]` of each list
+    // haven't been emitted yet
+    let mut remaining = vec![0u32; vertex_count];
+    for &vertex in indices.iter() {
+        remaining[vertex as usize] += 1;
+    }
+    let mut offsets = Vec::with_capacity(vertex_count + 1);
+    offsets.push(0);
+    for count in &remaining {
+        offsets.push(offsets.last().unwrap() + *count as usize);
+    }
+    let mut vertex_triangles = vec![0; indices.len()];
+    let mut fill = offsets.clone();
+    for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+        for &vertex in vertices {
+            vertex_triangles[fill[vertex as usize]] = triangle;
+            fill[vertex as usize] += 1;
+        }
+    }
+
+    let mut cache_positions = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = remaining
+        .iter()
+        .map(|&count| vertex_score(None, count))
+        .collect();
+    let mut triangle_scores: Vec<f32> = indices
+        .chunks_exact(3)
+        .map(|vertices| vertices.iter().map(|&v| vertex_scores[v as usize]).sum())
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+
+    let best_remaining = |emitted: &[bool], triangle_scores: &[f32]| {
+        (0..triangle_count)
+            .filter(|&triangle| !emitted[triangle])
+            .max_by(|a, b| triangle_scores[*a].total_cmp(&triangle_scores[*b]))
+    };
+    let mut next = best_remaining(&emitted, &triangle_scores);
+    while let Some(triangle) = next {
+        emitted[triangle] = true;
+        let vertices = [
+            indices[triangle * 3],
+            indices[triangle * 3 + 1],
+            indices[triangle * 3 + 2],
+        ];
+        output.extend(vertices);
+
+        for vertex in vertices {
+            let vertex = vertex as usize;
+            let start = offsets[vertex];
+            let list = &mut vertex_triangles[start..start + remaining[vertex] as usize];
+            if let Some(position) = list.iter().position(|&t| t == triangle) {
+                list.swap(position, list.len() - 1);
+                remaining[vertex] -= 1;
+            }
+        }
+
+        // the vertices of the triangle move to the front of the cache
+        cache.retain(|vertex| !vertices.contains(vertex));
+        let mut front = Vec::with_capacity(3);
+        for vertex in vertices {
+            if !front.contains(&vertex) {
+                front.push(vertex);
+            }
+        }
+        cache.splice(0..0, front);
+        let evicted: Vec<u32> = cache.drain(CACHE_SIZE.min(cache.len())..).collect();
+        for &vertex in &evicted {
+            cache_positions[vertex as usize] = None;
+        }
+        for (position, &vertex) in cache.iter().enumerate() {
+            cache_positions[vertex as usize] = Some(position);
+        }
+
+        for &vertex in cache.iter().chain(&evicted) {
+            let vertex = vertex as usize;
+            let score = vertex_score(cache_positions[vertex], remaining[vertex]);
+            let delta = score - vertex_scores[vertex];
+            vertex_scores[vertex] = score;
+            let start = offsets[vertex];
+            for &triangle in &vertex_triangles[start..start + remaining[vertex] as usize] {
+                triangle_scores[triangle] += delta;
+            }
+        }
+
+        // the best triangle is almost always one using a cached vertex
+        next = cache
+            .iter()
+            .flat_map(|&vertex| {
+                let start = offsets[vertex as usize];
+                vertex_triangles[start..start + remaining[vertex as usize] as usize].iter()
+            })
+            .copied()
+            .max_by(|a, b| triangle_scores[*a].total_cmp(&triangle_scores[*b]))
+            .or_else(|| best_remaining(&emitted, &triangle_scores));
+    }
+
+    indices.copy_from_slice(&output);
+}
+
+/// Average cache miss ratio, the number of vertices transformed per triangle
+/// with a FIFO cache of `cache_size` vertices.
+///
+/// It's 3 when no vertex is reused and approaches 0.5 for a large regular grid.
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+    let mut cache = VecDeque::with_capacity(cache_size + 1);
+    let mut misses = 0;
+    for vertex in indices {
+        if !cache.contains(vertex) {
+            misses += 1;
+            cache.push_back(*vertex);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / triangles as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangles of a grid of `size` by `size` quads, in a scattered order
+    fn scattered_grid(size: u32) -> (Vec<u32>, usize) {
+        let row = size + 1;
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let a = y * row + x;
+                let [b, c, d] = [a + 1, a + row + 1, a + row];
+                triangles.push([a, b, c]);
+                triangles.push([a, c, d]);
+            }
+        }
+        let count = triangles.len();
+        // 7919 is prime so this visits every triangle once
+        let indices = (0..count)
+            .flat_map(|i| triangles[i * 7919 % count])
+            .collect();
+        (indices, (row * row) as usize)
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<_> = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn keeps_the_triangles() {
+        let (mut indices, vertex_count) = scattered_grid(16);
+        let original = indices.clone();
+        optimize_vertex_cache(&mut indices, vertex_count);
+        assert_eq!(sorted_triangles(&indices), sorted_triangles(&original));
+    }
+
+    #[test]
+    fn lowers_acmr() {
+        let (mut indices, vertex_count) = scattered_grid(32);
+        let before = acmr(&indices, CACHE_SIZE);
+        optimize_vertex_cache(&mut indices, vertex_count);
+        let after = acmr(&indices, CACHE_SIZE);
+        assert!(after < 1.0, "acmr {before} -> {after}");
+        assert!(after < before, "acmr {before} -> {after}");
+    }
+}
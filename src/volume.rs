@@ -0,0 +1,302 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, ShaderStages,
+            TextureSampleType, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+    },
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    chunk::{Chunk, ChunkIsolevel},
+    density_texture::{sync_density_textures, DensityTexture},
+    generation::WorldSettings,
+    terrain_root::TerrainRoot,
+    Data,
+};
+
+pub const VOLUME_PREVIEW_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x4c3a_9b21_7e05_d8f6);
+
+/// Ray-marches the raw density field of every chunk instead of only showing
+/// the extracted surface
+#[derive(Inspectable)]
+pub struct VolumePreview {
+    pub enabled: bool,
+    /// Opacity gained per world unit for a density of 1
+    #[inspectable(min = 0.0, max = 4.0, speed = 0.01)]
+    pub opacity: f32,
+    #[inspectable(min = 8, max = 512)]
+    pub steps: u32,
+    /// Hide the marched meshes while the preview is enabled
+    pub hide_surface: bool,
+}
+
+impl Default for VolumePreview {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            opacity: 0.3,
+            steps: 128,
+            hide_surface: true,
+        }
+    }
+}
+
+pub struct VolumePreviewPlugin;
+
+impl Plugin for VolumePreviewPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            VOLUME_PREVIEW_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/volume_preview.wgsl")),
+        );
+
+        app.add_plugin(MaterialPlugin::<VolumeMaterial>::default())
+            .init_resource::<VolumePreview>()
+            .add_system(update_volume_preview.after(sync_density_textures));
+    }
+}
+
+/// Links a chunk to the box entity used to preview its density
+#[derive(Component)]
+pub struct VolumePreviewEntity(Entity);
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "0b3f6d2e-8a41-4c7e-9f15-6e2d7a9c3b58"]
+pub struct VolumeMaterial {
+    pub density: Handle<Image>,
+    pub origin: Vec3,
+    /// Number of cells on each axis
+    pub size: Vec3,
+    pub cell_size: f32,
+    pub isolevel: f32,
+    pub opacity: f32,
+    pub steps: u32,
+}
+
+#[derive(Clone)]
+pub struct GpuVolumeMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for VolumeMaterial {
+    type ExtractedAsset = VolumeMaterial;
+    type PreparedAsset = GpuVolumeMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<MaterialPipeline<Self>>,
+        SRes<RenderAssets<Image>>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline, images): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let density = match images.get(&material.density) {
+            Some(density) => density,
+            None => return Err(PrepareAssetError::RetryNextUpdate(material)),
+        };
+
+        // matches the VolumeMaterial struct of the shader
+        let uniform = [
+            material.origin.x,
+            material.origin.y,
+            material.origin.z,
+            material.cell_size,
+            material.size.x,
+            material.size.y,
+            material.size.z,
+            0.0,
+            material.isolevel,
+            material.opacity,
+            material.steps as f32,
+            0.0,
+        ];
+        let contents: Vec<u8> = uniform.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("volume_material_uniform"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("volume_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&density.texture_view),
+                },
+            ],
+        });
+
+        Ok(GpuVolumeMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for VolumeMaterial {
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(VOLUME_PREVIEW_SHADER_HANDLE.typed())
+    }
+
+    fn alpha_mode(_material: &<Self as RenderAsset>::PreparedAsset) -> AlphaMode {
+        AlphaMode::Blend
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("volume_material_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(12 * 4),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D3,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_volume_preview(
+    mut commands: Commands,
+    settings: Res<VolumePreview>,
+    terrains: Query<(&Data, &WorldSettings), With<TerrainRoot>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<VolumeMaterial>>,
+    mut chunks: Query<(
+        Entity,
+        ChangeTrackers<Chunk>,
+        &Chunk,
+        &DensityTexture,
+        &GlobalTransform,
+        &mut Visibility,
+        Option<&VolumePreviewEntity>,
+        Option<&ChunkIsolevel>,
+        &Parent,
+    )>,
+    previews: Query<&Handle<VolumeMaterial>>,
+) {
+    for (
+        entity,
+        chunk_tracker,
+        chunk,
+        density,
+        transform,
+        mut visibility,
+        preview,
+        chunk_isolevel,
+        parent,
+    ) in chunks.iter_mut()
+    {
+        let (data, world_settings) = match terrains.get(parent.0) {
+            Ok(terrain) => terrain,
+            Err(_) => continue,
+        };
+        let isolevel = chunk_isolevel.map_or(data.isolevel, |isolevel| isolevel.0);
+
+        if settings.is_changed() {
+            visibility.is_visible = !(settings.enabled && settings.hide_surface);
+        }
+
+        match preview {
+            Some(VolumePreviewEntity(preview)) if !settings.enabled => {
+                commands.entity(*preview).despawn();
+                commands.entity(entity).remove::<VolumePreviewEntity>();
+            }
+            Some(VolumePreviewEntity(preview)) => {
+                let handle = match previews.get(*preview) {
+                    Ok(handle) => handle,
+                    Err(_) => continue,
+                };
+                let outdated = materials.get(handle).map_or(false, |material| {
+                    material.isolevel != isolevel
+                        || material.opacity != settings.opacity
+                        || material.steps != settings.steps
+                });
+                // get_mut marks the material as modified, only call it when needed.
+                // The bind group of the material still points to the previous
+                // texture when the chunk changed, touching it rebuilds the bind group
+                if !(outdated || chunk_tracker.is_changed()) {
+                    continue;
+                }
+                if let Some(material) = materials.get_mut(handle) {
+                    material.density = density.0.clone();
+                    material.isolevel = isolevel;
+                    material.opacity = settings.opacity;
+                    material.steps = settings.steps;
+                }
+            }
+            None if settings.enabled => {
+                let size = chunk.size.as_vec3() * world_settings.cell_size;
+                let material = materials.add(VolumeMaterial {
+                    density: density.0.clone(),
+                    origin: transform.translation,
+                    size: chunk.size.as_vec3(),
+                    cell_size: world_settings.cell_size,
+                    isolevel,
+                    opacity: settings.opacity,
+                    steps: settings.steps,
+                });
+                let preview = commands
+                    .spawn_bundle(MaterialMeshBundle {
+                        mesh: meshes.add(Mesh::from(shape::Box {
+                            min_x: 0.0,
+                            max_x: size.x,
+                            min_y: 0.0,
+                            max_y: size.y,
+                            min_z: 0.0,
+                            max_z: size.z,
+                        })),
+                        material,
+                        transform: Transform::from_translation(transform.translation),
+                        ..default()
+                    })
+                    .id();
+                commands.entity(entity).insert(VolumePreviewEntity(preview));
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,694 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    brush::edit_box, chunk::Chunk, flatten::surface_density, generation::WorldSettings,
+    iters::Iter3d, terrain_root::MainTerrain, Data, StartMarching,
+};
+
+pub type Triangle = [Vec3; 3];
+
+#[derive(Inspectable, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportMode {
+    /// The model is added to the terrain
+    Add,
+    /// The model is carved out of the terrain
+    Subtract,
+}
+
+/// Press I to voxelize the model at `path` into the density field, it can
+/// then be edited and destroyed like the rest of the terrain.
+///
+/// The path is relative to the `assets` directory. OBJ files and the first
+/// primitive of the first mesh of glTF files are supported, the meshes must
+/// be closed for the inside to be found.
+#[derive(Inspectable)]
+pub struct ModelImport {
+    /// Number of voxels along the longest side of the model
+    #[inspectable(min = 4, max = 512)]
+    pub resolution: u32,
+    #[inspectable(min = 0.01, max = 100.0, speed = 0.05)]
+    pub scale: f32,
+    /// Center of the bounding box of the imported model
+    pub position: Vec3,
+    pub mode: ImportMode,
+    #[inspectable(ignore)]
+    pub path: PathBuf,
+}
+
+impl Default for ModelImport {
+    fn default() -> Self {
+        Self {
+            resolution: 64,
+            scale: 1.0,
+            position: Vec3::new(16.0, 16.0, 16.0),
+            mode: ImportMode::Add,
+            path: PathBuf::from("models/model.obj"),
+        }
+    }
+}
+
+impl ModelImport {
+    /// Scales and moves the triangles of the model to the world, then samples
+    /// their signed distance field at the resolution of the settings
+    pub fn voxelize(&self, triangles: &[Triangle], cell_size: f32) -> Option<SignedDistanceGrid> {
+        let (min, max) = bounds(triangles)?;
+        let offset = self.position - (min + max) / 2.0 * self.scale;
+        let placed: Vec<Triangle> = triangles
+            .iter()
+            .map(|triangle| triangle.map(|v| v * self.scale + offset))
+            .collect();
+        let extent = (max - min).max_element() * self.scale;
+        let spacing = (extent / self.resolution.max(1) as f32).max(f32::EPSILON);
+        // the density is saturated two cells away from the surface
+        let band = 2.0 * cell_size + spacing;
+        Some(SignedDistanceGrid::from_triangles(&placed, spacing, band))
+    }
+
+    /// Density of the point at `pos` with the model applied over `value`
+    pub fn apply(
+        &self,
+        grid: &SignedDistanceGrid,
+        pos: Vec3,
+        value: f32,
+        isolevel: f32,
+        cell_size: f32,
+    ) -> f32 {
+        let density = surface_density(grid.distance(pos), isolevel, cell_size);
+        match self.mode {
+            ImportMode::Add => value.max(density),
+            // mirrored around the isolevel, the inside of the model is empty
+            ImportMode::Subtract => value.min((2.0 * isolevel - density).clamp(0.0, 1.0)),
+        }
+    }
+}
+
+/// Points on each axis of the bricks of a [`SignedDistanceGrid`]
+const BRICK_SIZE: u32 = 8;
+
+/// Block of points of a [`SignedDistanceGrid`]
+#[derive(Clone, Debug, PartialEq)]
+enum Brick {
+    /// Every point is clamped to the band, inside or outside
+    Uniform(f32),
+    /// Some points are near the surface, ordered X first then Y then Z
+    Band(Vec<f32>),
+}
+
+/// Narrow band signed distances to a closed mesh, negative inside.
+///
+/// The distances are exact up to `band` away from the surface and clamped
+/// further away. Only the bricks of points crossed by the band are stored,
+/// the others keep a single value, so fine grids of large models stay small.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignedDistanceGrid {
+    /// World position of the first point
+    pub origin: Vec3,
+    /// Distance between the points
+    pub spacing: f32,
+    /// Number of points on each axis
+    pub size: UVec3,
+    /// Largest distance stored, the points further away are clamped to it
+    pub band: f32,
+    bricks: Vec<Brick>,
+}
+
+impl SignedDistanceGrid {
+    /// Samples the distances to `triangles` up to `band` away from them, the
+    /// grid covers the bounds of the triangles plus the band.
+    ///
+    /// The sign is found with the parity of the triangles crossed by a ray
+    /// along X from each row of points, so the mesh must be closed but its
+    /// winding doesn't matter.
+    pub fn from_triangles(triangles: &[Triangle], spacing: f32, band: f32) -> Self {
+        let (min, max) = bounds(triangles).unwrap_or_default();
+        let padding = (band / spacing).ceil() + 1.0;
+        let origin = min - Vec3::splat(padding * spacing);
+        let size = (((max - min) / spacing).ceil() + Vec3::splat(2.0 * padding + 1.0)).as_uvec3();
+        let index = |point: UVec3| {
+            (point.z as usize * size.y as usize + point.y as usize) * size.x as usize
+                + point.x as usize
+        };
+        let mut distances = vec![band; size.x as usize * size.y as usize * size.z as usize];
+
+        let last = size.as_ivec3() - IVec3::ONE;
+        let to_grid = |pos: Vec3| (pos - origin) / spacing;
+        for triangle in triangles {
+            let (t_min, t_max) = bounds(std::slice::from_ref(triangle)).unwrap();
+            let first = to_grid(t_min - Vec3::splat(band))
+                .ceil()
+                .as_ivec3()
+                .max(IVec3::ZERO);
+            let end = to_grid(t_max + Vec3::splat(band))
+                .floor()
+                .as_ivec3()
+                .min(last);
+            for z in first.z..=end.z {
+                for y in first.y..=end.y {
+                    for x in first.x..=end.x {
+                        let point = UVec3::new(x as u32, y as u32, z as u32);
+                        let pos = origin + point.as_vec3() * spacing;
+                        let distance = (closest_point(pos, triangle) - pos).length();
+                        let i = index(point);
+                        distances[i] = distances[i].min(distance);
+                    }
+                }
+            }
+        }
+
+        // X coordinates where the rows of points cross the triangles
+        let mut crossings = vec![Vec::new(); size.y as usize * size.z as usize];
+        // the rays are nudged off the grid so they don't go through the shared edges
+        let nudge = Vec2::new(spacing * 1.3e-3, spacing * 0.7e-3);
+        for [a, b, c] in triangles {
+            let (a2, b2, c2) = (
+                Vec2::new(a.y, a.z),
+                Vec2::new(b.y, b.z),
+                Vec2::new(c.y, c.z),
+            );
+            let det = (b2 - a2).perp_dot(c2 - a2);
+            if det.abs() <= f32::EPSILON {
+                // parallel to the rays
+                continue;
+            }
+            let row_min = (a2.min(b2).min(c2) - Vec2::new(origin.y, origin.z) - nudge) / spacing;
+            let row_max = (a2.max(b2).max(c2) - Vec2::new(origin.y, origin.z) - nudge) / spacing;
+            let first = row_min.ceil().as_ivec2().max(IVec2::ZERO);
+            let end = row_max.floor().as_ivec2().min(IVec2::new(last.y, last.z));
+            for z in first.y..=end.y {
+                for y in first.x..=end.x {
+                    let p = Vec2::new(origin.y, origin.z)
+                        + IVec2::new(y, z).as_vec2() * spacing
+                        + nudge;
+                    let v = (p - a2).perp_dot(c2 - a2) / det;
+                    let w = (b2 - a2).perp_dot(p - a2) / det;
+                    if v >= 0.0 && w >= 0.0 && v + w <= 1.0 {
+                        let x = a.x + v * (b.x - a.x) + w * (c.x - a.x);
+                        crossings[z as usize * size.y as usize + y as usize].push(x);
+                    }
+                }
+            }
+        }
+        for z in 0..size.z {
+            for y in 0..size.y {
+                let row = &mut crossings[(z * size.y + y) as usize];
+                row.sort_by(f32::total_cmp);
+                let mut crossed = 0;
+                for x in 0..size.x {
+                    let pos_x = origin.x + x as f32 * spacing;
+                    while crossed < row.len() && row[crossed] < pos_x {
+                        crossed += 1;
+                    }
+                    if crossed % 2 == 1 {
+                        let i = index(UVec3::new(x, y, z));
+                        distances[i] = -distances[i];
+                    }
+                }
+            }
+        }
+
+        let brick_counts = Self::brick_counts(size);
+        let bricks = Iter3d::new(UVec3::ZERO, brick_counts - UVec3::ONE)
+            .map(|brick| {
+                let first = brick * BRICK_SIZE;
+                let points: Vec<f32> = Iter3d::new(UVec3::ZERO, UVec3::splat(BRICK_SIZE - 1))
+                    .map(|local| {
+                        // the bricks on the far sides are partly outside of the grid
+                        let point = (first + local).min(size - UVec3::ONE);
+                        distances[index(point)]
+                    })
+                    .collect();
+                if points.iter().all(|d| *d == points[0] && d.abs() >= band) {
+                    Brick::Uniform(points[0])
+                } else {
+                    Brick::Band(points)
+                }
+            })
+            .collect();
+        Self {
+            origin,
+            spacing,
+            size,
+            band,
+            bricks,
+        }
+    }
+
+    fn brick_counts(size: UVec3) -> UVec3 {
+        (size + UVec3::splat(BRICK_SIZE - 1)) / BRICK_SIZE
+    }
+
+    /// Number of points stored in the bricks crossed by the band
+    pub fn band_points(&self) -> usize {
+        self.bricks
+            .iter()
+            .filter(|brick| matches!(brick, Brick::Band(_)))
+            .count()
+            * BRICK_SIZE.pow(3) as usize
+    }
+
+    /// World position of the last point
+    pub fn max(&self) -> Vec3 {
+        self.origin + (self.size.as_vec3() - Vec3::ONE) * self.spacing
+    }
+
+    /// Distance stored for a point of the grid
+    pub fn get(&self, point: UVec3) -> f32 {
+        let counts = Self::brick_counts(self.size);
+        let brick = point / BRICK_SIZE;
+        let brick_index = ((brick.z * counts.y + brick.y) * counts.x + brick.x) as usize;
+        match &self.bricks[brick_index] {
+            Brick::Uniform(distance) => *distance,
+            Brick::Band(points) => {
+                let local = point % BRICK_SIZE;
+                points[((local.z * BRICK_SIZE + local.y) * BRICK_SIZE + local.x) as usize]
+            }
+        }
+    }
+
+    /// First corner and position in the cell of the grid containing `pos`
+    fn cell(&self, pos: Vec3) -> Option<(UVec3, Vec3)> {
+        let local = (pos - self.origin) / self.spacing;
+        let last = self.size.as_vec3() - Vec3::ONE;
+        if local.cmplt(Vec3::ZERO).any() || local.cmpgt(last).any() || last.min_element() < 1.0 {
+            return None;
+        }
+        let first = local.floor().min(last - Vec3::ONE);
+        Some((first.as_uvec3(), local - first))
+    }
+
+    /// Trilinear interpolation of the distances, points outside of the grid
+    /// are outside of the mesh
+    pub fn distance(&self, pos: Vec3) -> f32 {
+        let (first, t) = match self.cell(pos) {
+            Some(cell) => cell,
+            None => return self.band,
+        };
+        let at = |x: u32, y: u32, z: u32| self.get(first + UVec3::new(x, y, z));
+        let plane = |z: u32| {
+            lerp(
+                lerp(at(0, 0, z), at(1, 0, z), t.x),
+                lerp(at(0, 1, z), at(1, 1, z), t.x),
+                t.y,
+            )
+        };
+        lerp(plane(0), plane(1), t.z)
+    }
+
+    /// Derivative of [`SignedDistanceGrid::distance`], its length is close
+    /// to 1 in the band and 0 where the distances are clamped
+    pub fn gradient(&self, pos: Vec3) -> Vec3 {
+        let (first, t) = match self.cell(pos) {
+            Some(cell) => cell,
+            None => return Vec3::ZERO,
+        };
+        let at = |p: UVec3| self.get(first + p);
+        // differences along `axis` interpolated on the two other axes
+        let along = |axis: UVec3, u: UVec3, v: UVec3, tu: f32, tv: f32| {
+            let delta = |p: UVec3| at(p + axis) - at(p);
+            lerp(
+                lerp(delta(UVec3::ZERO), delta(u), tu),
+                lerp(delta(v), delta(u + v), tu),
+                tv,
+            )
+        };
+        Vec3::new(
+            along(UVec3::X, UVec3::Y, UVec3::Z, t.y, t.z),
+            along(UVec3::Y, UVec3::X, UVec3::Z, t.x, t.z),
+            along(UVec3::Z, UVec3::X, UVec3::Y, t.x, t.y),
+        ) / self.spacing
+    }
+
+    /// Outward normal of the surface closest to `pos`, zero away from the band
+    pub fn normal(&self, pos: Vec3) -> Vec3 {
+        self.gradient(pos).normalize_or_zero()
+    }
+
+    /// Projects `pos` on the surface of the mesh along the gradient, it's
+    /// only moved in the band
+    pub fn surface_point(&self, pos: Vec3) -> Vec3 {
+        pos - self.normal(pos) * self.distance(pos)
+    }
+
+    /// Distance along the ray to the first hit of the surface, marching the
+    /// ray by the distance to the surface
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<f32> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return None;
+        }
+        // the ray starts where it enters the grid
+        let inverse = direction.recip();
+        let t0 = (self.origin - origin) * inverse;
+        let t1 = (self.max() - origin) * inverse;
+        let enter = t0.min(t1).max_element().max(0.0);
+        let exit = t0.max(t1).min_element().min(max_distance);
+        let mut t = enter;
+        while t <= exit {
+            let distance = self.distance(origin + direction * t);
+            if distance <= self.spacing * 0.01 {
+                return Some(t);
+            }
+            t += distance.max(self.spacing * 0.05);
+        }
+        None
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn bounds(triangles: &[Triangle]) -> Option<(Vec3, Vec3)> {
+    let mut vertices = triangles.iter().flatten();
+    let first = *vertices.next()?;
+    Some(vertices.fold((first, first), |(min, max), v| (min.min(*v), max.max(*v))))
+}
+
+/// Point of `triangle` closest to `p`
+fn closest_point(p: Vec3, [a, b, c]: &Triangle) -> Vec3 {
+    let (a, b, c) = (*a, *b, *c);
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    // inside the face
+    let denom = va + vb + vc;
+    if denom.abs() <= f32::EPSILON {
+        return a;
+    }
+    a + ab * (vb / denom) + ac * (vc / denom)
+}
+
+/// Triangles of the `v` and `f` lines of a Wavefront OBJ file, the polygons
+/// are split in fans
+pub fn parse_obj(text: &str) -> Result<Vec<Triangle>, String> {
+    let mut vertices = Vec::new();
+    let mut triangles = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let coords: Vec<f32> = tokens
+                    .take(3)
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|err| format!("line {}: {err}", number + 1))?;
+                if coords.len() != 3 {
+                    return Err(format!("line {}: vertex without 3 coordinates", number + 1));
+                }
+                vertices.push(Vec3::new(coords[0], coords[1], coords[2]));
+            }
+            Some("f") => {
+                let face = tokens
+                    .map(|token| {
+                        // `v`, `v/vt`, `v//vn` or `v/vt/vn`, negative indices count from the end
+                        let index: i64 = token
+                            .split('/')
+                            .next()
+                            .unwrap_or_default()
+                            .parse()
+                            .map_err(|err| format!("line {}: {err}", number + 1))?;
+                        let index = if index < 0 {
+                            vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        usize::try_from(index)
+                            .ok()
+                            .and_then(|index| vertices.get(index).copied())
+                            .ok_or_else(|| format!("line {}: missing vertex {token}", number + 1))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                for i in 1..face.len().saturating_sub(1) {
+                    triangles.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+/// Triangles of a triangle list mesh
+pub fn mesh_triangles(mesh: &Mesh) -> Option<Vec<Triangle>> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+        VertexAttributeValues::Float32x3(positions) => positions,
+        _ => return None,
+    };
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+    indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let vertex = |i: usize| positions.get(triangle[i]).map(|p| Vec3::from(*p));
+            Some([vertex(0)?, vertex(1)?, vertex(2)?])
+        })
+        .collect()
+}
+
+/// Distances to the last imported model, to query its normals and its
+/// surface with a better precision than the density field
+#[derive(Default)]
+pub struct ImportedModel(pub Option<SignedDistanceGrid>);
+
+fn read_obj(path: &Path) -> Result<Vec<Triangle>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    parse_obj(&text)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn import_model(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<ModelImport>,
+    data: Res<Data>,
+    asset_server: Res<AssetServer>,
+    meshes: Res<Assets<Mesh>>,
+    terrain: MainTerrain,
+    world_settings: Res<WorldSettings>,
+    mut chunks: Query<&mut Chunk>,
+    mut start_marching_events: EventWriter<StartMarching>,
+    mut imported: ResMut<ImportedModel>,
+    mut loading: Local<Option<Handle<Mesh>>>,
+) {
+    let chunk_map = match terrain.chunk_map() {
+        Some(chunk_map) => chunk_map,
+        None => return,
+    };
+    let triangles = if keyboard_input.just_pressed(KeyCode::I) {
+        let extension = settings
+            .path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("obj") => match read_obj(&Path::new("assets").join(&settings.path)) {
+                Ok(triangles) => triangles,
+                Err(err) => {
+                    error!("Failed to read {:?}: {err}", settings.path);
+                    return;
+                }
+            },
+            Some("gltf" | "glb") => {
+                // loaded in the background, imported once it's ready
+                let label = format!("{}#Mesh0/Primitive0", settings.path.display());
+                *loading = Some(asset_server.load(label.as_str()));
+                return;
+            }
+            _ => {
+                error!("Can't import {:?}, only OBJ and glTF", settings.path);
+                return;
+            }
+        }
+    } else if let Some(handle) = &*loading {
+        if asset_server.get_load_state(handle) == LoadState::Failed {
+            error!("Failed to load {:?}", settings.path);
+            *loading = None;
+            return;
+        }
+        let mesh = match meshes.get(handle) {
+            Some(mesh) => mesh,
+            None => return,
+        };
+        let triangles = mesh_triangles(mesh);
+        *loading = None;
+        match triangles {
+            Some(triangles) => triangles,
+            None => {
+                error!("{:?} isn't a triangle mesh", settings.path);
+                return;
+            }
+        }
+    } else {
+        return;
+    };
+
+    let start = bevy::utils::Instant::now();
+    let cell_size = world_settings.cell_size;
+    let grid = match settings.voxelize(&triangles, cell_size) {
+        Some(grid) => grid,
+        None => {
+            warn!("{:?} has no triangles", settings.path);
+            return;
+        }
+    };
+    let edited = edit_box(
+        chunk_map,
+        &mut chunks,
+        &world_settings,
+        grid.origin,
+        grid.max(),
+        |pos, value| settings.apply(&grid, pos, value, data.isolevel, cell_size),
+    );
+    let size = grid.size;
+    info!(
+        "Imported {} triangles of {:?} on a {size} grid in {:?}, {} of {} points in the band",
+        triangles.len(),
+        settings.path,
+        start.elapsed(),
+        grid.band_points(),
+        size.x as usize * size.y as usize * size.z as usize,
+    );
+    imported.0 = Some(grid);
+    if edited {
+        start_marching_events.send_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2/1 3/2 7/3 6/4
+";
+
+    #[test]
+    fn cube_is_solid_inside() {
+        let triangles = parse_obj(CUBE).unwrap();
+        assert_eq!(triangles.len(), 12);
+
+        let grid = SignedDistanceGrid::from_triangles(&triangles, 0.1, 0.3);
+        let center = grid.distance(Vec3::splat(0.5));
+        assert!((center + 0.3).abs() < 1e-4, "{center}");
+        let near_face = grid.distance(Vec3::new(0.5, 0.5, 0.9));
+        assert!((near_face + 0.1).abs() < 1e-3, "{near_face}");
+        let outside = grid.distance(Vec3::new(0.5, 1.2, 0.5));
+        assert!((outside - 0.2).abs() < 1e-3, "{outside}");
+        assert_eq!(grid.distance(Vec3::splat(5.0)), 0.3);
+    }
+
+    #[test]
+    fn only_the_band_is_stored() {
+        let triangles = parse_obj(CUBE).unwrap();
+        let grid = SignedDistanceGrid::from_triangles(&triangles, 0.02, 0.05);
+        let points = grid.size.x as usize * grid.size.y as usize * grid.size.z as usize;
+        assert!(grid.band_points() < points, "{}", grid.band_points());
+        // the clamped bricks keep their sign
+        assert_eq!(grid.distance(Vec3::splat(0.5)), -0.05);
+        assert_eq!(grid.get(UVec3::ZERO), 0.05);
+    }
+
+    #[test]
+    fn gradient_points_out_of_the_faces() {
+        let triangles = parse_obj(CUBE).unwrap();
+        let grid = SignedDistanceGrid::from_triangles(&triangles, 0.05, 0.2);
+        let normal = grid.normal(Vec3::new(0.52, 0.97, 0.48));
+        assert!(normal.abs_diff_eq(Vec3::Y, 1e-3), "{normal}");
+        let normal = grid.normal(Vec3::new(0.04, 0.51, 0.47));
+        assert!(normal.abs_diff_eq(-Vec3::X, 1e-3), "{normal}");
+        assert!((grid.gradient(Vec3::new(0.5, 1.1, 0.5)).length() - 1.0).abs() < 1e-3);
+
+        let projected = grid.surface_point(Vec3::new(0.5, 1.1, 0.5));
+        assert!(
+            projected.abs_diff_eq(Vec3::new(0.5, 1.0, 0.5), 1e-3),
+            "{projected}"
+        );
+
+        let hit = grid
+            .raycast(Vec3::new(0.5, 3.0, 0.5), -Vec3::Y, 10.0)
+            .unwrap();
+        assert!((hit - 2.0).abs() < 0.01, "{hit}");
+        assert_eq!(grid.raycast(Vec3::new(3.0, 3.0, 0.5), -Vec3::Y, 10.0), None);
+    }
+
+    #[test]
+    fn imported_cube_is_placed_and_carved() {
+        let triangles = parse_obj(CUBE).unwrap();
+        let mut import = ModelImport {
+            resolution: 10,
+            scale: 4.0,
+            position: Vec3::new(10.0, 5.0, 10.0),
+            ..default()
+        };
+        let grid = import.voxelize(&triangles, 1.0).unwrap();
+        assert!(import.apply(&grid, Vec3::new(10.0, 5.0, 10.0), 0.0, 0.5, 1.0) > 0.5);
+        assert_eq!(
+            import.apply(&grid, Vec3::new(10.0, 8.0, 10.0), 0.2, 0.5, 1.0),
+            0.2
+        );
+
+        import.mode = ImportMode::Subtract;
+        assert!(import.apply(&grid, Vec3::new(10.0, 5.0, 10.0), 1.0, 0.5, 1.0) < 0.5);
+        assert_eq!(
+            import.apply(&grid, Vec3::new(10.0, 8.0, 10.0), 1.0, 0.5, 1.0),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        render_asset::{PrepareAssetError, RenderAsset},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, ShaderStages,
+        },
+        renderer::RenderDevice,
+    },
+};
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+
+use crate::{
+    chunk::ChunkMesh, field::DensityField, lines::line_mesh, marching_squares::slice_contour,
+    unlit_material, Data,
+};
+
+pub const XRAY_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x61e0_3fa8_c92d_7b14);
+
+/// Renders the terrain as a translucent shell to see the cavities inside of it.
+///
+/// Surfaces seen edge-on are more opaque so the outlines of the caves stand
+/// out, and everything above the slice height can be cut away.
+#[derive(Inspectable)]
+pub struct XRay {
+    pub enabled: bool,
+    pub color: Color,
+    /// Opacity of the surfaces facing the camera
+    #[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+    pub opacity: f32,
+    /// Higher values keep the opaque rim closer to the silhouettes
+    #[inspectable(min = 0.5, max = 8.0, speed = 0.05)]
+    pub rim_power: f32,
+    /// Only render the side of the triangles facing the camera
+    pub front_faces_only: bool,
+    /// Cut away everything above `slice_height`
+    pub slice_enabled: bool,
+    #[inspectable(speed = 0.1)]
+    pub slice_height: f32,
+    /// Outlines the terrain where the slice cuts it
+    pub slice_contour: bool,
+}
+
+impl Default for XRay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::rgb(0.4, 0.8, 1.0),
+            opacity: 0.05,
+            rim_power: 3.0,
+            front_faces_only: true,
+            slice_enabled: false,
+            slice_height: 8.0,
+            slice_contour: true,
+        }
+    }
+}
+
+pub struct XRayPlugin;
+
+impl Plugin for XRayPlugin {
+    fn build(&self, app: &mut App) {
+        let mut shaders = app.world.get_resource_mut::<Assets<Shader>>().unwrap();
+        shaders.set_untracked(
+            XRAY_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("shaders/xray.wgsl")),
+        );
+
+        app.add_plugin(MaterialPlugin::<XRayMaterial>::default())
+            .add_plugin(InspectorPlugin::<XRay>::new())
+            .init_resource::<XRayMaterialHandle>()
+            .add_system(update_xray_material)
+            .add_system(update_slice_contour);
+    }
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "c5f0a2d9-7b31-4e86-9d4c-2a6e8f1b3d07"]
+pub struct XRayMaterial {
+    pub color: Color,
+    pub opacity: f32,
+    pub rim_power: f32,
+    pub front_faces_only: bool,
+    /// Height above which the surface is cut away, `None` to keep everything
+    pub slice_height: Option<f32>,
+}
+
+impl From<&XRay> for XRayMaterial {
+    fn from(settings: &XRay) -> Self {
+        Self {
+            color: settings.color,
+            opacity: settings.opacity,
+            rim_power: settings.rim_power,
+            front_faces_only: settings.front_faces_only,
+            slice_height: if settings.slice_enabled {
+                Some(settings.slice_height)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GpuXRayMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for XRayMaterial {
+    type ExtractedAsset = XRayMaterial;
+    type PreparedAsset = GpuXRayMaterial;
+    type Param = (SRes<RenderDevice>, SRes<MaterialPipeline<Self>>);
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, material_pipeline): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        // matches the XRayMaterial struct of the shader
+        let [r, g, b, a] = material.color.as_linear_rgba_f32();
+        let uniform = [
+            r,
+            g,
+            b,
+            a,
+            material.opacity,
+            material.rim_power,
+            0.0,
+            0.0,
+            material.slice_height.is_some() as u32 as f32,
+            material.slice_height.unwrap_or_default(),
+            material.front_faces_only as u32 as f32,
+            0.0,
+        ];
+        let contents: Vec<u8> = uniform.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("xray_material_uniform"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("xray_material_bind_group"),
+            layout: &material_pipeline.material_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Ok(GpuXRayMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for XRayMaterial {
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(XRAY_SHADER_HANDLE.typed())
+    }
+
+    fn alpha_mode(_material: &<Self as RenderAsset>::PreparedAsset) -> AlphaMode {
+        AlphaMode::Blend
+    }
+
+    fn bind_group(material: &<Self as RenderAsset>::PreparedAsset) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("xray_material_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(12 * 4),
+                },
+                count: None,
+            }],
+        })
+    }
+}
+
+/// Material shared by every chunk while the x-ray mode is enabled
+pub struct XRayMaterialHandle(pub Handle<XRayMaterial>);
+
+impl FromWorld for XRayMaterialHandle {
+    fn from_world(world: &mut World) -> Self {
+        let material = XRayMaterial::from(&*world.get_resource_or_insert_with(XRay::default));
+        let mut materials = world.get_resource_mut::<Assets<XRayMaterial>>().unwrap();
+        Self(materials.add(material))
+    }
+}
+
+fn update_xray_material(
+    settings: Res<XRay>,
+    handle: Res<XRayMaterialHandle>,
+    mut materials: ResMut<Assets<XRayMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        *material = XRayMaterial::from(&*settings);
+    }
+}
+
+/// Lines of the outline drawn at the slice height
+#[derive(Component)]
+pub struct SliceContour;
+
+#[allow(clippy::too_many_arguments)]
+fn update_slice_contour(
+    mut commands: Commands,
+    settings: Res<XRay>,
+    data: Res<Data>,
+    field: DensityField,
+    changed_meshes: Query<(), Changed<ChunkMesh>>,
+    contours: Query<Entity, With<SliceContour>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !(settings.is_changed() || data.is_changed() || !changed_meshes.is_empty()) {
+        return;
+    }
+    for entity in contours.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !(settings.enabled && settings.slice_enabled && settings.slice_contour) {
+        return;
+    }
+    let segments = slice_contour(
+        &field,
+        settings.slice_height,
+        data.isolevel,
+        field.cell_size(),
+    );
+    if segments.is_empty() {
+        return;
+    }
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(line_mesh(&segments)),
+            material: materials.add(unlit_material(settings.color)),
+            ..default()
+        })
+        .insert(SliceContour);
+}